@@ -0,0 +1,58 @@
+/*
+ * Copyright (C) Hygon Info Technologies Ltd.
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+/*
+ * C interface of csv-rs, built with the `ffi` feature:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Functions return CSV_OK or a negative error code. csv_last_error()
+ * describes the last failure on the calling thread.
+ */
+
+#ifndef CSV_RS_H
+#define CSV_RS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CSV_OK 0
+#define CSV_ERROR_INVALID_ARGUMENT (-1)
+#define CSV_ERROR_IO (-2)
+#define CSV_ERROR_FIRMWARE (-3)
+#define CSV_ERROR_VERIFICATION (-4)
+#define CSV_ERROR_PANIC (-5)
+
+/* The attestation report (336 bytes) followed by its restored signer. */
+#define CSV_EVIDENCE_SIZE (336 + 2212)
+
+/* The HSK and CEK certificates of a chip. */
+typedef struct CsvHskCek CsvHskCek;
+
+/* Valid until the next call into csv-rs on the same thread. */
+const char *csv_last_error(void);
+
+/* data (64 bytes) and mnonce (16 bytes) may be NULL. */
+int csv_get_attestation_report(const uint8_t *data, const uint8_t *mnonce,
+                               uint8_t *out, size_t out_len);
+
+int csv_hsk_cek_load(const uint8_t *bytes, size_t len, CsvHskCek **out);
+int csv_hsk_cek_load_file(const char *path, CsvHskCek **out);
+void csv_hsk_cek_free(CsvHskCek *chain);
+
+/* Returns CSV_ERROR_VERIFICATION if the evidence does not verify. */
+int csv_verify_attestation_report(const uint8_t *evidence, size_t len,
+                                  const CsvHskCek *chain);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CSV_RS_H */
@@ -0,0 +1,109 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! An asynchronous handle to the CSV guest device, for tokio-based
+//! attestation agents.
+//!
+//! Requests are issued on tokio's blocking pool, so awaiting a report
+//! does not stall the executor while the firmware signs it.
+
+use super::{AttestationReport, Builder, ReportRsp, ReportSigner};
+use crate::error::Error;
+
+use std::{io, os::unix::io::RawFd, sync::Arc};
+
+/// Runs `f` on the blocking pool.
+async fn blocking<T, F>(f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::from(io::Error::other(e)))?
+}
+
+/// An asynchronous handle to the CSV guest device, see
+/// [`super::CsvGuest`].
+///
+/// Handles are cheap to clone and share the underlying device.
+#[derive(Clone)]
+pub struct CsvGuest(Arc<super::CsvGuest>);
+
+impl CsvGuest {
+    /// Generate a handle to the CSV guest platform via the first csv-guest
+    /// device node found, see [`super::KNOWN_PATHS`].
+    pub async fn open() -> io::Result<CsvGuest> {
+        Self::open_with(super::CsvGuest::builder()).await
+    }
+
+    /// Opens a handle configured by `builder`.
+    pub async fn open_with(builder: Builder) -> io::Result<CsvGuest> {
+        tokio::task::spawn_blocking(move || builder.open())
+            .await
+            .map_err(io::Error::other)?
+            .map(Self::from)
+    }
+
+    /// The synchronous handle requests are issued on.
+    pub fn blocking(&self) -> &super::CsvGuest {
+        &self.0
+    }
+
+    /// See [`super::CsvGuest::raw_fd`].
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        self.0.raw_fd()
+    }
+
+    /// Requests an attestation report from the HYGON Secure Processor,
+    /// see [`super::CsvGuest::get_report`].
+    pub async fn get_report(
+        &self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<(AttestationReport, ReportSigner), Error> {
+        let rsp = self.get_report_boxed(data, mnonce).await?;
+        Ok((rsp.report, rsp.signer))
+    }
+
+    /// Like [`CsvGuest::get_report`], returning the whole response page.
+    pub async fn get_report_boxed(
+        &self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<Box<ReportRsp>, Error> {
+        let guest = self.0.clone();
+        blocking(move || guest.get_report_boxed(data, mnonce)).await
+    }
+}
+
+impl From<super::CsvGuest> for CsvGuest {
+    fn from(guest: super::CsvGuest) -> Self {
+        Self(Arc::new(guest))
+    }
+}
+
+impl From<Arc<super::CsvGuest>> for CsvGuest {
+    fn from(guest: Arc<super::CsvGuest>) -> Self {
+        Self(guest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::guest::mock::MockGuest;
+
+    #[tokio::test]
+    async fn get_report() {
+        let builder = Builder::default().transport(MockGuest::new("NZA9T14052605"));
+        let guest = CsvGuest::open_with(builder).await.unwrap();
+
+        let (report, _) = guest
+            .get_report(Some([7u8; 64]), Some([1u8; 16]))
+            .await
+            .unwrap();
+        assert_eq!(report.unmask(report.body.report_data), [7u8; 64]);
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Configuration of [`CsvGuest`] handles.
+
+use super::{transport::*, CsvGuest, Inner};
+use crate::crypto::DefaultRng;
+
+use rand::RngCore;
+
+use std::{
+    io,
+    os::unix::io::{FromRawFd, OwnedFd, RawFd},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+
+/// Describes how transient failures of the guest device are retried.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt.
+    pub retries: u32,
+
+    /// The delay between two attempts.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry a failed request.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        retries: 0,
+        delay: Duration::ZERO,
+    };
+
+    /// Returns true if the error is transient and may succeed on retry.
+    pub fn is_transient(err: &io::Error) -> bool {
+        matches!(
+            err.kind(),
+            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+        ) || err.raw_os_error() == Some(libc::EBUSY)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            delay: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Builds a [`CsvGuest`] handle.
+///
+/// Every option has a default value, so `CsvGuest::builder().open()` is
+/// equivalent to `CsvGuest::open()`.
+#[derive(Default)]
+pub struct Builder {
+    path: Option<PathBuf>,
+    fd: Option<OwnedFd>,
+    retry: RetryPolicy,
+    cache: bool,
+    encrypted: bool,
+    timeout: Option<Duration>,
+    transport: Option<Box<dyn Transport>>,
+    rng: Option<Box<dyn RngCore + Send>>,
+    #[cfg(feature = "capture")]
+    capture: Option<std::sync::Arc<crate::capture::Capture>>,
+}
+
+impl Builder {
+    /// Sets the path of the csv-guest device node.
+    ///
+    /// By default the node named by [`PATH_ENV`] is used if set, otherwise
+    /// the names in [`KNOWN_PATHS`] are probed in order.
+    /// Ignored when a custom transport is provided.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Uses an already opened csv-guest device node instead of opening
+    /// one, e.g. in a sandbox denying access to `/dev`.
+    ///
+    /// Takes precedence over [`Builder::path`], and is ignored when a
+    /// custom transport is provided.
+    pub fn fd(mut self, fd: OwnedFd) -> Self {
+        self.fd = Some(fd);
+        self
+    }
+
+    /// Sets how transient device failures are retried.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Enables caching of the last report response.
+    ///
+    /// Only requests carrying a caller-provided mnonce are cached, since
+    /// a freshly generated mnonce can never match a previous request.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Refuses to open the handle unless the guest runs with CSV memory
+    /// encryption active, see [`super::require_memory_encryption`].
+    ///
+    /// Ignored when a custom transport is provided.
+    pub fn require_encryption(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    /// Sets an upper bound on the time spent servicing a single request,
+    /// retries included.
+    ///
+    /// Each device ioctl is individually bounded by the same value, so a
+    /// wedged PSP cannot hang the calling thread indefinitely.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Uses a custom transport instead of the csv-guest device node.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    /// Uses `rng` to generate the mnonce of requests which do not carry
+    /// one, e.g. a seeded generator for reproducible tests.
+    ///
+    /// Defaults to the OpenSSL CSPRNG.
+    pub fn rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = Some(Box::new(rng));
+        self
+    }
+
+    /// Records the raw request and response pages of every request to
+    /// `capture`, see [`crate::capture`].
+    #[cfg(feature = "capture")]
+    pub fn capture(mut self, capture: std::sync::Arc<crate::capture::Capture>) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    fn device(mut device: Device, timeout: Option<Duration>) -> Box<dyn Transport> {
+        device.set_timeout(timeout);
+        Box::new(device)
+    }
+
+    /// Opens the handle.
+    pub fn open(mut self) -> io::Result<CsvGuest> {
+        if self.encrypted && self.transport.is_none() {
+            super::require_memory_encryption()?;
+        }
+
+        let transport = match (self.transport.take(), self.fd.take(), &self.path) {
+            (Some(transport), _, _) => transport,
+            (None, Some(fd), _) => Self::device(fd.into(), self.timeout),
+            (None, None, Some(path)) => Self::device(Device::open(path)?, self.timeout),
+            (None, None, None) => Self::device(Device::probe()?, self.timeout),
+        };
+
+        Ok(self.build(transport))
+    }
+
+    fn build(self, transport: Box<dyn Transport>) -> CsvGuest {
+        #[cfg(feature = "capture")]
+        let transport: Box<dyn Transport> = match self.capture {
+            Some(capture) => Box::new(crate::capture::Recorder::new(transport, capture)),
+            None => transport,
+        };
+
+        CsvGuest {
+            inner: Mutex::new(Inner {
+                transport,
+                rng: self.rng.unwrap_or_else(|| Box::<DefaultRng>::default()),
+                last: None,
+            }),
+            retry: self.retry,
+            timeout: self.timeout,
+            cache: self.cache,
+        }
+    }
+}
+
+impl From<OwnedFd> for CsvGuest {
+    /// Opens a handle with the default configuration on an already opened
+    /// csv-guest device node, see [`Builder::fd`].
+    fn from(fd: OwnedFd) -> Self {
+        let builder = CsvGuest::builder();
+        let transport = Builder::device(fd.into(), builder.timeout);
+        builder.build(transport)
+    }
+}
+
+impl FromRawFd for CsvGuest {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        OwnedFd::from_raw_fd(fd).into()
+    }
+}
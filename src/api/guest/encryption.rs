@@ -0,0 +1,70 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Detection of the memory encryption protecting the current guest.
+
+use crate::{capabilities::Generation, util::cpu};
+
+use std::io;
+
+/// The SEV_STATUS bits enabling each generation, most recent first.
+const STATUS_BITS: &[(u32, Generation)] = &[
+    (30, Generation::Csv3),
+    (1, Generation::Csv2),
+    (0, Generation::Csv),
+];
+
+/// Decodes the most recent generation active from a SEV_STATUS value.
+fn active(status: u64) -> Option<Generation> {
+    STATUS_BITS
+        .iter()
+        .find(|(bit, _)| status & (1 << bit) != 0)
+        .map(|(_, generation)| *generation)
+}
+
+/// Returns the generation of CSV protecting the memory of the current
+/// guest, or `None` if it runs unprotected, e.g. on bare metal or in a
+/// regular virtual machine.
+///
+/// The status is read from the SEV_STATUS MSR, which requires root and the
+/// msr module inside the guest. Fails if the processor advertises CSV to
+/// a guest whose MSR cannot be read.
+pub fn memory_encryption() -> io::Result<Option<Generation>> {
+    // Guests only see the memory encryption leaf when the hypervisor
+    // enabled CSV for them.
+    if !cpu::hypervisor() || cpu::encryption() & 0b10 == 0 {
+        return Ok(None);
+    }
+
+    cpu::sev_status().map(active).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "cannot read SEV_STATUS from /dev/cpu/0/msr",
+        )
+    })
+}
+
+/// Like [`memory_encryption`], but fails unless the guest runs with CSV
+/// memory encryption active, e.g. before collecting evidence.
+pub fn require_memory_encryption() -> io::Result<Generation> {
+    memory_encryption()?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the guest memory is not encrypted by CSV",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(active(0), None);
+        assert_eq!(active(0b1), Some(Generation::Csv));
+        assert_eq!(active(0b11), Some(Generation::Csv2));
+        assert_eq!(active(1 << 30 | 0b1), Some(Generation::Csv3));
+    }
+}
@@ -7,6 +7,8 @@ use iocuddle::{Group, Ioctl, WriteRead};
 
 use std::marker::PhantomData;
 
+use crate::util::uapi::uapi_layout;
+
 pub enum CsvGuestIoctl {
     GetReport = 0x1,
     _Undefined,
@@ -40,3 +42,8 @@ impl<'a> GuestReportRequest<'a> {
         }
     }
 }
+
+// drivers/virt/coco/csv-guest/csv-guest.h: struct csv_report_req
+uapi_layout! {
+    GuestReportRequest<'static> = 16, { addr: 0, len: 8 },
+}
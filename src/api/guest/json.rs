@@ -0,0 +1,221 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A JSON friendly rendering of reports, with byte arrays as hex strings.
+//!
+//! The serde derives of [`AttestationReport`] and [`ReportSigner`] render
+//! byte arrays as lists of integers. Wrapped in [`Hex`], every byte array
+//! becomes a lowercase hex string instead, which is what attestation
+//! services exchange:
+//!
+//! ```
+//! use csv_rs::api::guest::{json::Hex, AttestationReport};
+//!
+//! let report = AttestationReport::default();
+//! let json = serde_json::to_value(Hex(&report)).unwrap();
+//! assert_eq!(json["body"]["measure"], "00".repeat(32));
+//!
+//! let Hex(parsed): Hex<AttestationReport> = serde_json::from_value(json).unwrap();
+//! assert_eq!(parsed, report);
+//! ```
+//!
+//! The body fields, policy, signature usage and algorithm of a report are
+//! rendered unmasked, as they read. Parsing masks them again with the
+//! anonce, so the signature of a report read back stays verifiable. The
+//! signer is rendered as is.
+
+use super::{AttestationReport, Body, GuestPolicy, ReportSigner};
+use crate::crypto::sig::ecdsa;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Renders the byte arrays of the wrapped report or signer as hex strings.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hex<T>(pub T);
+
+/// Declares the mirror of a structure whose byte arrays are rendered as
+/// hex strings, each converted by a function applied both ways. Other
+/// fields are copied as they are.
+macro_rules! mirror {
+    ($name:literal, $mirror:ident of $ty:ty {
+        $($field:ident: [u8; $len:literal]),* $(; $($other:ident: $other_ty:ty),*)?
+    }) => {
+        #[derive(Serialize, Deserialize)]
+        #[serde(rename = $name)]
+        struct $mirror {
+            $(
+                #[serde(with = "crate::util::hex")]
+                $field: [u8; $len],
+            )*
+            $($($other: $other_ty,)*)?
+        }
+
+        impl $mirror {
+            fn from_with(value: &$ty, convert: impl Fn(&mut [u8])) -> Self {
+                Self {
+                    $($field: {
+                        let mut field = value.$field;
+                        convert(&mut field);
+                        field
+                    },)*
+                    $($($other: value.$other,)*)?
+                }
+            }
+
+            fn into_with(self, convert: impl Fn(&mut [u8])) -> $ty {
+                let mut value = <$ty>::default();
+                $(
+                    value.$field = self.$field;
+                    convert(&mut value.$field);
+                )*
+                $($(value.$other = self.$other;)*)?
+                value
+            }
+        }
+    };
+}
+
+mirror!("Body", HexBody of Body {
+    user_pubkey_digest: [u8; 32],
+    vm_id: [u8; 16],
+    vm_version: [u8; 16],
+    report_data: [u8; 64],
+    mnonce: [u8; 16],
+    measure: [u8; 32];
+    policy: GuestPolicy
+});
+
+mirror!("Signature", HexSignature of ecdsa::Signature {
+    r: [u8; 72],
+    s: [u8; 72]
+});
+
+mirror!("ReportSigner", HexSigner of ReportSigner {
+    pek_cert: [u8; 2084],
+    sn: [u8; 64],
+    reserved: [u8; 32],
+    mac: [u8; 32]
+});
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "AttestationReport")]
+struct HexReport {
+    body: HexBody,
+    sig_usage: u32,
+    sig_algo: u32,
+    #[serde(with = "crate::util::le")]
+    anonce: u32,
+    sig: HexSignature,
+}
+
+impl From<&AttestationReport> for HexReport {
+    fn from(report: &AttestationReport) -> Self {
+        let anonce = report.anonce;
+        let mut body = HexBody::from_with(&report.body, |f| AttestationReport::mask(anonce, f));
+        body.policy = report.body.policy.xor(&anonce);
+
+        Self {
+            body,
+            sig_usage: u32::from_le(report.sig_usage ^ anonce),
+            sig_algo: u32::from_le(report.sig_algo ^ anonce),
+            anonce,
+            sig: HexSignature::from_with(&report.sig, |_| ()),
+        }
+    }
+}
+
+impl From<HexReport> for AttestationReport {
+    fn from(report: HexReport) -> Self {
+        let anonce = report.anonce;
+        let policy = report.body.policy.mask(&anonce);
+        let mut body = report
+            .body
+            .into_with(|f| AttestationReport::mask(anonce, f));
+        body.policy = policy;
+
+        Self {
+            body,
+            sig_usage: report.sig_usage.to_le() ^ anonce,
+            sig_algo: report.sig_algo.to_le() ^ anonce,
+            anonce,
+            sig: report.sig.into_with(|_| ()),
+        }
+    }
+}
+
+impl From<&ReportSigner> for HexSigner {
+    fn from(signer: &ReportSigner) -> Self {
+        Self::from_with(signer, |_| ())
+    }
+}
+
+impl From<HexSigner> for ReportSigner {
+    fn from(signer: HexSigner) -> Self {
+        signer.into_with(|_| ())
+    }
+}
+
+macro_rules! hex {
+    ($($ty:ty => $mirror:ty),*) => {
+        $(
+            impl Serialize for Hex<$ty> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    <$mirror>::from(&self.0).serialize(serializer)
+                }
+            }
+
+            impl Serialize for Hex<&$ty> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    <$mirror>::from(self.0).serialize(serializer)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for Hex<$ty> {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    <$mirror>::deserialize(deserializer).map(|value| Hex(value.into()))
+                }
+            }
+        )*
+    };
+}
+
+hex!(AttestationReport => HexReport, ReportSigner => HexSigner);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pod, util::hex::encode};
+
+    const REPORT: &[u8] = include_bytes!("../../../tests/test_data/report.cert");
+
+    #[test]
+    fn roundtrip() {
+        let report = AttestationReport::from_bytes(&REPORT[..AttestationReport::SIZE]).unwrap();
+        let signer: ReportSigner = pod::from_bytes(&REPORT[AttestationReport::SIZE..]).unwrap();
+
+        let json = serde_json::to_value(Hex(&report)).unwrap();
+        let body = &report.body;
+        assert_eq!(
+            json["body"]["measure"],
+            encode(&report.unmask(body.measure))
+        );
+        assert_eq!(
+            json["body"]["report_data"],
+            encode(&report.unmask(body.report_data))
+        );
+        assert_eq!(
+            json["body"]["policy"],
+            u32::from(body.policy.xor(&report.anonce))
+        );
+        assert_eq!(json["sig_usage"], u32::from(report.sig_usage()));
+        assert_eq!(json["anonce"], u32::from_le(report.anonce));
+        let Hex(again): Hex<AttestationReport> = serde_json::from_value(json).unwrap();
+        assert_eq!(again, report);
+
+        let json = serde_json::to_string(&Hex(signer.clone())).unwrap();
+        assert!(json.contains(&encode(&signer.mac)));
+        let Hex(again): Hex<ReportSigner> = serde_json::from_str(&json).unwrap();
+        assert_eq!(again, signer);
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Binding caller data larger than the report data field, across several
+//! linked reports.
+//!
+//! The data is prefixed with its length as a little-endian `u64`, padded
+//! with zeros and split into segments of [`SEGMENT`] bytes. The report data
+//! of each report holds one segment followed by the SM3 digest of the report
+//! data of the next report, or zeros for the last one. Verifying the first
+//! report therefore binds the data of the whole chain, in order.
+
+use super::{AttestationReport, CsvGuest, ReportSigner};
+use crate::{crypto::gm, error::Error};
+
+use std::io::{self, ErrorKind};
+
+/// The number of caller bytes bound by each report.
+pub const SEGMENT: usize = 32;
+
+/// The length of the digest linking a report to the next one.
+const LINK: usize = 64 - SEGMENT;
+
+fn digest(report_data: &[u8; 64]) -> io::Result<[u8; LINK]> {
+    let mut link = [0u8; LINK];
+    link.copy_from_slice(&gm::sm3(report_data)?[..LINK]);
+    Ok(link)
+}
+
+/// Splits `data` into the report data of linked reports, in order.
+pub fn split_report_data(data: &[u8]) -> io::Result<Vec<[u8; 64]>> {
+    let mut bytes = (data.len() as u64).to_le_bytes().to_vec();
+    bytes.extend_from_slice(data);
+
+    let segments: Vec<_> = bytes.chunks(SEGMENT).collect();
+    let mut chain = vec![[0u8; 64]; segments.len()];
+
+    // Each report links to the next, so the chain is built backwards.
+    let mut link = [0u8; LINK];
+    for (report_data, segment) in chain.iter_mut().zip(&segments).rev() {
+        report_data[..segment.len()].copy_from_slice(segment);
+        report_data[SEGMENT..].copy_from_slice(&link);
+        link = digest(report_data)?;
+    }
+
+    Ok(chain)
+}
+
+/// Checks the links between the report data of linked reports and
+/// reassembles the caller data.
+pub fn join_report_data(chain: &[[u8; 64]]) -> io::Result<Vec<u8>> {
+    let invalid = |msg: &str| io::Error::new(ErrorKind::InvalidData, msg.to_string());
+
+    let (last, rest) = chain
+        .split_last()
+        .ok_or_else(|| invalid("no linked report"))?;
+    if last[SEGMENT..] != [0u8; LINK] {
+        return Err(invalid("the last linked report links to another one"));
+    }
+
+    for (index, (report_data, next)) in rest.iter().zip(&chain[1..]).enumerate() {
+        if report_data[SEGMENT..] != digest(next)? {
+            return Err(invalid(&format!(
+                "linked report {index} does not bind report {}",
+                index + 1
+            )));
+        }
+    }
+
+    let bytes: Vec<u8> = chain
+        .iter()
+        .flat_map(|report_data| report_data[..SEGMENT].iter().copied())
+        .collect();
+
+    let mut len = [0u8; 8];
+    len.copy_from_slice(&bytes[..8]);
+    let len = u64::from_le_bytes(len) as usize;
+
+    let data = &bytes[8..];
+    if len > data.len() || data.len() - len >= SEGMENT || data[len..].iter().any(|&b| b != 0) {
+        return Err(invalid("the linked reports do not hold the data length"));
+    }
+
+    Ok(data[..len].to_vec())
+}
+
+/// Checks that linked reports come from the same guest and reassembles
+/// the caller data they bind.
+///
+/// The signature of each report must be verified separately.
+pub fn join_reports(reports: &[AttestationReport]) -> io::Result<Vec<u8>> {
+    if let Some(first) = reports.first() {
+        let identity = |report: &AttestationReport| {
+            (
+                report.unmask(report.body.user_pubkey_digest),
+                report.unmask(report.body.vm_id),
+                report.unmask(report.body.vm_version),
+                report.unmask(report.body.measure),
+            )
+        };
+
+        if reports[1..].iter().any(|r| identity(r) != identity(first)) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "the linked reports come from different guests",
+            ));
+        }
+    }
+
+    let chain: Vec<_> = reports
+        .iter()
+        .map(|report| report.unmask(report.body.report_data))
+        .collect();
+    join_report_data(&chain)
+}
+
+impl CsvGuest {
+    /// Requests linked reports binding `data`, which may be larger than the
+    /// report data field, see [`join_reports`].
+    ///
+    /// Every report is requested with the same `mnonce`.
+    pub fn get_linked_reports(
+        &self,
+        data: &[u8],
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<Vec<(AttestationReport, ReportSigner)>, Error> {
+        split_report_data(data)?
+            .into_iter()
+            .map(|report_data| self.get_report(Some(report_data), mnonce))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::guest::mock::MockGuest, test_utils::ReportBuilder};
+
+    #[test]
+    fn roundtrip() {
+        for len in [0usize, 1, 24, 25, 56, 57, 1000] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let chain = split_report_data(&data).unwrap();
+            assert_eq!(chain.len(), (len + 8).div_ceil(SEGMENT));
+            assert_eq!(join_report_data(&chain).unwrap(), data);
+        }
+
+        let data = [7u8; 100];
+        let chain = split_report_data(&data).unwrap();
+
+        let mut reordered = chain.clone();
+        reordered.swap(1, 2);
+        assert!(join_report_data(&reordered).is_err());
+        assert!(join_report_data(&chain[..3]).is_err());
+        assert!(join_report_data(&chain[1..]).is_err());
+        assert!(join_report_data(&[]).is_err());
+    }
+
+    #[test]
+    fn reports() {
+        let guest = CsvGuest::builder()
+            .transport(MockGuest::new("NZA9T14052605"))
+            .open()
+            .unwrap();
+
+        let data = [9u8; 200];
+        let reports: Vec<_> = guest
+            .get_linked_reports(&data, None)
+            .unwrap()
+            .into_iter()
+            .map(|(report, _)| report)
+            .collect();
+        assert_eq!(join_reports(&reports).unwrap(), data);
+
+        let chain = split_report_data(&[1u8; 40]).unwrap();
+        let reports = [
+            ReportBuilder::default()
+                .report_data(chain[0])
+                .build()
+                .unwrap(),
+            ReportBuilder::default()
+                .report_data(chain[1])
+                .measure([1u8; 32])
+                .build()
+                .unwrap(),
+        ];
+        assert!(join_reports(&reports).is_err());
+    }
+}
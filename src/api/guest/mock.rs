@@ -0,0 +1,273 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! A software stand-in for the csv-guest device, with scriptable faults.
+//!
+//! Reports are built and signed like the firmware does, using a freshly
+//! generated PEK for every response. The PEK is not endorsed by any CEK,
+//! so only the signer MAC and the report signature can be verified.
+//!
+//! Apart from the signatures, reports are deterministic: the anonce is
+//! fixed and the report data and mnonce echo the request. Code written
+//! against [`super::ReportProvider`] can be tested with
+//! [`MockGuest::into_guest`]:
+//!
+//! ```
+//! use csv_rs::api::guest::{mock::MockGuest, ReportProvider};
+//!
+//! fn report_data(provider: &impl ReportProvider) -> [u8; 64] {
+//!     let (report, _) = provider.get_report(Some([7u8; 64]), None).unwrap();
+//!     report.unmask(report.body.report_data)
+//! }
+//!
+//! assert_eq!(report_data(&MockGuest::default().into_guest()), [7u8; 64]);
+//! ```
+
+use super::{AttestationReport, ReportReq, ReportRsp, SigAlgo, SigUsage, Transport};
+use crate::{
+    certs::{csv::Certificate, Signer, Usage},
+    crypto::gm,
+    error::Indeterminate,
+    util::*,
+};
+
+use std::{
+    collections::VecDeque,
+    io,
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+/// A failure to inject into a single request.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The ioctl fails with the given OS error, e.g. `libc::EBUSY`.
+    Os(i32),
+
+    /// The firmware rejects the request with the given status code.
+    Firmware(u32),
+
+    /// Only the first bytes of the response page are written.
+    Truncated(usize),
+
+    /// The MAC of the signer evidence is corrupted.
+    BadMac,
+
+    /// The report echoes a different mnonce than the requested one.
+    WrongMnonce,
+
+    /// The report signature is corrupted.
+    BadSignature,
+}
+
+/// A shared queue of faults, consumed one per request.
+#[derive(Clone, Default)]
+pub struct Faults(Arc<Mutex<VecDeque<Fault>>>);
+
+impl Faults {
+    /// Schedules a fault for the next request without a pending fault.
+    pub fn push(&self, fault: Fault) {
+        self.lock().push_back(fault);
+    }
+
+    /// The number of faults not injected yet.
+    pub fn pending(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn pop(&self) -> Option<Fault> {
+        self.lock().pop_front()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Fault>> {
+        crate::util::lock::lock(&self.0)
+    }
+}
+
+/// A mock csv-guest device.
+pub struct MockGuest {
+    anonce: u32,
+    sn: [u8; 64],
+    faults: Faults,
+}
+
+impl Default for MockGuest {
+    fn default() -> Self {
+        Self::new("MOCK0000000000")
+    }
+}
+
+impl MockGuest {
+    /// Creates a mock device reporting the given chip serial number.
+    pub fn new(chip_id: &str) -> Self {
+        let mut sn = [0u8; 64];
+        let len = chip_id.len().min(sn.len());
+        sn[..len].copy_from_slice(&chip_id.as_bytes()[..len]);
+
+        Self {
+            anonce: 0x5a5a_a5a5,
+            sn,
+            faults: Faults::default(),
+        }
+    }
+
+    /// The fault queue, which stays usable once the mock is moved into a
+    /// `CsvGuest`.
+    pub fn faults(&self) -> Faults {
+        self.faults.clone()
+    }
+
+    /// A guest handle issuing its requests to this mock.
+    pub fn into_guest(self) -> super::CsvGuest {
+        super::CsvGuest::builder()
+            .transport(self)
+            .open()
+            .expect("the mock transport needs no device")
+    }
+
+    fn respond(&self, request: &ReportReq, fault: Option<Fault>) -> io::Result<Box<ReportRsp>> {
+        let mut rsp = ReportRsp::boxed();
+        let anonce = self.anonce;
+        let report = &mut rsp.report;
+
+        report.anonce = anonce;
+        report.body.report_data = request.data;
+        report.body.mnonce = request.mnonce;
+        if fault == Some(Fault::WrongMnonce) {
+            report.body.mnonce[0] ^= 0xff;
+        }
+
+        let mut body = Vec::new();
+        body.save(&report.body)?;
+        AttestationReport::mask(anonce, &mut body);
+        report.body = (&mut &body[..]).load()?;
+        report.sig_usage = u32::from(SigUsage::Pek).to_le() ^ anonce;
+        report.sig_algo = u32::from(SigAlgo::Sm2Sa).to_le() ^ anonce;
+
+        let (pek, prv) = Certificate::generate(Usage::PEK, None)?;
+        prv.sign(report, String::try_from(Usage::PEK)?)?;
+        if fault == Some(Fault::BadSignature) {
+            report.sig.r[0] ^= 0xff;
+        }
+
+        let signer = &mut rsp.signer;
+        let mut cert = Vec::new();
+        cert.save(&pek)?;
+        signer.pek_cert[..cert.len()].copy_from_slice(&cert);
+        signer.sn = self.sn;
+        AttestationReport::mask(anonce, &mut signer.pek_cert);
+        AttestationReport::mask(anonce, &mut signer.sn);
+
+        signer.mac = gm::hmac_sm3(
+            &request.mnonce,
+            &[&signer.pek_cert, &signer.sn, &signer.reserved],
+        )?;
+        if fault == Some(Fault::BadMac) {
+            signer.mac[0] ^= 0xff;
+        }
+
+        Ok(rsp)
+    }
+}
+
+impl Transport for MockGuest {
+    fn get_report(&mut self, page: &mut [u8]) -> io::Result<()> {
+        let fault = self.faults.pop();
+        match fault {
+            Some(Fault::Os(errno)) => return Err(io::Error::from_raw_os_error(errno)),
+            Some(Fault::Firmware(code)) => return Err(Indeterminate::from(code).into()),
+            _ => (),
+        }
+
+        if page.len() < size_of::<ReportRsp>() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        let request: ReportReq = unsafe { std::ptr::read_unaligned(page.as_ptr() as *const _) };
+        let rsp = self.respond(&request, fault)?;
+
+        let bytes = rsp.as_bytes();
+
+        let len = match fault {
+            Some(Fault::Truncated(len)) => len.min(page.len()),
+            _ => page.len(),
+        };
+        page[..len].copy_from_slice(&bytes[..len]);
+        page[len..].fill(0);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::guest::{CsvGuest, ReportProvider},
+        certs::Verifiable,
+        error::Error,
+    };
+
+    fn guest() -> (CsvGuest, Faults) {
+        let mock = MockGuest::new("NZA9T14052605");
+        let faults = mock.faults();
+        (mock.into_guest(), faults)
+    }
+
+    #[test]
+    fn report() {
+        let (guest, _) = guest();
+        let (mut report, signer) = guest.get_report(Some([7u8; 64]), None).unwrap();
+
+        assert_eq!(report.unmask(report.body.report_data), [7u8; 64]);
+        assert_eq!(report.sig_usage(), SigUsage::Pek);
+        assert_eq!(report.sig_algo(), SigAlgo::Sm2Sa);
+        assert_eq!(signer.chip_id().unwrap().as_str(), "NZA9T14052605");
+        (&signer.pek().unwrap(), &report).verify().unwrap();
+
+        report.sig_algo ^= 0x0001;
+        assert_eq!(report.sig_algo(), SigAlgo::Unknown(0x0005));
+        (&signer.pek().unwrap(), &report).verify().unwrap_err();
+    }
+
+    #[test]
+    fn provider() {
+        fn mnonce(provider: impl ReportProvider) -> [u8; 16] {
+            let (report, _) = provider.get_report(None, Some([3u8; 16])).unwrap();
+            report.unmask(report.body.mnonce)
+        }
+
+        let (guest, _) = guest();
+        let guest = std::sync::Arc::new(guest);
+        assert_eq!(mnonce(&*guest), [3u8; 16]);
+        assert_eq!(mnonce(guest), [3u8; 16]);
+    }
+
+    #[test]
+    fn faults() {
+        let (guest, faults) = guest();
+        let get = || guest.get_report(None, None).map(|_| ());
+
+        faults.push(Fault::Os(libc::EBUSY));
+        get().unwrap();
+        assert_eq!(faults.pending(), 0);
+
+        faults.push(Fault::Firmware(0x01));
+        let Err(Error::IoError(err)) = get() else {
+            panic!("expected an I/O error");
+        };
+        let inner = err.get_ref().and_then(|e| e.downcast_ref::<Error>());
+        assert!(matches!(inner, Some(Error::InvalidPlatformState)));
+
+        for fault in [Fault::BadMac, Fault::WrongMnonce, Fault::Truncated(512)] {
+            faults.push(fault);
+            assert!(matches!(get(), Err(Error::BadSignature)), "{fault:?}");
+        }
+
+        faults.push(Fault::BadSignature);
+        let (report, signer) = guest.get_report(None, None).unwrap();
+        (&signer.pek().unwrap(), &report).verify().unwrap_err();
+    }
+}
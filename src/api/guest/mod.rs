@@ -4,42 +4,179 @@
 //
 
 use crate::error::*;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod builder;
+pub use builder::*;
+mod encryption;
+pub use encryption::{memory_encryption, require_memory_encryption};
 mod ioctl;
 pub use ioctl::*;
+pub mod json;
+mod linked;
+pub use linked::*;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod pool;
+mod provider;
+mod raw;
+pub use pool::Pool;
+pub use provider::ReportProvider;
+pub use raw::AttestationReportExt;
+mod stream;
+pub use stream::SignerMac;
+mod transport;
+pub use transport::*;
 mod types;
-use rand::Rng;
-use std::fs::{File, OpenOptions};
+use rand::RngCore;
+use static_assertions::assert_impl_all;
+use std::{
+    io,
+    os::unix::io::{BorrowedFd, RawFd},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
 pub use types::*;
 
+/// A previous request and the raw response page it produced.
+struct Cached {
+    request: ReportReq,
+    page: Box<[u8]>,
+}
+
+/// The mutable state of a [`CsvGuest`], guarded by its lock.
+struct Inner {
+    transport: Box<dyn Transport>,
+    rng: Box<dyn RngCore + Send>,
+    last: Option<Cached>,
+}
+
 /// A handle to the CSV guest device.
-pub struct CsvGuest(File);
+///
+/// Requests are serialized through an internal lock, so a single handle
+/// may be shared between threads or tasks (e.g. behind an `Arc`).
+///
+/// On CSV and CSV2 guests the csv-guest driver requests the report from
+/// KVM with the `KVM_HC_VM_ATTESTATION` hypercall. The crate does not issue
+/// it itself: KVM rejects hypercalls made outside the kernel, so a guest
+/// without the driver cannot request reports.
+pub struct CsvGuest {
+    inner: Mutex<Inner>,
+    retry: RetryPolicy,
+    timeout: Option<Duration>,
+    cache: bool,
+}
+
+assert_impl_all!(CsvGuest: Send, Sync);
 
 impl CsvGuest {
-    /// Generate a handle to the CSV guest platform via `/dev/csv-guest`.
+    /// Generate a handle to the CSV guest platform via the first csv-guest
+    /// device node found, see [`KNOWN_PATHS`].
+    ///
+    /// The `CSV_GUEST_DEVICE` environment variable ([`PATH_ENV`]) overrides
+    /// the node if set, and fails the call if it names no device. Use
+    /// [`CsvGuest::open_at`] to ignore the environment.
     pub fn open() -> std::io::Result<CsvGuest> {
-        let file = OpenOptions::new().read(true).open("/dev/csv-guest")?;
-        Ok(CsvGuest(file))
+        Self::builder().open()
+    }
+
+    /// Generate a handle to the CSV guest platform via the csv-guest device
+    /// node at `path`, e.g. `/dev/csv-guest2`.
+    pub fn open_at(path: impl Into<std::path::PathBuf>) -> std::io::Result<CsvGuest> {
+        Self::builder().path(path).open()
+    }
+
+    /// Returns a builder to configure the handle before opening it.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// The file descriptor of the csv-guest device node the handle issues
+    /// its requests on, e.g. to register it with a supervisor, or `None`
+    /// for transports without one.
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        self.lock().transport.raw_fd()
+    }
+
+    /// Borrows the file descriptor of the csv-guest device node, or
+    /// `None` for transports without one, see [`CsvGuest::raw_fd`].
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        // SAFETY: the transport is owned by the handle and never replaced,
+        // so its descriptor stays open for as long as `self` is borrowed.
+        self.raw_fd()
+            .map(|fd| unsafe { BorrowedFd::borrow_raw(fd) })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        crate::util::lock::lock(&self.inner)
+    }
+
+    /// Issues the request page to the transport, honoring the retry
+    /// policy and the timeout.
+    fn issue(&self, inner: &mut Inner, page: &mut [u8]) -> io::Result<()> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let mut retries = 0;
+
+        loop {
+            let err = match inner.transport.get_report(page) {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            if retries >= self.retry.retries || !RetryPolicy::is_transient(&err) {
+                return Err(err);
+            }
+
+            if let Some(deadline) = deadline {
+                if Instant::now() + self.retry.delay >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "CSV guest request timed out",
+                    ));
+                }
+            }
+
+            retries += 1;
+            std::thread::sleep(self.retry.delay);
+        }
     }
 
     /// Requests an attestation report from the HYGON Secure Processor.
     pub fn get_report(
-        &mut self,
+        &self,
         data: Option<[u8; 64]>,
         mnonce: Option<[u8; 16]>,
     ) -> Result<(AttestationReport, ReportSigner), Error> {
+        let rsp = self.get_report_boxed(data, mnonce)?;
+        Ok((rsp.report, rsp.signer))
+    }
+
+    /// Like [`CsvGuest::get_report`], returning the whole response page.
+    ///
+    /// The page lives on the heap, which avoids large stack frames and
+    /// copies, e.g. in deep async call stacks.
+    pub fn get_report_boxed(
+        &self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<Box<ReportRsp>, Error> {
+        let cacheable = self.cache && mnonce.is_some();
+
+        let mut inner = self.lock();
+
         let mut mnonce_value = [0u8; 16];
         if let Some(mnonce) = mnonce {
             mnonce_value = mnonce;
         } else {
-            let mut rng = rand::thread_rng();
-            for element in &mut mnonce_value {
-                *element = rng.gen();
-            }
+            inner
+                .rng
+                .try_fill_bytes(&mut mnonce_value)
+                .map_err(io::Error::other)?;
         }
 
         let report_request = ReportReq::new(data, mnonce_value)?;
 
-        let mut report_response = ReportRsp::default();
+        let mut report_response = ReportRsp::boxed();
 
         // Convert ReportReq to bytes
         let request_bytes: &[u8] = unsafe {
@@ -47,24 +184,128 @@ impl CsvGuest {
             std::slice::from_raw_parts(req_ptr, std::mem::size_of::<ReportReq>())
         };
 
-        let response_bytes: &mut [u8] = unsafe {
-            let rsp_ptr = &mut report_response as *mut ReportRsp as *mut u8;
-            std::slice::from_raw_parts_mut(rsp_ptr, std::mem::size_of::<ReportRsp>())
+        let response_bytes = report_response.as_bytes_mut();
+
+        match inner.last.as_ref() {
+            Some(last) if cacheable && last.request == report_request => {
+                response_bytes.copy_from_slice(&last.page);
+            }
+            _ => {
+                // Copy bytes from report_request to report_response
+                response_bytes[..request_bytes.len()].copy_from_slice(request_bytes);
+
+                self.issue(&mut inner, response_bytes)?;
+
+                if cacheable {
+                    inner.last = Some(Cached {
+                        request: report_request,
+                        page: response_bytes.into(),
+                    });
+                }
+            }
+        }
+        drop(inner);
+
+        let ReportRsp { report, signer, .. } = &mut *report_response;
+        signer.verify(&mnonce_value, &report.body.mnonce, &report.anonce)?;
+
+        Ok(report_response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Fails with the given errors before leaving the page untouched.
+    struct Flaky(Vec<io::Error>);
+
+    impl Transport for Flaky {
+        fn get_report(&mut self, _: &mut [u8]) -> io::Result<()> {
+            match self.0.pop() {
+                Some(err) => Err(err),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn retry_transient() {
+        let guest = CsvGuest::builder()
+            .retry(RetryPolicy {
+                retries: 2,
+                delay: Duration::ZERO,
+            })
+            .transport(Flaky(vec![io::ErrorKind::Interrupted.into()]))
+            .open()
+            .unwrap();
+
+        guest.issue(&mut guest.lock(), &mut [0u8; 16]).unwrap();
+    }
+
+    /// Records the mnonce of every request, then fails it.
+    struct Recorder(std::sync::Arc<Mutex<Vec<[u8; 16]>>>);
+
+    impl Transport for Recorder {
+        fn get_report(&mut self, page: &mut [u8]) -> io::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(page[64..80].try_into().unwrap());
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn injected_rng() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mnonces = |seed| {
+            let seen = std::sync::Arc::new(Mutex::new(Vec::new()));
+            let guest = CsvGuest::builder()
+                .transport(Recorder(seen.clone()))
+                .rng(StdRng::seed_from_u64(seed))
+                .open()
+                .unwrap();
+
+            for _ in 0..2 {
+                assert!(guest.get_report(None, None).is_err());
+            }
+
+            let seen = seen.lock().unwrap().clone();
+            seen
         };
 
-        // Copy bytes from report_request to report_response
-        response_bytes[..request_bytes.len()].copy_from_slice(request_bytes);
+        let first = mnonces(1);
+        assert_ne!(first[0], first[1]);
+        assert_eq!(first, mnonces(1));
+        assert_ne!(first, mnonces(2));
+    }
 
-        let mut guest_report_request = GuestReportRequest::new(response_bytes.as_ref());
+    #[test]
+    fn no_retry_permanent() {
+        let guest = CsvGuest::builder()
+            .transport(Flaky(vec![io::ErrorKind::PermissionDenied.into()]))
+            .open()
+            .unwrap();
 
-        CSV_GET_REPORT.ioctl(&mut self.0, &mut guest_report_request)?;
+        let err = guest.issue(&mut guest.lock(), &mut [0u8; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
 
-        report_response.signer.verify(
-            &mnonce_value,
-            &report_response.report.body.mnonce,
-            &report_response.report.anonce,
-        )?;
+    #[test]
+    fn retries_exhausted() {
+        let errors = (0..3).map(|_| io::ErrorKind::WouldBlock.into()).collect();
+        let guest = CsvGuest::builder()
+            .retry(RetryPolicy {
+                retries: 1,
+                delay: Duration::ZERO,
+            })
+            .transport(Flaky(errors))
+            .open()
+            .unwrap();
 
-        Ok((report_response.report, report_response.signer))
+        let err = guest.issue(&mut guest.lock(), &mut [0u8; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
     }
 }
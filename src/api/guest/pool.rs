@@ -0,0 +1,135 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Several guest handles serving requests concurrently.
+
+use super::{AttestationReport, Builder, CsvGuest, ReportRsp, ReportSigner};
+use crate::error::Error;
+
+use std::{
+    io,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A pool of pre-opened [`CsvGuest`] handles.
+///
+/// A single handle serializes its requests, so services attesting each
+/// incoming connection would all wait on the same device. The pool hands
+/// requests to its handles in turn instead, each with its own file
+/// descriptor and lock.
+pub struct Pool {
+    guests: Vec<CsvGuest>,
+    next: AtomicUsize,
+}
+
+impl Pool {
+    /// Creates a pool from already opened handles.
+    pub fn new(guests: Vec<CsvGuest>) -> io::Result<Self> {
+        if guests.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a guest pool needs at least one handle",
+            ));
+        }
+
+        Ok(Self {
+            guests,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Opens `size` handles with the default configuration, see
+    /// [`CsvGuest::open`].
+    pub fn open(size: usize) -> io::Result<Self> {
+        Self::with_builder(size, CsvGuest::builder)
+    }
+
+    /// Opens `size` handles, each configured by a builder from `builder`.
+    pub fn with_builder(size: usize, mut builder: impl FnMut() -> Builder) -> io::Result<Self> {
+        Self::new(
+            (0..size)
+                .map(|_| builder().open())
+                .collect::<io::Result<_>>()?,
+        )
+    }
+
+    /// The number of handles.
+    pub fn len(&self) -> usize {
+        self.guests.len()
+    }
+
+    /// Always false, as a pool holds at least one handle.
+    pub fn is_empty(&self) -> bool {
+        self.guests.is_empty()
+    }
+
+    /// The handle the next request should go to.
+    pub fn guest(&self) -> &CsvGuest {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.guests.len();
+        &self.guests[index]
+    }
+
+    /// Requests an attestation report through the next handle, see
+    /// [`CsvGuest::get_report`].
+    pub fn get_report(
+        &self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<(AttestationReport, ReportSigner), Error> {
+        self.guest().get_report(data, mnonce)
+    }
+
+    /// Like [`Pool::get_report`], returning the whole response page.
+    pub fn get_report_boxed(
+        &self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<Box<ReportRsp>, Error> {
+        self.guest().get_report_boxed(data, mnonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::guest::{mock::MockGuest, Transport};
+
+    use std::sync::{Arc, Mutex};
+
+    /// Counts the requests of the handle with the given index.
+    struct Counter(usize, MockGuest, Arc<Mutex<Vec<usize>>>);
+
+    impl Transport for Counter {
+        fn get_report(&mut self, page: &mut [u8]) -> io::Result<()> {
+            self.2.lock().unwrap()[self.0] += 1;
+            self.1.get_report(page)
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        let counts = Arc::new(Mutex::new(vec![0; 3]));
+        let mut index = 0;
+        let pool = Pool::with_builder(3, || {
+            index += 1;
+            let transport = Counter(index - 1, MockGuest::new("NZA9T14052605"), counts.clone());
+            CsvGuest::builder().transport(transport)
+        })
+        .unwrap();
+        assert_eq!(pool.len(), 3);
+
+        std::thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    for _ in 0..2 {
+                        pool.get_report(Some([1u8; 64]), None).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(*counts.lock().unwrap(), [2, 2, 2]);
+
+        assert!(Pool::new(Vec::new()).is_err());
+    }
+}
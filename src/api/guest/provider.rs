@@ -0,0 +1,52 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! An abstraction over the sources of attestation reports.
+
+use super::{AttestationReport, CsvGuest, ReportSigner};
+use crate::error::Error;
+
+use std::{rc::Rc, sync::Arc};
+
+/// A source of attestation reports.
+///
+/// Applications taking a `ReportProvider` instead of a [`CsvGuest`] can be
+/// tested without HYGON hardware, e.g. against a [`CsvGuest`] built on the
+/// mock transport of the `mock` feature.
+pub trait ReportProvider {
+    /// Requests an attestation report, see [`CsvGuest::get_report`].
+    fn get_report(
+        &self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<(AttestationReport, ReportSigner), Error>;
+}
+
+impl ReportProvider for CsvGuest {
+    fn get_report(
+        &self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<(AttestationReport, ReportSigner), Error> {
+        CsvGuest::get_report(self, data, mnonce)
+    }
+}
+
+macro_rules! forward {
+    ($($ptr:ty),*) => {
+        $(
+            impl<T: ReportProvider + ?Sized> ReportProvider for $ptr {
+                fn get_report(
+                    &self,
+                    data: Option<[u8; 64]>,
+                    mnonce: Option<[u8; 16]>,
+                ) -> Result<(AttestationReport, ReportSigner), Error> {
+                    (**self).get_report(data, mnonce)
+                }
+            }
+        )*
+    };
+}
+
+forward!(&T, Box<T>, Rc<T>, Arc<T>);
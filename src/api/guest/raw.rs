@@ -0,0 +1,118 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! An attestation report carried as the bytes the firmware signed.
+
+use super::AttestationReport;
+use crate::{pod, util::hex};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{io, mem::size_of, ops::Deref};
+
+/// The size of a raw attestation report.
+const SIZE: usize = size_of::<AttestationReport>();
+
+/// An [`AttestationReport`] along with its raw bytes.
+///
+/// The raw bytes are what gets serialized, hex-encoded, so the report
+/// comes back byte for byte from storage layers which re-encode the
+/// structured representation, and its signature stays verifiable. The
+/// parsed report is available through `Deref`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttestationReportExt {
+    raw: [u8; SIZE],
+    report: AttestationReport,
+}
+
+impl AttestationReportExt {
+    /// Parses a report from exactly its raw bytes.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let report = pod::from_bytes(bytes)?;
+        let mut raw = [0u8; SIZE];
+        raw.copy_from_slice(bytes);
+        Ok(Self { raw, report })
+    }
+
+    /// The raw bytes of the report, as the firmware returned them.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// The parsed report.
+    pub fn report(&self) -> &AttestationReport {
+        &self.report
+    }
+
+    /// Returns the parsed report.
+    pub fn into_inner(self) -> AttestationReport {
+        self.report
+    }
+}
+
+impl From<AttestationReport> for AttestationReportExt {
+    fn from(report: AttestationReport) -> Self {
+        let mut raw = [0u8; SIZE];
+        raw.copy_from_slice(pod::as_bytes(&report));
+        Self { raw, report }
+    }
+}
+
+impl Deref for AttestationReportExt {
+    type Target = AttestationReport;
+
+    fn deref(&self) -> &AttestationReport {
+        &self.report
+    }
+}
+
+impl AsRef<[u8]> for AttestationReportExt {
+    fn as_ref(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
+impl Serialize for AttestationReportExt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        hex::serialize(&self.raw, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AttestationReportExt {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw: [u8; SIZE] = hex::deserialize(deserializer)?;
+        Self::from_bytes(&raw).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::guest::ReportSigner;
+
+    const REPORT: &[u8] = include_bytes!("../../../tests/test_data/report.cert");
+
+    #[test]
+    fn roundtrip() {
+        let report = AttestationReportExt::from_bytes(&REPORT[..SIZE]).unwrap();
+        assert_eq!(report.as_bytes(), &REPORT[..SIZE]);
+
+        let json = serde_json::to_string(&report).unwrap();
+        let again: AttestationReportExt = serde_json::from_str(&json).unwrap();
+        assert_eq!(again, report);
+        assert_eq!(
+            AttestationReportExt::from(again.clone().into_inner()),
+            again
+        );
+
+        let mut signer: ReportSigner = pod::from_bytes(&REPORT[SIZE..]).unwrap();
+        let mnonce = again.unmask(again.body.mnonce);
+        signer
+            .verify(&mnonce, &again.body.mnonce, &again.anonce)
+            .unwrap();
+        again.verify_signature(&signer.pek().unwrap()).unwrap();
+
+        assert!(AttestationReportExt::from_bytes(&REPORT[..SIZE - 1]).is_err());
+        assert!(serde_json::from_str::<AttestationReportExt>(r#""00""#).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verifying the MAC of a [`ReportSigner`] as its bytes go by, without
+//! holding the whole structure.
+
+use super::{AttestationReport, ReportSigner};
+use crate::{
+    crypto::gm::{mac_eq, HmacSm3},
+    error::Error,
+};
+
+use std::{
+    io::{self, Read, Write},
+    mem::size_of,
+};
+
+/// The number of bytes covered by the MAC: the PEK certificate, the serial
+/// number and the reserved field.
+const COVERED: usize = size_of::<ReportSigner>() - 32;
+
+/// Computes the HMAC-SM3 of a [`ReportSigner`] written to it in its raw,
+/// masked form, e.g. while proxying evidence.
+///
+/// ```
+/// # use csv_rs::api::guest::SignerMac;
+/// # fn check(mut stream: impl std::io::Read, mnonce: [u8; 16], masked: [u8; 16], anonce: u32)
+/// #     -> Result<(), csv_rs::error::Error> {
+/// let mut mac = SignerMac::new(&mnonce, &masked, anonce)?;
+/// std::io::copy(&mut stream, &mut mac)?;
+/// mac.finish()
+/// # }
+/// ```
+pub struct SignerMac {
+    inner: HmacSm3,
+    written: usize,
+    mac: [u8; 32],
+}
+
+impl SignerMac {
+    /// Starts verifying the signer of a report requested with
+    /// `input_mnonce`, given the masked `mnonce` and `anonce` of the
+    /// report.
+    pub fn new(input_mnonce: &[u8], mnonce: &[u8], anonce: u32) -> Result<Self, Error> {
+        let mut key = mnonce.to_vec();
+        AttestationReport::mask(anonce, &mut key);
+
+        if key != input_mnonce {
+            return Err(Error::BadSignature);
+        }
+
+        Ok(Self {
+            inner: HmacSm3::new(&key)?,
+            written: 0,
+            mac: [0u8; 32],
+        })
+    }
+
+    /// Checks the MAC once the whole signer was written.
+    pub fn finish(self) -> Result<(), Error> {
+        if self.written != size_of::<ReportSigner>() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the report signer is truncated",
+            )
+            .into());
+        }
+
+        if !mac_eq(&self.inner.finish()?, &self.mac) {
+            return Err(Error::BadSignature);
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for SignerMac {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written + buf.len() > size_of::<ReportSigner>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected data after the report signer",
+            ));
+        }
+
+        let covered = COVERED.saturating_sub(self.written).min(buf.len());
+        self.inner.update(&buf[..covered])?;
+
+        let start = (self.written + covered).saturating_sub(COVERED);
+        let mac = &buf[covered..];
+        self.mac[start..start + mac.len()].copy_from_slice(mac);
+
+        self.written += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ReportSigner {
+    /// Copies a raw signer from `reader` to `writer`, verifying its MAC on
+    /// the way, see [`SignerMac`].
+    ///
+    /// The bytes are forwarded as read, still masked. Exactly the size of a
+    /// signer is read.
+    pub fn verify_stream(
+        reader: impl Read,
+        mut writer: impl Write,
+        input_mnonce: &[u8],
+        mnonce: &[u8],
+        anonce: u32,
+    ) -> Result<(), Error> {
+        let mut mac = SignerMac::new(input_mnonce, mnonce, anonce)?;
+        let mut reader = reader.take(size_of::<ReportSigner>() as u64);
+
+        let mut buf = [0u8; 512];
+        loop {
+            let len = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            mac.write_all(&buf[..len])?;
+            writer.write_all(&buf[..len])?;
+        }
+
+        mac.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::guest::AttestationReport;
+
+    fn fixture() -> (AttestationReport, Vec<u8>, [u8; 16]) {
+        let bytes = std::fs::read("tests/test_data/report.cert").unwrap();
+        let (report, signer) = bytes.split_at(size_of::<AttestationReport>());
+        let report: AttestationReport = crate::pod::from_bytes(report).unwrap();
+        let mnonce = report.unmask(report.body.mnonce);
+        (report, signer.to_vec(), mnonce)
+    }
+
+    #[test]
+    fn stream() {
+        let (report, signer, mnonce) = fixture();
+        let masked = report.body.mnonce;
+
+        let mut copy = Vec::new();
+        ReportSigner::verify_stream(&signer[..], &mut copy, &mnonce, &masked, report.anonce)
+            .unwrap();
+        assert_eq!(copy, signer);
+
+        // Chunks of any size, across the MAC boundary.
+        let mut mac = SignerMac::new(&mnonce, &masked, report.anonce).unwrap();
+        for chunk in signer.chunks(7) {
+            mac.write_all(chunk).unwrap();
+        }
+        mac.finish().unwrap();
+
+        let mut tampered = signer.clone();
+        tampered[100] ^= 1;
+        assert!(matches!(
+            ReportSigner::verify_stream(&tampered[..], io::sink(), &mnonce, &masked, report.anonce),
+            Err(Error::BadSignature)
+        ));
+
+        let truncated = &signer[..signer.len() - 1];
+        assert!(ReportSigner::verify_stream(
+            truncated,
+            io::sink(),
+            &mnonce,
+            &masked,
+            report.anonce
+        )
+        .is_err());
+        assert!(SignerMac::new(&[0u8; 16], &masked, report.anonce).is_err());
+    }
+}
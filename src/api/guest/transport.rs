@@ -0,0 +1,241 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Backends able to carry guest requests to the HYGON Secure Processor.
+
+use super::ioctl::*;
+use crate::util::{deadline, lock::lock};
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The default device node exported by the csv-guest kernel driver.
+pub const DEFAULT_PATH: &str = "/dev/csv-guest";
+
+/// The device node names used by the csv-guest driver across kernel
+/// versions, in probing order.
+pub const KNOWN_PATHS: &[&str] = &[DEFAULT_PATH, "/dev/csv_guest"];
+
+/// The environment variable overriding the csv-guest device node, for
+/// kernels exposing it under another name.
+pub const PATH_ENV: &str = "CSV_GUEST_DEVICE";
+
+/// A channel to the HYGON Secure Processor used by [`super::CsvGuest`].
+///
+/// The request page handed to [`Transport::get_report`] holds the
+/// `ReportReq` on input and receives the `ReportRsp` on output, exactly
+/// as the csv-guest kernel driver expects it.
+///
+/// Transports must be `Send` so that the owning handle can be shared
+/// across threads.
+pub trait Transport: Send {
+    /// Issues a GET_REPORT request using the given request/response page.
+    fn get_report(&mut self, page: &mut [u8]) -> std::io::Result<()>;
+
+    /// The file descriptor the transport issues its requests on, if any.
+    fn raw_fd(&self) -> Option<RawFd> {
+        None
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn get_report(&mut self, page: &mut [u8]) -> std::io::Result<()> {
+        (**self).get_report(page)
+    }
+
+    fn raw_fd(&self) -> Option<RawFd> {
+        (**self).raw_fd()
+    }
+}
+
+/// The in-guest device node transport.
+pub struct Device {
+    file: Arc<Mutex<File>>,
+    fd: RawFd,
+    timeout: Option<Duration>,
+}
+
+impl Device {
+    /// Opens the csv-guest device node at `path`.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        Ok(OwnedFd::from(file).into())
+    }
+
+    /// Opens the device node named by [`PATH_ENV`] if set, otherwise the
+    /// first device node of [`KNOWN_PATHS`] that exists.
+    pub fn probe() -> std::io::Result<Self> {
+        if let Some(path) = std::env::var_os(PATH_ENV) {
+            return Self::open(path);
+        }
+
+        for path in KNOWN_PATHS {
+            match Self::open(path) {
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                result => return result,
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "no csv-guest device found (tried {})",
+                KNOWN_PATHS.join(", ")
+            ),
+        ))
+    }
+
+    /// Bounds the time a single GET_REPORT ioctl may take.
+    ///
+    /// A timed out request is abandoned, not cancelled: the kernel keeps
+    /// working on a private copy of the request page, and later requests
+    /// wait for it to complete, timing out in turn.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    fn ioctl(file: &mut File, page: &mut [u8]) -> std::io::Result<()> {
+        let mut guest_report_request = GuestReportRequest::new(page);
+        CSV_GET_REPORT.ioctl(file, &mut guest_report_request)?;
+        Ok(())
+    }
+}
+
+impl From<OwnedFd> for Device {
+    /// Uses an already opened csv-guest device node, e.g. one passed in by
+    /// a privileged parent or opened before entering a sandbox.
+    fn from(fd: OwnedFd) -> Self {
+        Device {
+            fd: fd.as_raw_fd(),
+            file: Arc::new(Mutex::new(fd.into())),
+            timeout: None,
+        }
+    }
+}
+
+impl From<Device> for OwnedFd {
+    fn from(device: Device) -> Self {
+        deadline::into_file(device.file).into()
+    }
+}
+
+impl FromRawFd for Device {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        OwnedFd::from_raw_fd(fd).into()
+    }
+}
+
+impl IntoRawFd for Device {
+    fn into_raw_fd(self) -> RawFd {
+        OwnedFd::from(self).into_raw_fd()
+    }
+}
+
+impl AsFd for Device {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: the file is owned by the device and never replaced, so
+        // the descriptor stays open for as long as `self` is borrowed.
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl AsRawFd for Device {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Transport for Device {
+    fn raw_fd(&self) -> Option<RawFd> {
+        Some(self.as_raw_fd())
+    }
+
+    fn get_report(&mut self, page: &mut [u8]) -> std::io::Result<()> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Self::ioctl(&mut lock(&self.file), page),
+        };
+
+        let mut owned = page.to_vec();
+        let owned = deadline::run_locked(timeout, &self.file, move |file| {
+            Self::ioctl(file, &mut owned).map(|_| owned)
+        })??;
+
+        page.copy_from_slice(&owned);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{guest::CsvGuest, platform::Firmware};
+
+    #[test]
+    fn fds() {
+        let fd = OwnedFd::from(File::open("/dev/null").unwrap());
+        let raw = fd.as_raw_fd();
+
+        let device = Device::from(fd);
+        assert_eq!(device.as_fd().as_raw_fd(), raw);
+        let guest = CsvGuest::builder().fd(device.into()).open().unwrap();
+        assert_eq!(guest.raw_fd(), Some(raw));
+        assert_eq!(guest.fd().map(|fd| fd.as_raw_fd()), Some(raw));
+
+        let fd = guest.fd().unwrap().try_clone_to_owned().unwrap();
+        let raw = fd.as_raw_fd();
+        let guest = CsvGuest::from(fd);
+        assert_eq!(guest.raw_fd(), Some(raw));
+
+        let raw = guest
+            .fd()
+            .unwrap()
+            .try_clone_to_owned()
+            .unwrap()
+            .into_raw_fd();
+        let guest = unsafe { CsvGuest::from_raw_fd(raw) };
+        assert_eq!(guest.raw_fd(), Some(raw));
+
+        let guest = CsvGuest::builder()
+            .transport(crate::api::guest::mock::MockGuest::default())
+            .open()
+            .unwrap();
+        assert_eq!(guest.raw_fd(), None);
+        assert!(guest.fd().is_none());
+
+        let firmware = Firmware::from(OwnedFd::from(File::open("/dev/null").unwrap()));
+        let raw = firmware.as_raw_fd();
+        assert_eq!(firmware.as_fd().as_raw_fd(), raw);
+        assert_eq!(firmware.into_raw_fd(), raw);
+        drop(unsafe { OwnedFd::from_raw_fd(raw) });
+    }
+
+    #[test]
+    fn path_env() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("csv-guest");
+        File::create(&path).unwrap();
+
+        // The only test of this binary to set it.
+        std::env::set_var(PATH_ENV, &path);
+        let guest = CsvGuest::open().unwrap();
+        let fd = guest.raw_fd().unwrap();
+        assert_eq!(
+            std::fs::read_link(format!("/proc/self/fd/{fd}")).unwrap(),
+            path.canonicalize().unwrap()
+        );
+
+        std::env::set_var(PATH_ENV, tmp.path().join("missing"));
+        let err = CsvGuest::open().err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        std::env::remove_var(PATH_ENV);
+    }
+}
@@ -3,30 +3,33 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use super::SignerMac;
 use crate::error::*;
+pub use crate::layout::report::{AttestationReport, Body, GuestPolicy, ReportSigner};
+#[cfg(feature = "openssl")]
+use crate::{
+    certs::Signer,
+    crypto::{sm, PrivateKey},
+};
 use crate::{
     certs::{csv::Certificate, Usage, Verifiable},
-    crypto::{sig::ecdsa, PublicKey, Signature},
+    crypto::{gm, sig::ecdsa, PublicKey, Signature},
     util::*,
+    ChipId,
 };
 
-use openssl::{
-    hash::{Hasher, MessageDigest},
-    pkey, sign,
-};
+use codicon::Decoder;
 
 use static_assertions::const_assert;
 
-use serde::{Deserialize, Serialize};
-use serde_big_array::BigArray;
-use std::io::Write;
-
-use bitfield::bitfield;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, io::Write, mem::size_of, str::FromStr};
+use uuid::Uuid;
 
 /// Data provieded by the guest owner for requesting an attestation report
 /// from the HYGON Secure Processor.
 #[repr(C)]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq)]
 pub struct ReportReq {
     /// Guest-provided data to be included in the attestation report
     pub data: [u8; 64],
@@ -46,6 +49,16 @@ impl Default for ReportReq {
     }
 }
 
+impl fmt::Debug for ReportReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReportReq")
+            .field("data", &self.data)
+            .field("mnonce", &redact::Redacted(&self.mnonce))
+            .field("hash", &self.hash)
+            .finish()
+    }
+}
+
 impl ReportReq {
     pub fn new(data: Option<[u8; 64]>, mnonce: [u8; 16]) -> Result<Self, Error> {
         let mut request = Self::default();
@@ -62,11 +75,10 @@ impl ReportReq {
     }
 
     fn calculate_hash(&mut self) -> Result<(), Error> {
-        let mut hasher = Hasher::new(MessageDigest::sm3())?;
+        let mut hasher = gm::Sm3::new()?;
         hasher.update(self.data.as_ref())?;
         hasher.update(self.mnonce.as_ref())?;
-        let hash = &hasher.finish()?;
-        self.hash.copy_from_slice(hash.as_ref());
+        self.hash = hasher.finish()?;
 
         Ok(())
     }
@@ -75,8 +87,10 @@ impl ReportReq {
 /// The response from the PSP containing the generated attestation report.
 ///
 /// The Report is padded to exactly 4096 Bytes to make sure the page size
-/// matches.
-#[repr(C)]
+/// matches. It is page aligned as well, and at that size it is best kept on
+/// the heap, see [`ReportRsp::boxed`].
+#[repr(C, align(4096))]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ReportRsp {
     /// The attestation report generated by the firmware.
     pub report: AttestationReport,
@@ -89,6 +103,43 @@ pub struct ReportRsp {
 
 // Compile-time check that the size is what is expected.
 const_assert!(std::mem::size_of::<ReportRsp>() == 4096);
+const_assert!(std::mem::align_of::<ReportRsp>() == 4096);
+
+impl ReportRsp {
+    /// Allocates a zeroed, page-aligned response on the heap without
+    /// building it on the stack first.
+    pub fn boxed() -> Box<Self> {
+        let layout = std::alloc::Layout::new::<Self>();
+        unsafe {
+            // All-zero bytes are a valid response: it only holds integers.
+            let ptr = std::alloc::alloc_zeroed(layout) as *mut Self;
+            if ptr.is_null() {
+                std::alloc::handle_alloc_error(layout);
+            }
+            Box::from_raw(ptr)
+        }
+    }
+
+    /// The raw bytes of the response page.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                std::mem::size_of::<Self>(),
+            )
+        }
+    }
+
+    /// The raw bytes of the response page, as handed to the device.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self as *mut Self as *mut u8,
+                std::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
 
 impl Default for ReportRsp {
     fn default() -> Self {
@@ -101,19 +152,6 @@ impl Default for ReportRsp {
     }
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
-pub struct Body {
-    pub user_pubkey_digest: [u8; 32],
-    pub vm_id: [u8; 16],
-    pub vm_version: [u8; 16],
-    #[serde(with = "BigArray")]
-    pub report_data: [u8; 64],
-    pub mnonce: [u8; 16],
-    pub measure: [u8; 32],
-    pub policy: GuestPolicy,
-}
-
 impl Default for Body {
     fn default() -> Self {
         Self {
@@ -128,16 +166,131 @@ impl Default for Body {
     }
 }
 
-/// Data provieded by the guest owner for requesting an attestation report
-/// from the HYGON Secure Processor.
-#[repr(C)]
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AttestationReport {
-    pub body: Body,
-    pub sig_usage: u32,
-    pub sig_algo: u32,
-    pub anonce: u32,
-    pub sig: ecdsa::Signature,
+/// The version the guest owner assigned to a VM at launch time.
+///
+/// The firmware treats the 16 bytes as opaque. By convention they hold a
+/// NUL-padded ASCII string (e.g. `"1.2.0"`), which is how the version is
+/// displayed and serialized; any other content is rendered as lowercase hex.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VmVersion(pub [u8; 16]);
+
+impl VmVersion {
+    /// Returns the version as text if it is a NUL-padded ASCII string.
+    pub fn as_str(&self) -> Option<&str> {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(self.0.len());
+        let (text, padding) = self.0.split_at(len);
+
+        if padding.iter().any(|&b| b != 0) || !text.iter().all(|b| b.is_ascii_graphic()) {
+            return None;
+        }
+
+        std::str::from_utf8(text).ok()
+    }
+}
+
+impl fmt::Display for VmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(text) = self.as_str() {
+            return write!(f, "{text}");
+        }
+
+        for b in self.0.iter() {
+            write!(f, "{b:02x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for VmVersion {
+    type Err = Error;
+
+    /// Parses either the 32 hex digit form or an ASCII string of at most 16 bytes.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut version = [0u8; 16];
+
+        if s.len() == 32 && s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            for (i, byte) in version.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)
+                    .map_err(|_| Error::InvalidParam)?;
+            }
+        } else if s.len() <= version.len() && s.bytes().all(|b| b.is_ascii_graphic()) {
+            version[..s.len()].copy_from_slice(s.as_bytes());
+        } else {
+            return Err(Error::InvalidParam);
+        }
+
+        Ok(Self(version))
+    }
+}
+
+impl Serialize for VmVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for VmVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// The key usage a report signature claims, as defined by the firmware.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SigUsage {
+    /// Signed by the Platform Endorsement Key.
+    Pek,
+
+    /// A value this crate does not know about.
+    Unknown(u32),
+}
+
+impl From<u32> for SigUsage {
+    fn from(value: u32) -> Self {
+        match value {
+            0x1002 => Self::Pek,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<SigUsage> for u32 {
+    fn from(value: SigUsage) -> Self {
+        match value {
+            SigUsage::Pek => 0x1002,
+            SigUsage::Unknown(other) => other,
+        }
+    }
+}
+
+/// The algorithm a report signature claims, as defined by the firmware.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SigAlgo {
+    /// SM2 signature over an SM3 digest.
+    Sm2Sa,
+
+    /// A value this crate does not know about.
+    Unknown(u32),
+}
+
+impl From<u32> for SigAlgo {
+    fn from(value: u32) -> Self {
+        match value {
+            0x0004 => Self::Sm2Sa,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<SigAlgo> for u32 {
+    fn from(value: SigAlgo) -> Self {
+        match value {
+            SigAlgo::Sm2Sa => 0x0004,
+            SigAlgo::Unknown(other) => other,
+        }
+    }
 }
 
 impl Default for AttestationReport {
@@ -152,6 +305,128 @@ impl Default for AttestationReport {
     }
 }
 
+impl AttestationReport {
+    /// The size of a report, as signed by the firmware.
+    pub const SIZE: usize = size_of::<AttestationReport>();
+
+    /// Parses a report from exactly its [`AttestationReport::SIZE`] raw
+    /// bytes, e.g. as received over the network.
+    ///
+    /// The layout is the firmware's: fields in declaration order without
+    /// padding, integers as little-endian bytes, see
+    /// [`AttestationReport::unmask`]. The bytes need no alignment.
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        crate::pod::from_bytes(bytes)
+    }
+
+    /// The raw bytes of the report, the inverse of
+    /// [`AttestationReport::from_bytes`].
+    pub fn as_bytes(&self) -> &[u8] {
+        crate::pod::as_bytes(self)
+    }
+
+    /// Applies the anonce mask of the firmware to `bytes` in place, as it
+    /// does to every body field and to the PEK certificate and serial
+    /// number of the signer. Masking twice removes the mask.
+    ///
+    /// Like every integer of the firmware structures, `anonce` holds the
+    /// little-endian bytes the firmware wrote, so the mask is its in-memory
+    /// representation whatever the byte order of the host.
+    pub fn mask(anonce: u32, bytes: &mut [u8]) {
+        let anonce = anonce.to_ne_bytes();
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte ^= anonce[index % 4];
+        }
+    }
+
+    /// Removes the anonce mask the firmware applies to every body field,
+    /// see [`AttestationReport::mask`].
+    pub fn unmask<const N: usize>(&self, mut field: [u8; N]) -> [u8; N] {
+        Self::mask(self.anonce, &mut field);
+        field
+    }
+
+    /// The guest owner assigned identifier of the VM.
+    pub fn vm_id(&self) -> Uuid {
+        Uuid::from_bytes(self.unmask(self.body.vm_id))
+    }
+
+    /// The guest owner assigned version of the VM.
+    pub fn vm_version(&self) -> VmVersion {
+        VmVersion(self.unmask(self.body.vm_version))
+    }
+
+    /// The unmasked usage of the key which signed the report.
+    pub fn sig_usage(&self) -> SigUsage {
+        u32::from_le(self.sig_usage ^ self.anonce).into()
+    }
+
+    /// The unmasked algorithm of the report signature.
+    pub fn sig_algo(&self) -> SigAlgo {
+        u32::from_le(self.sig_algo ^ self.anonce).into()
+    }
+
+    /// Describes every difference from `self` to `other`, one line each,
+    /// e.g. `"measure: 01ab.. -> 02cd.."` or `"policy.nodbg: set -> clear"`.
+    ///
+    /// Fields are compared unmasked. The mnonce, anonce and signature,
+    /// which differ between any two reports, are not compared.
+    pub fn diff(&self, other: &AttestationReport) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut field = |name: &str, a: String, b: String| {
+            if a != b {
+                lines.push(format!("{name}: {a} -> {b}"));
+            }
+        };
+
+        let encode = |report: &Self, bytes: &[u8]| {
+            let mut unmasked = bytes.to_vec();
+            Self::mask(report.anonce, &mut unmasked);
+            hex::encode(&unmasked)
+        };
+
+        let (a, b) = (&self.body, &other.body);
+        for (name, a, b) in [
+            (
+                "user_pubkey_digest",
+                &a.user_pubkey_digest[..],
+                &b.user_pubkey_digest[..],
+            ),
+            ("report_data", &a.report_data[..], &b.report_data[..]),
+            ("measure", &a.measure[..], &b.measure[..]),
+        ] {
+            field(name, encode(self, a), encode(other, b));
+        }
+
+        field("vm_id", self.vm_id().to_string(), other.vm_id().to_string());
+        field(
+            "vm_version",
+            self.vm_version().to_string(),
+            other.vm_version().to_string(),
+        );
+        field(
+            "sig_usage",
+            format!("{:?}", self.sig_usage()),
+            format!("{:?}", other.sig_usage()),
+        );
+        field(
+            "sig_algo",
+            format!("{:?}", self.sig_algo()),
+            format!("{:?}", other.sig_algo()),
+        );
+
+        let policy = |report: &Self| report.body.policy.xor(&report.anonce);
+        lines.extend(
+            policy(self)
+                .diff(&policy(other))
+                .into_iter()
+                .map(|line| format!("policy.{line}")),
+        );
+
+        lines
+    }
+}
+
 impl codicon::Encoder<crate::Body> for AttestationReport {
     type Error = std::io::Error;
 
@@ -175,91 +450,348 @@ impl TryFrom<&AttestationReport> for Signature {
     }
 }
 
+/// Signs the report body, as the firmware does with the PEK.
+#[cfg(feature = "openssl")]
+impl Signer<AttestationReport> for PrivateKey<Usage> {
+    type Output = ();
+
+    fn sign(&self, target: &mut AttestationReport, uid: String) -> std::io::Result<()> {
+        let mut msg = Vec::new();
+        codicon::Encoder::encode(target, &mut msg, crate::Body)?;
+
+        let sig = sm::SM2::sign(self.key, &uid.into_bytes(), &msg)?;
+        target.sig = ecdsa::Signature::try_from(&sig[..])?;
+        Ok(())
+    }
+}
+
+impl AttestationReport {
+    /// The signed part of the report.
+    pub fn body_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(&self.body as *const Body as *const u8, size_of::<Body>())
+        }
+    }
+
+    /// Verifies the report signature with the PEK, borrowing both and
+    /// without any heap allocation on the Rust side.
+    ///
+    /// Reports claiming any other signer than the PEK or any other
+    /// algorithm than SM2 are rejected before the signature is checked.
+    pub fn verify_signature(&self, pek: &Certificate) -> std::io::Result<()> {
+        if self.sig_usage() != SigUsage::Pek {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unexpected report signer: {:?}", self.sig_usage()),
+            ));
+        }
+        if self.sig_algo() != SigAlgo::Sm2Sa {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unsupported report signature: {:?}", self.sig_algo()),
+            ));
+        }
+
+        let key: PublicKey = pek.try_into()?;
+        if key.usage != Usage::PEK {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        }
+
+        let data = &pek.body.data;
+        let mut der = [0u8; ecdsa::MAX_DER_LEN];
+        key.verify_der(
+            self.body_bytes(),
+            crate::certs::user_id(data.uid_size, &data.user_id)?,
+            self.sig.write_der(&mut der),
+        )
+    }
+}
+
 impl Verifiable for (&Certificate, &AttestationReport) {
     type Output = ();
 
     fn verify(self) -> Result<(), std::io::Error> {
-        let key: PublicKey = self.0.try_into()?;
-        let sig: Signature = self.1.try_into()?;
-        key.verify(
-            self.1,
-            &self.0.body.data.user_id[..self.0.body.data.uid_size as usize],
-            &sig,
-        )
+        self.1.verify_signature(self.0)
     }
 }
 
-bitfield! {
-    /// The firmware associates each guest with a guest policy that the guest owner provides. The
-    /// firmware restricts what actions the hypervisor can take on the guest according to the guest policy.
-    /// The policy also indicates the minimum firmware version to for the guest.
-    ///
-    /// The guest owner provides the guest policy to the firmware during launch. The firmware then binds
-    /// the policy to the guest. The policy cannot be changed throughout the lifetime of the guest. The
-    /// policy is also migrated with the guest and enforced by the destination platform firmware.
-    ///
-    /// | Bit(s) | Name           | Description                                                                                 >
-    /// |--------|----------------|--------------------------------------------------------------------------------------------->
-    /// | 0      | NODBG          | Debugging of the guest is disallowed when set                                               >
-    /// | 1      | NOKS           | Sharing keys with other guests is disallowed when set                                       >
-    /// | 2      | ES             | CSV2 is required when set                                                                   >
-    /// | 3      | NOSEND         | Sending the guest to another platform is disallowed when set                                >
-    /// | 4      | DOMAIN         | The guest must not be transmitted to another platform that is not in the domain when set.   >
-    /// | 5      | CSV            | The guest must not be transmitted to another platform that is not CSV capable when set.     >
-    /// | 6      | CSV3           | The guest must not be transmitted to another platform that is not CSV3 capable when set.    >
-    /// | 7      | ASID_REUSE     | Sharing asids with other guests owned by same user is allowed when set                      >
-    /// | 11:8   | HSK_VERSION    | The guest must not be transmitted to another platform with a lower HSK version.             >
-    /// | 15:12  | CEK_VERSION    | The guest must not be transmitted to another platform with a lower CEK version.             >
-    /// | 23:16  | API_MAJOR      | The guest must not be transmitted to another platform with a lower platform version.        >
-    /// | 31:24  | API_MINOR      | The guest must not be transmitted to another platform with a lower platform version.        >
-    #[repr(C)]
-    #[derive(Copy, Clone, Serialize, Deserialize, Default)]
-    pub struct GuestPolicy(u32);
-    impl Debug;
-    pub nodbg, _: 0, 0;
-    pub noks, _: 1, 1;
-    pub es, _: 2, 2;
-    pub nosend, _: 3, 3;
-    pub domain, _: 4, 4;
-    pub csv, _: 5, 5;
-    pub csv3, _: 6, 6;
-    pub asid_reuse, _: 7, 7;
-    pub hsk_version, _: 11, 8;
-    pub cek_version, _: 15, 12;
-    pub api_major, _: 23, 16;
-    pub api_minor, _: 31, 24;
+/// Verifies the CSV chain, then the report against its PEK.
+impl Verifiable for (&crate::certs::csv::Chain, &AttestationReport) {
+    type Output = ();
+
+    fn verify(self) -> Result<(), std::io::Error> {
+        self.0.verify()?;
+        (&self.0.pek, self.1).verify()
+    }
 }
 
+/// The policy flags, with the meaning of each when set.
+const POLICY_FLAGS: &[(&str, u32, &str)] = &[
+    ("nodbg", 0, "debugging of the guest is disallowed"),
+    ("noks", 1, "sharing keys with other guests is disallowed"),
+    ("es", 2, "encrypted state (CSV2) is required"),
+    (
+        "nosend",
+        3,
+        "sending the guest to another platform is disallowed",
+    ),
+    ("domain", 4, "the guest may only be sent within the domain"),
+    ("csv", 5, "the guest may only be sent to CSV platforms"),
+    ("csv3", 6, "the guest may only be sent to CSV3 platforms"),
+    (
+        "asid_reuse",
+        7,
+        "sharing ASIDs with other guests of the same user is allowed",
+    ),
+];
+
 impl GuestPolicy {
-    #[allow(dead_code)]
+    /// Starts a policy with every flag clear and no minimum versions.
+    pub fn builder() -> GuestPolicyBuilder {
+        GuestPolicyBuilder::default()
+    }
+
+    /// Unmasks a policy as stored in a report, little-endian and masked,
+    /// given the report's anonce.
+    ///
+    /// The result holds the policy value in the byte order of the host,
+    /// like every other policy, so that its bits can be read.
     pub fn xor(&self, anonce: &u32) -> Self {
-        Self(self.0 ^ anonce)
+        Self(u32::from_le(self.0 ^ anonce))
+    }
+
+    /// Masks a policy with `anonce` and stores it little-endian, as the
+    /// firmware does in reports, the inverse of [`GuestPolicy::xor`].
+    pub fn mask(&self, anonce: &u32) -> Self {
+        Self(self.0.to_le() ^ anonce)
+    }
+
+    fn versions(&self) -> [(&'static str, String); 3] {
+        [
+            ("api", format!("{}.{}", self.api_major(), self.api_minor())),
+            ("hsk_version", self.hsk_version().to_string()),
+            ("cek_version", self.cek_version().to_string()),
+        ]
+    }
+
+    /// Describes every set flag and the minimum versions, one line each.
+    ///
+    /// The policy must be unmasked, see [`GuestPolicy::xor`].
+    pub fn explain(&self) -> Vec<String> {
+        let flags = POLICY_FLAGS
+            .iter()
+            .filter(|(_, bit, _)| self.0 & (1 << bit) != 0)
+            .map(|(name, _, meaning)| format!("{name}: {meaning}"));
+        let versions = self
+            .versions()
+            .into_iter()
+            .map(|(name, value)| format!("{name}: {value}"));
+
+        flags.chain(versions).collect()
+    }
+
+    /// Describes every difference from `self` to `other`, one line each,
+    /// e.g. `"nodbg: set -> clear"` or `"api: 1.2 -> 1.3"`.
+    pub fn diff(&self, other: &GuestPolicy) -> Vec<String> {
+        let state = |policy: &GuestPolicy, bit: u32| match policy.0 & (1 << bit) {
+            0 => "clear",
+            _ => "set",
+        };
+
+        let flags = POLICY_FLAGS
+            .iter()
+            .filter(|(_, bit, _)| state(self, *bit) != state(other, *bit))
+            .map(|(name, bit, _)| {
+                format!("{name}: {} -> {}", state(self, *bit), state(other, *bit))
+            });
+        let versions = self
+            .versions()
+            .into_iter()
+            .zip(other.versions())
+            .filter(|((_, a), (_, b))| a != b)
+            .map(|((name, a), (_, b))| format!("{name}: {a} -> {b}"));
+
+        flags.chain(versions).collect()
+    }
+
+    /// Names the flags set in `required` but clear in `self`.
+    ///
+    /// Both policies must be unmasked; their versions are ignored.
+    pub fn missing(&self, required: &GuestPolicy) -> Vec<&'static str> {
+        POLICY_FLAGS
+            .iter()
+            .filter(|(_, bit, _)| required.0 & !self.0 & (1 << bit) != 0)
+            .map(|(name, _, _)| *name)
+            .collect()
     }
 }
 
-#[repr(C)]
-#[derive(Serialize, Deserialize)]
-pub struct ReportSigner {
-    #[serde(with = "BigArray")]
-    pub pek_cert: [u8; 2084],
-    #[serde(with = "BigArray")]
-    pub sn: [u8; 64],
-    pub reserved: [u8; 32],
-    pub mac: [u8; 32],
+/// From the policy value in the byte order of the host, e.g. `0x0201_0005`
+/// for NODBG, ES and API 1.2. A policy read from a report must be unmasked
+/// with [`GuestPolicy::xor`] instead.
+impl From<u32> for GuestPolicy {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+/// The policy value in the byte order of the host, as LAUNCH_START takes
+/// it.
+impl From<GuestPolicy> for u32 {
+    fn from(policy: GuestPolicy) -> Self {
+        policy.0
+    }
+}
+
+/// Builds a [`GuestPolicy`], e.g. for LAUNCH_START or as the expectation
+/// of an appraisal.
+///
+/// ```
+/// use csv_rs::api::guest::GuestPolicy;
+///
+/// let policy = GuestPolicy::builder()
+///     .nodbg(true)
+///     .es(true)
+///     .api_major(1)
+///     .api_minor(2)
+///     .build();
+/// assert_eq!(u32::from(policy), 0x0201_0005);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuestPolicyBuilder(GuestPolicy);
+
+macro_rules! policy_setters {
+    ($($(#[$doc:meta])* $name:ident: $setter:ident($ty:ty);)*) => {
+        impl GuestPolicyBuilder {
+            $(
+                $(#[$doc])*
+                pub fn $name(mut self, value: $ty) -> Self {
+                    self.0.$setter(value as u32);
+                    self
+                }
+            )*
+        }
+    };
+}
+
+policy_setters! {
+    /// Disallows debugging of the guest.
+    nodbg: set_nodbg(bool);
+
+    /// Disallows sharing keys with other guests.
+    noks: set_noks(bool);
+
+    /// Requires CSV2.
+    es: set_es(bool);
+
+    /// Disallows sending the guest to another platform.
+    nosend: set_nosend(bool);
+
+    /// Restricts migration to platforms in the domain.
+    domain: set_domain(bool);
+
+    /// Restricts migration to CSV capable platforms.
+    csv: set_csv(bool);
+
+    /// Restricts migration to CSV3 capable platforms.
+    csv3: set_csv3(bool);
+
+    /// Allows other guests of the same owner to reuse the ASID.
+    asid_reuse: set_asid_reuse(bool);
+
+    /// The minimum API major version of a destination.
+    api_major: set_api_major(u8);
+
+    /// The minimum API minor version of a destination.
+    api_minor: set_api_minor(u8);
+}
+
+impl GuestPolicyBuilder {
+    /// The minimum HSK version of a destination.
+    ///
+    /// # Panics
+    ///
+    /// If `version` does not fit the 4-bit field, i.e. is above 15.
+    pub fn hsk_version(mut self, version: u8) -> Self {
+        assert!(version <= 0xf, "HSK version {version} exceeds 15");
+        self.0.set_hsk_version(version as u32);
+        self
+    }
+
+    /// The minimum CEK version of a destination.
+    ///
+    /// # Panics
+    ///
+    /// If `version` does not fit the 4-bit field, i.e. is above 15.
+    pub fn cek_version(mut self, version: u8) -> Self {
+        assert!(version <= 0xf, "CEK version {version} exceeds 15");
+        self.0.set_cek_version(version as u32);
+        self
+    }
+
+    /// The policy, in the byte order of the host.
+    pub fn build(self) -> GuestPolicy {
+        self.0
+    }
+}
+
+impl fmt::Debug for ReportSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReportSigner")
+            .field("pek_cert", &self.pek_cert)
+            .field("sn", &self.sn)
+            .field("reserved", &self.reserved)
+            .field("mac", &redact::Redacted(&self.mac))
+            .finish()
+    }
 }
 
-fn xor_with_anonce(data: &mut [u8], anonce: &u32) -> Result<(), Error> {
-    let mut anonce_array = [0u8; 4];
-    anonce_array[..].copy_from_slice(&anonce.to_le_bytes());
+/// Decodes a value from the start of a fixed-size field, requiring the
+/// remainder of the field to be zero.
+///
+/// Failures are located within the field named `name`.
+fn decode_padded<T: Decoder<(), Error = std::io::Error>>(
+    name: &str,
+    field: &[u8],
+) -> std::io::Result<T> {
+    let mut rest = field;
+    let value = T::decode(&mut rest, ()).map_err(|e| DecodeError::locate(e, name, 0))?;
 
-    for (index, item) in data.iter_mut().enumerate() {
-        *item ^= anonce_array[index % 4];
+    let len = field.len() - rest.len();
+    if let Some(pos) = rest.iter().position(|&b| b != 0) {
+        return Err(DecodeError::locate(
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unexpected data after the {len} byte certificate"),
+            ),
+            name,
+            (len + pos) as u64,
+        ));
     }
 
-    Ok(())
+    Ok(value)
 }
 
 impl ReportSigner {
+    /// Decodes the PEK certificate.
+    ///
+    /// Only meaningful once [`ReportSigner::verify`] restored the certificate.
+    ///
+    /// The certificate is decoded by its own encoding and any space left
+    /// after it in the field must be zero. A CSV certificate is always 2084
+    /// bytes, the size of the field, so with current firmware nothing is
+    /// left: a field holding a shorter certificate fails to decode.
+    pub fn pek(&self) -> std::io::Result<Certificate> {
+        decode_padded("pek_cert", &self.pek_cert)
+    }
+
+    /// Parses the serial number of the chip which signed the report.
+    ///
+    /// Only meaningful once [`ReportSigner::verify`] restored the serial number.
+    pub fn chip_id(&self) -> std::io::Result<ChipId> {
+        ChipId::try_from(&self.sn[..])
+    }
+
     /// Verifies the signature evidence's hmac.
     pub fn verify(
         &mut self,
@@ -267,23 +799,12 @@ impl ReportSigner {
         mnonce: &[u8],
         anonce: &u32,
     ) -> Result<(), Error> {
-        let mut real_mnonce = Vec::from(mnonce);
-        xor_with_anonce(&mut real_mnonce, anonce)?;
-
-        if real_mnonce != input_mnonce {
-            return Err(Error::BadSignature);
-        }
-
-        let key = pkey::PKey::hmac(&real_mnonce)?;
-        let mut sig = sign::Signer::new(MessageDigest::sm3(), &key)?;
-
-        sig.update(&self.pek_cert)?;
-        sig.update(&self.sn)?;
-        sig.update(&self.reserved)?;
-
-        if sig.sign_to_vec()? != self.mac {
-            return Err(Error::BadSignature);
-        }
+        let mut mac = SignerMac::new(input_mnonce, mnonce, *anonce)?;
+        mac.write_all(&self.pek_cert)?;
+        mac.write_all(&self.sn)?;
+        mac.write_all(&self.reserved)?;
+        mac.write_all(&self.mac)?;
+        mac.finish()?;
 
         // restore pek cert and serial number.
         self.restore(anonce)?;
@@ -292,8 +813,8 @@ impl ReportSigner {
     }
 
     fn restore(&mut self, anonce: &u32) -> Result<(), Error> {
-        xor_with_anonce(&mut self.pek_cert, anonce)?;
-        xor_with_anonce(&mut self.sn, anonce)?;
+        AttestationReport::mask(*anonce, &mut self.pek_cert);
+        AttestationReport::mask(*anonce, &mut self.sn);
 
         // reset reserved to 0.
         self.reserved.fill(0);
@@ -315,6 +836,269 @@ impl Default for ReportSigner {
 
 #[cfg(test)]
 mod test {
+    mod vm_version {
+        use crate::api::guest::types::VmVersion;
+
+        #[test]
+        pub fn test_ascii() {
+            let version: VmVersion = "1.2.0".parse().unwrap();
+            assert_eq!(&version.0[..6], b"1.2.0\0");
+            assert_eq!(version.to_string(), "1.2.0");
+        }
+
+        #[test]
+        pub fn test_binary() {
+            let mut bytes = [0u8; 16];
+            bytes[15] = 0x01;
+            let version = VmVersion(bytes);
+            assert_eq!(version.as_str(), None);
+            assert_eq!(version.to_string(), "00000000000000000000000000000001");
+            assert_eq!(version.to_string().parse::<VmVersion>().unwrap(), version);
+        }
+    }
+
+    mod guest_policy {
+        use crate::api::guest::types::GuestPolicy;
+
+        #[test]
+        pub fn test_explain() {
+            let policy = GuestPolicy::from(0x0201_0105);
+            assert_eq!(
+                policy.explain(),
+                [
+                    "nodbg: debugging of the guest is disallowed",
+                    "es: encrypted state (CSV2) is required",
+                    "api: 1.2",
+                    "hsk_version: 1",
+                    "cek_version: 0",
+                ]
+            );
+        }
+
+        #[test]
+        pub fn test_diff() {
+            let policy = GuestPolicy::from(0x0201_0105);
+            assert!(policy.diff(&policy).is_empty());
+            assert_eq!(
+                policy.diff(&GuestPolicy::from(0x0301_0104)),
+                ["nodbg: set -> clear", "api: 1.2 -> 1.3"]
+            );
+        }
+
+        #[test]
+        pub fn test_missing() {
+            let policy = GuestPolicy::from(0x0201_0105);
+            let required = GuestPolicy::builder().nodbg(true).nosend(true).build();
+            assert_eq!(policy.missing(&required), ["nosend"]);
+            assert!(policy.missing(&GuestPolicy::from(0x0301_0001)).is_empty());
+        }
+
+        #[test]
+        pub fn test_builder() {
+            let policy = GuestPolicy::builder()
+                .nodbg(true)
+                .es(true)
+                .hsk_version(1)
+                .api_major(1)
+                .api_minor(2)
+                .build();
+            assert_eq!(policy, GuestPolicy::from(0x0201_0105));
+
+            // Clearing a flag, and the largest version of a field.
+            let policy = GuestPolicy::builder()
+                .nodbg(true)
+                .nodbg(false)
+                .cek_version(0xf)
+                .build();
+            assert_eq!(u32::from(policy), 0x0000_f000);
+
+            let mut policy = policy;
+            policy.set_csv3(1);
+            assert_eq!(policy.csv3(), 1);
+        }
+
+        #[test]
+        #[should_panic(expected = "CEK version 31 exceeds 15")]
+        pub fn test_builder_range() {
+            GuestPolicy::builder().cek_version(0x1f);
+        }
+
+        #[test]
+        #[should_panic(expected = "HSK version 16 exceeds 15")]
+        pub fn test_builder_hsk_range() {
+            GuestPolicy::builder().hsk_version(16);
+        }
+
+        #[test]
+        pub fn test_byte_order() {
+            // NODBG and ES, HSK version 1, API 1.2, as the firmware stores
+            // it, masked with an anonce of 0x11223344.
+            let anonce = u32::from_le_bytes([0x44, 0x33, 0x22, 0x11]);
+            let stored = u32::from_le_bytes([0x05 ^ 0x44, 0x01 ^ 0x33, 0x01 ^ 0x22, 0x02 ^ 0x11]);
+
+            let policy = GuestPolicy::from(stored).xor(&anonce);
+            assert_eq!(u32::from(policy), 0x0201_0105);
+            assert_eq!(
+                (policy.nodbg(), policy.es(), policy.hsk_version()),
+                (1, 1, 1)
+            );
+            assert_eq!((policy.api_major(), policy.api_minor()), (1, 2));
+            assert_eq!(u32::from(policy.mask(&anonce)), stored);
+
+            // Serialized by value, whether alone or stored in a report.
+            assert_eq!(serde_json::to_string(&policy).unwrap(), "33620229");
+            let mut report = crate::api::guest::AttestationReport::default();
+            report.body.policy = policy.mask(&0);
+            let json = serde_json::to_value(&report).unwrap();
+            assert_eq!(json["body"]["policy"], 33620229);
+        }
+    }
+
+    mod mask {
+        use crate::api::guest::types::AttestationReport;
+
+        #[test]
+        pub fn test_mask() {
+            let report = AttestationReport {
+                anonce: u32::from_le_bytes([1, 2, 3, 4]),
+                ..Default::default()
+            };
+
+            let mut bytes = [0x10u8; 6];
+            AttestationReport::mask(report.anonce, &mut bytes);
+            assert_eq!(bytes, [0x11, 0x12, 0x13, 0x14, 0x11, 0x12]);
+            assert_eq!(report.unmask(bytes), [0x10u8; 6]);
+        }
+    }
+
+    mod decode_padded {
+        use crate::{
+            api::guest::types::{decode_padded, AttestationReport, ReportSigner},
+            certs::{ca, csv},
+            error::DecodeError,
+            pod,
+        };
+        use codicon::Decoder;
+        use std::mem::size_of;
+
+        const HSK: &[u8] = include_bytes!("../../../tests/test_data/hsk_cek.cert");
+        const REPORT: &[u8] = include_bytes!("../../../tests/test_data/report.cert");
+
+        #[test]
+        pub fn test_smaller() {
+            let len = size_of::<ca::Certificate>();
+            let mut field = [0u8; 2084];
+            field[..len].copy_from_slice(&HSK[..len]);
+            decode_padded::<ca::Certificate>("hsk", &field).unwrap();
+
+            field[len + 3] = 1;
+            let err = decode_padded::<ca::Certificate>("hsk", &field).unwrap_err();
+            let located = DecodeError::find(&err).unwrap();
+            assert_eq!(located.field, "hsk");
+            assert_eq!(located.offset as usize, len + 3);
+
+            let err = decode_padded::<ca::Certificate>("hsk", &field[..len - 1]).unwrap_err();
+            let located = DecodeError::find(&err).unwrap();
+            assert_eq!(located.field, "hsk.reserved");
+        }
+
+        #[test]
+        pub fn test_pek() {
+            let (report, signer) = REPORT.split_at(AttestationReport::SIZE);
+            let report = AttestationReport::from_bytes(report).unwrap();
+            let mut signer: ReportSigner = pod::from_bytes(signer).unwrap();
+            let mnonce = report.unmask(report.body.mnonce);
+            signer
+                .verify(&mnonce, &report.body.mnonce, &report.anonce)
+                .unwrap();
+
+            // The certificate fills the whole field.
+            let mut rest = &signer.pek_cert[..];
+            csv::Certificate::decode(&mut rest, ()).unwrap();
+            assert!(rest.is_empty());
+            signer.pek().unwrap();
+
+            let len = signer.pek_cert.len();
+            let err = decode_padded::<csv::Certificate>("pek_cert", &signer.pek_cert[..len - 1])
+                .unwrap_err();
+            let located = DecodeError::find(&err).unwrap();
+            assert!(located.field.starts_with("pek_cert.sigs[1]"));
+        }
+    }
+
+    mod report_rsp {
+        use crate::{api::guest::types::ReportRsp, pod};
+
+        const REPORT: &[u8] = include_bytes!("../../../tests/test_data/report.cert");
+
+        #[test]
+        pub fn test_clone() {
+            let mut rsp = ReportRsp::boxed();
+            rsp.as_bytes_mut()[..REPORT.len()].copy_from_slice(REPORT);
+
+            let copy: ReportRsp = (*rsp).clone();
+            assert_eq!(*rsp, copy);
+            assert_eq!(pod::as_bytes(&copy), rsp.as_bytes());
+
+            let mut signer = copy.signer.clone();
+            signer.mac[0] ^= 0xff;
+            assert_ne!(rsp.signer, signer);
+            assert_eq!(rsp.report, copy.report);
+        }
+    }
+
+    mod attestation_report {
+        use crate::{
+            api::guest::types::{AttestationReport, GuestPolicy},
+            test_utils::ReportBuilder,
+        };
+
+        const REPORT: &[u8] = include_bytes!("../../../tests/test_data/report.cert");
+
+        #[test]
+        pub fn test_bytes() {
+            let bytes = &REPORT[..AttestationReport::SIZE];
+            let report = AttestationReport::from_bytes(bytes).unwrap();
+            assert_eq!(report.as_bytes(), bytes);
+
+            // Unaligned input parses the same.
+            let mut shifted = vec![0u8];
+            shifted.extend_from_slice(bytes);
+            assert_eq!(
+                AttestationReport::from_bytes(&shifted[1..]).unwrap(),
+                report
+            );
+
+            assert!(AttestationReport::from_bytes(&bytes[1..]).is_err());
+            assert!(AttestationReport::from_bytes(REPORT).is_err());
+        }
+
+        #[test]
+        pub fn test_diff() {
+            let builder = ReportBuilder::default()
+                .measure([1u8; 32])
+                .policy(GuestPolicy::from(0x0201_0105));
+            let report = builder.clone().anonce(0x1234_5678).build().unwrap();
+
+            // Masking with another anonce alone makes no difference.
+            let same = builder.clone().anonce(0x8765_4321).build().unwrap();
+            assert!(report.diff(&same).is_empty());
+
+            let other = builder
+                .measure([2u8; 32])
+                .policy(GuestPolicy::from(0x0201_0104))
+                .build()
+                .unwrap();
+            assert_eq!(
+                report.diff(&other),
+                [
+                    format!("measure: {} -> {}", "01".repeat(32), "02".repeat(32)),
+                    "policy.nodbg: set -> clear".to_string(),
+                ]
+            );
+        }
+    }
+
     mod report_req {
         use crate::api::guest::types::ReportReq;
         #[test]
@@ -339,6 +1123,16 @@ mod test {
             assert_eq!(expected, actual);
         }
 
+        #[test]
+        #[cfg(not(feature = "unredacted_debug"))]
+        pub fn test_debug() {
+            let req = ReportReq::new(Some([7u8; 64]), [0xa5u8; 16]).unwrap();
+            let debug = format!("{req:?}");
+
+            assert!(debug.contains("mnonce: <redacted>"));
+            assert!(!debug.contains("165"));
+        }
+
         #[test]
         #[should_panic]
         pub fn test_new_error() {
@@ -10,6 +10,7 @@ use crate::{
     api::launch::types::*,
     error::{Error, Indeterminate},
     impl_const_id,
+    util::uapi::uapi_layout,
 };
 
 use std::{
@@ -33,7 +34,19 @@ impl_const_id! {
     LaunchSecret<'_> = 5,
     LaunchMeasure<'_> = 6,
     LaunchFinish = 7,
+    SendStart<'_> = 8,
+    SendUpdateData<'_> = 9,
+    SendFinish = 11,
+    ReceiveStart<'_> = 12,
+    ReceiveUpdateData<'_> = 13,
+    ReceiveFinish = 15,
+    GuestStatus = 16,
     Attestation<'_> = 20,
+
+    // The CSV3 commands are defined by the HYGON kernel.
+    Csv3Init = 0xc0,
+    Csv3LaunchEncryptData<'_> = 0xc1,
+    Csv3LaunchEncryptVmcb = 0xc2,
 }
 
 const KVM: Group = Group::new(0xAE);
@@ -57,6 +70,9 @@ pub const INIT: Ioctl<WriteRead, &Command<Init>> = unsafe { ENC_OP.lie() };
 /// Initialize the CSV2 platform context.
 pub const ES_INIT: Ioctl<WriteRead, &Command<EsInit>> = unsafe { ENC_OP.lie() };
 
+/// Initialize the CSV3 platform context.
+pub const CSV3_INIT: Ioctl<WriteRead, &Command<Csv3Init>> = unsafe { ENC_OP.lie() };
+
 /// Create encrypted guest context.
 pub const LAUNCH_START: Ioctl<WriteRead, &Command<LaunchStart>> = unsafe { ENC_OP.lie() };
 
@@ -78,6 +94,36 @@ pub const LAUNCH_MEASUREMENT: Ioctl<WriteRead, &Command<LaunchMeasure>> = unsafe
 /// the ready state.
 pub const LAUNCH_FINISH: Ioctl<WriteRead, &Command<LaunchFinish>> = unsafe { ENC_OP.lie() };
 
+/// Export the guest context to another platform.
+pub const SEND_START: Ioctl<WriteRead, &Command<SendStart>> = unsafe { ENC_OP.lie() };
+
+/// Encrypt guest memory for transport.
+pub const SEND_UPDATE_DATA: Ioctl<WriteRead, &Command<SendUpdateData>> = unsafe { ENC_OP.lie() };
+
+/// Complete the export of the guest context.
+pub const SEND_FINISH: Ioctl<WriteRead, &Command<SendFinish>> = unsafe { ENC_OP.lie() };
+
+/// Import a guest context from another platform.
+pub const RECEIVE_START: Ioctl<WriteRead, &Command<ReceiveStart>> = unsafe { ENC_OP.lie() };
+
+/// Decrypt transported memory into the guest.
+pub const RECEIVE_UPDATE_DATA: Ioctl<WriteRead, &Command<ReceiveUpdateData>> =
+    unsafe { ENC_OP.lie() };
+
+/// Complete the import of the guest context.
+pub const RECEIVE_FINISH: Ioctl<WriteRead, &Command<ReceiveFinish>> = unsafe { ENC_OP.lie() };
+
+/// Encrypt guest memory in place for CSV3.
+pub const CSV3_LAUNCH_ENCRYPT_DATA: Ioctl<WriteRead, &Command<Csv3LaunchEncryptData>> =
+    unsafe { ENC_OP.lie() };
+
+/// Encrypt the VMCBs of all vCPUs for CSV3.
+pub const CSV3_LAUNCH_ENCRYPT_VMCB: Ioctl<WriteRead, &Command<Csv3LaunchEncryptVmcb>> =
+    unsafe { ENC_OP.lie() };
+
+/// Query the firmware state of the guest.
+pub const GUEST_STATUS: Ioctl<WriteRead, &Command<GuestStatus>> = unsafe { ENC_OP.lie() };
+
 pub const ATTESTATION: Ioctl<WriteRead, &Command<Attestation>> = unsafe { ENC_OP.lie() };
 
 /// Corresponds to the `KVM_MEMORY_ENCRYPT_REG_REGION` ioctl
@@ -150,3 +196,9 @@ impl<'a, T: Id> Command<'a, T> {
         }
     }
 }
+
+// include/uapi/linux/kvm.h: struct kvm_enc_region, struct kvm_sev_cmd
+uapi_layout! {
+    KvmEncRegion<'static> = 16, { addr: 0, size: 8 },
+    Command<'static, LaunchFinish> = 24, { code: 0, data: 8, error: 16, csv_fd: 20 },
+}
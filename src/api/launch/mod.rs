@@ -11,9 +11,19 @@ use ioctl::*;
 pub(crate) mod types;
 use types::*;
 
+#[cfg(feature = "openssl")]
+mod secret;
+#[cfg(feature = "openssl")]
+pub use secret::SecretBuilder;
+
+#[cfg(feature = "openssl")]
+use crate::{
+    certs::Signer,
+    crypto::{sm, PrivateKey},
+};
 use crate::{
-    certs::{csv::Certificate, Signer, Usage, Verifiable},
-    crypto::{sig::ecdsa, sm, PrivateKey, PublicKey, Signature},
+    certs::{csv::Certificate, Usage, Verifiable},
+    crypto::{sig::ecdsa, PublicKey, Signature},
     util::*,
     Version,
 };
@@ -37,7 +47,23 @@ pub struct Started(Handle);
 /// Launcher type-state that indicates the availability of a measurement.
 pub struct Measured(Handle, Measurement);
 
+/// Launcher type-state that indicates a completed launch.
+pub struct Finished(Handle);
+
 /// Facilitates the correct execution of the CSV launch process.
+///
+/// Each command wraps a `KVM_MEMORY_ENCRYPT_OP` ioctl on the vm fd and is
+/// only available in the state the firmware accepts it in:
+/// [`New`] → [`Started`] → [`Measured`] → [`Finished`].
+///
+/// KVM binds the guest to its ASID with the ACTIVATE firmware command when
+/// it creates the guest context, and unbinds it with DEACTIVATE when the VM
+/// is destroyed. Neither is exposed as a command: the ASID binding follows
+/// the lifetime of the VM file descriptor.
+///
+/// Likewise, KVM releases the firmware context of the guest with
+/// DECOMMISSION once the last file descriptor of the VM is closed, whether
+/// the launch finished or was abandoned by dropping the launcher.
 pub struct Launcher<T, U: AsRawFd, V: AsRawFd> {
     state: T,
     vm_fd: U,
@@ -49,6 +75,30 @@ impl<T, U: AsRawFd, V: AsRawFd> Launcher<T, U, V> {
     pub fn as_mut_vmfd(&mut self) -> &mut U {
         &mut self.vm_fd
     }
+
+    /// Query the firmware state of the guest.
+    pub fn status(&mut self) -> Result<Status> {
+        status(&mut self.vm_fd, &mut self.csv)
+    }
+}
+
+/// Query the firmware state of the guest of `vm_fd`, e.g. once its
+/// launch finished.
+///
+/// KVM reports the handle, policy and state of the guest, but not its
+/// ASID.
+pub fn status(vm_fd: &mut impl AsRawFd, csv: &mut impl AsRawFd) -> Result<Status> {
+    let mut guest_status = GuestStatus::default();
+    let mut cmd = Command::from_mut(csv, &mut guest_status);
+    GUEST_STATUS
+        .ioctl(vm_fd, &mut cmd)
+        .map_err(|e| cmd.encapsulate(e))?;
+
+    Ok(Status {
+        handle: guest_status.handle,
+        policy: Policy::from(guest_status.policy),
+        state: GuestState::try_from(guest_status.state)?,
+    })
 }
 
 impl<U: AsRawFd, V: AsRawFd> Launcher<New, U, V> {
@@ -83,6 +133,26 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<New, U, V> {
         Ok(launcher)
     }
 
+    /// Begin the CSV3 launch process.
+    ///
+    /// The guest's secure memory is allocated on the NUMA nodes set in
+    /// `nodemask`.
+    pub fn new_csv3(kvm: U, csv: V, nodemask: u64) -> Result<Self> {
+        let mut launcher = Launcher {
+            vm_fd: kvm,
+            csv,
+            state: New,
+        };
+
+        let init = Csv3Init::new(nodemask);
+        let mut cmd = Command::from(&mut launcher.csv, &init);
+        CSV3_INIT
+            .ioctl(&mut launcher.vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok(launcher)
+    }
+
     /// Create an encrypted guest context.
     pub fn start(mut self, start: Start) -> Result<Launcher<Started, U, V>> {
         let mut launch_start = LaunchStart::new(&start.policy, &start.cert, &start.session);
@@ -186,6 +256,31 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<Started, U, V> {
         Ok(())
     }
 
+    /// Encrypt guest data of a CSV3 guest into its secure memory at the
+    /// guest physical address `gpa`.
+    pub fn encrypt_data(&mut self, gpa: u64, data: &[u8]) -> Result<()> {
+        let encrypt_data = Csv3LaunchEncryptData::new(gpa, data);
+        let mut cmd = Command::from(&mut self.csv, &encrypt_data);
+
+        CSV3_LAUNCH_ENCRYPT_DATA
+            .ioctl(&mut self.vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok(())
+    }
+
+    /// Encrypt the VMCBs of all vCPUs of a CSV3 guest, once they are
+    /// created.
+    pub fn encrypt_vmcb(&mut self) -> Result<()> {
+        let mut cmd = Command::from(&mut self.csv, &Csv3LaunchEncryptVmcb);
+
+        CSV3_LAUNCH_ENCRYPT_VMCB
+            .ioctl(&mut self.vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok(())
+    }
+
     /// Request a measurement from the CSV firmware.
     pub fn measure(mut self) -> Result<Launcher<Measured, U, V>> {
         let mut measurement = MaybeUninit::uninit();
@@ -238,12 +333,31 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<Measured, U, V> {
     }
 
     /// Complete the CSV launch process.
-    pub fn finish(mut self) -> Result<Handle> {
+    pub fn finish(mut self) -> Result<Launcher<Finished, U, V>> {
         let mut cmd = Command::from(&mut self.csv, &LaunchFinish);
         LAUNCH_FINISH
             .ioctl(&mut self.vm_fd, &mut cmd)
             .map_err(|e| cmd.encapsulate(e))?;
-        Ok(self.state.0)
+
+        let next = Launcher {
+            state: Finished(self.state.0),
+            vm_fd: self.vm_fd,
+            csv: self.csv,
+        };
+
+        Ok(next)
+    }
+}
+
+impl<U: AsRawFd, V: AsRawFd> Launcher<Finished, U, V> {
+    /// The firmware handle of the launched guest.
+    pub fn handle(&self) -> u32 {
+        u32::from(&self.state.0)
+    }
+
+    /// Release the vm fd, e.g. to run the guest.
+    pub fn into_vmfd(self) -> U {
+        self.vm_fd
     }
 }
 
@@ -299,6 +413,90 @@ impl From<u32> for Policy {
     }
 }
 
+/// Convert a Policy struct to its u32 representation, e.g. for the
+/// `policy` property of QEMU's `sev-guest` object.
+impl From<Policy> for u32 {
+    fn from(p: Policy) -> Self {
+        let minfw = ((p.minfw.major as u32) << 4 | p.minfw.minor as u32) & 0xff;
+        p.flags.bits() as u32 | minfw << 16
+    }
+}
+
+/// The firmware state of a guest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GuestState {
+    /// The guest has no context.
+    Uninitialized,
+
+    /// The guest is being launched.
+    LaunchUpdate,
+
+    /// The guest was measured and may receive secrets.
+    LaunchSecret,
+
+    /// The guest is launched and may run.
+    Running,
+
+    /// The guest is being sent to another platform.
+    SendUpdate,
+
+    /// The guest is being received from another platform.
+    ReceiveUpdate,
+
+    /// The guest was sent to another platform.
+    Sent,
+}
+
+impl std::fmt::Display for GuestState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            GuestState::Uninitialized => "uninitialized",
+            GuestState::LaunchUpdate => "launch update",
+            GuestState::LaunchSecret => "launch secret",
+            GuestState::Running => "running",
+            GuestState::SendUpdate => "send update",
+            GuestState::ReceiveUpdate => "receive update",
+            GuestState::Sent => "sent",
+        };
+        write!(f, "{state}")
+    }
+}
+
+impl TryFrom<u32> for GuestState {
+    type Error = std::io::Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        Ok(match value {
+            0 => Self::Uninitialized,
+            1 => Self::LaunchUpdate,
+            2 => Self::LaunchSecret,
+            3 => Self::Running,
+            4 => Self::SendUpdate,
+            5 => Self::ReceiveUpdate,
+            6 => Self::Sent,
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("unknown guest state {value}"),
+                ))
+            }
+        })
+    }
+}
+
+/// The status of a guest, as reported by the firmware.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Status {
+    /// The firmware handle of the guest.
+    pub handle: u32,
+
+    /// The policy the guest was launched with.
+    pub policy: Policy,
+
+    /// The current state of the guest.
+    pub state: GuestState,
+}
+
 /// the SessionBody of the session
 #[repr(C)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -400,6 +598,7 @@ pub struct Session {
     pub sig: SessionSig,
 }
 
+#[cfg(feature = "openssl")]
 impl Signer<Session> for PrivateKey<Usage> {
     type Output = ();
 
@@ -451,7 +650,7 @@ impl Verifiable for (&Certificate, &Session) {
         let sig: Signature = self.1.try_into()?;
         key.verify(
             self.1,
-            &self.0.body.data.user_id[..self.0.body.data.uid_size as usize],
+            crate::certs::user_id(self.0.body.data.uid_size, &self.0.body.data.user_id)?,
             &sig,
         )
     }
@@ -487,6 +686,39 @@ impl codicon::Encoder<()> for Start {
     }
 }
 
+/// The file holding the guest owner's PDH, for the `dh-cert-file`
+/// property of QEMU's `sev-guest` object.
+pub const DH_CERT_FILE: &str = "godh.b64";
+
+/// The file holding the launch session, for the `session-file` property
+/// of QEMU's `sev-guest` object.
+pub const SESSION_FILE: &str = "launch_blob.b64";
+
+#[cfg(feature = "openssl")]
+impl Start {
+    /// The guest owner's PDH certificate, base64-encoded as QEMU expects.
+    pub fn dh_cert_base64(&self) -> Result<String> {
+        let mut bytes = Vec::new();
+        bytes.save(&self.cert)?;
+        Ok(openssl::base64::encode_block(&bytes))
+    }
+
+    /// The launch session, base64-encoded as QEMU expects.
+    pub fn session_base64(&self) -> Result<String> {
+        let mut bytes = Vec::new();
+        bytes.save(&self.session)?;
+        Ok(openssl::base64::encode_block(&bytes))
+    }
+
+    /// Writes [`DH_CERT_FILE`] and [`SESSION_FILE`] into `dir`, to be
+    /// passed to QEMU along with the policy, see [`u32::from`].
+    pub fn save_qemu_files(&self, dir: impl AsRef<std::path::Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        std::fs::write(dir.join(DH_CERT_FILE), self.dh_cert_base64()?)?;
+        std::fs::write(dir.join(SESSION_FILE), self.session_base64()?)
+    }
+}
+
 bitflags! {
     /// Additional descriptions of the secret header packet.
     #[derive(Default, Deserialize, Serialize)]
@@ -500,7 +732,7 @@ bitflags! {
 /// The header for a data packet that contains secret information
 /// to be injected into the guest.
 #[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Header {
     /// Describes the secret packet (for example: if it is
     /// compressed).
@@ -513,8 +745,18 @@ pub struct Header {
     pub mac: [u8; 32],
 }
 
+impl std::fmt::Debug for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Header")
+            .field("flags", &self.flags)
+            .field("iv", &self.iv)
+            .field("mac", &redact::Redacted(&self.mac))
+            .finish()
+    }
+}
+
 /// A packet containing secret information to be injected
-/// into the guest.
+/// into the guest, see [`Secret::builder`].
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Secret {
     /// The header for this packet.
@@ -526,7 +768,7 @@ pub struct Secret {
 
 /// A measurement of the CSV guest.
 #[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Measurement {
     /// The measurement.
     pub measure: [u8; 32],
@@ -535,8 +777,17 @@ pub struct Measurement {
     pub mnonce: [u8; 16],
 }
 
+impl std::fmt::Debug for Measurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Measurement")
+            .field("measure", &self.measure)
+            .field("mnonce", &redact::Redacted(&self.mnonce))
+            .finish()
+    }
+}
+
 #[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct AttestationReport {
     ///
     pub mnonce: [u8; 16],
@@ -553,3 +804,57 @@ pub struct AttestationReport {
     ///
     pub sig1: [[u8; 16]; 9],
 }
+
+impl std::fmt::Debug for AttestationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttestationReport")
+            .field("mnonce", &redact::Redacted(&self.mnonce))
+            .field("digest", &self.digest)
+            .field("policy", &self.policy)
+            .field("sig_usage", &self.sig_usage)
+            .field("sig_algo", &self.sig_algo)
+            .field("sig1", &self.sig1)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guest_state() {
+        let states = [
+            GuestState::Uninitialized,
+            GuestState::LaunchUpdate,
+            GuestState::LaunchSecret,
+            GuestState::Running,
+            GuestState::SendUpdate,
+            GuestState::ReceiveUpdate,
+            GuestState::Sent,
+        ];
+        for (value, state) in states.into_iter().enumerate() {
+            assert_eq!(GuestState::try_from(value as u32).unwrap(), state);
+        }
+
+        for value in [7, 0xff, u32::MAX] {
+            let err = GuestState::try_from(value).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), format!("unknown guest state {value}"));
+        }
+    }
+
+    #[test]
+    fn session_uid_size() {
+        use codicon::Decoder;
+
+        const CEK: &[u8] = include_bytes!("../../../tests/test_data/cek.cert");
+        let mut cert = Certificate::decode(&mut &CEK[..], ()).unwrap();
+        let session: Session = (&[0u8; std::mem::size_of::<Session>()][..]).load().unwrap();
+
+        cert.body.data.uid_size = (cert.body.data.user_id.len() as u16 + 1).to_le();
+        let err = (&cert, &session).verify().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "user id size 255 exceeds 254 bytes");
+    }
+}
@@ -8,11 +8,12 @@
 use crate::{
     api::launch::{AttestationReport, Header, Measurement, Policy, Session},
     certs::csv::Certificate,
+    util::uapi::uapi_layout,
 };
 
 use std::{
     marker::PhantomData,
-    mem::{size_of_val, MaybeUninit},
+    mem::{size_of, size_of_val, MaybeUninit},
 };
 
 /// Initialize the CSV platform context.
@@ -23,9 +24,27 @@ pub struct Init;
 #[repr(C)]
 pub struct EsInit;
 
+/// Initialize the CSV3 platform context.
+#[repr(C)]
+pub struct Csv3Init {
+    nodemask: u64,
+}
+
+impl Csv3Init {
+    pub fn new(nodemask: u64) -> Self {
+        Self { nodemask }
+    }
+}
+
 #[repr(transparent)]
 pub struct Handle(u32);
 
+impl From<&Handle> for u32 {
+    fn from(handle: &Handle) -> Self {
+        handle.0
+    }
+}
+
 impl From<LaunchStart<'_>> for Handle {
     fn from(ls: LaunchStart) -> Self {
         ls.handle
@@ -147,6 +166,158 @@ impl<'a> LaunchMeasure<'a> {
 #[repr(C)]
 pub struct LaunchFinish;
 
+/// Encrypt guest memory in place at its guest physical address for CSV3.
+#[repr(C)]
+pub struct Csv3LaunchEncryptData<'a> {
+    gpa: u64,
+    addr: u64,
+    len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> Csv3LaunchEncryptData<'a> {
+    pub fn new(gpa: u64, data: &'a [u8]) -> Self {
+        Self {
+            gpa,
+            addr: data.as_ptr() as _,
+            len: data.len() as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Encrypt the VMCBs of all vCPUs for CSV3.
+#[repr(C)]
+pub struct Csv3LaunchEncryptVmcb;
+
+/// Export the guest context to another platform.
+#[repr(C)]
+pub struct SendStart<'a> {
+    pub policy: u32,
+    pdh_addr: u64,
+    pdh_len: u32,
+    plat_certs_addr: u64,
+    plat_certs_len: u32,
+    hygon_certs_addr: u64,
+    hygon_certs_len: u32,
+    session_addr: u64,
+    session_len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> SendStart<'a> {
+    pub fn new(
+        pdh: &'a [u8],
+        plat_certs: &'a [u8],
+        hygon_certs: &'a [u8],
+        session: &'a mut MaybeUninit<Session>,
+    ) -> Self {
+        Self {
+            policy: 0,
+            pdh_addr: pdh.as_ptr() as _,
+            pdh_len: pdh.len() as _,
+            plat_certs_addr: plat_certs.as_ptr() as _,
+            plat_certs_len: plat_certs.len() as _,
+            hygon_certs_addr: hygon_certs.as_ptr() as _,
+            hygon_certs_len: hygon_certs.len() as _,
+            session_addr: session.as_mut_ptr() as _,
+            session_len: size_of_val(session) as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// The buffers of a SEND_UPDATE_DATA or RECEIVE_UPDATE_DATA command.
+#[repr(C)]
+pub struct TransferData<'a> {
+    hdr_addr: u64,
+    hdr_len: u32,
+    guest_addr: u64,
+    guest_len: u32,
+    trans_addr: u64,
+    trans_len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+/// Encrypt guest memory for transport.
+#[repr(transparent)]
+pub struct SendUpdateData<'a>(TransferData<'a>);
+
+impl<'a> SendUpdateData<'a> {
+    pub fn new(header: &'a mut MaybeUninit<Header>, guest: &'a [u8], trans: &'a mut [u8]) -> Self {
+        Self(TransferData {
+            hdr_addr: header.as_mut_ptr() as _,
+            hdr_len: size_of::<Header>() as _,
+            guest_addr: guest.as_ptr() as _,
+            guest_len: guest.len() as _,
+            trans_addr: trans.as_mut_ptr() as _,
+            trans_len: trans.len() as _,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// Complete the export of the guest context.
+#[repr(C)]
+pub struct SendFinish;
+
+/// Import a guest context from another platform.
+#[repr(C)]
+pub struct ReceiveStart<'a> {
+    pub handle: u32,
+    policy: u32,
+    pdh_addr: u64,
+    pdh_len: u32,
+    session_addr: u64,
+    session_len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> ReceiveStart<'a> {
+    pub fn new(policy: u32, pdh: &'a Certificate, session: &'a Session) -> Self {
+        Self {
+            handle: 0,
+            policy,
+            pdh_addr: pdh as *const _ as _,
+            pdh_len: size_of_val(pdh) as _,
+            session_addr: session as *const _ as _,
+            session_len: size_of_val(session) as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Decrypt transported memory into the guest.
+#[repr(transparent)]
+pub struct ReceiveUpdateData<'a>(TransferData<'a>);
+
+impl<'a> ReceiveUpdateData<'a> {
+    pub fn new(header: &'a Header, guest: &'a mut [u8], trans: &'a [u8]) -> Self {
+        Self(TransferData {
+            hdr_addr: header as *const _ as _,
+            hdr_len: size_of_val(header) as _,
+            guest_addr: guest.as_mut_ptr() as _,
+            guest_len: guest.len() as _,
+            trans_addr: trans.as_ptr() as _,
+            trans_len: trans.len() as _,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// Complete the import of the guest context.
+#[repr(C)]
+pub struct ReceiveFinish;
+
+/// Query the firmware state of the guest.
+#[repr(C)]
+#[derive(Default)]
+pub struct GuestStatus {
+    pub handle: u32,
+    pub policy: u32,
+    pub state: u32,
+}
+
 #[repr(C)]
 pub struct Attestation<'a> {
     mnonce: [u8; 16],
@@ -165,3 +336,27 @@ impl<'a> Attestation<'a> {
         }
     }
 }
+
+// include/uapi/linux/kvm.h: struct kvm_sev_*
+uapi_layout! {
+    LaunchStart<'static> = 40, {
+        handle: 0, policy: 4, dh_addr: 8, dh_len: 16, session_addr: 24, session_len: 32,
+    },
+    LaunchUpdateData<'static> = 16, { addr: 0, len: 8 },
+    LaunchSecret<'static> = 48, {
+        hdr_addr: 0, hdr_len: 8, guest_addr: 16, guest_len: 24, trans_addr: 32, trans_len: 40,
+    },
+    LaunchMeasure<'static> = 16, { addr: 0, len: 8 },
+    SendStart<'static> = 72, {
+        policy: 0, pdh_addr: 8, pdh_len: 16, plat_certs_addr: 24, plat_certs_len: 32,
+        hygon_certs_addr: 40, hygon_certs_len: 48, session_addr: 56, session_len: 64,
+    },
+    TransferData<'static> = 48, {
+        hdr_addr: 0, hdr_len: 8, guest_addr: 16, guest_len: 24, trans_addr: 32, trans_len: 40,
+    },
+    ReceiveStart<'static> = 40, {
+        handle: 0, policy: 4, pdh_addr: 8, pdh_len: 16, session_addr: 24, session_len: 32,
+    },
+    GuestStatus = 12, { handle: 0, policy: 4, state: 8 },
+    Attestation<'static> = 32, { mnonce: 0, addr: 16, len: 24 },
+}
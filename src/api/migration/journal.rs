@@ -0,0 +1,166 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Progress of a migration, persisted to resume interrupted transfers.
+
+use crate::crypto::gm;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+/// A packet of guest memory exported by the source platform.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// The guest physical address of the data.
+    pub gpa: u64,
+
+    /// The length of the data.
+    pub len: u32,
+
+    /// The SM3 digest of the packet header followed by the data.
+    pub digest: [u8; 32],
+}
+
+impl Chunk {
+    /// Describes the packet made of `header` and the encrypted `data`.
+    pub fn new(gpa: u64, header: &[u8], data: &[u8]) -> Result<Self> {
+        let len = u32::try_from(data.len()).map_err(|_| ErrorKind::InvalidInput)?;
+        let packet = [header, data].concat();
+
+        let digest = gm::sm3(&packet)?;
+
+        Ok(Self { gpa, len, digest })
+    }
+}
+
+/// The chunks transferred so far, in order.
+///
+/// The source records every chunk it exports; the destination accepts
+/// chunks against its own journal, so chunks sent again after a resumed
+/// transfer are recognized and altered chunks rejected. The transport keys
+/// are not part of the journal and must be kept separately.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Journal {
+    chunks: Vec<Chunk>,
+    complete: bool,
+}
+
+impl Journal {
+    /// Starts an empty journal.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a journal saved with [`Journal::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Writes the journal, replacing the previous one atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// The index of the next chunk to transfer.
+    pub fn next(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The chunks transferred so far.
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Returns true once the transfer was marked complete.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Marks the transfer complete; no further chunk is accepted.
+    pub fn finish(&mut self) {
+        self.complete = true;
+    }
+
+    /// Records a chunk exported by the source, returning its index.
+    pub fn record(&mut self, chunk: Chunk) -> Result<usize> {
+        self.accept(self.next(), chunk)?;
+        Ok(self.next() - 1)
+    }
+
+    /// Accepts the chunk at `index` on the destination.
+    ///
+    /// Returns false if the same chunk was already accepted, which happens
+    /// when the source resumes from an older journal. Fails if the chunk
+    /// differs from the one accepted at that index or if chunks are missing.
+    pub fn accept(&mut self, index: usize, chunk: Chunk) -> Result<bool> {
+        if self.complete {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the transfer is complete",
+            ));
+        }
+
+        match self.chunks.get(index) {
+            Some(accepted) if *accepted == chunk => Ok(false),
+            Some(_) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("chunk {index} differs from the one transferred before"),
+            )),
+            None if index == self.next() => {
+                self.chunks.push(chunk);
+                Ok(true)
+            }
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("chunk {index} received, expected chunk {}", self.next()),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume() {
+        let chunk = |gpa: u64| Chunk::new(gpa, &[0u8; 52], &[gpa as u8; 4096]).unwrap();
+
+        let mut source = Journal::new();
+        let mut destination = Journal::new();
+        for gpa in [0x0000, 0x1000] {
+            let index = source.record(chunk(gpa)).unwrap();
+            assert!(destination.accept(index, chunk(gpa)).unwrap());
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("journal");
+        source.save(&path).unwrap();
+        let mut source = Journal::load(&path).unwrap();
+        assert_eq!(source.next(), 2);
+
+        // The source resumes one chunk behind the destination.
+        assert!(!destination.accept(1, chunk(0x1000)).unwrap());
+        assert_eq!(
+            destination.accept(1, chunk(0x2000)).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            destination.accept(3, chunk(0x3000)).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        source.record(chunk(0x2000)).unwrap();
+        source.finish();
+        assert!(source.record(chunk(0x3000)).is_err());
+        assert_eq!(source.chunks()[2].gpa, 0x2000);
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checks performed before migrating a guest to another platform.
+//!
+//! The destination firmware enforces the guest policy, but only fails the
+//! transfer with an opaque `PolicyFailure` once data has been exported.
+//! [`check_send`] evaluates the same rules up front and reports which one
+//! forbids the migration.
+//!
+//! On the guest owner side, [`Destination::from_chain`] authenticates the
+//! destination platform before its PDH is trusted with the transport keys.
+//! A [`Journal`] tracks the transferred chunks so that an interrupted
+//! transfer can resume where it stopped.
+//!
+//! The transfer itself is driven by a [`Sender`] on the source and a
+//! [`Receiver`] on the destination, which wrap the SEND_* and RECEIVE_*
+//! firmware commands.
+
+mod journal;
+pub use journal::*;
+
+mod transfer;
+pub use transfer::*;
+
+use crate::{
+    api::guest::GuestPolicy,
+    certs::{ca, Chain, Verifiable},
+    Version,
+};
+
+use std::{error, fmt, io};
+
+/// What the guest policy needs to know about a destination platform.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Destination {
+    /// Whether the destination belongs to the domain of the source.
+    pub same_domain: bool,
+
+    /// Whether the destination supports CSV.
+    pub csv: bool,
+
+    /// Whether the destination supports CSV3.
+    pub csv3: bool,
+
+    /// The API version of the destination firmware.
+    pub api: Version,
+
+    /// The version of the destination HSK.
+    pub hsk_version: u8,
+
+    /// The version of the destination CEK.
+    pub cek_version: u8,
+}
+
+impl Destination {
+    /// Verifies the certificate chain exported by the destination platform
+    /// up to `hrk`, and reads the firmware version it certifies.
+    ///
+    /// The API version is the firmware version in the body of the PEK
+    /// certificate, which the CEK signs. A platform with a chain is CSV
+    /// capable. Whether it is in the domain or supports CSV3, and its HSK
+    /// and CEK versions, cannot be told from the chain: the `ver` fields of
+    /// the certificates are their format version. These are left unset for
+    /// the caller to fill.
+    pub fn from_chain(chain: &Chain, hrk: &ca::Certificate) -> io::Result<Self> {
+        if chain.ca.hrk != *hrk {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the chain is not rooted in the trusted HRK",
+            ));
+        }
+
+        chain.verify()?;
+
+        Ok(Self {
+            csv: true,
+            api: chain.csv.pek.body.data.firmware,
+            ..Default::default()
+        })
+    }
+}
+
+/// A rule of the guest policy which forbids a migration.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// The NOSEND bit is set.
+    NoSend,
+
+    /// The DOMAIN bit is set and the destination is outside the domain.
+    Domain,
+
+    /// The CSV bit is set and the destination does not support CSV.
+    Csv,
+
+    /// The CSV3 bit is set and the destination does not support CSV3.
+    Csv3,
+
+    /// The destination firmware API is older than the policy allows.
+    Api {
+        /// The minimum version set by the policy.
+        required: Version,
+        /// The version of the destination.
+        actual: Version,
+    },
+
+    /// The destination HSK is older than the policy allows.
+    HskVersion {
+        /// The minimum version set by the policy.
+        required: u8,
+        /// The version of the destination.
+        actual: u8,
+    },
+
+    /// The destination CEK is older than the policy allows.
+    CekVersion {
+        /// The minimum version set by the policy.
+        required: u8,
+        /// The version of the destination.
+        actual: u8,
+    },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSend => write!(f, "the guest policy disallows sending the guest"),
+            Self::Domain => write!(f, "the guest policy restricts sending to the domain"),
+            Self::Csv => write!(f, "the guest policy requires a CSV capable destination"),
+            Self::Csv3 => write!(f, "the guest policy requires a CSV3 capable destination"),
+            Self::Api { required, actual } => write!(
+                f,
+                "the guest policy requires API version {required}, the destination has {actual}"
+            ),
+            Self::HskVersion { required, actual } => write!(
+                f,
+                "the guest policy requires HSK version {required}, the destination has {actual}"
+            ),
+            Self::CekVersion { required, actual } => write!(
+                f,
+                "the guest policy requires CEK version {required}, the destination has {actual}"
+            ),
+        }
+    }
+}
+
+impl error::Error for PolicyViolation {}
+
+impl From<PolicyViolation> for io::Error {
+    fn from(violation: PolicyViolation) -> Self {
+        io::Error::new(io::ErrorKind::PermissionDenied, violation)
+    }
+}
+
+/// Checks that `policy` allows sending the guest to `destination`.
+///
+/// The policy must be unmasked, see [`GuestPolicy::xor`]. The first rule
+/// forbidding the migration is returned, in the order of the policy bits.
+pub fn check_send(policy: GuestPolicy, destination: &Destination) -> Result<(), PolicyViolation> {
+    if policy.nosend() != 0 {
+        return Err(PolicyViolation::NoSend);
+    }
+
+    if policy.domain() != 0 && !destination.same_domain {
+        return Err(PolicyViolation::Domain);
+    }
+
+    if policy.csv() != 0 && !destination.csv {
+        return Err(PolicyViolation::Csv);
+    }
+
+    if policy.csv3() != 0 && !destination.csv3 {
+        return Err(PolicyViolation::Csv3);
+    }
+
+    let hsk = policy.hsk_version() as u8;
+    if destination.hsk_version < hsk {
+        return Err(PolicyViolation::HskVersion {
+            required: hsk,
+            actual: destination.hsk_version,
+        });
+    }
+
+    let cek = policy.cek_version() as u8;
+    if destination.cek_version < cek {
+        return Err(PolicyViolation::CekVersion {
+            required: cek,
+            actual: destination.cek_version,
+        });
+    }
+
+    let api = Version {
+        major: policy.api_major() as u8,
+        minor: policy.api_minor() as u8,
+    };
+    if destination.api < api {
+        return Err(PolicyViolation::Api {
+            required: api,
+            actual: destination.api,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certs::{builtin::HRK, csv, Usage};
+    use codicon::Decoder;
+
+    const HSK_CEK: &[u8] = include_bytes!("../../../tests/test_data/hsk_cek.cert");
+
+    fn destination() -> Destination {
+        Destination {
+            same_domain: true,
+            csv: true,
+            csv3: true,
+            api: Version { major: 1, minor: 2 },
+            hsk_version: 1,
+            cek_version: 1,
+        }
+    }
+
+    #[test]
+    fn check() {
+        // nosend
+        check_send(GuestPolicy::from(0x0000_0000), &destination()).unwrap();
+        assert_eq!(
+            check_send(GuestPolicy::from(0x0000_0008), &destination()),
+            Err(PolicyViolation::NoSend)
+        );
+
+        // domain, csv and csv3
+        let outside = Destination {
+            same_domain: false,
+            csv3: false,
+            ..destination()
+        };
+        check_send(GuestPolicy::from(0x0000_0020), &outside).unwrap();
+        assert_eq!(
+            check_send(GuestPolicy::from(0x0000_0010), &outside),
+            Err(PolicyViolation::Domain)
+        );
+        assert_eq!(
+            check_send(GuestPolicy::from(0x0000_0040), &outside),
+            Err(PolicyViolation::Csv3)
+        );
+
+        // api 1.3, hsk 1, cek 2
+        assert_eq!(
+            check_send(GuestPolicy::from(0x0301_2100), &destination()),
+            Err(PolicyViolation::CekVersion {
+                required: 2,
+                actual: 1
+            })
+        );
+        let err = check_send(GuestPolicy::from(0x0301_1100), &destination()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the guest policy requires API version 1.3, the destination has 1.2"
+        );
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn from_chain() {
+        let hrk = ca::Certificate::decode(HRK, ()).unwrap();
+        let mut reader = HSK_CEK;
+        let hsk = ca::Certificate::decode(&mut reader, ()).unwrap();
+        let cek = csv::Certificate::decode(&mut reader, ()).unwrap();
+        let generate = |usage| csv::Certificate::generate(usage, None).unwrap().0;
+
+        // The CEK does not endorse a generated PEK.
+        let mut chain = Chain {
+            ca: ca::Chain { hsk, hrk },
+            csv: csv::Chain {
+                pdh: generate(Usage::PDH),
+                pek: generate(Usage::PEK),
+                oca: generate(Usage::OCA),
+                cek,
+            },
+        };
+        assert!(Destination::from_chain(&chain, &hrk).is_err());
+
+        chain.ca.hrk = hsk;
+        let err = Destination::from_chain(&chain, &hrk).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn from_verified_chain() {
+        use crate::{sim::Platform, Build};
+
+        let build = Build {
+            version: Version { major: 1, minor: 3 },
+            build: 7,
+        };
+        let platform = Platform::with_build("SIM0000000001".parse().unwrap(), build).unwrap();
+        let destination = Destination::from_chain(&platform.chain(), platform.hrk()).unwrap();
+        assert_eq!(
+            destination,
+            Destination {
+                csv: true,
+                api: Version { major: 1, minor: 3 },
+                ..Default::default()
+            }
+        );
+    }
+}
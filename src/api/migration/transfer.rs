@@ -0,0 +1,218 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The SEND_* and RECEIVE_* commands moving a guest between platforms.
+
+use super::{check_send, Destination};
+use crate::{
+    api::{
+        guest::GuestPolicy,
+        launch::{self, ioctl::*, types::*, Header, Session},
+    },
+    certs::{ca, Chain},
+    util::*,
+};
+
+use std::{io::Result, mem::MaybeUninit, os::unix::io::AsRawFd};
+
+/// The destination PDH and the certificates authenticating it, as passed
+/// to SEND_START.
+pub struct Certificates {
+    /// The PDH certificate of the destination.
+    pub pdh: Vec<u8>,
+
+    /// The PEK, OCA and CEK certificates of the destination.
+    pub platform: Vec<u8>,
+
+    /// The HSK and HRK certificates of the destination.
+    pub hygon: Vec<u8>,
+}
+
+impl Certificates {
+    /// Packages the chain exported by the destination platform.
+    pub fn new(chain: &Chain) -> Result<Self> {
+        let mut pdh = Vec::new();
+        pdh.save(&chain.csv.pdh)?;
+
+        let mut platform = Vec::new();
+        platform.save(&chain.csv.pek)?;
+        platform.save(&chain.csv.oca)?;
+        platform.save(&chain.csv.cek)?;
+
+        let mut hygon = Vec::new();
+        hygon.save(&chain.ca.hsk)?;
+        hygon.save(&chain.ca.hrk)?;
+
+        Ok(Self {
+            pdh,
+            platform,
+            hygon,
+        })
+    }
+}
+
+/// Exports a running guest to another platform.
+///
+/// The guest memory is transferred in chunks with [`Sender::update_data`],
+/// which the destination imports in the same order with
+/// [`Receiver::update_data`].
+pub struct Sender<U: AsRawFd, V: AsRawFd> {
+    vm_fd: U,
+    csv: V,
+}
+
+impl<U: AsRawFd, V: AsRawFd> Sender<U, V> {
+    /// Starts exporting the guest of `vm_fd` to the platform owning
+    /// `chain`.
+    ///
+    /// The chain is verified up to `hrk` and the destination it describes,
+    /// see [`Destination::from_chain`], is checked against the guest policy
+    /// before the firmware is asked to export anything. Whether the
+    /// destination is in the domain of this platform and supports CSV3, and
+    /// its HSK and CEK versions, cannot be told from its chain, so the
+    /// caller vouches for them in `vouched`. Its CSV support and API
+    /// version are replaced by those the chain certifies. The returned
+    /// session is the one to pass to RECEIVE_START on the destination.
+    pub fn start(
+        mut vm_fd: U,
+        mut csv: V,
+        chain: &Chain,
+        hrk: &ca::Certificate,
+        vouched: Destination,
+    ) -> Result<(Self, Session)> {
+        let certified = Destination::from_chain(chain, hrk)?;
+        let destination = Destination {
+            csv: certified.csv,
+            api: certified.api,
+            ..vouched
+        };
+
+        let status = launch::status(&mut vm_fd, &mut csv)?;
+        check_send(GuestPolicy::from(u32::from(status.policy)), &destination)?;
+
+        let certs = Certificates::new(chain)?;
+        let mut session = MaybeUninit::uninit();
+        let mut send_start =
+            SendStart::new(&certs.pdh, &certs.platform, &certs.hygon, &mut session);
+        let mut cmd = Command::from_mut(&mut csv, &mut send_start);
+        SEND_START
+            .ioctl(&mut vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok((Self { vm_fd, csv }, unsafe { session.assume_init() }))
+    }
+
+    /// Encrypts a chunk of guest memory for transport.
+    ///
+    /// `guest` must be memory of the guest registered with KVM. Returns the
+    /// packet header and the encrypted chunk.
+    pub fn update_data(&mut self, guest: &[u8]) -> Result<(Header, Vec<u8>)> {
+        let mut header = MaybeUninit::uninit();
+        let mut trans = vec![0u8; guest.len()];
+        let send_update_data = SendUpdateData::new(&mut header, guest, &mut trans);
+        let mut cmd = Command::from(&mut self.csv, &send_update_data);
+        SEND_UPDATE_DATA
+            .ioctl(&mut self.vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok((unsafe { header.assume_init() }, trans))
+    }
+
+    /// Completes the export. The guest may not run on this platform
+    /// anymore.
+    pub fn finish(mut self) -> Result<U> {
+        let mut cmd = Command::from(&mut self.csv, &SendFinish);
+        SEND_FINISH
+            .ioctl(&mut self.vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok(self.vm_fd)
+    }
+}
+
+/// Imports a guest exported by a [`Sender`] on another platform.
+pub struct Receiver<U: AsRawFd, V: AsRawFd> {
+    vm_fd: U,
+    csv: V,
+    handle: u32,
+}
+
+impl<U: AsRawFd, V: AsRawFd> Receiver<U, V> {
+    /// Starts importing a guest into `vm_fd`.
+    ///
+    /// `start` holds the guest policy, the PDH of the source and the
+    /// session returned by [`Sender::start`].
+    pub fn start(mut vm_fd: U, mut csv: V, start: &launch::Start) -> Result<Self> {
+        let mut receive_start =
+            ReceiveStart::new(u32::from(start.policy), &start.cert, &start.session);
+        let mut cmd = Command::from_mut(&mut csv, &mut receive_start);
+        RECEIVE_START
+            .ioctl(&mut vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        let handle = receive_start.handle;
+        Ok(Self { vm_fd, csv, handle })
+    }
+
+    /// The firmware handle of the imported guest.
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    /// Decrypts a chunk produced by [`Sender::update_data`] into `guest`,
+    /// which must be memory of the guest registered with KVM.
+    pub fn update_data(&mut self, header: &Header, guest: &mut [u8], trans: &[u8]) -> Result<()> {
+        let receive_update_data = ReceiveUpdateData::new(header, guest, trans);
+        let mut cmd = Command::from(&mut self.csv, &receive_update_data);
+        RECEIVE_UPDATE_DATA
+            .ioctl(&mut self.vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok(())
+    }
+
+    /// Completes the import and releases the vm fd to run the guest.
+    pub fn finish(mut self) -> Result<U> {
+        let mut cmd = Command::from(&mut self.csv, &ReceiveFinish);
+        RECEIVE_FINISH
+            .ioctl(&mut self.vm_fd, &mut cmd)
+            .map_err(|e| cmd.encapsulate(e))?;
+
+        Ok(self.vm_fd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certs::{ca, csv};
+    use codicon::Decoder;
+
+    const HSK_CEK: &[u8] = include_bytes!("../../../tests/test_data/hsk_cek.cert");
+
+    #[test]
+    fn certificates() {
+        let mut reader = HSK_CEK;
+        let hsk = ca::Certificate::decode(&mut reader, ()).unwrap();
+        let cek = csv::Certificate::decode(&mut reader, ()).unwrap();
+        let chain = Chain {
+            ca: ca::Chain { hsk, hrk: hsk },
+            csv: csv::Chain {
+                pdh: cek,
+                pek: cek,
+                oca: cek,
+                cek,
+            },
+        };
+
+        let certs = Certificates::new(&chain).unwrap();
+        assert_eq!(certs.pdh.len(), std::mem::size_of::<csv::Certificate>());
+        assert_eq!(certs.platform.len(), 3 * certs.pdh.len());
+
+        let mut hsk = Vec::new();
+        hsk.save(&chain.ca.hsk).unwrap();
+        assert_eq!(certs.hygon.len(), 2 * hsk.len());
+        assert!(certs.hygon.starts_with(&hsk));
+    }
+}
@@ -7,4 +7,6 @@
 
 pub mod guest;
 pub mod launch;
+pub mod migration;
 pub mod platform;
+pub mod watch;
@@ -0,0 +1,221 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! An asynchronous handle to the CSV platform, for tokio-based management
+//! daemons.
+//!
+//! Every command is issued by the synchronous [`super::Firmware`] on
+//! tokio's blocking pool, so both APIs share the command encoding, the
+//! internal lock and the timeout. The commands of a [`PlatformProvider`]
+//! and the CEK provisioning are available for any provider, e.g. a
+//! simulated platform in tests.
+
+use super::{cek, Bundle, CekIdentity, Chain, Identifier, KeyRotation, PlatformProvider, Status};
+#[cfg(feature = "network")]
+use crate::kds;
+use crate::{
+    certs::{self, csv::Certificate, HskCek},
+    error::{Error, Indeterminate},
+    Build,
+};
+
+use codicon::Decoder;
+use std::{io, path::PathBuf, sync::Arc};
+
+/// An asynchronous handle to the CSV platform, see [`super::Firmware`].
+///
+/// Handles are cheap to clone and share the underlying device.
+pub struct Firmware<P = super::Firmware>(Arc<P>);
+
+impl<P> Clone for Firmware<P> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<P: Send + Sync + 'static> Firmware<P> {
+    /// The synchronous handle commands are issued on.
+    pub fn blocking(&self) -> &P {
+        &self.0
+    }
+
+    /// Runs `f` with the synchronous handle on the blocking pool, e.g. for
+    /// commands without an asynchronous wrapper.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, Indeterminate<Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&P) -> Result<T, Indeterminate<Error>> + Send + 'static,
+    {
+        let firmware = self.0.clone();
+        tokio::task::spawn_blocking(move || f(&firmware))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+impl<P: PlatformProvider + Send + Sync + 'static> Firmware<P> {
+    /// See [`super::Firmware::platform_status`].
+    pub async fn platform_status(&self) -> Result<Status, Indeterminate<Error>> {
+        self.run(|fw| fw.platform_status()).await
+    }
+
+    /// See [`super::Firmware::pdh_cert_export`].
+    pub async fn pdh_cert_export(&self) -> Result<Chain, Indeterminate<Error>> {
+        self.run(|fw| fw.pdh_cert_export()).await
+    }
+
+    /// See [`super::Firmware::get_identifier`].
+    pub async fn get_identifier(&self) -> Result<Identifier, Indeterminate<Error>> {
+        self.run(|fw| fw.get_identifier()).await
+    }
+
+    /// See [`super::Firmware::cek_identity`].
+    pub async fn cek_identity(&self) -> Result<CekIdentity, Indeterminate<Error>> {
+        self.run(|fw| cek::identity(fw)).await
+    }
+
+    /// See [`super::Firmware::fetch_cek`].
+    #[cfg(feature = "network")]
+    pub async fn fetch_cek(
+        &self,
+        client: kds::Client,
+    ) -> Result<certs::Chain, Indeterminate<Error>> {
+        self.run(move |fw| cek::fetch(fw, &client)).await
+    }
+
+    /// See [`super::Firmware::install_cek`].
+    pub async fn install_cek(
+        &self,
+        signed: HskCek,
+        path: impl Into<PathBuf>,
+    ) -> Result<certs::Chain, Indeterminate<Error>> {
+        let path = path.into();
+        self.run(move |fw| {
+            let hrk = certs::ca::Certificate::decode(certs::builtin::HRK, ())?;
+            cek::install(fw, &signed, &hrk, path)
+        })
+        .await
+    }
+}
+
+impl Firmware {
+    /// Create a handle to the CSV platform.
+    pub async fn open() -> io::Result<Firmware> {
+        tokio::task::spawn_blocking(super::Firmware::open)
+            .await
+            .map_err(io::Error::other)?
+            .map(Self::from)
+    }
+
+    /// Create a handle to the PSP at `path`, see
+    /// [`super::Firmware::open_path`].
+    pub async fn open_path(path: impl Into<PathBuf>) -> io::Result<Firmware> {
+        let path = path.into();
+        tokio::task::spawn_blocking(move || super::Firmware::open_path(path))
+            .await
+            .map_err(io::Error::other)?
+            .map(Self::from)
+    }
+
+    /// See [`super::Firmware::platform_reset`].
+    pub async fn platform_reset(&self) -> Result<(), Indeterminate<Error>> {
+        self.run(|fw| fw.platform_reset()).await
+    }
+
+    /// See [`super::Firmware::check_update`].
+    pub async fn check_update(&self, before: Build) -> Result<Build, Indeterminate<Error>> {
+        self.run(move |fw| fw.check_update(before)).await
+    }
+
+    /// See [`super::Firmware::pek_generate`].
+    pub async fn pek_generate(&self) -> Result<(), Indeterminate<Error>> {
+        self.run(|fw| fw.pek_generate()).await
+    }
+
+    /// See [`super::Firmware::pek_csr`].
+    pub async fn pek_csr(&self) -> Result<Certificate, Indeterminate<Error>> {
+        self.run(|fw| fw.pek_csr()).await
+    }
+
+    /// See [`super::Firmware::pdh_generate`].
+    pub async fn pdh_generate(&self) -> Result<(), Indeterminate<Error>> {
+        self.run(|fw| fw.pdh_generate()).await
+    }
+
+    /// See [`super::Firmware::pek_cert_import`].
+    pub async fn pek_cert_import(
+        &self,
+        pek: Certificate,
+        oca: Certificate,
+    ) -> Result<(), Indeterminate<Error>> {
+        self.run(move |fw| fw.pek_cert_import(&pek, &oca)).await
+    }
+
+    /// See [`super::Firmware::rotate_keys`].
+    pub async fn rotate_keys(&self) -> Result<KeyRotation, Indeterminate<Error>> {
+        self.run(|fw| fw.rotate_keys()).await
+    }
+
+    /// See [`super::Firmware::export_bundle`].
+    pub async fn export_bundle(&self) -> Result<Bundle, Indeterminate<Error>> {
+        self.run(|fw| fw.export_bundle()).await
+    }
+}
+
+impl<P> From<P> for Firmware<P> {
+    fn from(firmware: P) -> Self {
+        Self(Arc::new(firmware))
+    }
+}
+
+impl<P> From<Arc<P>> for Firmware<P> {
+    fn from(firmware: Arc<P>) -> Self {
+        Self(firmware)
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+mod tests {
+    use super::*;
+    use crate::{api::platform::State, sim::Platform, ChipId};
+
+    fn platform() -> Firmware<Platform> {
+        Platform::new("SIM0000000001".parse().unwrap())
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn commands() {
+        let firmware = platform();
+        let status = firmware.platform_status().await.unwrap();
+        assert_eq!(status.state, State::Initialized);
+
+        let identity = firmware.cek_identity().await.unwrap();
+        assert_eq!(
+            ChipId::try_from(&identity.identifier).unwrap(),
+            "SIM0000000001".parse().unwrap()
+        );
+        assert_eq!(identity.cek, firmware.pdh_cert_export().await.unwrap().cek);
+    }
+
+    #[tokio::test]
+    async fn install_cek() {
+        let firmware = platform();
+        let tmp = tempfile::tempdir().unwrap();
+        let signed = firmware.blocking().hsk_cek();
+
+        // The simulated chain does not verify up to HYGON's root.
+        let path = tmp.path().join("chain");
+        firmware.install_cek(signed, &path).await.unwrap_err();
+        assert!(!path.exists());
+
+        // It does up to its own.
+        let chain = firmware
+            .run(move |fw| cek::install(fw, &signed, fw.hrk(), path))
+            .await
+            .unwrap();
+        assert_eq!(chain, firmware.blocking().chain());
+    }
+}
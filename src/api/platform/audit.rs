@@ -0,0 +1,117 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Audit trail of the commands altering the platform state.
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A platform-mutating command issued through a [`super::Firmware`] handle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The command name, e.g. `PEK_GEN`.
+    pub command: &'static str,
+
+    /// When the command completed.
+    pub timestamp: SystemTime,
+
+    /// The process which issued the command.
+    pub pid: u32,
+
+    /// The real user ID of the process.
+    pub uid: u32,
+
+    /// The name of the calling thread, if any.
+    pub thread: Option<String>,
+
+    /// The outcome, with the error message on failure.
+    pub result: Result<(), String>,
+}
+
+impl Record {
+    pub(crate) fn new(command: &'static str, result: Result<(), String>) -> Self {
+        Self {
+            command,
+            timestamp: SystemTime::now(),
+            pid: std::process::id(),
+            uid: unsafe { libc::getuid() },
+            thread: std::thread::current().name().map(String::from),
+            result,
+        }
+    }
+}
+
+/// Formats the record as a single log line.
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        write!(
+            f,
+            "{}.{:03} {} pid={} uid={} thread={} ",
+            time.as_secs(),
+            time.subsec_millis(),
+            self.command,
+            self.pid,
+            self.uid,
+            self.thread.as_deref().unwrap_or("-"),
+        )?;
+
+        match &self.result {
+            Ok(()) => write!(f, "ok"),
+            Err(e) => write!(f, "failed: {e}"),
+        }
+    }
+}
+
+/// A destination for audit records.
+pub trait AuditSink: Send + Sync {
+    /// Stores a record. Failures must be handled by the sink itself since
+    /// the command already completed.
+    fn record(&self, record: &Record);
+}
+
+/// Appends one line per record to a file.
+pub struct FileSink(Mutex<File>);
+
+impl FileSink {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self(Mutex::new(file)))
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record(&self, record: &Record) {
+        let mut file = crate::util::lock::lock(&self.0);
+        // There is no caller left to report a failed write to.
+        let _ = writeln!(file, "{record}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let mut record = Record::new("PEK_GEN", Err("Invalid platform state".into()));
+        record.timestamp = UNIX_EPOCH + std::time::Duration::from_millis(1_500);
+        record.thread = None;
+
+        let line = record.to_string();
+        assert!(line.starts_with("1.500 PEK_GEN pid="));
+        assert!(line.ends_with("thread=- failed: Invalid platform state"));
+    }
+}
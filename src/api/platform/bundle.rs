@@ -0,0 +1,237 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Platform identity bundles for offline auditing.
+//!
+//! A bundle is a single file with the following little-endian layout:
+//!
+//! | Offset | Size     | Contents                                     |
+//! |--------|----------|----------------------------------------------|
+//! | 0      | 8        | magic, `CSVPBNDL`                            |
+//! | 8      | 4        | format version, currently 1                  |
+//! | 12     | 4        | identifier length `n`                        |
+//! | 16     | n        | GET_ID output                                |
+//! | 16 + n | 12       | status: major, minor, build, state, flags, guests |
+//! | 28 + n | 4 * 2084 | PDH, PEK, OCA and CEK certificates           |
+//!
+//! Certificates are stored with their signatures, so the chain can be
+//! verified offline.
+
+use super::{Identifier, PlatformStatusFlags, State, Status};
+use crate::{
+    certs::{csv, Usage, Verifiable},
+    util::{tracked::Tracked, *},
+    Build, Version,
+};
+
+use codicon::{Decoder, Encoder};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write},
+    path::Path,
+};
+
+const MAGIC: [u8; 8] = *b"CSVPBNDL";
+const FORMAT: u32 = 1;
+
+/// The identity of a machine: its chip identifier, status and exported
+/// certificate chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bundle {
+    /// The output of GET_ID.
+    pub identifier: Identifier,
+
+    /// The platform status at export time.
+    pub status: Status,
+
+    /// The exported certificate chain.
+    pub chain: csv::Chain,
+}
+
+impl Bundle {
+    /// Writes the bundle to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.encode(&mut writer, ())?;
+        writer.flush()
+    }
+
+    /// Reads a bundle from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::decode(BufReader::new(File::open(path)?), ())
+    }
+
+    /// Verifies the signatures of the certificate chain.
+    ///
+    /// The CEK is not checked against the HYGON root keys, which requires
+    /// the HSK and CEK certificates issued by the HYGON KDS.
+    pub fn verify(&self) -> Result<()> {
+        (&self.chain).verify().map(|_| ())
+    }
+}
+
+fn load_cert(reader: &mut impl Read, usage: Usage) -> Result<csv::Certificate> {
+    let cert = csv::Certificate::decode(&mut *reader, ())?;
+    if Usage::try_from(&cert)? != usage {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("expected a {usage:?} certificate"),
+        ));
+    }
+
+    Ok(cert)
+}
+
+impl Decoder<()> for Bundle {
+    type Error = Error;
+
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
+
+        reader.field("magic", |r| {
+            if r.load::<[u8; 8]>()? != MAGIC {
+                return Err(Error::new(ErrorKind::InvalidData, "not a platform bundle"));
+            }
+            Ok(())
+        })?;
+
+        reader.field("format", |r| {
+            let format = u32::from_le_bytes(r.load()?);
+            if format != FORMAT {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unsupported platform bundle format {format}"),
+                ));
+            }
+            Ok(())
+        })?;
+
+        let identifier = reader.field("identifier", |r| {
+            let len = u32::from_le_bytes(r.load()?);
+            if len > 64 {
+                return Err(Error::new(ErrorKind::InvalidData, "identifier too long"));
+            }
+            let mut identifier = vec![0u8; len as usize];
+            r.read_exact(&mut identifier)?;
+            Ok(identifier)
+        })?;
+
+        let status = reader.field("status", |r| {
+            let [major, minor, build, state]: [u8; 4] = r.load()?;
+            let flags = u32::from_le_bytes(r.load()?);
+            let guests = u32::from_le_bytes(r.load()?);
+
+            Ok(Status {
+                build: Build {
+                    version: Version { major, minor },
+                    build,
+                },
+                state: match state {
+                    0 => State::Uninitialized,
+                    1 => State::Initialized,
+                    2 => State::Working,
+                    _ => return Err(Error::new(ErrorKind::InvalidData, "invalid platform state")),
+                },
+                flags: PlatformStatusFlags::from_bits_truncate(flags),
+                guests,
+            })
+        })?;
+
+        let chain = csv::Chain {
+            pdh: reader.field("pdh", |r| load_cert(r, Usage::PDH))?,
+            pek: reader.field("pek", |r| load_cert(r, Usage::PEK))?,
+            oca: reader.field("oca", |r| load_cert(r, Usage::OCA))?,
+            cek: reader.field("cek", |r| load_cert(r, Usage::CEK))?,
+        };
+
+        Ok(Self {
+            identifier: Identifier(identifier),
+            status,
+            chain,
+        })
+    }
+}
+
+impl Encoder<()> for Bundle {
+    type Error = Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<()> {
+        let status = &self.status;
+        let version = status.build.version;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&FORMAT.to_le_bytes())?;
+        writer.write_all(&(self.identifier.0.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.identifier.0)?;
+        writer.write_all(&[
+            version.major,
+            version.minor,
+            status.build.build,
+            status.state as u8,
+        ])?;
+        writer.write_all(&status.flags.bits().to_le_bytes())?;
+        writer.write_all(&status.guests.to_le_bytes())?;
+
+        for cert in [
+            &self.chain.pdh,
+            &self.chain.pek,
+            &self.chain.oca,
+            &self.chain.cek,
+        ] {
+            writer.save(cert)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cert(usage: Usage) -> csv::Certificate {
+        csv::Certificate::generate(usage, None).unwrap().0
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bundle = Bundle {
+            identifier: Identifier(b"NZA9T14052605".to_vec()),
+            status: Status {
+                build: Build {
+                    version: Version { major: 1, minor: 3 },
+                    build: 7,
+                },
+                state: State::Initialized,
+                flags: PlatformStatusFlags::OWNED,
+                guests: 2,
+            },
+            chain: csv::Chain {
+                pdh: cert(Usage::PDH),
+                pek: cert(Usage::PEK),
+                oca: cert(Usage::OCA),
+                cek: cert(Usage::CEK),
+            },
+        };
+
+        let mut bytes = Vec::new();
+        bundle.encode(&mut bytes, ()).unwrap();
+        assert_eq!(bytes.len(), 28 + 13 + 4 * 2084);
+
+        let decoded = Bundle::decode(&bytes[..], ()).unwrap();
+        assert_eq!(decoded.identifier, bundle.identifier);
+        assert_eq!(decoded.status, bundle.status);
+        assert!(decoded.chain.pdh == bundle.chain.pdh);
+        assert!(decoded.chain.cek == bundle.chain.cek);
+
+        let err = Bundle::decode(&bytes[..bytes.len() - 1], ()).unwrap_err();
+        let located = crate::error::DecodeError::find(&err).unwrap();
+        assert_eq!(located.field, "cek.sigs[1].reserved");
+        assert_eq!(located.offset as usize, bytes.len() - 368);
+
+        bytes[0] ^= 1;
+        let err = Bundle::decode(&bytes[..], ()).unwrap_err();
+        assert_eq!(err.to_string(), "magic at byte 0: not a platform bundle");
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provisioning of the HYGON-signed CEK certificate.
+//!
+//! HYGON signs the Chip Endorsement Key of every chip with its HSK and
+//! serves the certificate by chip identifier, see [`crate::kds`]. The
+//! firmware has no command to import it, so the platform chain exported by
+//! `pdh_cert_export` cannot be verified up to the HRK on its own.
+//!
+//! With the `network` feature, [`Firmware::fetch_cek`] downloads them
+//! directly. Machines without access to the KDS are provisioned
+//! out-of-band: their [`CekIdentity`] is exported, the HSK and CEK
+//! certificates are fetched elsewhere, and [`Firmware::install_cek`] stores
+//! the completed chain where [`crate::cached_chain::get`] finds it.
+//!
+//! The same flows are available for any [`PlatformProvider`], as
+//! [`identity`], [`fetch`] and [`install`].
+
+use super::{Firmware, Identifier, PlatformProvider};
+#[cfg(feature = "network")]
+use crate::kds;
+use crate::{
+    certs::{builtin::HRK, ca, csv, Chain, HskCek, Verifiable},
+    error::{Error, Indeterminate},
+};
+
+use codicon::{Decoder, Encoder};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// What identifies the CEK of a platform to HYGON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CekIdentity {
+    /// The unique identifier of the chip, by which the KDS serves its
+    /// certificates.
+    pub identifier: Identifier,
+
+    /// The CEK certificate as exported by the firmware.
+    pub cek: csv::Certificate,
+}
+
+/// Completes the chain exported by a platform with the HSK and CEK
+/// certificates HYGON issued for it, and verifies it up to `hrk`.
+///
+/// Fails with `InvalidInput` if the CEK certificate certifies another key
+/// than the platform's.
+pub fn complete(platform: csv::Chain, signed: &HskCek, hrk: &ca::Certificate) -> io::Result<Chain> {
+    if signed.cek.body.data.pubkey != platform.cek.body.data.pubkey {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the CEK certificate certifies another key than the platform's",
+        ));
+    }
+
+    let chain = Chain {
+        ca: ca::Chain {
+            hsk: signed.hsk,
+            hrk: *hrk,
+        },
+        csv: csv::Chain {
+            cek: signed.cek,
+            ..platform
+        },
+    };
+    (&chain).verify()?;
+
+    Ok(chain)
+}
+
+/// Exports the identity HYGON issues the CEK certificate of `platform`
+/// for, see [`Firmware::cek_identity`].
+pub fn identity(platform: &impl PlatformProvider) -> Result<CekIdentity, Indeterminate<Error>> {
+    Ok(CekIdentity {
+        identifier: platform.get_identifier()?,
+        cek: platform.pdh_cert_export()?.cek,
+    })
+}
+
+/// Downloads the HSK and CEK certificates of `platform` from the KDS and
+/// completes its exported chain with them, see [`Firmware::fetch_cek`].
+#[cfg(feature = "network")]
+pub fn fetch(
+    platform: &impl PlatformProvider,
+    client: &kds::Client,
+) -> Result<Chain, Indeterminate<Error>> {
+    let signed = client.hsk_cek_of(&platform.get_identifier()?)?;
+    let hrk = ca::Certificate::decode(HRK, ())?;
+    Ok(complete(platform.pdh_cert_export()?, &signed, &hrk)?)
+}
+
+/// Completes the chain exported by `platform` with `signed`, verified up
+/// to `hrk`, and writes it to `path`, see [`Firmware::install_cek`].
+pub fn install(
+    platform: &impl PlatformProvider,
+    signed: &HskCek,
+    hrk: &ca::Certificate,
+    path: impl AsRef<Path>,
+) -> Result<Chain, Indeterminate<Error>> {
+    let chain = complete(platform.pdh_cert_export()?, signed, hrk)?;
+
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    chain.encode(&mut writer, ())?;
+    writer.flush()?;
+    fs::rename(tmp, path)?;
+
+    Ok(chain)
+}
+
+impl Firmware {
+    /// Export the identity HYGON issues the CEK certificate for, e.g. to
+    /// fetch it from another machine.
+    pub fn cek_identity(&self) -> Result<CekIdentity, Indeterminate<Error>> {
+        identity(self)
+    }
+
+    /// Download the HSK and CEK certificates of the platform from the KDS,
+    /// by the chip identifier returned by `GET_ID`, and complete the
+    /// exported chain with them.
+    ///
+    /// The chain is verified up to the builtin HRK. Nothing is stored, see
+    /// [`Firmware::install_cek`].
+    #[cfg(feature = "network")]
+    pub fn fetch_cek(&self, client: &kds::Client) -> Result<Chain, Indeterminate<Error>> {
+        fetch(self, client)
+    }
+
+    /// Install the HYGON-signed CEK certificate of the platform, fetched
+    /// out-of-band, by writing the completed chain to `path`.
+    ///
+    /// The chain is verified up to the builtin HRK first. `path` is
+    /// usually one of [`crate::cached_chain::path`].
+    pub fn install_cek(
+        &self,
+        signed: &HskCek,
+        path: impl AsRef<Path>,
+    ) -> Result<Chain, Indeterminate<Error>> {
+        install(self, signed, &ca::Certificate::decode(HRK, ())?, path)
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+mod tests {
+    use super::*;
+    use crate::{cached_chain, sim::Platform};
+
+    #[test]
+    fn install_then_get() {
+        let platform = Platform::new("SIM0000000001".parse().unwrap()).unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("hygon-csv").join("chain");
+
+        let chain = install(&platform, &platform.hsk_cek(), platform.hrk(), &path).unwrap();
+        assert_eq!(chain, platform.chain());
+        assert!(!path.with_extension("tmp").exists());
+
+        // The only test of this binary to set it.
+        std::env::set_var("CSV_CHAIN", &path);
+        assert_eq!(cached_chain::get().unwrap(), chain);
+
+        // Nothing is written if the chain does not verify.
+        let other = Platform::new("SIM0000000002".parse().unwrap()).unwrap();
+        let path = tmp.path().join("other");
+        install(&platform, &other.hsk_cek(), other.hrk(), &path).unwrap_err();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enumeration of the PSP device nodes of multi-socket servers.
+
+use super::{Firmware, Identifier};
+use crate::{error::*, ChipId};
+
+use std::{
+    fs,
+    io::{ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+/// The device node of the primary PSP.
+pub const DEFAULT_PATH: &str = "/dev/sev";
+
+/// The environment variable overriding the device node opened by
+/// [`Firmware::open`].
+pub const PATH_ENV: &str = "CSV_PLATFORM_DEVICE";
+
+/// A PSP device node.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PspDevice {
+    /// The socket the PSP belongs to, `None` for the primary node which
+    /// does not name one.
+    pub socket: Option<u32>,
+
+    /// The path of the device node.
+    pub path: PathBuf,
+}
+
+impl PspDevice {
+    /// Opens a handle to this PSP.
+    pub fn open(&self) -> Result<Firmware> {
+        Firmware::open_path(&self.path)
+    }
+
+    /// Opens a handle to this PSP and reads the chip identifier.
+    pub fn chip_id(&self) -> std::result::Result<ChipId, Indeterminate<Error>> {
+        let id: Identifier = self.open()?.get_identifier()?;
+        Ok(ChipId::try_from(&id)?)
+    }
+}
+
+/// Parses the socket of a PSP node name: `sev` is the primary node and
+/// `sevN` the one of socket N.
+fn socket(name: &str) -> Option<Option<u32>> {
+    match name.strip_prefix("sev")? {
+        "" => Some(None),
+        digits if digits.bytes().all(|b| b.is_ascii_digit()) => digits.parse().ok().map(Some),
+        _ => None,
+    }
+}
+
+/// Lists the PSP device nodes found in `dir`, the primary one first and
+/// the others by socket.
+pub fn enumerate_in(dir: impl AsRef<Path>) -> Result<Vec<PspDevice>> {
+    let mut devices = Vec::new();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(devices),
+        Err(e) => return Err(e),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        if let Some(socket) = entry.file_name().to_str().and_then(socket) {
+            devices.push(PspDevice {
+                socket,
+                path: entry.path(),
+            });
+        }
+    }
+
+    devices.sort();
+    Ok(devices)
+}
+
+/// Lists the PSP device nodes of the machine.
+///
+/// Single-socket machines, and kernels routing every command through the
+/// primary PSP, only expose [`DEFAULT_PATH`].
+pub fn enumerate() -> Result<Vec<PspDevice>> {
+    enumerate_in("/dev")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(socket("sev"), Some(None));
+        assert_eq!(socket("sev1"), Some(Some(1)));
+        assert_eq!(socket("sev-guest"), None);
+        assert_eq!(socket("csv-guest"), None);
+    }
+
+    #[test]
+    fn enumerate_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for name in ["sev1", "sev", "sev0", "sev-guest"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let sockets: Vec<_> = enumerate_in(dir)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.socket)
+            .collect();
+        assert_eq!(sockets, [None, Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn path_env() {
+        use std::os::fd::AsRawFd;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("sev");
+        fs::write(&path, b"").unwrap();
+
+        // The only test of this binary to set it.
+        std::env::set_var(PATH_ENV, &path);
+        let firmware = Firmware::open().unwrap();
+        let fd = firmware.as_raw_fd();
+        assert_eq!(
+            fs::read_link(format!("/proc/self/fd/{fd}")).unwrap(),
+            path.canonicalize().unwrap()
+        );
+
+        std::env::set_var(PATH_ENV, tmp.path().join("missing"));
+        let err = Firmware::open().err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        std::env::remove_var(PATH_ENV);
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ownership rollouts across a fleet of machines.
+
+use super::Firmware;
+use crate::{
+    certs::{csv::Certificate, Usage},
+    error::{Error, Indeterminate},
+    ChipId,
+};
+
+use codicon::Decoder;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, ErrorKind},
+    path::Path,
+};
+
+/// The name of the OCA certificate inside a fleet directory.
+pub const OCA_FILE: &str = "oca.cert";
+
+/// OCA-signed PEK certificates for a fleet of machines, keyed by chip ID.
+pub struct Fleet {
+    /// The OCA certificate which signed every PEK.
+    pub oca: Certificate,
+
+    /// The signed PEK certificate of each machine.
+    pub peks: HashMap<ChipId, Certificate>,
+}
+
+fn load_cert(path: &Path, usage: Usage) -> io::Result<Certificate> {
+    let cert = Certificate::decode(BufReader::new(File::open(path)?), ())?;
+    if Usage::try_from(&cert)? != usage {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is not a {usage:?} certificate", path.display()),
+        ));
+    }
+
+    Ok(cert)
+}
+
+impl Fleet {
+    /// Loads a fleet directory.
+    ///
+    /// The directory holds the OCA certificate as [`OCA_FILE`] and one
+    /// `<chip id>.cert` file per machine with its signed PEK certificate.
+    pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        let oca = load_cert(&dir.join(OCA_FILE), Usage::OCA)?;
+
+        let mut peks = HashMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() != Some("cert".as_ref()) {
+                continue;
+            }
+
+            let chip_id = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) if stem != "oca" => stem.parse::<ChipId>()?,
+                _ => continue,
+            };
+
+            peks.insert(chip_id, load_cert(&path, Usage::PEK)?);
+        }
+
+        Ok(Self { oca, peks })
+    }
+}
+
+impl Firmware {
+    /// Take ownership of the platform using the PEK signed for this
+    /// machine, as identified by GET_ID.
+    ///
+    /// The OCA and the PEK are checked before anything is imported, see
+    /// [`Firmware::pek_cert_import`]. Returns the chip ID of the local
+    /// machine.
+    pub fn import_from_fleet(&self, fleet: &Fleet) -> Result<ChipId, Indeterminate<Error>> {
+        let chip_id = ChipId::try_from(&self.get_identifier()?)?;
+
+        let pek = fleet.peks.get(&chip_id).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("no PEK certificate for chip {chip_id}"),
+            )
+        })?;
+
+        self.pek_cert_import(pek, &fleet.oca)?;
+        Ok(chip_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TypeSave;
+
+    #[test]
+    fn load_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let save = |name: &str, usage| {
+            let (cert, _) = Certificate::generate(usage, None).unwrap();
+            File::create(dir.join(name)).unwrap().save(&cert).unwrap();
+            cert
+        };
+
+        let oca = save(OCA_FILE, Usage::OCA);
+        let pek = save("NZA9T14052605.cert", Usage::PEK);
+        fs::write(dir.join("README"), "ignored").unwrap();
+
+        let fleet = Fleet::load_dir(dir).unwrap();
+
+        assert!(fleet.oca == oca);
+        assert_eq!(fleet.peks.len(), 1);
+        assert!(fleet.peks[&"NZA9T14052605".parse().unwrap()] == pek);
+    }
+}
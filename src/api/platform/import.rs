@@ -0,0 +1,113 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Local checks of the certificates handed to PEK_CERT_IMPORT, which the
+//! firmware would otherwise reject with a bare status code.
+
+use crate::certs::{csv::Certificate, Usage, Verifiable};
+
+use std::{error, fmt, io};
+
+/// A reason the firmware would reject a PEK certificate import.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    /// The OCA certificate has another usage.
+    OcaUsage(Usage),
+
+    /// The OCA certificate is not self-signed.
+    OcaSignature,
+
+    /// The PEK certificate has another usage.
+    PekUsage(Usage),
+
+    /// The PEK certificate is not signed by the OCA.
+    PekSignature,
+
+    /// The PEK certificate certifies another key than the outstanding CSR.
+    CsrMismatch,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OcaUsage(usage) => write!(f, "the OCA certificate has usage {usage:?}"),
+            Self::OcaSignature => write!(f, "the OCA certificate is not self-signed"),
+            Self::PekUsage(usage) => write!(f, "the PEK certificate has usage {usage:?}"),
+            Self::PekSignature => write!(f, "the PEK certificate is not signed by the OCA"),
+            Self::CsrMismatch => write!(
+                f,
+                "the PEK certificate does not match the outstanding signing request"
+            ),
+        }
+    }
+}
+
+impl error::Error for ImportError {}
+
+impl From<ImportError> for io::Error {
+    fn from(err: ImportError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// Checks that `pek` certifies the key of `csr` and is signed by the
+/// self-signed `oca`.
+pub(crate) fn check(
+    pek: &Certificate,
+    oca: &Certificate,
+    csr: &Certificate,
+) -> Result<(), ImportError> {
+    let usage = oca.body.data.pubkey.usage;
+    if usage != Usage::OCA {
+        return Err(ImportError::OcaUsage(usage));
+    }
+    (oca, oca).verify().map_err(|_| ImportError::OcaSignature)?;
+
+    let usage = pek.body.data.pubkey.usage;
+    if usage != Usage::PEK {
+        return Err(ImportError::PekUsage(usage));
+    }
+    (oca, pek).verify().map_err(|_| ImportError::PekSignature)?;
+
+    if pek.body.data.pubkey != csr.body.data.pubkey {
+        return Err(ImportError::CsrMismatch);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certs::Signer;
+
+    #[test]
+    fn validate() {
+        let (mut oca, oca_key) = Certificate::generate(Usage::OCA, None).unwrap();
+        oca_key.sign(&mut oca, "HYGON-SSD-OCA".into()).unwrap();
+        let (csr, _) = Certificate::generate(Usage::PEK, None).unwrap();
+
+        let mut pek = csr;
+        oca_key.sign(&mut pek, "HYGON-SSD-OCA".into()).unwrap();
+        check(&pek, &oca, &csr).unwrap();
+
+        assert_eq!(
+            check(&pek, &pek, &csr),
+            Err(ImportError::OcaUsage(Usage::PEK))
+        );
+        assert_eq!(
+            check(&oca, &oca, &csr),
+            Err(ImportError::PekUsage(Usage::OCA))
+        );
+        assert_eq!(check(&csr, &oca, &csr), Err(ImportError::PekSignature));
+
+        let (other, _) = Certificate::generate(Usage::PEK, None).unwrap();
+        assert_eq!(check(&pek, &oca, &other), Err(ImportError::CsrMismatch));
+
+        let (unsigned, _) = Certificate::generate(Usage::OCA, None).unwrap();
+        let err = io::Error::from(check(&pek, &unsigned, &csr).unwrap_err());
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "the OCA certificate is not self-signed");
+    }
+}
@@ -6,7 +6,11 @@
 //! (CSV) platform. These ioctls are exported by the Linux kernel.
 
 use super::types::*;
-use crate::impl_const_id;
+use crate::{
+    error::{Error, Indeterminate},
+    impl_const_id,
+    util::uapi::uapi_layout,
+};
 use iocuddle::{Group, Ioctl, WriteRead};
 use std::marker::PhantomData;
 
@@ -91,4 +95,18 @@ impl<'a, T: Id> Command<'a, T> {
             _phantom: PhantomData,
         }
     }
+
+    /// Encapsulate a `std::io::Error` in an `Indeterminate<Error>`, typed by
+    /// the status code of the firmware if it reported one.
+    pub fn encapsulate(&self, err: std::io::Error) -> Indeterminate<Error> {
+        match self.error {
+            0 => Indeterminate::<Error>::from(err),
+            _ => Indeterminate::<Error>::from(self.error),
+        }
+    }
+}
+
+// include/uapi/linux/psp-sev.h: struct sev_issue_cmd
+uapi_layout! {
+    Command<'static, PekGen> = 16, { code: 0, data: 4, error: 12 },
 }
@@ -9,14 +9,47 @@
 mod ioctl;
 pub use ioctl::*;
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+
 mod types;
 pub use types::*;
 
-use crate::{certs::csv::*, error::*, Build, Version};
+pub mod provision;
+
+mod bundle;
+pub use bundle::Bundle;
+
+pub mod device;
+pub use device::PspDevice;
+
+mod fleet;
+pub use fleet::{Fleet, OCA_FILE};
+
+mod rollback;
+pub use rollback::Rollback;
+
+mod import;
+pub use import::ImportError;
+
+pub mod cek;
+pub use cek::CekIdentity;
+
+mod provider;
+pub use provider::PlatformProvider;
+
+#[cfg(feature = "audit")]
+pub mod audit;
+
+use crate::{certs::csv::*, error::*, util::deadline, Build, ChipId, Version};
+use static_assertions::assert_impl_all;
 use std::{
     fs::{File, OpenOptions},
     mem::MaybeUninit,
-    os::unix::io::{AsRawFd, RawFd},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
 };
 
 /// The CPU-unique identifier for the platform.
@@ -39,110 +72,419 @@ impl std::fmt::Display for Identifier {
     }
 }
 
-pub struct Firmware(File);
+/// Everything needed to re-certify the platform after a key rotation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyRotation {
+    /// The signing request for the new PEK, to be signed by the OCA and
+    /// imported with [`Firmware::pek_cert_import`].
+    pub pek_csr: Certificate,
+
+    /// The certificate chain exported after the rotation.
+    pub chain: Chain,
+}
+
+/// The identifier returned by GET_ID is the chip's serial number.
+impl TryFrom<&Identifier> for ChipId {
+    type Error = std::io::Error;
+
+    fn try_from(id: &Identifier) -> std::io::Result<Self> {
+        ChipId::try_from(&id.0[..])
+    }
+}
+
+/// A handle to the CSV platform.
+///
+/// Commands are serialized through an internal lock, so a single handle
+/// may be shared between threads or tasks (e.g. behind an `Arc`).
+///
+/// The secure memory of CSV3 guests is configured by the host kernel: it
+/// issues SET_SMR and SET_SMCR itself when it initializes CSV3, from the
+/// memory it reserved at boot. Neither is exposed as a command, so the
+/// region sizes are set through the kernel's boot parameters instead.
+pub struct Firmware {
+    file: Arc<Mutex<File>>,
+    fd: RawFd,
+    timeout: Option<Duration>,
+    #[cfg(feature = "audit")]
+    audit: Option<Box<dyn audit::AuditSink>>,
+    #[cfg(feature = "capture")]
+    capture: Option<std::sync::Arc<crate::capture::Capture>>,
+}
+
+assert_impl_all!(Firmware: Send, Sync);
 
 impl Firmware {
-    /// Create a handle to the CSV platform.
+    /// Create a handle to the CSV platform, via [`device::DEFAULT_PATH`].
+    ///
+    /// The `CSV_PLATFORM_DEVICE` environment variable
+    /// ([`device::PATH_ENV`]) overrides the node if set. Use
+    /// [`Firmware::open_path`] to ignore the environment.
     pub fn open() -> std::io::Result<Firmware> {
-        Ok(Firmware(
-            OpenOptions::new().read(true).write(true).open("/dev/sev")?,
-        ))
+        match std::env::var_os(device::PATH_ENV) {
+            Some(path) => Self::open_path(path),
+            None => Self::open_path(device::DEFAULT_PATH),
+        }
+    }
+
+    /// Create a handle to the PSP at `path`, e.g. one socket of a
+    /// multi-socket server as listed by [`device::enumerate`].
+    pub fn open_path(path: impl AsRef<Path>) -> std::io::Result<Firmware> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(OwnedFd::from(file).into())
+    }
+
+    /// Bounds the time each command issued through this handle may take.
+    ///
+    /// A command that times out fails with `std::io::ErrorKind::TimedOut`.
+    /// It is abandoned rather than cancelled: the kernel keeps working on
+    /// buffers owned by a helper thread until the PSP answers, and the
+    /// handle stays locked until then, so later commands time out too.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Records every platform-mutating command issued through this handle.
+    #[cfg(feature = "audit")]
+    pub fn set_audit_sink(&mut self, sink: impl audit::AuditSink + 'static) {
+        self.audit = Some(Box::new(sink));
+    }
+
+    /// Records the raw buffers of every command issued through this handle
+    /// to `capture`, see [`crate::capture`].
+    #[cfg(feature = "capture")]
+    pub fn set_capture(&mut self, capture: std::sync::Arc<crate::capture::Capture>) {
+        self.capture = Some(capture);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, File> {
+        crate::util::lock::lock(&self.file)
+    }
+
+    /// Runs a command against the device, bounded by the handle's timeout.
+    ///
+    /// Every buffer the command hands to the kernel must be owned by `cmd`.
+    /// `_request` holds the input buffers, for the capture if any.
+    fn issue<T, F>(
+        &self,
+        _name: &'static str,
+        _request: &[&[u8]],
+        cmd: F,
+    ) -> Result<T, Indeterminate<Error>>
+    where
+        T: Response + Send + 'static,
+        F: FnOnce(&mut File) -> Result<T, Indeterminate<Error>> + Send + 'static,
+    {
+        let result = match self.timeout {
+            None => cmd(&mut self.lock()),
+            Some(timeout) => deadline::run_locked(timeout, &self.file, cmd)?,
+        };
+
+        #[cfg(feature = "capture")]
+        if let Some(capture) = &self.capture {
+            use crate::capture::{Device, Exchange, Failure};
+
+            capture.record(&Exchange {
+                device: Device::Platform,
+                command: _name.into(),
+                request: _request.concat(),
+                response: result.as_ref().map(Response::bytes).unwrap_or_default(),
+                error: result.as_ref().err().map(|e| Failure {
+                    errno: None,
+                    message: e.to_string(),
+                }),
+            });
+        }
+
+        result
+    }
+
+    /// Runs a command altering the platform state, recording it to the
+    /// audit sink if any.
+    fn mutate<F>(
+        &self,
+        name: &'static str,
+        request: &[&[u8]],
+        cmd: F,
+    ) -> Result<(), Indeterminate<Error>>
+    where
+        F: FnOnce(&mut File) -> Result<(), Indeterminate<Error>> + Send + 'static,
+    {
+        let result = self.issue(name, request, cmd);
+
+        #[cfg(feature = "audit")]
+        if let Some(sink) = &self.audit {
+            let outcome = result.as_ref().map(|_| ()).map_err(|e| e.to_string());
+            sink.record(&audit::Record::new(name, outcome));
+        }
+
+        result
     }
 
     /// Reset the platform persistent state.
-    pub fn platform_reset(&mut self) -> Result<(), Indeterminate<Error>> {
-        PLATFORM_RESET.ioctl(&mut self.0, &mut Command::from(&PlatformReset))?;
-        Ok(())
+    pub fn platform_reset(&self) -> Result<(), Indeterminate<Error>> {
+        self.mutate("PLATFORM_RESET", &[], |dev| {
+            let mut cmd = Command::from(&PlatformReset);
+            PLATFORM_RESET
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
+            Ok(())
+        })
     }
 
     /// Query the platform status.
-    pub fn platform_status(&mut self) -> Result<Status, Indeterminate<Error>> {
-        let mut info: PlatformStatus = Default::default();
-        PLATFORM_STATUS.ioctl(&mut self.0, &mut Command::from_mut(&mut info))?;
-
-        Ok(Status {
-            build: Build {
-                version: Version {
-                    major: info.version.major,
-                    minor: info.version.minor,
-                },
-                build: info.build,
-            },
-            guests: info.guest_count,
-            flags: info.flags,
-            state: match info.state {
-                0 => State::Uninitialized,
-                1 => State::Initialized,
-                2 => State::Working,
-                _ => return Err(Indeterminate::Unknown),
-            },
-        })
+    pub fn platform_status(&self) -> Result<Status, Indeterminate<Error>> {
+        let info = self.issue("PLATFORM_STATUS", &[], |dev| {
+            let mut info: PlatformStatus = Default::default();
+            let mut cmd = Command::from_mut(&mut info);
+            PLATFORM_STATUS
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
+            Ok(info)
+        })?;
+
+        status(&info)
+    }
+
+    /// Queries the firmware build after an update and fails with a
+    /// [`Rollback`] if it is older than `before`, the build queried before
+    /// the update.
+    ///
+    /// The kernel installs firmware updates when loading the driver, so the
+    /// handle must be opened after the update.
+    pub fn check_update(&self, before: Build) -> Result<Build, Indeterminate<Error>> {
+        let after = self.platform_status()?.build;
+        rollback::check(before, after).map_err(std::io::Error::other)?;
+        Ok(after)
     }
 
     /// Generate a new Platform Encryption Key (PEK).
-    pub fn pek_generate(&mut self) -> Result<(), Indeterminate<Error>> {
-        PEK_GEN.ioctl(&mut self.0, &mut Command::from(&PekGen))?;
-        Ok(())
+    pub fn pek_generate(&self) -> Result<(), Indeterminate<Error>> {
+        self.mutate("PEK_GEN", &[], |dev| {
+            let mut cmd = Command::from(&PekGen);
+            PEK_GEN
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
+            Ok(())
+        })
     }
 
     /// Request a signature for the PEK.
-    pub fn pek_csr(&mut self) -> Result<Certificate, Indeterminate<Error>> {
-        let mut pek = MaybeUninit::uninit();
-        let mut csr = PekCsr::new(&mut pek);
-        PEK_CSR.ioctl(&mut self.0, &mut Command::from_mut(&mut csr))?;
+    pub fn pek_csr(&self) -> Result<Certificate, Indeterminate<Error>> {
+        self.issue("PEK_CSR", &[], |dev| {
+            let mut pek = MaybeUninit::uninit();
+            let mut csr = PekCsr::new(&mut pek);
+            let mut cmd = Command::from_mut(&mut csr);
+            PEK_CSR
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
 
-        Ok(unsafe { pek.assume_init() })
+            Ok(unsafe { pek.assume_init() })
+        })
     }
 
     /// Generate a new Platform Diffie-Hellman (PDH) key pair.
-    pub fn pdh_generate(&mut self) -> Result<(), Indeterminate<Error>> {
-        PDH_GEN.ioctl(&mut self.0, &mut Command::from(&PdhGen))?;
-        Ok(())
+    pub fn pdh_generate(&self) -> Result<(), Indeterminate<Error>> {
+        self.mutate("PDH_GEN", &[], |dev| {
+            let mut cmd = Command::from(&PdhGen);
+            PDH_GEN
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
+            Ok(())
+        })
     }
 
     /// Export the CSV certificate chain.
-    pub fn pdh_cert_export(&mut self) -> Result<Chain, Indeterminate<Error>> {
-        let mut chain: MaybeUninit<[Certificate; 3]> = MaybeUninit::uninit();
-        let mut pdh = MaybeUninit::uninit();
-
-        let mut pdh_cert_export = PdhCertExport::new(&mut pdh, &mut chain);
-        PDH_CERT_EXPORT.ioctl(&mut self.0, &mut Command::from_mut(&mut pdh_cert_export))?;
-
-        Ok(Chain {
-            pdh: unsafe { pdh.assume_init() },
-            pek: unsafe { chain.assume_init() }[0],
-            oca: unsafe { chain.assume_init() }[1],
-            cek: unsafe { chain.assume_init() }[2],
+    pub fn pdh_cert_export(&self) -> Result<Chain, Indeterminate<Error>> {
+        self.issue("PDH_CERT_EXPORT", &[], |dev| {
+            let mut chain: MaybeUninit<[Certificate; 3]> = MaybeUninit::uninit();
+            let mut pdh = MaybeUninit::uninit();
+
+            let mut pdh_cert_export = PdhCertExport::new(&mut pdh, &mut chain);
+            let mut cmd = Command::from_mut(&mut pdh_cert_export);
+            PDH_CERT_EXPORT
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
+
+            Ok(Chain {
+                pdh: unsafe { pdh.assume_init() },
+                pek: unsafe { chain.assume_init() }[0],
+                oca: unsafe { chain.assume_init() }[1],
+                cek: unsafe { chain.assume_init() }[2],
+            })
         })
     }
 
     /// Take ownership of the CSV platform.
+    ///
+    /// The certificates are checked against the outstanding signing
+    /// request before anything is imported, failing with an
+    /// [`ImportError`] where the firmware would reject them.
     pub fn pek_cert_import(
-        &mut self,
+        &self,
         pek: &Certificate,
         oca: &Certificate,
     ) -> Result<(), Indeterminate<Error>> {
-        let pek_cert_import = PekCertImport::new(pek, oca);
-        PEK_CERT_IMPORT.ioctl(&mut self.0, &mut Command::from(&pek_cert_import))?;
-        Ok(())
+        import::check(pek, oca, &self.pek_csr()?).map_err(std::io::Error::from)?;
+
+        let request = [crate::pod::as_bytes(pek), crate::pod::as_bytes(oca)];
+        let (pek, oca) = (*pek, *oca);
+        self.mutate("PEK_CERT_IMPORT", &request, move |dev| {
+            let pek_cert_import = PekCertImport::new(&pek, &oca);
+            let mut cmd = Command::from(&pek_cert_import);
+            PEK_CERT_IMPORT
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
+            Ok(())
+        })
+    }
+
+    /// Regenerate the PEK and the PDH, then export what is needed to
+    /// re-certify the platform.
+    ///
+    /// Until the new PEK is signed and imported, the platform is
+    /// self-owned and previously exported chains are invalid.
+    pub fn rotate_keys(&self) -> Result<KeyRotation, Indeterminate<Error>> {
+        self.pek_generate()?;
+        self.pdh_generate()?;
+
+        Ok(KeyRotation {
+            pek_csr: self.pek_csr()?,
+            chain: self.pdh_cert_export()?,
+        })
+    }
+
+    /// Gather the platform identity into a bundle for offline auditing.
+    pub fn export_bundle(&self) -> Result<Bundle, Indeterminate<Error>> {
+        Ok(Bundle {
+            identifier: self.get_identifier()?,
+            status: self.platform_status()?,
+            chain: self.pdh_cert_export()?,
+        })
     }
 
     /// Get the unique CPU identifier.
     ///
     /// This is especially helpful for sending HYGON an HTTP request to fetch
     /// the signed CEK certificate.
-    pub fn get_identifier(&mut self) -> Result<Identifier, Indeterminate<Error>> {
-        let mut bytes = [0u8; 64];
-        let mut id = GetId::new(&mut bytes);
+    pub fn get_identifier(&self) -> Result<Identifier, Indeterminate<Error>> {
+        self.issue("GET_ID", &[], |dev| {
+            let mut bytes = [0u8; 64];
+            let mut id = GetId::new(&mut bytes);
+
+            let mut cmd = Command::from_mut(&mut id);
+            GET_ID
+                .ioctl(dev, &mut cmd)
+                .map_err(|e| cmd.encapsulate(e))?;
+
+            Ok(Identifier(id.as_slice().to_vec()))
+        })
+    }
+}
+
+/// Interprets the buffer returned by PLATFORM_STATUS.
+pub(crate) fn status(info: &PlatformStatus) -> Result<Status, Indeterminate<Error>> {
+    Ok(Status {
+        build: Build {
+            version: Version {
+                major: info.version.major,
+                minor: info.version.minor,
+            },
+            build: info.build,
+        },
+        guests: info.guest_count,
+        flags: info.flags,
+        state: match info.state {
+            0 => State::Uninitialized,
+            1 => State::Initialized,
+            2 => State::Working,
+            _ => return Err(Indeterminate::Unknown),
+        },
+    })
+}
+
+/// The output buffers of a command, as recorded to a capture.
+#[cfg_attr(not(feature = "capture"), allow(dead_code))]
+trait Response {
+    fn bytes(&self) -> Vec<u8>;
+}
 
-        GET_ID.ioctl(&mut self.0, &mut Command::from_mut(&mut id))?;
+impl Response for () {
+    fn bytes(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+impl Response for PlatformStatus {
+    fn bytes(&self) -> Vec<u8> {
+        crate::pod::as_bytes(self).to_vec()
+    }
+}
+
+impl Response for Certificate {
+    fn bytes(&self) -> Vec<u8> {
+        crate::pod::as_bytes(self).to_vec()
+    }
+}
+
+impl Response for Chain {
+    fn bytes(&self) -> Vec<u8> {
+        [&self.pdh, &self.pek, &self.oca, &self.cek]
+            .iter()
+            .flat_map(|cert| cert.bytes())
+            .collect()
+    }
+}
+
+impl Response for Identifier {
+    fn bytes(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+impl From<OwnedFd> for Firmware {
+    /// Uses an already opened CSV platform device, e.g. one passed in by a
+    /// privileged parent or opened before entering a sandbox.
+    fn from(fd: OwnedFd) -> Self {
+        Firmware {
+            fd: fd.as_raw_fd(),
+            file: Arc::new(Mutex::new(fd.into())),
+            timeout: None,
+            #[cfg(feature = "audit")]
+            audit: None,
+            #[cfg(feature = "capture")]
+            capture: None,
+        }
+    }
+}
+
+impl From<Firmware> for OwnedFd {
+    fn from(firmware: Firmware) -> Self {
+        deadline::into_file(firmware.file).into()
+    }
+}
+
+impl FromRawFd for Firmware {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        OwnedFd::from_raw_fd(fd).into()
+    }
+}
+
+impl IntoRawFd for Firmware {
+    fn into_raw_fd(self) -> RawFd {
+        OwnedFd::from(self).into_raw_fd()
+    }
+}
 
-        Ok(Identifier(id.as_slice().to_vec()))
+impl AsFd for Firmware {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: the file is owned by the handle and never replaced, so
+        // the descriptor stays open for as long as `self` is borrowed.
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
     }
 }
 
 impl AsRawFd for Firmware {
     fn as_raw_fd(&self) -> RawFd {
-        self.0.as_raw_fd()
+        self.fd
     }
 }
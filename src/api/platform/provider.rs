@@ -0,0 +1,67 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! An abstraction over the sources of platform identities and
+//! certificates.
+
+use super::{Firmware, Identifier, Status};
+use crate::{
+    certs::csv::Chain,
+    error::{Error, Indeterminate},
+};
+
+use std::{rc::Rc, sync::Arc};
+
+/// A source of the identity and certificates of a platform.
+///
+/// The CEK provisioning of [`super::cek`] takes a `PlatformProvider`, so
+/// it can be tested without HYGON hardware, e.g. against the simulated
+/// platform of the `sim` feature.
+pub trait PlatformProvider {
+    /// Queries the platform status, see [`Firmware::platform_status`].
+    fn platform_status(&self) -> Result<Status, Indeterminate<Error>>;
+
+    /// Exports the CSV certificate chain, see
+    /// [`Firmware::pdh_cert_export`].
+    fn pdh_cert_export(&self) -> Result<Chain, Indeterminate<Error>>;
+
+    /// Gets the unique CPU identifier, see [`Firmware::get_identifier`].
+    fn get_identifier(&self) -> Result<Identifier, Indeterminate<Error>>;
+}
+
+impl PlatformProvider for Firmware {
+    fn platform_status(&self) -> Result<Status, Indeterminate<Error>> {
+        Firmware::platform_status(self)
+    }
+
+    fn pdh_cert_export(&self) -> Result<Chain, Indeterminate<Error>> {
+        Firmware::pdh_cert_export(self)
+    }
+
+    fn get_identifier(&self) -> Result<Identifier, Indeterminate<Error>> {
+        Firmware::get_identifier(self)
+    }
+}
+
+macro_rules! forward {
+    ($($ptr:ty),*) => {
+        $(
+            impl<T: PlatformProvider + ?Sized> PlatformProvider for $ptr {
+                fn platform_status(&self) -> Result<Status, Indeterminate<Error>> {
+                    (**self).platform_status()
+                }
+
+                fn pdh_cert_export(&self) -> Result<Chain, Indeterminate<Error>> {
+                    (**self).pdh_cert_export()
+                }
+
+                fn get_identifier(&self) -> Result<Identifier, Indeterminate<Error>> {
+                    (**self).get_identifier()
+                }
+            }
+        )*
+    };
+}
+
+forward!(&T, Box<T>, Rc<T>, Arc<T>);
@@ -0,0 +1,132 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Type-states guiding the provisioning of the CSV platform.
+//!
+//! Ownership and key management commands are rejected by the firmware
+//! while guests are running. [`Platform`] tracks the platform state in its
+//! type so that such commands are only available when they can succeed.
+
+use super::{Firmware, State};
+use crate::{
+    certs::csv::Certificate,
+    error::{Error, Indeterminate},
+};
+
+/// Platform type-state that indicates an uninitialized platform.
+pub struct Uninitialized;
+
+/// Platform type-state that indicates an initialized platform which does
+/// not oversee any guest.
+pub struct Initialized;
+
+/// Platform type-state that indicates a platform overseeing guests.
+pub struct Working;
+
+mod sealed {
+    pub trait Idle {}
+
+    impl Idle for super::Uninitialized {}
+    impl Idle for super::Initialized {}
+}
+
+/// Type-states in which no guest is running.
+pub trait Idle: sealed::Idle {}
+
+impl Idle for Uninitialized {}
+impl Idle for Initialized {}
+
+/// A handle to the CSV platform whose state is known to be `S`.
+pub struct Platform<S> {
+    fw: Firmware,
+    _state: S,
+}
+
+/// A platform in the state it was found in.
+pub enum Detected {
+    Uninitialized(Platform<Uninitialized>),
+    Initialized(Platform<Initialized>),
+    Working(Platform<Working>),
+}
+
+impl Detected {
+    /// Queries the platform status to determine its state.
+    pub fn detect(fw: Firmware) -> Result<Self, Indeterminate<Error>> {
+        Ok(match fw.platform_status()?.state {
+            State::Uninitialized => Detected::Uninitialized(Platform::new(fw, Uninitialized)),
+            State::Initialized => Detected::Initialized(Platform::new(fw, Initialized)),
+            State::Working => Detected::Working(Platform::new(fw, Working)),
+        })
+    }
+
+    /// The detected state.
+    pub fn state(&self) -> State {
+        match self {
+            Detected::Uninitialized(_) => State::Uninitialized,
+            Detected::Initialized(_) => State::Initialized,
+            Detected::Working(_) => State::Working,
+        }
+    }
+}
+
+impl<S> Platform<S> {
+    fn new(fw: Firmware, state: S) -> Self {
+        Self { fw, _state: state }
+    }
+
+    /// Gives access to the commands which are valid in every state, such
+    /// as `platform_status` or `pdh_cert_export`.
+    pub fn firmware(&self) -> &Firmware {
+        &self.fw
+    }
+
+    /// Re-queries the platform state, e.g. after guests were started or
+    /// stopped by another process.
+    pub fn refresh(self) -> Result<Detected, Indeterminate<Error>> {
+        Detected::detect(self.fw)
+    }
+
+    /// Releases the underlying handle.
+    pub fn into_inner(self) -> Firmware {
+        self.fw
+    }
+}
+
+impl<S: Idle> Platform<S> {
+    /// Reset the platform persistent state.
+    pub fn platform_reset(self) -> Result<Platform<Uninitialized>, Indeterminate<Error>> {
+        self.fw.platform_reset()?;
+        Ok(Platform::new(self.fw, Uninitialized))
+    }
+
+    /// Generate a new Platform Encryption Key (PEK).
+    ///
+    /// The kernel initializes the platform as needed.
+    pub fn pek_generate(self) -> Result<Platform<Initialized>, Indeterminate<Error>> {
+        self.fw.pek_generate()?;
+        Ok(Platform::new(self.fw, Initialized))
+    }
+
+    /// Generate a new Platform Diffie-Hellman (PDH) key pair.
+    pub fn pdh_generate(self) -> Result<Platform<Initialized>, Indeterminate<Error>> {
+        self.fw.pdh_generate()?;
+        Ok(Platform::new(self.fw, Initialized))
+    }
+
+    /// Request a signature for the PEK.
+    pub fn pek_csr(self) -> Result<(Platform<Initialized>, Certificate), Indeterminate<Error>> {
+        let csr = self.fw.pek_csr()?;
+        Ok((Platform::new(self.fw, Initialized), csr))
+    }
+
+    /// Take ownership of the CSV platform.
+    pub fn pek_cert_import(
+        self,
+        pek: &Certificate,
+        oca: &Certificate,
+    ) -> Result<Platform<Initialized>, Indeterminate<Error>> {
+        self.fw.pek_cert_import(pek, oca)?;
+        Ok(Platform::new(self.fw, Initialized))
+    }
+}
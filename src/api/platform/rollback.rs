@@ -0,0 +1,64 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Detection of firmware downgrades across an update.
+
+use crate::Build;
+
+use std::{error, fmt};
+
+/// The firmware build went backwards across an update.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rollback {
+    /// The build running before the update.
+    pub before: Build,
+
+    /// The build running after the update.
+    pub after: Build,
+}
+
+impl fmt::Display for Rollback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "firmware rolled back from {} build {} to {} build {}",
+            self.before.version, self.before.build, self.after.version, self.after.build
+        )
+    }
+}
+
+impl error::Error for Rollback {}
+
+/// Fails if `after` is older than `before`.
+pub(crate) fn check(before: Build, after: Build) -> Result<(), Rollback> {
+    if after < before {
+        return Err(Rollback { before, after });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Version;
+
+    #[test]
+    fn downgrade() {
+        let build = |minor, build| Build {
+            version: Version { major: 1, minor },
+            build,
+        };
+
+        check(build(2, 10), build(2, 10)).unwrap();
+        check(build(2, 10), build(3, 1)).unwrap();
+
+        let err = check(build(2, 10), build(2, 9)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "firmware rolled back from 1.2 build 10 to 1.2 build 9"
+        );
+        assert!(check(build(3, 1), build(2, 10)).is_err());
+    }
+}
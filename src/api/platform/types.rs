@@ -7,7 +7,7 @@ use std::{
     mem::{size_of_val, MaybeUninit},
 };
 
-use crate::{certs::csv, Build, Version};
+use crate::{certs::csv, util::uapi::uapi_layout, Build, Version};
 
 /// Reset the platform's persistent state.
 pub struct PlatformReset;
@@ -15,6 +15,7 @@ pub struct PlatformReset;
 bitflags::bitflags! {
     /// The platform's status flags.
     #[derive(Default)]
+    #[repr(transparent)]
     pub struct PlatformStatusFlags: u32 {
         /// If set, this platform is owned. Otherwise, it is self-owned.
         const OWNED           = 1 << 0;
@@ -73,6 +74,62 @@ pub struct Status {
     pub guests: u32,
 }
 
+impl Status {
+    /// The number of valid guests supervised by this platform.
+    pub fn guest_count(&self) -> u32 {
+        self.guests
+    }
+
+    /// Returns true if the platform is owned by an external OCA.
+    pub fn is_owned(&self) -> bool {
+        self.flags.contains(PlatformStatusFlags::OWNED)
+    }
+
+    /// Returns true if encrypted state (CSV2) guests are supported.
+    pub fn has_encrypted_state(&self) -> bool {
+        self.flags.contains(PlatformStatusFlags::ENCRYPTED_STATE)
+    }
+}
+
+/// The encrypted guest capacity reported by the processor.
+///
+/// PLATFORM_STATUS does not describe ASIDs, so they are read from CPUID
+/// leaf 0x8000001F instead.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Asids {
+    /// The number of encrypted guests supported simultaneously, which is
+    /// also the highest usable ASID.
+    pub max: u32,
+
+    /// The lowest ASID usable by guests without encrypted state. ASIDs
+    /// below it are reserved for encrypted state (CSV2) guests.
+    pub min_no_es: u32,
+}
+
+impl Asids {
+    /// Reads the ASID ranges of the current processor.
+    ///
+    /// Returns `None` if the processor does not report them, which is
+    /// always the case outside of x86_64.
+    pub fn query() -> Option<Self> {
+        let leaf = crate::util::cpu::cpuid(0x8000_001F)?;
+        if leaf.ecx == 0 {
+            return None;
+        }
+
+        Some(Self {
+            max: leaf.ecx,
+            min_no_es: leaf.edx,
+        })
+    }
+
+    /// The number of guests that can still be launched, given the
+    /// current platform status.
+    pub fn available(&self, status: &Status) -> u32 {
+        self.max.saturating_sub(status.guests)
+    }
+}
+
 /// Query CSV platform status.
 #[derive(Default)]
 #[repr(C, packed)]
@@ -193,3 +250,12 @@ impl<'a> GetId<'a> {
         unsafe { std::slice::from_raw_parts(self.id_addr as *const u8, self.id_len as _) }
     }
 }
+
+// include/uapi/linux/psp-sev.h: struct sev_user_data_*
+uapi_layout! {
+    PlatformStatus = 12, { version: 0, state: 2, flags: 3, build: 7, guest_count: 8 },
+    PekCsr<'static> = 12, { addr: 0, len: 8 },
+    PekCertImport<'static> = 24, { pek_addr: 0, pek_len: 8, oca_addr: 12, oca_len: 20 },
+    PdhCertExport<'static> = 24, { pdh_addr: 0, pdh_len: 8, certs_addr: 12, certs_len: 20 },
+    GetId<'static> = 12, { id_addr: 0, id_len: 8 },
+}
@@ -0,0 +1,252 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Waiting for the CSV device nodes to appear, for services started before
+//! the PSP or csv-guest driver finished probing.
+//!
+//! ```no_run
+//! use csv_rs::api::{guest, watch};
+//! use std::time::Duration;
+//!
+//! let path = watch::wait_for(guest::KNOWN_PATHS, Some(Duration::from_secs(30))).unwrap();
+//! let guest = guest::CsvGuest::builder().path(path).open().unwrap();
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::{CString, OsStr},
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Read, Result},
+    mem::size_of,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd},
+    },
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// The longest time [`wait_for`] relies on notifications alone, as a
+/// driver may become usable without its node changing.
+const RECHECK: Duration = Duration::from_secs(1);
+
+/// A change of a directory entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The entry was created or moved into the directory.
+    Added(PathBuf),
+
+    /// The entry was deleted or moved out of the directory.
+    Removed(PathBuf),
+
+    /// The attributes of the entry changed, e.g. udev applied its
+    /// permissions.
+    Changed(PathBuf),
+}
+
+/// Watches directories for device nodes being added or removed, using
+/// inotify.
+pub struct Watcher {
+    file: File,
+    dirs: HashMap<i32, PathBuf>,
+    pending: VecDeque<Event>,
+}
+
+impl Watcher {
+    /// Creates a watcher without any directory to watch.
+    pub fn new() -> Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(Self {
+            file: unsafe { File::from_raw_fd(fd) },
+            dirs: HashMap::new(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Starts watching the entries of `dir`.
+    pub fn watch(&mut self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+
+        let mask = libc::IN_CREATE
+            | libc::IN_DELETE
+            | libc::IN_ATTRIB
+            | libc::IN_MOVED_TO
+            | libc::IN_MOVED_FROM;
+        let wd = unsafe { libc::inotify_add_watch(self.file.as_raw_fd(), path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        self.dirs.insert(wd, dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Waits at most `timeout`, or forever if `None`, for the next change.
+    ///
+    /// Returns `None` if the timeout expired.
+    pub fn next(&mut self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        if self.pending.is_empty() {
+            let mut pollfd = libc::pollfd {
+                fd: self.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+
+            match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+                0 => return Ok(None),
+                n if n < 0 => {
+                    let err = Error::last_os_error();
+                    if err.kind() == ErrorKind::Interrupted {
+                        return Ok(None);
+                    }
+                    return Err(err);
+                }
+                _ => self.read()?,
+            }
+        }
+
+        Ok(self.pending.pop_front())
+    }
+
+    /// Queues the events available.
+    fn read(&mut self) -> Result<()> {
+        let mut buf = [0u8; 4096];
+        let len = match self.file.read(&mut buf) {
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let mut rest = &buf[..len];
+        while rest.len() >= size_of::<libc::inotify_event>() {
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(rest.as_ptr() as *const _) };
+            let end = size_of::<libc::inotify_event>() + event.len as usize;
+            let name = &rest[size_of::<libc::inotify_event>()..end.min(rest.len())];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            rest = &rest[end.min(rest.len())..];
+
+            let Some(dir) = self.dirs.get(&event.wd) else {
+                continue;
+            };
+            let path = dir.join(OsStr::from_bytes(name));
+
+            if event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                self.pending.push_back(Event::Added(path));
+            } else if event.mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+                self.pending.push_back(Event::Removed(path));
+            } else if event.mask & libc::IN_ATTRIB != 0 {
+                self.pending.push_back(Event::Changed(path));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if the device node at `path` exists and can be opened.
+///
+/// A node which exists but cannot be opened yet, because the driver has
+/// not bound to the device or udev has not applied its permissions, is
+/// not ready.
+pub fn is_ready(path: impl AsRef<Path>) -> Result<bool> {
+    match OpenOptions::new().read(true).open(path) {
+        Ok(_) => Ok(true),
+        Err(e) => match (e.kind(), e.raw_os_error()) {
+            (ErrorKind::NotFound | ErrorKind::PermissionDenied, _) => Ok(false),
+            (_, Some(libc::ENXIO | libc::ENODEV)) => Ok(false),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Waits at most `timeout`, or forever if `None`, for one of the device
+/// nodes at `paths` to become ready, see [`is_ready`].
+///
+/// Returns the first path of `paths` found ready.
+pub fn wait_for<P: AsRef<Path>>(paths: &[P], timeout: Option<Duration>) -> Result<PathBuf> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+
+    let mut watcher = Watcher::new()?;
+    let mut dirs: Vec<&Path> = paths.iter().filter_map(|p| p.as_ref().parent()).collect();
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        // The parent may itself not exist yet, which the periodic
+        // rechecks cover.
+        let _ = watcher.watch(dir);
+    }
+
+    loop {
+        for path in paths {
+            if is_ready(path)? {
+                return Ok(path.as_ref().to_path_buf());
+            }
+        }
+
+        let wait = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(left) if !left.is_zero() => left.min(RECHECK),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::TimedOut,
+                        "timed out waiting for the CSV device",
+                    ))
+                }
+            },
+            None => RECHECK,
+        };
+
+        watcher.next(Some(wait))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, thread};
+
+    #[test]
+    fn wait() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let paths = [dir.join("csv-guest"), dir.join("csv_guest")];
+
+        let mut watcher = Watcher::new().unwrap();
+        watcher.watch(dir).unwrap();
+
+        let err = wait_for(&paths, Some(Duration::from_millis(20))).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+        let node = paths[1].clone();
+        let creator = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            fs::write(node, b"").unwrap();
+        });
+        let path = wait_for(&paths, Some(Duration::from_secs(10))).unwrap();
+        creator.join().unwrap();
+        assert_eq!(path, paths[1]);
+
+        let timeout = Some(Duration::from_secs(1));
+        assert_eq!(
+            watcher.next(timeout).unwrap(),
+            Some(Event::Added(paths[1].clone()))
+        );
+        fs::remove_file(&paths[1]).unwrap();
+        loop {
+            match watcher.next(timeout).unwrap().unwrap() {
+                Event::Removed(path) => break assert_eq!(path, paths[1]),
+                _ => continue,
+            }
+        }
+        assert_eq!(watcher.next(Some(Duration::ZERO)).unwrap(), None);
+    }
+}
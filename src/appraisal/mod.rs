@@ -0,0 +1,126 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Appraisal of verified attestation reports against reference values.
+//!
+//! [`References`] only approves launch measurements; a [`Policy`] also
+//! declares the required guest policy flags, allowed chips and minimum
+//! firmware, and explains every claim it rejects.
+
+mod policy;
+pub use policy::*;
+
+use crate::{api::guest::AttestationReport, verifier::VerifiedReport};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, Error, ErrorKind, Read, Result},
+    path::Path,
+};
+
+/// An approved launch measurement.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reference {
+    /// A human-readable name for the measured image.
+    pub label: String,
+
+    /// The version of the measured image, if tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// The expected launch digest, hex-encoded.
+    #[serde(with = "crate::util::hex")]
+    pub measure: [u8; 32],
+}
+
+/// A list of approved launch measurements.
+///
+/// The JSON representation is a plain array of references:
+///
+/// ```json
+/// [
+///   { "label": "guest-image", "version": "1.2.0", "measure": "9f86d081..." }
+/// ]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct References(pub Vec<Reference>);
+
+impl References {
+    /// Parses references from JSON.
+    pub fn from_json(reader: impl Read) -> Result<Self> {
+        serde_json::from_reader(reader).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Loads references from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(BufReader::new(File::open(path)?))
+    }
+
+    /// Returns the first reference with the given launch digest.
+    pub fn find(&self, measure: &[u8; 32]) -> Option<&Reference> {
+        self.0.iter().find(|r| &r.measure == measure)
+    }
+
+    /// Matches the launch digest of a verified report, returning the
+    /// reference it matched.
+    pub fn appraise(&self, report: &VerifiedReport) -> Result<&Reference> {
+        self.appraise_report(report.report())
+    }
+
+    fn appraise_report(&self, report: &AttestationReport) -> Result<&Reference> {
+        let measure = report.unmask(report.body.measure);
+        self.find(&measure).ok_or_else(|| {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "measurement {} is not approved",
+                    crate::util::hex::encode(&measure)
+                ),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"[
+        { "label": "a", "measure": "0000000000000000000000000000000000000000000000000000000000000000" },
+        { "label": "b", "version": "2", "measure": "0101010101010101010101010101010101010101010101010101010101010101" }
+    ]"#;
+
+    #[test]
+    fn find() {
+        let refs = References::from_json(JSON.as_bytes()).unwrap();
+        assert_eq!(refs.0.len(), 2);
+
+        let b = refs.find(&[1u8; 32]).unwrap();
+        assert_eq!(b.label, "b");
+        assert_eq!(b.version.as_deref(), Some("2"));
+        assert!(refs.find(&[2u8; 32]).is_none());
+    }
+
+    #[test]
+    fn appraise() {
+        let refs = References::from_json(JSON.as_bytes()).unwrap();
+        let mut report = AttestationReport {
+            anonce: 0x01010101,
+            ..Default::default()
+        };
+
+        assert_eq!(refs.appraise_report(&report).unwrap().label, "b");
+
+        report.anonce = 0x02020202;
+        let err = refs.appraise_report(&report).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn invalid_json() {
+        References::from_json(r#"[{ "label": "a", "measure": "00" }]"#.as_bytes()).unwrap_err();
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Declarative appraisal of the claims of a verified report.
+
+use super::References;
+use crate::{
+    api::guest::GuestPolicy,
+    util::hex,
+    verifier::{cert_fingerprint, Check, Outcome, Pin, Pins, Tcb, VerifiedReport},
+    Version,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, Error, ErrorKind, Read, Result},
+    path::Path,
+};
+
+/// The reference values a verified report must match.
+///
+/// Claims left unset are not appraised. The JSON representation is, e.g.:
+///
+/// ```json
+/// {
+///   "references": [{ "label": "guest-image", "measure": "9f86d081..." }],
+///   "flags": 5,
+///   "chips": { "NZA9T14052605": { "cek": [{ "fingerprint": "5d3a6e2c..." }] } },
+///   "min_firmware": { "major": 1, "minor": 2 }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// The approved launch measurements.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub references: Option<References>,
+
+    /// The guest policy flags which must be set, e.g. built with
+    /// [`GuestPolicy::builder`]. Its versions are ignored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<GuestPolicy>,
+
+    /// The chips the report may come from, each by the fingerprints of the
+    /// CEK certificates HYGON publishes for it, see [`cert_fingerprint`].
+    ///
+    /// The serial number of the report signer is not authenticated, so the
+    /// chip is identified by the CEK of the verified chain. HSK pins are
+    /// ignored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chips: Option<Pins>,
+
+    /// The minimum platform firmware version, from the PEK certificate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_firmware: Option<Version>,
+}
+
+/// The outcome of [`Policy::evaluate`], one check per claim.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Appraisal {
+    /// The appraised claims, in a fixed order.
+    pub claims: Vec<Check>,
+}
+
+impl Appraisal {
+    /// Whether no claim failed.
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// The claims which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.claims.iter().filter(|c| c.outcome == Outcome::Failed)
+    }
+
+    /// Looks a claim up by name.
+    pub fn claim(&self, name: &str) -> Option<&Check> {
+        self.claims.iter().find(|c| c.name == name)
+    }
+
+    /// Records a claim, which is skipped if `result` is `None` and failed
+    /// with the given reason if it holds one.
+    fn push(&mut self, name: &'static str, result: Option<std::result::Result<(), String>>) {
+        let (outcome, detail) = match result {
+            None => (Outcome::Skipped, Some("not required".into())),
+            Some(Ok(())) => (Outcome::Passed, None),
+            Some(Err(reason)) => (Outcome::Failed, Some(reason)),
+        };
+
+        self.claims.push(Check {
+            name,
+            outcome,
+            detail,
+        });
+    }
+}
+
+impl Policy {
+    /// Parses a policy from JSON.
+    pub fn from_json(reader: impl Read) -> Result<Self> {
+        serde_json::from_reader(reader).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Loads a policy from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(BufReader::new(File::open(path)?))
+    }
+
+    /// Appraises every claim of a verified report, see
+    /// [`Appraisal::passed`].
+    pub fn evaluate(&self, report: &VerifiedReport) -> Appraisal {
+        let mut appraisal = Appraisal::default();
+
+        let measure = report.measure();
+        appraisal.push(
+            "measure",
+            self.references
+                .as_ref()
+                .map(|references| match references.find(&measure) {
+                    Some(_) => Ok(()),
+                    None => Err(format!("{} is not approved", hex::encode(&measure))),
+                }),
+        );
+
+        let policy = report.policy();
+        appraisal.push(
+            "flags",
+            self.flags.map(|required| {
+                let missing = policy.missing(&required);
+                match missing.is_empty() {
+                    true => Ok(()),
+                    false => Err(format!("{} not set", missing.join(", "))),
+                }
+            }),
+        );
+
+        appraisal.push(
+            "chip id",
+            self.chips.as_ref().map(|chips| {
+                let cek = Pin::Fingerprint(
+                    cert_fingerprint(&report.evidence().chain.cek).map_err(|e| e.to_string())?,
+                );
+                match chips.0.values().any(|pins| pins.cek.contains(&cek)) {
+                    true => Ok(()),
+                    false => Err(format!(
+                        "chip {} is not allowed, or not under this CEK",
+                        report.chip_id()
+                    )),
+                }
+            }),
+        );
+
+        appraisal.push(
+            "firmware",
+            self.min_firmware.map(|minimum| {
+                let firmware = Tcb::of(report.evidence())
+                    .map_err(|e| e.to_string())?
+                    .firmware;
+                match firmware >= minimum {
+                    true => Ok(()),
+                    false => Err(format!("firmware {firmware} < {minimum}")),
+                }
+            }),
+        );
+
+        appraisal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        appraisal::Reference,
+        evidence::hag,
+        verifier::{ChipPins, Fingerprint, Verifier},
+    };
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    fn verified() -> VerifiedReport {
+        let evidence = hag::read(REPORT, HSK_CEK).unwrap();
+        Verifier::new().unwrap().accept(evidence).unwrap()
+    }
+
+    /// Allows the chip of `report` under the given CEK.
+    fn chips(report: &VerifiedReport, cek: Fingerprint) -> Pins {
+        let pins = ChipPins {
+            hsk: vec![],
+            cek: vec![Pin::Fingerprint(cek)],
+        };
+        Pins([(report.chip_id().clone(), pins)].into())
+    }
+
+    #[test]
+    fn evaluate() {
+        let report = verified();
+        let appraisal = Policy::default().evaluate(&report);
+        assert!(appraisal.passed());
+        assert!(appraisal
+            .claims
+            .iter()
+            .all(|c| c.outcome == Outcome::Skipped));
+
+        let policy = Policy {
+            references: Some(References(vec![Reference {
+                label: "a".into(),
+                version: None,
+                measure: report.measure(),
+            }])),
+            flags: Some(report.policy()),
+            chips: Some(chips(
+                &report,
+                cert_fingerprint(&report.evidence().chain.cek).unwrap(),
+            )),
+            min_firmware: Some(Version::default()),
+        };
+        let appraisal = policy.evaluate(&report);
+        assert!(appraisal.passed(), "{appraisal:?}");
+        assert!(appraisal
+            .claims
+            .iter()
+            .all(|c| c.outcome == Outcome::Passed));
+    }
+
+    #[test]
+    fn reasons() {
+        let report = verified();
+        let policy = Policy {
+            references: Some(References::default()),
+            // The right chip id, with the CEK of another chip.
+            chips: Some(chips(&report, Fingerprint::default())),
+            min_firmware: Some(Version {
+                major: u8::MAX,
+                minor: 0,
+            }),
+            ..Default::default()
+        };
+        let appraisal = policy.evaluate(&report);
+        let failures: Vec<_> = appraisal.failures().map(|c| c.name).collect();
+        assert_eq!(failures, ["measure", "chip id", "firmware"]);
+        assert_eq!(
+            appraisal.claim("chip id").unwrap().detail.as_deref(),
+            Some("chip NZA9T14052605 is not allowed, or not under this CEK")
+        );
+        assert_eq!(appraisal.claim("flags").unwrap().outcome, Outcome::Skipped);
+    }
+
+    #[test]
+    fn from_json() {
+        let policy = Policy::from_json(
+            r#"{ "flags": 5, "chips": { "NZA9T14052605": { "cek": [] } }, "min_firmware": { "major": 1, "minor": 2 } }"#
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(policy.flags, Some(GuestPolicy::from(5)));
+        assert_eq!(policy.min_firmware, Some(Version { major: 1, minor: 2 }));
+        assert!(policy.references.is_none());
+
+        Policy::from_json(r#"{ "measure": "00" }"#.as_bytes()).unwrap_err();
+    }
+}
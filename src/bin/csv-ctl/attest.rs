@@ -0,0 +1,94 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! `csv-ctl attest`: an end-to-end attestation from inside a guest.
+
+use crate::hex;
+
+use codicon::Decoder;
+use csv_rs::{
+    api::guest::{CsvGuest, VmVersion},
+    certs::HskCek,
+    evidence::Evidence,
+    kds::{Client, DEFAULT_URL},
+    verifier::{Tcb, Verifier},
+    ChipId,
+};
+
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufReader, Error, Result},
+    path::PathBuf,
+    process::ExitCode,
+};
+use uuid::Uuid;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The HSK and CEK certificates of the chip, downloaded from the KDS
+    /// unless given.
+    #[arg(long)]
+    chain: Option<PathBuf>,
+
+    /// The key distribution server.
+    #[arg(long, default_value = DEFAULT_URL)]
+    url: String,
+}
+
+/// The claims printed once the report is fetched.
+#[derive(Serialize)]
+struct Claims {
+    verified: bool,
+    error: Option<String>,
+    chip_id: ChipId,
+    mnonce: String,
+    vm_id: Uuid,
+    vm_version: VmVersion,
+    measure: String,
+    report_data: String,
+    user_pubkey_digest: String,
+    tcb: Option<Tcb>,
+    policy: Vec<String>,
+}
+
+pub fn run(args: Args) -> Result<ExitCode> {
+    let mnonce: [u8; 16] = rand::random();
+    let (report, signer) = CsvGuest::open()?
+        .get_report(None, Some(mnonce))
+        .map_err(Error::other)?;
+
+    let chip_id = signer.chip_id()?;
+    let chain = match &args.chain {
+        Some(path) => HskCek::decode(BufReader::new(File::open(path)?), ())?,
+        None => Client::new(args.url).hsk_cek(&chip_id)?,
+    };
+    let evidence = Evidence::new(report, signer, chain)?;
+    let result = Verifier::new()?.verify(&evidence);
+
+    let report = &evidence.report;
+    let claims = Claims {
+        verified: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+        chip_id,
+        mnonce: hex::encode(&mnonce),
+        vm_id: report.vm_id(),
+        vm_version: report.vm_version(),
+        measure: hex::encode(&report.unmask(report.body.measure)),
+        report_data: hex::encode(&report.unmask(report.body.report_data)),
+        user_pubkey_digest: hex::encode(&report.unmask(report.body.user_pubkey_digest)),
+        tcb: Tcb::of(&evidence).ok(),
+        policy: report.body.policy.xor(&report.anonce).explain(),
+    };
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&claims).map_err(Error::other)?
+    );
+
+    Ok(match claims.verified {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    })
+}
@@ -0,0 +1,10 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hexadecimal rendering of the byte arrays printed by the commands.
+
+/// Encodes bytes as lowercase hexadecimal.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
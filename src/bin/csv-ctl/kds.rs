@@ -0,0 +1,95 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! `csv-ctl kds`: management of the chip certificate cache.
+
+use codicon::Encoder;
+use csv_rs::{
+    kds::{Cache, Client, DEFAULT_URL},
+    ChipId,
+};
+
+use clap::Subcommand;
+use std::{
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Result, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The cache directory, `$HOME/.cache/hygon-csv/kds` by default.
+    #[arg(long, global = true)]
+    cache_dir: Option<PathBuf>,
+
+    /// The key distribution server.
+    #[arg(long, global = true, default_value = DEFAULT_URL)]
+    url: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Download the HSK and CEK certificates of a chip into a file.
+    Fetch {
+        chip_id: ChipId,
+
+        /// The output file.
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+
+    /// Download the certificates of chips into the cache, unless cached.
+    Cache {
+        #[arg(required = true)]
+        chip_ids: Vec<ChipId>,
+    },
+
+    /// Remove the certificates of the given chips, or of every chip, from
+    /// the cache.
+    Clear { chip_ids: Vec<ChipId> },
+}
+
+pub fn run(args: Args) -> Result<ExitCode> {
+    let client = Client::new(args.url);
+    let cache = match args.cache_dir {
+        Some(dir) => Cache::new(dir),
+        None => Cache::user()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no user cache directory"))?,
+    };
+
+    match args.command {
+        Command::Fetch { chip_id, out } => {
+            let hsk_cek = client.hsk_cek(&chip_id)?;
+            let mut writer = BufWriter::new(File::create(&out)?);
+            hsk_cek.encode(&mut writer, ())?;
+            writer.flush()?;
+        }
+
+        Command::Cache { chip_ids } => {
+            for chip_id in chip_ids {
+                cache.get_or_fetch(&chip_id, &client)?;
+                println!("{chip_id}");
+            }
+        }
+
+        Command::Clear { chip_ids } if chip_ids.is_empty() => {
+            let count = cache.clear()?;
+            println!("removed {count} chip(s) from {}", cache.dir().display());
+        }
+
+        Command::Clear { chip_ids } => {
+            for chip_id in chip_ids {
+                if !cache.remove(&chip_id)? {
+                    eprintln!("{chip_id}: not cached");
+                }
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
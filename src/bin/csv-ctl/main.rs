@@ -0,0 +1,57 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Command line access to the csv-rs functionality.
+
+mod attest;
+mod hex;
+mod kds;
+mod platform;
+mod verify;
+
+use clap::{Parser, Subcommand};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(
+    name = "csv-ctl",
+    version,
+    about = "Tooling for HYGON CSV platforms and attestation"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch, verify and print the claims of a report from inside a guest.
+    Attest(attest::Args),
+
+    /// Verify and appraise attestation evidence offline.
+    Verify(verify::Args),
+
+    /// Download and cache chip certificates from the HYGON KDS.
+    Kds(kds::Args),
+
+    #[command(flatten)]
+    Platform(platform::Command),
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Attest(args) => attest::run(args),
+        Command::Verify(args) => verify::run(args),
+        Command::Kds(args) => kds::run(args),
+        Command::Platform(command) => platform::run(command),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("csv-ctl: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! `csv-ctl status`, `pek-gen`, ...: management of the platform.
+
+use codicon::{Decoder, Encoder};
+use csv_rs::{api::platform::Firmware, certs::csv, pod, Build, ChipId};
+
+use clap::Subcommand;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Error, Result, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the platform status.
+    Status,
+
+    /// Reset the platform, deleting its keys and the imported certificates.
+    Reset,
+
+    /// Generate a new PEK, which invalidates the certificate chain.
+    PekGen,
+
+    /// Write a signing request for the PEK, to be signed by the OCA.
+    PekCsr {
+        /// The output file.
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+
+    /// Import the PEK certificate signed by the OCA, which takes ownership
+    /// of the platform.
+    PekCertImport {
+        /// The signed PEK certificate.
+        #[arg(long)]
+        pek: PathBuf,
+
+        /// The OCA certificate.
+        #[arg(long)]
+        oca: PathBuf,
+    },
+
+    /// Generate a new PDH key pair.
+    PdhGen,
+
+    /// Export the PDH, PEK, OCA and CEK certificates.
+    PdhExport {
+        /// The output file.
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+
+    /// Print the serial number of the chip.
+    GetId,
+}
+
+/// What `status` prints.
+#[derive(Serialize)]
+struct Status {
+    build: Build,
+    state: String,
+    owned: bool,
+    encrypted_state: bool,
+    guests: u32,
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+fn certificate(path: &Path) -> Result<csv::Certificate> {
+    csv::Certificate::decode(BufReader::new(File::open(path)?), ())
+}
+
+pub fn run(command: Command) -> Result<ExitCode> {
+    let firmware = Firmware::open()?;
+
+    match command {
+        Command::Status => {
+            let status = firmware.platform_status()?;
+            let status = Status {
+                build: status.build,
+                state: status.state.to_string(),
+                owned: status.is_owned(),
+                encrypted_state: status.has_encrypted_state(),
+                guests: status.guest_count(),
+            };
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&status).map_err(Error::other)?
+            );
+        }
+
+        Command::Reset => firmware.platform_reset()?,
+
+        Command::PekGen => firmware.pek_generate()?,
+
+        Command::PekCsr { out } => {
+            let csr = firmware.pek_csr()?;
+            let mut writer = create(&out)?;
+            pod::save(&mut writer, &csr)?;
+            writer.flush()?;
+        }
+
+        Command::PekCertImport { pek, oca } => {
+            firmware.pek_cert_import(&certificate(&pek)?, &certificate(&oca)?)?;
+        }
+
+        Command::PdhGen => firmware.pdh_generate()?,
+
+        Command::PdhExport { out } => {
+            let chain = firmware.pdh_cert_export()?;
+            let mut writer = create(&out)?;
+            chain.encode(&mut writer, ())?;
+            writer.flush()?;
+        }
+
+        Command::GetId => {
+            let id = firmware.get_identifier()?;
+            match ChipId::try_from(&id) {
+                Ok(chip_id) => println!("{chip_id}"),
+                Err(_) => println!("{id}"),
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
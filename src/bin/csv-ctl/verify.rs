@@ -0,0 +1,186 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! `csv-ctl verify`: offline verification and appraisal of a report.
+
+use crate::hex;
+
+use codicon::Decoder;
+use csv_rs::{
+    api::guest::{AttestationReport, ReportSigner, VmVersion},
+    appraisal::{Reference, References},
+    certs::{builtin::HRK, ca, csv, HskCek},
+    evidence::Evidence,
+    kds::Cache,
+    pod,
+    verifier::{SignatureChain, SignerChipId, Tcb, Verifier},
+    ChipId,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufReader, Error, ErrorKind, Result},
+    mem::size_of,
+    path::PathBuf,
+    process::ExitCode,
+};
+use uuid::Uuid;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The attestation report followed by its signer, as returned by the
+    /// guest device.
+    #[arg(long)]
+    report: PathBuf,
+
+    /// The HSK and CEK certificates of the chip, as served by the KDS.
+    #[arg(long)]
+    chain: PathBuf,
+
+    /// The YAML appraisal policy.
+    #[arg(long)]
+    policy: Option<PathBuf>,
+
+    /// The OCA certificate of the platform owner, which must also have
+    /// signed the PEK.
+    #[arg(long)]
+    oca: Option<PathBuf>,
+
+    /// The KDS cache holding the certificates of the chip pinned by the
+    /// policy, `$HOME/.cache/hygon-csv/kds` by default.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+}
+
+/// The appraisal policy, e.g.:
+///
+/// ```yaml
+/// chip_id: NZA9T14052605
+/// min_tcb: { firmware: { major: 1, minor: 2 }, hsk: 1, cek: 1 }
+/// references:
+///   - { label: guest-image, measure: 9f86d081... }
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Policy {
+    chip_id: Option<ChipId>,
+    min_tcb: Option<Tcb>,
+    references: Option<References>,
+}
+
+/// What is printed once the evidence is parsed.
+#[derive(Serialize)]
+struct Outcome {
+    verified: bool,
+    error: Option<String>,
+    chip_id: ChipId,
+    vm_id: Uuid,
+    vm_version: VmVersion,
+    measure: String,
+    report_data: String,
+    tcb: Option<Tcb>,
+    pek_signers: Option<csv::PekSigners>,
+    policy: Vec<String>,
+    reference: Option<Reference>,
+}
+
+fn invalid(e: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+fn load_evidence(args: &Args) -> Result<Evidence> {
+    let bytes = fs::read(&args.report)?;
+    let expected = size_of::<AttestationReport>() + size_of::<ReportSigner>();
+    if bytes.len() != expected {
+        return Err(invalid(format!(
+            "{}: expected {expected} bytes, got {}",
+            args.report.display(),
+            bytes.len()
+        )));
+    }
+
+    let (report, signer) = bytes.split_at(size_of::<AttestationReport>());
+    let report: AttestationReport = pod::from_bytes(report)?;
+    let mut signer: ReportSigner = pod::from_bytes(signer)?;
+
+    let mnonce = report.unmask(report.body.mnonce);
+    signer
+        .verify(&mnonce, &report.body.mnonce, &report.anonce)
+        .map_err(|e| invalid(format!("signer MAC: {e}")))?;
+
+    let chain = HskCek::decode(BufReader::new(File::open(&args.chain)?), ())?;
+    Evidence::new(report, signer, chain)
+}
+
+pub fn run(args: Args) -> Result<ExitCode> {
+    let policy: Policy = match &args.policy {
+        Some(path) => serde_yaml::from_reader(File::open(path)?).map_err(invalid)?,
+        None => Policy::default(),
+    };
+    let evidence = load_evidence(&args)?;
+    let report = &evidence.report;
+
+    let oca = match &args.oca {
+        Some(path) => Some(csv::Certificate::decode(
+            BufReader::new(File::open(path)?),
+            (),
+        )?),
+        None => None,
+    };
+
+    let mut verifier = match oca {
+        Some(oca) => Verifier::empty()
+            .step(SignatureChain::new(ca::Certificate::decode(HRK, ())?).oca(oca))
+            .step(SignerChipId),
+        None => Verifier::new()?,
+    };
+    if let Some(chip_id) = policy.chip_id {
+        let cache = match &args.cache_dir {
+            Some(dir) => Cache::new(dir),
+            None => Cache::user()
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "no user cache directory"))?,
+        };
+        verifier = verifier.chip_id(chip_id, cache);
+    }
+    if let Some(minimum) = policy.min_tcb {
+        verifier = verifier.min_tcb(minimum);
+    }
+
+    let mut reference = None;
+    let result = verifier.accept(evidence.clone()).and_then(|verified| {
+        if let Some(references) = &policy.references {
+            reference = Some(references.appraise(&verified)?.clone());
+        }
+        Ok(())
+    });
+
+    let outcome = Outcome {
+        verified: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+        chip_id: evidence.chip_id.clone(),
+        vm_id: report.vm_id(),
+        vm_version: report.vm_version(),
+        measure: hex::encode(&report.unmask(report.body.measure)),
+        report_data: hex::encode(&report.unmask(report.body.report_data)),
+        tcb: Tcb::of(&evidence).ok(),
+        pek_signers: evidence
+            .signer
+            .pek()
+            .and_then(|pek| pek.pek_signers(&evidence.chain.cek, oca.as_ref()))
+            .ok(),
+        policy: report.body.policy.xor(&report.anonce).explain(),
+        reference,
+    };
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&outcome).map_err(invalid)?
+    );
+
+    Ok(match outcome.verified {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    })
+}
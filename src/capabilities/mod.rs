@@ -0,0 +1,141 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Inspection of the processor and kernel interfaces for CSV support, so
+//! tooling can self-configure.
+
+use crate::{api::guest::KNOWN_PATHS, util::cpu};
+
+use serde::Serialize;
+use std::{fs, path::PathBuf};
+
+/// The platform device node exported by the PSP driver on hosts.
+const PLATFORM_PATH: &str = "/dev/sev";
+
+/// The KVM module parameters enabling each generation.
+const KVM_PARAMS: &[(Generation, &str)] = &[
+    (Generation::Csv, "/sys/module/kvm_amd/parameters/sev"),
+    (Generation::Csv2, "/sys/module/kvm_amd/parameters/sev_es"),
+    (Generation::Csv3, "/sys/module/kvm_amd/parameters/csv3"),
+];
+
+/// A generation of the CSV technology.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum Generation {
+    /// Encrypted guest memory.
+    Csv,
+
+    /// Encrypted guest memory and register state.
+    Csv2,
+
+    /// Isolated guest memory.
+    Csv3,
+}
+
+/// The CSV features offered by the current machine.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// The processor is a HYGON one.
+    pub hygon: bool,
+
+    /// The generations supported by the processor, as reported by CPUID.
+    pub cpu: Vec<Generation>,
+
+    /// The generations enabled in the KVM module, empty in guests.
+    pub kvm: Vec<Generation>,
+
+    /// Whether memory encryption is enabled in SYSCFG, or `None` if the
+    /// MSR cannot be read (reading it requires root and the msr module).
+    pub memory_encryption: Option<bool>,
+
+    /// Whether the code runs inside a virtual machine.
+    pub guest: bool,
+
+    /// The CSV device nodes present, platform and guest ones.
+    pub devices: Vec<PathBuf>,
+}
+
+impl Capabilities {
+    /// The most recent generation usable to launch guests from this host.
+    pub fn highest(&self) -> Option<Generation> {
+        self.cpu
+            .iter()
+            .filter(|g| self.kvm.contains(g))
+            .max()
+            .copied()
+    }
+}
+
+/// Inspects the processor and the kernel interfaces for CSV support.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        hygon: cpu::cpuid(0).is_some_and(|vendor| {
+            [vendor.ebx, vendor.edx, vendor.ecx]
+                .iter()
+                .flat_map(|r| r.to_le_bytes())
+                .eq(*b"HygonGenuine")
+        }),
+        cpu: generations(cpu::encryption()),
+        kvm: KVM_PARAMS
+            .iter()
+            .filter(|(_, path)| {
+                fs::read_to_string(path)
+                    .map(|value| enabled(&value))
+                    .unwrap_or(false)
+            })
+            .map(|(generation, _)| *generation)
+            .collect(),
+        memory_encryption: cpu::syscfg().map(|syscfg| syscfg & (1 << 23) != 0),
+        guest: cpu::hypervisor(),
+        devices: std::iter::once(PLATFORM_PATH)
+            .chain(KNOWN_PATHS.iter().copied())
+            .map(PathBuf::from)
+            .filter(|path| path.exists())
+            .collect(),
+    }
+}
+
+/// Decodes the generations from the EAX value of CPUID 0x8000001F.
+fn generations(eax: u32) -> Vec<Generation> {
+    [
+        (1, Generation::Csv),
+        (3, Generation::Csv2),
+        (30, Generation::Csv3),
+    ]
+    .into_iter()
+    .filter(|(bit, _)| eax & (1 << bit) != 0)
+    .map(|(_, generation)| generation)
+    .collect()
+}
+
+/// Parses a boolean KVM module parameter.
+fn enabled(value: &str) -> bool {
+    matches!(value.trim(), "Y" | "y" | "1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(generations(0), []);
+        assert_eq!(
+            generations(0x4000_000A),
+            [Generation::Csv, Generation::Csv2, Generation::Csv3]
+        );
+        assert!(enabled("Y\n") && enabled("1") && !enabled("N\n"));
+    }
+
+    #[test]
+    fn highest() {
+        let caps = Capabilities {
+            cpu: vec![Generation::Csv, Generation::Csv2, Generation::Csv3],
+            kvm: vec![Generation::Csv, Generation::Csv2],
+            ..Default::default()
+        };
+        assert_eq!(caps.highest(), Some(Generation::Csv2));
+        assert_eq!(Capabilities::default().highest(), None);
+    }
+}
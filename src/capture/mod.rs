@@ -0,0 +1,434 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recording of the raw buffers exchanged with the firmware, and replay of
+//! guest requests from such a recording.
+//!
+//! A capture file holds one JSON [`Exchange`] per line. Guest handles
+//! record through [`crate::api::guest::Builder::capture`] and platform
+//! handles through [`crate::api::platform::Firmware::set_capture`]:
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use csv_rs::{api::guest::CsvGuest, capture::Capture};
+//! use std::sync::Arc;
+//!
+//! let capture = Arc::new(Capture::create("/tmp/guest.capture")?);
+//! let guest = CsvGuest::builder().capture(capture).open()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Replay`] serves the recorded guest responses back as a transport, and
+//! [`PlatformReplay`] the recorded platform responses as a
+//! [`PlatformProvider`], so a customer-reported interaction can be
+//! reproduced without the hardware.
+
+use crate::{
+    api::{
+        guest::Transport,
+        platform::{self, Identifier, PlatformProvider, PlatformStatus, Status},
+    },
+    certs::csv::{Certificate, Chain},
+    error::{Error as CsvError, Indeterminate},
+    pod,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Error, ErrorKind, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// The device a command was issued to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    /// The csv-guest device, or another guest transport.
+    Guest,
+
+    /// The CSV platform device.
+    Platform,
+}
+
+/// How a command failed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failure {
+    /// The OS error code, if the failure was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+
+    /// The error message.
+    pub message: String,
+}
+
+impl From<&io::Error> for Failure {
+    fn from(err: &io::Error) -> Self {
+        Self {
+            errno: err.raw_os_error(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<&Failure> for io::Error {
+    fn from(failure: &Failure) -> Self {
+        match failure.errno {
+            Some(errno) => io::Error::from_raw_os_error(errno),
+            None => io::Error::other(failure.message.clone()),
+        }
+    }
+}
+
+/// A command and the buffers it exchanged with the firmware.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exchange {
+    /// The device the command was issued to.
+    pub device: Device,
+
+    /// The command name, e.g. `GET_REPORT`.
+    pub command: String,
+
+    /// The buffer handed to the firmware, hex-encoded.
+    #[serde(with = "crate::util::hex::vec")]
+    pub request: Vec<u8>,
+
+    /// The buffer returned by the firmware, hex-encoded.
+    #[serde(with = "crate::util::hex::vec")]
+    pub response: Vec<u8>,
+
+    /// The failure of the command, if it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Failure>,
+}
+
+/// A capture file being recorded.
+pub struct Capture(Mutex<Box<dyn Write + Send>>);
+
+impl Capture {
+    /// Records to `writer`.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Mutex::new(Box::new(writer)))
+    }
+
+    /// Records to `path`, appending to it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+
+    /// Appends an exchange.
+    ///
+    /// Write failures are ignored: the command already completed and its
+    /// caller must not fail because of the recording.
+    pub fn record(&self, exchange: &Exchange) {
+        let mut writer = crate::util::lock::lock(&self.0);
+        if let Ok(line) = serde_json::to_string(exchange) {
+            let _ = writeln!(writer, "{line}");
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Reads the exchanges of a capture file.
+pub fn load(reader: impl Read) -> io::Result<Vec<Exchange>> {
+    BufReader::new(reader)
+        .lines()
+        .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|line| serde_json::from_str(&line?).map_err(|e| Error::new(ErrorKind::InvalidData, e)))
+        .collect()
+}
+
+/// A guest transport recording every request to a [`Capture`].
+pub struct Recorder<T> {
+    inner: T,
+    capture: Arc<Capture>,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Records the requests carried by `inner`.
+    pub fn new(inner: T, capture: Arc<Capture>) -> Self {
+        Self { inner, capture }
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn get_report(&mut self, page: &mut [u8]) -> io::Result<()> {
+        let request = page.to_vec();
+        let result = self.inner.get_report(page);
+
+        self.capture.record(&Exchange {
+            device: Device::Guest,
+            command: "GET_REPORT".into(),
+            request,
+            response: page.to_vec(),
+            error: result.as_ref().err().map(Failure::from),
+        });
+
+        result
+    }
+
+    fn raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        self.inner.raw_fd()
+    }
+}
+
+/// A mock guest transport answering with the responses of a capture, in
+/// order.
+///
+/// Each request must match the captured one byte for byte, so a replayed
+/// `get_report` needs the report data and mnonce of the original request.
+pub struct Replay {
+    exchanges: VecDeque<Exchange>,
+}
+
+impl Replay {
+    /// Replays the guest exchanges among `exchanges`.
+    pub fn new(exchanges: impl IntoIterator<Item = Exchange>) -> Self {
+        Self {
+            exchanges: exchanges
+                .into_iter()
+                .filter(|exchange| exchange.device == Device::Guest)
+                .collect(),
+        }
+    }
+
+    /// Replays the guest exchanges of the capture file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(load(File::open(path)?)?))
+    }
+
+    /// The number of exchanges not replayed yet.
+    pub fn pending(&self) -> usize {
+        self.exchanges.len()
+    }
+}
+
+impl Transport for Replay {
+    fn get_report(&mut self, page: &mut [u8]) -> io::Result<()> {
+        let Some(exchange) = self.exchanges.pop_front() else {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "the capture holds no more guest requests",
+            ));
+        };
+
+        if exchange.request != page || exchange.response.len() != page.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the request differs from the captured one",
+            ));
+        }
+
+        page.copy_from_slice(&exchange.response);
+        match &exchange.error {
+            Some(failure) => Err(failure.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A mock platform answering with the responses of a capture, in order.
+///
+/// Each command must be the captured one, and is answered with the exact
+/// buffer the firmware returned.
+pub struct PlatformReplay {
+    exchanges: Mutex<VecDeque<Exchange>>,
+}
+
+impl PlatformReplay {
+    /// Replays the platform exchanges among `exchanges`.
+    pub fn new(exchanges: impl IntoIterator<Item = Exchange>) -> Self {
+        Self {
+            exchanges: Mutex::new(
+                exchanges
+                    .into_iter()
+                    .filter(|exchange| exchange.device == Device::Platform)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Replays the platform exchanges of the capture file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(load(File::open(path)?)?))
+    }
+
+    /// The number of exchanges not replayed yet.
+    pub fn pending(&self) -> usize {
+        crate::util::lock::lock(&self.exchanges).len()
+    }
+
+    /// Returns the response captured for the next command, which must be
+    /// `command`.
+    fn next(&self, command: &str) -> io::Result<Vec<u8>> {
+        let Some(exchange) = crate::util::lock::lock(&self.exchanges).pop_front() else {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "the capture holds no more platform commands",
+            ));
+        };
+
+        if exchange.command != command {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("the capture holds {}, not {command}", exchange.command),
+            ));
+        }
+
+        match &exchange.error {
+            Some(failure) => Err(failure.into()),
+            None => Ok(exchange.response),
+        }
+    }
+}
+
+impl PlatformProvider for PlatformReplay {
+    fn platform_status(&self) -> Result<Status, Indeterminate<CsvError>> {
+        platform::status(&pod::from_bytes::<PlatformStatus>(
+            &self.next("PLATFORM_STATUS")?,
+        )?)
+    }
+
+    fn pdh_cert_export(&self) -> Result<Chain, Indeterminate<CsvError>> {
+        let response = self.next("PDH_CERT_EXPORT")?;
+        let size = std::mem::size_of::<Certificate>();
+        if response.len() != 4 * size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("expected {} bytes, got {}", 4 * size, response.len()),
+            )
+            .into());
+        }
+
+        let cert = |index: usize| pod::from_bytes(&response[index * size..][..size]);
+        Ok(Chain {
+            pdh: cert(0)?,
+            pek: cert(1)?,
+            oca: cert(2)?,
+            cek: cert(3)?,
+        })
+    }
+
+    fn get_identifier(&self) -> Result<Identifier, Indeterminate<CsvError>> {
+        Ok(Identifier(self.next("GET_ID")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::guest::{mock::MockGuest, CsvGuest};
+
+    /// A writer shared with the test.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_replay() {
+        let out = Shared::default();
+        let mock = MockGuest::new("NZA9T14052605");
+        let faults = mock.faults();
+        let guest = CsvGuest::builder()
+            .transport(mock)
+            .capture(Arc::new(Capture::new(out.clone())))
+            .open()
+            .unwrap();
+
+        faults.push(crate::api::guest::mock::Fault::Os(libc::EBUSY));
+        let (report, _) = guest.get_report(Some([7u8; 64]), Some([9u8; 16])).unwrap();
+
+        let exchanges = load(&out.0.lock().unwrap()[..]).unwrap();
+        assert_eq!(exchanges.len(), 2);
+        assert_eq!(
+            exchanges[0].error.as_ref().unwrap().errno,
+            Some(libc::EBUSY)
+        );
+        assert!(exchanges[1].error.is_none());
+
+        let replay = Replay::new(exchanges);
+        let guest = CsvGuest::builder().transport(replay).open().unwrap();
+        let (replayed, _) = guest.get_report(Some([7u8; 64]), Some([9u8; 16])).unwrap();
+        assert_eq!(replayed, report);
+
+        // Nothing left to replay.
+        assert!(guest.get_report(Some([7u8; 64]), Some([9u8; 16])).is_err());
+
+        let replay = Replay::new(load(&out.0.lock().unwrap()[..]).unwrap());
+        let guest = CsvGuest::builder().transport(replay).open().unwrap();
+        assert!(guest.get_report(Some([8u8; 64]), Some([9u8; 16])).is_err());
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn replay_platform() {
+        use crate::{
+            api::platform::{cek, PlatformStatusFlags, State},
+            sim::Platform,
+            Version,
+        };
+
+        let platform = Platform::new("SIM0000000001".parse().unwrap()).unwrap();
+        let chain = platform.pdh_cert_export().unwrap();
+        let info = PlatformStatus {
+            version: Version { major: 1, minor: 2 },
+            state: 2,
+            flags: PlatformStatusFlags::OWNED,
+            build: 3,
+            guest_count: 4,
+        };
+
+        let out = Shared::default();
+        let capture = Capture::new(out.clone());
+        let record = |command: &str, response: Vec<u8>| {
+            capture.record(&Exchange {
+                device: Device::Platform,
+                command: command.into(),
+                request: Vec::new(),
+                response,
+                error: None,
+            })
+        };
+        record("PLATFORM_STATUS", pod::as_bytes(&info).to_vec());
+        record("GET_ID", platform.get_identifier().unwrap().0);
+        record(
+            "PDH_CERT_EXPORT",
+            [&chain.pdh, &chain.pek, &chain.oca, &chain.cek]
+                .iter()
+                .flat_map(|cert| pod::as_bytes(*cert).to_vec())
+                .collect(),
+        );
+
+        let replay = PlatformReplay::new(load(&out.0.lock().unwrap()[..]).unwrap());
+        assert_eq!(replay.pending(), 3);
+
+        let status = replay.platform_status().unwrap();
+        assert_eq!(status.state, State::Working);
+        assert_eq!(status.flags, PlatformStatusFlags::OWNED);
+        assert_eq!((status.guests, status.build.build), (4, 3));
+        assert_eq!(
+            cek::identity(&replay).unwrap(),
+            cek::identity(&platform).unwrap()
+        );
+        assert_eq!(replay.pending(), 0);
+
+        // Out of order.
+        let replay = PlatformReplay::new(load(&out.0.lock().unwrap()[..]).unwrap());
+        replay.get_identifier().unwrap_err();
+        replay.platform_status().unwrap_err();
+    }
+}
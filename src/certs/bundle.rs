@@ -0,0 +1,121 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Complete certificate chains in a single file, for provisioning
+//! air-gapped hosts with certificates fetched on a connected machine.
+//!
+//! A bundle has the following little-endian layout:
+//!
+//! | Offset | Size | Contents                                         |
+//! |--------|------|--------------------------------------------------|
+//! | 0      | 8    | magic, `CSVCHAIN`                                |
+//! | 8      | 4    | format version, currently 1                      |
+//! | 12     | n    | PDH, PEK, OCA, CEK, HSK and HRK certificates     |
+//! | 12 + n | 32   | SM3 digest of the preceding bytes                |
+//!
+//! Loading checks the digest, the usage of every certificate and the
+//! signatures of the chain up to its own HRK. Whether that HRK is trusted
+//! is decided when verifying reports, see [`Chain::verify_report`].
+
+use super::{Chain, Verifiable};
+use crate::{
+    crypto::gm,
+    util::{tracked::Tracked, *},
+};
+
+use codicon::{Decoder, Encoder};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Error, ErrorKind, Read, Result, Write},
+    path::Path,
+};
+
+const MAGIC: [u8; 8] = *b"CSVCHAIN";
+const FORMAT: u32 = 1;
+const DIGEST: usize = 32;
+
+/// Writes `chain` as a bundle.
+pub fn write(chain: &Chain, mut writer: impl Write) -> Result<()> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&FORMAT.to_le_bytes());
+    chain.encode(&mut bytes, ())?;
+    let digest = gm::sm3(&bytes)?;
+
+    writer.write_all(&bytes)?;
+    writer.write_all(&digest)
+}
+
+/// Reads a bundle, checking its integrity.
+pub fn read(mut reader: impl Read) -> Result<Chain> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() < MAGIC.len() + DIGEST {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "truncated certificate bundle",
+        ));
+    }
+
+    let (bytes, digest) = bytes.split_at(bytes.len() - DIGEST);
+    let mut reader = Tracked::new(bytes);
+
+    reader.field("magic", |r| {
+        if r.load::<[u8; 8]>()? != MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not a certificate bundle",
+            ));
+        }
+        Ok(())
+    })?;
+
+    reader.field("format", |r| {
+        let format = u32::from_le_bytes(r.load()?);
+        if format != FORMAT {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported certificate bundle format {format}"),
+            ));
+        }
+        Ok(())
+    })?;
+
+    if gm::sm3(bytes)?[..] != *digest {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "certificate bundle digest mismatch",
+        ));
+    }
+
+    let chain = reader.field("chain", |r| Chain::decode(r, ()))?;
+    let mut rest = [0u8; 1];
+    if reader.read(&mut rest)? != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "trailing bytes in certificate bundle",
+        ));
+    }
+    (&chain).verify()?;
+
+    Ok(chain)
+}
+
+/// Writes `chain` as a bundle to the file at `path`, replacing it
+/// atomically.
+pub fn save(chain: &Chain, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    write(chain, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(tmp, path)
+}
+
+/// Reads a bundle from the file at `path`, checking its integrity.
+pub fn load(path: impl AsRef<Path>) -> Result<Chain> {
+    read(File::open(path)?)
+}
@@ -4,53 +4,22 @@
 
 //! Operations that can be done on a Certificate Authority chain.
 
+#[cfg(feature = "openssl")]
+use crate::{
+    certs::{csv, Signer},
+    crypto::{sig::ecdsa, sm, PrivateKey},
+};
 use crate::{
     certs::{Algorithm, Usage, Verifiable},
-    crypto::{key::ecc, sig::ecdsa, PublicKey, Signature},
-    util::*,
+    crypto::{PublicKey, Signature},
+    util::{tracked::Tracked, *},
 };
 
-use serde::{Deserialize, Serialize};
-use serde_big_array::BigArray;
 use std::io::{Error, Read, Result, Write};
 
-#[repr(C)]
-#[derive(Copy, Clone, Serialize, Deserialize)]
-pub struct Data {
-    pub kid: [u8; 16],
-    pub sid: [u8; 16],
-    pub usage: Usage,
-    pub reserved: [u8; 24],
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Serialize, Deserialize)]
-pub struct Preamble {
-    pub ver: u32,
-    pub data: Data,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Serialize, Deserialize)]
-pub struct Body {
-    pub preamble: Preamble,
-    pub pubkey: ecc::PubKey,
-    pub uid_size: u16,
-    #[serde(with = "BigArray")]
-    pub user_id: [u8; 254],
-    #[serde(with = "BigArray")]
-    pub reserved: [u8; 108],
-}
-
-/// A Certificate Authority chain.
-#[repr(C)]
-#[derive(Copy, Clone, Serialize, Deserialize)]
-pub struct Certificate {
-    pub body: Body,
-    signature: ecdsa::Signature,
-    #[serde(with = "BigArray")]
-    _reserved: [u8; 112],
-}
+pub use crate::layout::ca::Certificate;
+#[cfg(feature = "openssl")]
+use crate::layout::ca::{Body, Data, Preamble};
 
 impl TryFrom<&Certificate> for Signature {
     type Error = Error;
@@ -93,11 +62,12 @@ impl TryFrom<&Certificate> for Usage {
 impl codicon::Decoder<()> for Certificate {
     type Error = Error;
 
-    fn decode(mut reader: impl Read, _: ()) -> Result<Self> {
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
         Ok(Self {
-            body: reader.load()?,
-            signature: reader.load()?,
-            _reserved: reader.load()?,
+            body: reader.field("body", |r| r.load())?,
+            signature: reader.field("signature", |r| r.load())?,
+            _reserved: reader.field("reserved", |r| r.load())?,
         })
     }
 }
@@ -110,6 +80,71 @@ impl codicon::Encoder<crate::Body> for Certificate {
     }
 }
 
+#[cfg(feature = "openssl")]
+impl Certificate {
+    /// Generates a private key and its unsigned certificate, e.g. an HRK or
+    /// HSK for a test hierarchy.
+    ///
+    /// The key identifier is random; it becomes the signer identifier of
+    /// the certificates signed with the private key.
+    pub fn generate(usage: Usage, uid: Option<String>) -> Result<(Self, PrivateKey<Usage>)> {
+        let uid = match uid {
+            Some(uid) => uid,
+            None => String::try_from(usage)?,
+        };
+
+        let mut user_id = [0u8; 254];
+        if uid.len() > user_id.len() {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        }
+        user_id[..uid.len()].copy_from_slice(uid.as_bytes());
+
+        let mut kid = [0u8; 16];
+        openssl::rand::rand_bytes(&mut kid)?;
+        let (pubkey, prv) = csv::cert::key::PubKey::generate(usage, Some(kid))?;
+
+        Ok((
+            Self {
+                body: Body {
+                    preamble: Preamble {
+                        ver: 1u32.to_le(),
+                        data: Data {
+                            kid,
+                            sid: [0u8; 16],
+                            usage,
+                            reserved: [0u8; 24],
+                        },
+                    },
+                    pubkey: pubkey.key,
+                    uid_size: (uid.len() as u16).to_le(),
+                    user_id,
+                    reserved: [0u8; 108],
+                },
+                signature: Default::default(),
+                _reserved: [0u8; 112],
+            },
+            prv,
+        ))
+    }
+}
+
+/// Signs the certificate, recording the key identifier of the signer.
+#[cfg(feature = "openssl")]
+impl Signer<Certificate> for PrivateKey<Usage> {
+    type Output = ();
+
+    fn sign(&self, target: &mut Certificate, uid: String) -> Result<()> {
+        target.body.preamble.data.sid = self.id.unwrap_or_default();
+
+        let mut msg: Vec<u8> = Vec::new();
+        msg.save(&target.body)?;
+
+        let sig = sm::SM2::sign(self.key, &uid.into_bytes(), &msg)?;
+        target.signature = ecdsa::Signature::try_from(&sig[..])?;
+        Ok(())
+    }
+}
+
 impl Verifiable for (&Certificate, &Certificate) {
     type Output = ();
 
@@ -118,7 +153,7 @@ impl Verifiable for (&Certificate, &Certificate) {
         let sig: Signature = self.1.try_into()?;
         key.verify(
             self.1,
-            &self.0.body.user_id[..self.0.body.uid_size as usize],
+            crate::certs::user_id(self.0.body.uid_size, &self.0.body.user_id)?,
             &sig,
         )
     }
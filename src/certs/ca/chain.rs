@@ -5,13 +5,16 @@
 //! For operating on Certificate Authority chains.
 
 use super::*;
-use crate::certs::{ca::cert::Certificate, Usage};
+use crate::{
+    certs::{ca::cert::Certificate, Usage},
+    util::{tracked::Tracked, TypeSave},
+};
 
 use serde::{Deserialize, Serialize};
 
 /// A complete Certificate Authority chain.
 #[repr(C)]
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Chain {
     /// The HYGON Sighing Key certificate.
     pub hsk: Certificate,
@@ -23,16 +26,24 @@ pub struct Chain {
 impl codicon::Decoder<()> for Chain {
     type Error = Error;
 
-    fn decode(mut reader: impl Read, _: ()) -> Result<Self> {
-        let hsk = Certificate::decode(&mut reader, ())?;
-        if Usage::try_from(&hsk)? != Usage::HSK {
-            return Err(ErrorKind::InvalidInput.into());
-        }
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
 
-        let hrk: Certificate = Certificate::decode(&mut reader, ())?;
-        if Usage::try_from(&hrk)? != Usage::HRK {
-            return Err(ErrorKind::InvalidInput.into());
-        }
+        let hsk = reader.field("hsk", |r| {
+            let hsk = Certificate::decode(r, ())?;
+            if Usage::try_from(&hsk)? != Usage::HSK {
+                return Err(ErrorKind::InvalidInput.into());
+            }
+            Ok(hsk)
+        })?;
+
+        let hrk = reader.field("hrk", |r| {
+            let hrk = Certificate::decode(r, ())?;
+            if Usage::try_from(&hrk)? != Usage::HRK {
+                return Err(ErrorKind::InvalidInput.into());
+            }
+            Ok(hrk)
+        })?;
 
         Ok(Self { hsk, hrk })
     }
@@ -42,8 +53,8 @@ impl codicon::Encoder<()> for Chain {
     type Error = Error;
 
     fn encode(&self, mut writer: impl Write, _: ()) -> Result<()> {
-        self.hsk.encode(&mut writer, crate::Body)?;
-        self.hrk.encode(&mut writer, crate::Body)
+        writer.save(&self.hsk)?;
+        writer.save(&self.hrk)
     }
 }
 
@@ -56,3 +67,23 @@ impl<'a> Verifiable for &'a Chain {
         Ok(&self.hsk)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codicon::{Decoder, Encoder};
+
+    const HSK: &[u8] = include_bytes!("../../../tests/test_data/hsk.cert");
+
+    #[test]
+    fn encode_decode() {
+        let chain = Chain {
+            hsk: Certificate::decode(HSK, ()).unwrap(),
+            hrk: Certificate::decode(crate::certs::builtin::HRK, ()).unwrap(),
+        };
+
+        let mut bytes = Vec::new();
+        chain.encode(&mut bytes, ()).unwrap();
+        assert_eq!(Chain::decode(&bytes[..], ()).unwrap(), chain);
+    }
+}
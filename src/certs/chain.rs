@@ -5,13 +5,65 @@
 //! Utilities for operating on entire certificate chains.
 
 use super::*;
-use crate::certs::{ca, csv};
+use crate::{
+    api::guest::AttestationReport,
+    certs::{builtin::HRK, ca, csv},
+    error::ChainError,
+    util::tracked::Tracked,
+};
 
+use codicon::Decoder;
 use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// A link of a [`Chain`], named after the certificate or report it
+/// endorses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Link {
+    /// The HRK, self-signed and trusted as the root.
+    Hrk,
+
+    /// The HSK, signed by the HRK.
+    Hsk,
+
+    /// The CEK, signed by the HSK.
+    Cek,
+
+    /// The OCA, self-signed.
+    Oca,
+
+    /// The PEK, signed by both the OCA and the CEK.
+    Pek,
+
+    /// The PDH, signed by the PEK.
+    Pdh,
+
+    /// The attestation report, signed by the PEK.
+    Report,
+}
+
+impl Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Link::Hrk => "HRK",
+            Link::Hsk => "HSK",
+            Link::Cek => "CEK",
+            Link::Oca => "OCA",
+            Link::Pek => "PEK",
+            Link::Pdh => "PDH",
+            Link::Report => "report",
+        })
+    }
+}
+
+/// Tells which `link` failed with the result of its verification.
+fn link<T>(link: Link, result: Result<T>) -> Result<T> {
+    result.map_err(|e| ChainError::locate(e, link))
+}
 
 /// A complete certificate chain.
 #[repr(C)]
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Chain {
     /// The Certificate Authority chain
     pub ca: ca::Chain,
@@ -23,9 +75,10 @@ pub struct Chain {
 impl codicon::Decoder<()> for Chain {
     type Error = Error;
 
-    fn decode(mut reader: impl Read, _: ()) -> Result<Self> {
-        let csv = csv::Chain::decode(&mut reader, ())?;
-        let ca = ca::Chain::decode(&mut reader, ())?;
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
+        let csv = reader.field("csv", |r| csv::Chain::decode(r, ()))?;
+        let ca = reader.field("ca", |r| ca::Chain::decode(r, ()))?;
         Ok(Self { ca, csv })
     }
 }
@@ -39,12 +92,74 @@ impl codicon::Encoder<()> for Chain {
     }
 }
 
+impl Chain {
+    /// Verifies every link of the chain up to `root`, returning the PDH.
+    fn verify_links(&self, root: &ca::Certificate) -> Result<&csv::Certificate> {
+        let (ca, csv) = (&self.ca, &self.csv);
+
+        if ca.hrk != *root {
+            let untrusted = Error::new(ErrorKind::InvalidData, "not the trusted root");
+            return Err(ChainError::locate(untrusted, Link::Hrk));
+        }
+        link(Link::Hrk, (&ca.hrk, &ca.hrk).verify())?;
+        link(Link::Hsk, (&ca.hrk, &ca.hsk).verify())?;
+        link(Link::Cek, (&ca.hsk, &csv.cek).verify())?;
+        link(Link::Oca, (&csv.oca, &csv.oca).verify())?;
+        link(Link::Pek, (&csv.oca, &csv.pek).verify())?;
+        link(Link::Pek, (&csv.cek, &csv.pek).verify())?;
+        link(Link::Pdh, (&csv.pek, &csv.pdh).verify())?;
+
+        Ok(&csv.pdh)
+    }
+
+    /// Verifies `report` against the entire chain, anchored on the HYGON
+    /// root key, see [`builtin::HRK`].
+    ///
+    /// A failure names the link which did not verify, see
+    /// [`ChainError::find`].
+    pub fn verify_report(&self, report: &AttestationReport) -> Result<()> {
+        self.verify_report_with_root(report, &ca::Certificate::decode(HRK, ())?)
+    }
+
+    /// Like [`Chain::verify_report`], but anchored on another `root`, e.g.
+    /// a test HRK.
+    pub fn verify_report_with_root(
+        &self,
+        report: &AttestationReport,
+        root: &ca::Certificate,
+    ) -> Result<()> {
+        self.verify_links(root)?;
+        link(Link::Report, (&self.csv.pek, report).verify())
+    }
+}
+
+/// Verifies the chain up to its own HRK, returning the PDH. A failure names
+/// the link which did not verify, see [`ChainError::find`].
 impl<'a> Verifiable for &'a Chain {
     type Output = &'a csv::Certificate;
 
     fn verify(self) -> Result<Self::Output> {
-        let ask = self.ca.verify()?;
-        (ask, &self.csv.cek).verify()?;
-        self.csv.verify()
+        self.verify_links(&self.ca.hrk)
+    }
+}
+
+/// Verifies a report against the entire chain, as
+/// [`Chain::verify_report`].
+impl Verifiable for (&Chain, &AttestationReport) {
+    type Output = ();
+
+    fn verify(self) -> Result<()> {
+        self.0.verify_report(self.1)
+    }
+}
+
+/// Verifies a CSV chain endorsed by the CEK of a CA chain, returning the
+/// PDH, as for a [`Chain`] whose parts were obtained separately.
+impl<'a> Verifiable for (&ca::Chain, &'a csv::Chain) {
+    type Output = &'a csv::Certificate;
+
+    fn verify(self) -> Result<Self::Output> {
+        (self.0, &self.1.cek).verify()?;
+        self.1.verify()
     }
 }
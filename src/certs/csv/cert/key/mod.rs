@@ -7,13 +7,7 @@
 use super::*;
 use crate::crypto::{key::group, sm, PrivateKey};
 
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
-pub struct PubKey {
-    pub usage: Usage,
-    pub algo: Algorithm,
-    pub key: ecc::PubKey,
-}
+pub use crate::layout::csv::PubKey;
 
 impl PubKey {
     pub fn generate(usage: Usage, id: Option<[u8; 16]>) -> Result<(PubKey, PrivateKey<Usage>)> {
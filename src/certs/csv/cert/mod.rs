@@ -4,47 +4,25 @@
 
 //! Operations that can be done on a CSV certificate.
 
+#[cfg(feature = "openssl")]
 pub mod key;
 
+#[cfg(feature = "openssl")]
 use crate::{
-    certs::{ca, Algorithm, Signer, Usage, Verifiable},
-    crypto::{self, key::ecc, sig::ecdsa, sm, PrivateKey, PublicKey, Signature},
-    util::*,
+    certs::Signer,
+    crypto::{self, sm, PrivateKey},
+};
+use crate::{
+    certs::{ca, Algorithm, Usage, Verifiable},
+    crypto::{sig::ecdsa, PublicKey, Signature},
+    util::{tracked::Tracked, *},
 };
 use serde::{Deserialize, Serialize};
-use serde_big_array::BigArray;
 use std::io::{Error, ErrorKind, Read, Result, Write};
 
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
-pub struct Data {
-    pub firmware: crate::Version,
-    pub reserved1: u16,
-    pub pubkey: key::PubKey,
-    pub uid_size: u16,
-    #[serde(with = "BigArray")]
-    pub user_id: [u8; 254],
-    pub sid: [u8; 16],
-    #[serde(with = "BigArray")]
-    pub reserved2: [u8; 608],
-}
-
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
-pub struct Body {
-    pub ver: u32,
-    pub data: Data,
-}
-
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
-pub struct Signatures {
-    usage: Usage,
-    algo: Algorithm,
-    signature: ecdsa::Signature,
-    #[serde(with = "BigArray")]
-    _reserved: [u8; 368],
-}
+#[cfg(feature = "openssl")]
+use crate::layout::csv::Data;
+pub use crate::layout::csv::{Body, Certificate, Signatures};
 
 impl Default for Signatures {
     fn default() -> Self {
@@ -58,6 +36,7 @@ impl Default for Signatures {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<&crypto::Signature> for Signatures {
     type Error = Error;
 
@@ -73,13 +52,6 @@ impl TryFrom<&crypto::Signature> for Signatures {
     }
 }
 
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
-pub struct Certificate {
-    pub body: Body,
-    pub sigs: [Signatures; 2],
-}
-
 impl TryFrom<&Signatures> for Option<Signature> {
     type Error = Error;
 
@@ -124,6 +96,7 @@ impl TryFrom<&Certificate> for PublicKey {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl Signer<Certificate> for PrivateKey<Usage> {
     type Output = ();
 
@@ -160,16 +133,10 @@ impl Verifiable for (&Certificate, &Certificate) {
     fn verify(self) -> Result<()> {
         let key: PublicKey = self.0.try_into()?;
 
+        let id = crate::certs::user_id(self.0.body.data.uid_size, &self.0.body.data.user_id)?;
         let sigs: [Option<Signature>; 2] = self.1.try_into()?;
         for sig in sigs.iter().flatten() {
-            if key
-                .verify(
-                    self.1,
-                    &self.0.body.data.user_id[..self.0.body.data.uid_size as usize],
-                    sig,
-                )
-                .is_ok()
-            {
+            if key.verify(self.1, id, sig).is_ok() {
                 return Ok(());
             }
         }
@@ -183,16 +150,10 @@ impl Verifiable for (&ca::cert::Certificate, &Certificate) {
 
     fn verify(self) -> Result<()> {
         let key: PublicKey = self.0.try_into()?;
+        let id = crate::certs::user_id(self.0.body.uid_size, &self.0.body.user_id)?;
         let sigs: [Option<Signature>; 2] = self.1.try_into()?;
         for sig in sigs.iter().flatten() {
-            if key
-                .verify(
-                    self.1,
-                    &self.0.body.user_id[..self.0.body.uid_size as usize],
-                    sig,
-                )
-                .is_ok()
-            {
+            if key.verify(self.1, id, sig).is_ok() {
                 return Ok(());
             }
         }
@@ -200,16 +161,65 @@ impl Verifiable for (&ca::cert::Certificate, &Certificate) {
     }
 }
 
+/// The signers whose signature of a PEK certificate verified, see
+/// [`Certificate::pek_signers`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PekSigners {
+    /// The CEK signed the PEK, vouching that it belongs to a genuine chip.
+    pub cek: bool,
+
+    /// The self-signed OCA signed the PEK, vouching for the platform owner.
+    pub oca: bool,
+}
+
+impl PekSigners {
+    /// Returns true if both the CEK and the OCA signed the PEK.
+    pub fn both(&self) -> bool {
+        self.cek && self.oca
+    }
+}
+
+impl Certificate {
+    /// Checks both signatures of a PEK certificate, the CEK's and the
+    /// OCA's, reporting which verified.
+    ///
+    /// The OCA signature is reported unverified if no OCA certificate is
+    /// given, or if it is not self-signed. A PEK taken from a report signer
+    /// must be restored first, see `ReportSigner::verify`.
+    pub fn pek_signers(&self, cek: &Certificate, oca: Option<&Certificate>) -> Result<PekSigners> {
+        if Usage::try_from(self)? != Usage::PEK {
+            return Err(Error::new(ErrorKind::InvalidInput, "not a PEK certificate"));
+        }
+
+        Ok(PekSigners {
+            cek: (cek, self).verify().is_ok(),
+            oca: oca.is_some_and(|oca| (oca, oca).verify().is_ok() && (oca, self).verify().is_ok()),
+        })
+    }
+}
+
+/// Verifies the CA chain up to its self-signed HRK, then the certificate
+/// against the HSK, e.g. a CEK.
+impl Verifiable for (&ca::Chain, &Certificate) {
+    type Output = ();
+
+    fn verify(self) -> Result<()> {
+        let hsk = self.0.verify()?;
+        (hsk, self.1).verify()
+    }
+}
+
 impl codicon::Decoder<()> for Signatures {
     type Error = Error;
 
     #[inline]
-    fn decode(mut reader: impl Read, _: ()) -> Result<Self> {
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
         let mut _reserved = [0u8; 368];
-        let usage: Usage = reader.load()?;
-        let algo: Algorithm = reader.load()?;
-        let signature: ecdsa::Signature = reader.load()?;
-        reader.read_exact(&mut _reserved)?;
+        let usage: Usage = reader.field("usage", |r| r.load())?;
+        let algo: Algorithm = reader.field("algo", |r| r.load())?;
+        let signature: ecdsa::Signature = reader.field("signature", |r| r.load())?;
+        reader.field("reserved", |r| r.read_exact(&mut _reserved))?;
         Ok(Self {
             usage,
             algo,
@@ -222,10 +232,11 @@ impl codicon::Decoder<()> for Signatures {
 impl codicon::Decoder<()> for Certificate {
     type Error = Error;
 
-    fn decode(mut reader: impl Read, _: ()) -> Result<Self> {
-        let body: Body = reader.load()?;
-        let sig1 = Signatures::decode(&mut reader, ())?;
-        let sig2 = Signatures::decode(&mut reader, ())?;
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
+        let body: Body = reader.field("body", |r| r.load())?;
+        let sig1 = reader.field("sigs[0]", |r| Signatures::decode(r, ()))?;
+        let sig2 = reader.field("sigs[1]", |r| Signatures::decode(r, ()))?;
         Ok(Self {
             body,
             sigs: [sig1, sig2],
@@ -260,6 +271,7 @@ impl Signatures {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl Body {
     pub fn generate(usage: Usage, uid: Option<String>) -> Result<(Body, PrivateKey<Usage>)> {
         let uid: String = if let Some(value) = uid {
@@ -268,8 +280,8 @@ impl Body {
             String::try_from(usage)?
         };
 
-        let uid_size = if let Some(u16_value) = uid.len().try_into().ok() {
-            u16_value
+        let uid_size = if let Some(u16_value) = u16::try_from(uid.len()).ok() {
+            u16_value.to_le()
         } else {
             return Err(ErrorKind::InvalidInput.into());
         };
@@ -296,6 +308,7 @@ impl Body {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl Certificate {
     /// Generates a private key and its public certificate.
     pub fn generate(usage: Usage, uid: Option<String>) -> Result<(Self, PrivateKey<Usage>)> {
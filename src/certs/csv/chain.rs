@@ -6,14 +6,17 @@
 
 use super::cert::Certificate;
 use super::*;
-use crate::certs::Usage;
+use crate::{
+    certs::Usage,
+    util::{tracked::Tracked, TypeSave},
+};
 
 use serde::{Deserialize, Serialize};
 use std::io::{Error, ErrorKind, Result};
 
 /// The CSV certificate chain.
 #[repr(C)]
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Chain {
     /// The Platform Diffie-Hellman certificate
     pub pdh: Certificate,
@@ -31,39 +34,34 @@ pub struct Chain {
 impl codicon::Decoder<()> for Chain {
     type Error = Error;
 
-    fn decode(mut reader: impl Read, _: ()) -> Result<Self> {
-        let pdh = Certificate::decode(&mut reader, ())?;
-        if Usage::try_from(&pdh)? != Usage::PDH {
-            return Err(ErrorKind::InvalidInput.into());
-        }
-
-        let pek = Certificate::decode(&mut reader, ())?;
-        if Usage::try_from(&pek)? != Usage::PEK {
-            return Err(ErrorKind::InvalidInput.into());
-        }
-
-        let oca = Certificate::decode(&mut reader, ())?;
-        if Usage::try_from(&oca)? != Usage::OCA {
-            return Err(ErrorKind::InvalidInput.into());
-        }
-
-        let cek = Certificate::decode(&mut reader, ())?;
-        if Usage::try_from(&cek)? != Usage::CEK {
-            return Err(ErrorKind::InvalidInput.into());
-        }
-
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
+        let pdh = reader.field("pdh", |r| load(r, Usage::PDH))?;
+        let pek = reader.field("pek", |r| load(r, Usage::PEK))?;
+        let oca = reader.field("oca", |r| load(r, Usage::OCA))?;
+        let cek = reader.field("cek", |r| load(r, Usage::CEK))?;
         Ok(Self { pdh, pek, oca, cek })
     }
 }
 
+/// Decodes a certificate, requiring it to have the given usage.
+fn load(reader: impl Read, usage: Usage) -> Result<Certificate> {
+    let cert = codicon::Decoder::decode(reader, ())?;
+    if Usage::try_from(&cert)? != usage {
+        return Err(ErrorKind::InvalidInput.into());
+    }
+
+    Ok(cert)
+}
+
 impl codicon::Encoder<()> for Chain {
     type Error = Error;
 
     fn encode(&self, mut writer: impl Write, _: ()) -> Result<()> {
-        self.pdh.encode(&mut writer, crate::Body)?;
-        self.pek.encode(&mut writer, crate::Body)?;
-        self.oca.encode(&mut writer, crate::Body)?;
-        self.cek.encode(&mut writer, crate::Body)
+        writer.save(&self.pdh)?;
+        writer.save(&self.pek)?;
+        writer.save(&self.oca)?;
+        writer.save(&self.cek)
     }
 }
 
@@ -78,3 +76,31 @@ impl<'a> Verifiable for &'a Chain {
         Ok(&self.pdh)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codicon::{Decoder, Encoder};
+
+    const CEK: &[u8] = include_bytes!("../../../tests/test_data/cek.cert");
+
+    #[test]
+    fn encode_decode() {
+        let cek = Certificate::decode(CEK, ()).unwrap();
+        let with_usage = |usage| {
+            let mut cert = cek;
+            cert.body.data.pubkey.usage = usage;
+            cert
+        };
+        let chain = Chain {
+            pdh: with_usage(Usage::PDH),
+            pek: with_usage(Usage::PEK),
+            oca: with_usage(Usage::OCA),
+            cek,
+        };
+
+        let mut bytes = Vec::new();
+        chain.encode(&mut bytes, ()).unwrap();
+        assert_eq!(Chain::decode(&bytes[..], ()).unwrap(), chain);
+    }
+}
@@ -7,5 +7,5 @@
 use super::*;
 pub(crate) mod cert;
 mod chain;
-pub use cert::Certificate;
+pub use cert::{Certificate, PekSigners};
 pub use chain::Chain;
@@ -0,0 +1,85 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The chip-specific certificates HYGON publishes for every chip.
+
+use super::*;
+use crate::{
+    certs::{builtin::HRK, ca, csv},
+    util::{tracked::Tracked, *},
+};
+
+use codicon::Decoder;
+use serde::{Deserialize, Serialize};
+
+/// The HSK and CEK certificates of a chip, as served by the HYGON
+/// key distribution server (the `hsk_cek` file layout).
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct HskCek {
+    /// The HYGON Signing Key certificate.
+    pub hsk: ca::Certificate,
+
+    /// The Chip Endorsement Key certificate.
+    pub cek: csv::Certificate,
+}
+
+impl codicon::Decoder<()> for HskCek {
+    type Error = Error;
+
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        let mut reader = Tracked::new(reader);
+
+        let hsk = reader.field("hsk", |r| {
+            let hsk = ca::Certificate::decode(r, ())?;
+            if Usage::try_from(&hsk)? != Usage::HSK {
+                return Err(ErrorKind::InvalidInput.into());
+            }
+            Ok(hsk)
+        })?;
+
+        let cek = reader.field("cek", |r| {
+            let cek = csv::Certificate::decode(r, ())?;
+            if Usage::try_from(&cek)? != Usage::CEK {
+                return Err(ErrorKind::InvalidInput.into());
+            }
+            Ok(cek)
+        })?;
+
+        Ok(Self { hsk, cek })
+    }
+}
+
+impl codicon::Encoder<()> for HskCek {
+    type Error = Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<()> {
+        writer.save(&self.hsk)?;
+        writer.save(&self.cek)
+    }
+}
+
+/// Verifies the certificates up to the builtin HRK.
+impl<'a> Verifiable for &'a HskCek {
+    type Output = &'a csv::Certificate;
+
+    fn verify(self) -> Result<Self::Output> {
+        let hrk = ca::Certificate::decode(HRK, ())?;
+        (&hrk, &hrk).verify()?;
+        (&hrk, &self.hsk).verify()?;
+        (&self.hsk, &self.cek).verify()?;
+        Ok(&self.cek)
+    }
+}
+
+/// Verifies the certificates up to the builtin HRK, then the certificate
+/// against the CEK, e.g. a PEK.
+impl Verifiable for (&HskCek, &csv::Certificate) {
+    type Output = ();
+
+    fn verify(self) -> Result<()> {
+        let cek = self.0.verify()?;
+        (cek, self.1).verify()
+    }
+}
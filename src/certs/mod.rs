@@ -5,20 +5,36 @@
 //! Everything needed for working with HYGON CSV certificate chains.
 
 pub mod builtin;
+pub mod bundle;
 pub mod ca;
 mod chain;
 pub mod csv;
+mod hsk_cek;
 
-use serde::{Deserialize, Serialize};
 use std::{
     convert::*,
     io::{Error, ErrorKind, Read, Result, Write},
 };
 
-pub use chain::Chain;
+pub use crate::layout::{Algorithm, Usage};
+pub use chain::{Chain, Link};
+pub use hsk_cek::HskCek;
 
+#[cfg(feature = "openssl")]
 use openssl::hash;
 
+/// The signer id held in the first `size` bytes of a certificate's
+/// `user_id`, with `size` as stored in the certificate.
+pub(crate) fn user_id(size: u16, id: &[u8]) -> Result<&[u8]> {
+    let size = u16::from_le(size) as usize;
+    id.get(..size).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("user id size {size} exceeds {} bytes", id.len()),
+        )
+    })
+}
+
 /// An interface for types that may containe entities
 /// such as signatures that must be verified.
 pub trait Verifiable {
@@ -38,30 +54,7 @@ pub trait Signer<T> {
     fn sign(&self, target: &mut T, uid: String) -> Result<Self::Output>;
 }
 
-/// Denotes a certificate's usage.
-#[repr(C)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Usage(u32);
-
 impl Usage {
-    /// HYGON Root Key.
-    pub const HRK: Usage = Usage(0x0000u32.to_le());
-
-    /// HYGON Signing key.
-    pub const HSK: Usage = Usage(0x0013u32.to_le());
-
-    /// Owner Certificate Authority.
-    pub const OCA: Usage = Usage(0x1001u32.to_le());
-
-    /// Platform Endorsement Key.
-    pub const PEK: Usage = Usage(0x1002u32.to_le());
-
-    /// Platform Diffie-Hellman.
-    pub const PDH: Usage = Usage(0x1003u32.to_le());
-
-    /// Chip Endorsement Key.
-    pub const CEK: Usage = Usage(0x1004u32.to_le());
-
     const INV: Usage = Usage(0x1000u32.to_le());
 }
 
@@ -107,16 +100,6 @@ impl TryFrom<Usage> for Algorithm {
     }
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Algorithm(u32);
-
-impl Algorithm {
-    pub const SM2_SA: Algorithm = Algorithm(0x0004u32.to_le());
-    pub const SM2_DH: Algorithm = Algorithm(0x0005u32.to_le());
-    pub const NONE: Algorithm = Algorithm(0x0000u32.to_le());
-}
-
 impl Default for Algorithm {
     fn default() -> Self {
         Algorithm(0)
@@ -129,6 +112,9 @@ impl From<u32> for Algorithm {
     }
 }
 
+/// The digest signatures of an algorithm are computed over, for signing
+/// with a [`crate::crypto::PrivateKey`].
+#[cfg(feature = "openssl")]
 impl TryFrom<Algorithm> for hash::MessageDigest {
     type Error = Error;
 
@@ -0,0 +1,299 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The GM primitives of the verification paths: SM3, HMAC-SM3 and SM2
+//! signature verification.
+//!
+//! They are backed by OpenSSL by default. The `rust_crypto` feature backs
+//! them with the pure-Rust `sm2` and `sm3` crates instead, for targets
+//! whose OpenSSL lacks the GM algorithms: with the `openssl` feature
+//! disabled, reports and certificate chains are then verified without
+//! linking OpenSSL at all. Key generation, encryption and the launch
+//! session need the `openssl` feature.
+
+use std::io::Result;
+
+#[cfg(not(any(feature = "openssl", feature = "rust_crypto")))]
+compile_error!("the GM primitives need the openssl or the rust_crypto feature");
+
+/// The size of an SM3 digest.
+pub const SM3_LEN: usize = 32;
+
+#[cfg(not(feature = "rust_crypto"))]
+mod backend {
+    use openssl::{
+        hash::{Hasher, MessageDigest},
+        pkey::{PKey, Private},
+        sign::Signer,
+    };
+    use std::io::Result;
+
+    pub struct Sm3(Hasher);
+
+    impl Sm3 {
+        pub fn new() -> Result<Self> {
+            Ok(Self(Hasher::new(MessageDigest::sm3())?))
+        }
+
+        pub fn update(&mut self, data: &[u8]) -> Result<()> {
+            Ok(self.0.update(data)?)
+        }
+
+        pub fn finish(mut self) -> Result<[u8; super::SM3_LEN]> {
+            let mut digest = [0u8; super::SM3_LEN];
+            digest.copy_from_slice(&self.0.finish()?);
+            Ok(digest)
+        }
+    }
+
+    /// A `Signer` borrows its key, so the data is kept until the MAC is
+    /// computed; it is never more than a report signer.
+    pub struct HmacSm3 {
+        key: PKey<Private>,
+        data: Vec<u8>,
+    }
+
+    impl HmacSm3 {
+        pub fn new(key: &[u8]) -> Result<Self> {
+            Ok(Self {
+                key: PKey::hmac(key)?,
+                data: Vec::new(),
+            })
+        }
+
+        pub fn update(&mut self, data: &[u8]) -> Result<()> {
+            self.data.extend_from_slice(data);
+            Ok(())
+        }
+
+        pub fn finish(self) -> Result<[u8; super::SM3_LEN]> {
+            let mut signer = Signer::new(MessageDigest::sm3(), &self.key)?;
+            signer.update(&self.data)?;
+
+            let mut mac = [0u8; super::SM3_LEN];
+            signer.sign(&mut mac)?;
+            Ok(mac)
+        }
+    }
+
+    pub fn sm2_verify(x: &[u8], y: &[u8], id: &[u8], msg: &[u8], sig: &[u8]) -> Result<bool> {
+        crate::crypto::sm::SM2::verify_be(x, y, id, msg, sig)
+    }
+}
+
+#[cfg(feature = "rust_crypto")]
+mod backend {
+    use crate::layout::verify::verifying_key;
+    use hmac::{Hmac, Mac};
+    use sm2::dsa::{signature::Verifier, Signature};
+    use sm3::Digest;
+    use std::io::{Error, ErrorKind, Result};
+
+    pub struct Sm3(sm3::Sm3);
+
+    impl Sm3 {
+        pub fn new() -> Result<Self> {
+            Ok(Self(sm3::Sm3::new()))
+        }
+
+        pub fn update(&mut self, data: &[u8]) -> Result<()> {
+            self.0.update(data);
+            Ok(())
+        }
+
+        pub fn finish(self) -> Result<[u8; super::SM3_LEN]> {
+            Ok(self.0.finalize().into())
+        }
+    }
+
+    pub struct HmacSm3(Hmac<sm3::Sm3>);
+
+    impl HmacSm3 {
+        pub fn new(key: &[u8]) -> Result<Self> {
+            Hmac::new_from_slice(key)
+                .map(Self)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))
+        }
+
+        pub fn update(&mut self, data: &[u8]) -> Result<()> {
+            self.0.update(data);
+            Ok(())
+        }
+
+        pub fn finish(self) -> Result<[u8; super::SM3_LEN]> {
+            Ok(self.0.finalize().into_bytes().into())
+        }
+    }
+
+    pub fn sm2_verify(x: &[u8], y: &[u8], id: &[u8], msg: &[u8], sig: &[u8]) -> Result<bool> {
+        let key = verifying_key(x, y, id)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        let sig = match der_rs(sig).and_then(|rs| Signature::from_slice(&rs).ok()) {
+            Some(sig) => sig,
+            None => return Ok(false),
+        };
+
+        Ok(key.verify(msg, &sig).is_ok())
+    }
+
+    /// Reads the DER `SEQUENCE { r INTEGER, s INTEGER }` OpenSSL produces
+    /// into the fixed big-endian `r || s` layout.
+    pub fn der_rs(der: &[u8]) -> Option<[u8; 64]> {
+        fn tlv(der: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+            let (&t, rest) = der.split_first()?;
+            let (&len, rest) = rest.split_first()?;
+            let (len, rest) = match len {
+                0..=0x7f => (len as usize, rest),
+                0x81 => (*rest.first()? as usize, &rest[1..]),
+                _ => return None,
+            };
+            if t != tag || rest.len() < len {
+                return None;
+            }
+            Some(rest.split_at(len))
+        }
+
+        let (seq, rest) = tlv(der, 0x30)?;
+        if !rest.is_empty() {
+            return None;
+        }
+
+        let mut rs = [0u8; 64];
+        let mut seq = seq;
+        for half in rs.chunks_mut(32) {
+            let (int, rest) = tlv(seq, 0x02)?;
+            let int = &int[int.iter().position(|&b| b != 0).unwrap_or(int.len())..];
+            if int.len() > half.len() {
+                return None;
+            }
+            half[32 - int.len()..].copy_from_slice(int);
+            seq = rest;
+        }
+
+        seq.is_empty().then_some(rs)
+    }
+}
+
+/// An incremental SM3 hash.
+pub struct Sm3(backend::Sm3);
+
+impl Sm3 {
+    /// Starts a hash.
+    pub fn new() -> Result<Self> {
+        backend::Sm3::new().map(Self)
+    }
+
+    /// Hashes `data`.
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        self.0.update(data)
+    }
+
+    /// Returns the digest.
+    pub fn finish(self) -> Result<[u8; SM3_LEN]> {
+        self.0.finish()
+    }
+}
+
+/// Returns the SM3 digest of `data`.
+pub fn sm3(data: &[u8]) -> Result<[u8; SM3_LEN]> {
+    let mut hasher = Sm3::new()?;
+    hasher.update(data)?;
+    hasher.finish()
+}
+
+/// An incremental HMAC-SM3.
+pub struct HmacSm3(backend::HmacSm3);
+
+impl HmacSm3 {
+    /// Starts a MAC under `key`.
+    pub fn new(key: &[u8]) -> Result<Self> {
+        backend::HmacSm3::new(key).map(Self)
+    }
+
+    /// MACs `data`.
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        self.0.update(data)
+    }
+
+    /// Returns the MAC.
+    pub fn finish(self) -> Result<[u8; SM3_LEN]> {
+        self.0.finish()
+    }
+}
+
+/// Returns the HMAC-SM3 of the concatenated `parts` under `key`.
+pub fn hmac_sm3(key: &[u8], parts: &[&[u8]]) -> Result<[u8; SM3_LEN]> {
+    let mut mac = HmacSm3::new(key)?;
+    for part in parts {
+        mac.update(part)?;
+    }
+    mac.finish()
+}
+
+/// Compares two MACs in constant time.
+pub fn mac_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Verifies the DER encoded SM2 signature `sig` of `msg` by the signer
+/// `id`, given the big-endian affine coordinates of its public key.
+///
+/// Returns `Ok(false)` if the signature does not verify.
+pub fn sm2_verify(x: &[u8], y: &[u8], id: &[u8], msg: &[u8], sig: &[u8]) -> Result<bool> {
+    backend::sm2_verify(x, y, id, msg, sig)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sm3_vector() {
+        // GB/T 32905-2016, example 1.
+        assert_eq!(
+            crate::util::hex::encode(&sm3(b"abc").unwrap()),
+            "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0"
+        );
+    }
+
+    #[test]
+    fn hmac() {
+        // A key shorter than the block, and one hashed first.
+        let vectors = [
+            (
+                &[7u8; 16][..],
+                "0a6679a36ab8cfba96efb5af816d88f955c90198fd57c5476dd51eb5c48875bc",
+            ),
+            (
+                &[9u8; 100][..],
+                "80931f092535c16deecffa3ea1fbfccf1b488ad1c8e54ed38f31e2558968ad04",
+            ),
+        ];
+
+        for (key, mac) in vectors {
+            assert_eq!(
+                crate::util::hex::encode(&hmac_sm3(key, &[b"covered", b" bytes"]).unwrap()),
+                mac
+            );
+        }
+    }
+
+    #[cfg(feature = "rust_crypto")]
+    #[test]
+    fn der_rs() {
+        // r with the sign padding OpenSSL adds, s shorter than 32 bytes.
+        let mut der = vec![0x30, 0x44, 0x02, 0x21, 0x00];
+        der.extend_from_slice(&[0x80; 32]);
+        der.extend_from_slice(&[0x02, 0x1f]);
+        der.extend_from_slice(&[0x01; 31]);
+
+        let rs = backend::der_rs(&der).unwrap();
+        assert_eq!(rs[..32], [0x80; 32]);
+        assert_eq!(rs[32], 0);
+        assert_eq!(rs[33..], [0x01; 31]);
+
+        der.push(0);
+        assert_eq!(backend::der_rs(&der), None);
+    }
+}
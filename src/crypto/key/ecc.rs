@@ -4,23 +4,16 @@
 
 //! Interfaces for ecc keys.
 
+#[cfg(feature = "openssl")]
 use crate::{crypto::key::group::Group, util::*};
+#[cfg(feature = "openssl")]
 use openssl::{bn, ec, pkey};
-use serde::{Deserialize, Serialize};
-use serde_big_array::BigArray;
+#[cfg(feature = "openssl")]
 use std::io::{Error, Result};
 
-/// The Raw format of ecc pubkey.
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
-pub struct PubKey {
-    pub g: Group,
-    #[serde(with = "BigArray")]
-    pub x: [u8; 72],
-    #[serde(with = "BigArray")]
-    pub y: [u8; 72],
-}
+pub use crate::layout::key::PubKey;
 
+#[cfg(feature = "openssl")]
 impl TryFrom<&PubKey> for ec::EcKey<pkey::Public> {
     type Error = Error;
 
@@ -34,6 +27,7 @@ impl TryFrom<&PubKey> for ec::EcKey<pkey::Public> {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<&ec::EcKey<pkey::Private>> for PubKey {
     type Error = Error;
 
@@ -2,17 +2,15 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "openssl")]
 use openssl::{ec, nid};
-use serde::{Deserialize, Serialize};
-use std::io::{Error, ErrorKind, Result};
+#[cfg(feature = "openssl")]
+use std::io::Error;
+use std::io::{ErrorKind, Result};
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
-pub struct Group(u32);
+pub use crate::layout::key::Group;
 
 impl Group {
-    pub const SM2_256: Group = Group(3u32.to_le());
-
     pub fn size(self) -> Result<usize> {
         Ok(match self {
             Group::SM2_256 => 32,
@@ -21,6 +19,7 @@ impl Group {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<Group> for nid::Nid {
     type Error = Error;
     fn try_from(value: Group) -> Result<Self> {
@@ -31,6 +30,7 @@ impl TryFrom<Group> for nid::Nid {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<nid::Nid> for Group {
     type Error = Error;
 
@@ -42,6 +42,7 @@ impl TryFrom<nid::Nid> for Group {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<Group> for ec::EcGroup {
     type Error = Error;
 
@@ -50,6 +51,7 @@ impl TryFrom<Group> for ec::EcGroup {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<&ec::EcGroupRef> for Group {
     type Error = Error;
 
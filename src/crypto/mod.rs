@@ -4,6 +4,7 @@
 
 //! Interfaces for cryptography.
 
+pub mod gm;
 pub mod key;
 pub(crate) mod sig;
 pub mod sm;
@@ -13,10 +14,56 @@ use crate::{
     crypto::key::ecc,
     Body,
 };
+#[cfg(feature = "openssl")]
 use openssl::hash;
+#[cfg(feature = "openssl")]
 use openssl_sys::EC_KEY;
 use std::io::{Error, ErrorKind, Result};
 
+/// A random number generator backed by the OpenSSL CSPRNG.
+///
+/// This is the default source of randomness for nonces and keys, which
+/// callers may replace, e.g. with a seeded generator for reproducible tests.
+#[cfg(feature = "openssl")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpensslRng;
+
+/// The default source of randomness for nonces: the OpenSSL CSPRNG, or the
+/// one of the operating system without the `openssl` feature.
+#[cfg(feature = "openssl")]
+pub type DefaultRng = OpensslRng;
+
+/// The default source of randomness for nonces, the operating system's
+/// without the `openssl` feature.
+#[cfg(not(feature = "openssl"))]
+pub type DefaultRng = rand::rngs::OsRng;
+
+#[cfg(feature = "openssl")]
+impl rand::RngCore for OpensslRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("OpenSSL RNG failure")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> std::result::Result<(), rand::Error> {
+        openssl::rand::rand_bytes(dest).map_err(rand::Error::new)
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl rand::CryptoRng for OpensslRng {}
+
 #[derive(Debug)]
 pub struct Signature {
     pub id: Option<[u8; 16]>,
@@ -26,6 +73,7 @@ pub struct Signature {
 }
 
 /// Represents a private key.
+#[cfg(feature = "openssl")]
 pub struct PrivateKey<U> {
     pub id: Option<[u8; 16]>,
     pub key: *mut EC_KEY,
@@ -33,6 +81,17 @@ pub struct PrivateKey<U> {
     pub usage: U,
 }
 
+#[cfg(feature = "openssl")]
+impl<U: std::fmt::Debug> std::fmt::Debug for PrivateKey<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("id", &self.id)
+            .field("key", &crate::util::redact::Redacted(&self.key))
+            .field("usage", &self.usage)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug)]
 pub struct PublicKey {
     pub id: Option<[u8; 16]>,
@@ -57,18 +116,22 @@ impl PublicKey {
 
         let mut buf: Vec<u8> = Vec::new();
         msg.encode(&mut buf, Body)?;
-        sm::SM2::verify(self.key, &sig.sig, &Vec::from(uid), &buf).map(|ok| {
-            // SM2 verify will return Ok(true) if the signature
-            // is verified and Ok(false) if not. This patches the result
-            // to return Err if SM2 returns Ok(false).
-            if ok {
-                Ok(())
-            } else {
-                Err(ErrorKind::NotFound.into())
-            }
-        })?
+        self.verify_der(&buf, uid, &sig.sig)
+    }
+
+    /// Verifies a DER encoded signature over `msg`, borrowing every input.
+    pub fn verify_der(&self, msg: &[u8], uid: &[u8], sig: &[u8]) -> Result<()> {
+        // SM2 verify will return Ok(true) if the signature
+        // is verified and Ok(false) if not. This patches the result
+        // to return Err if SM2 returns Ok(false).
+        if sm::SM2::verify(self.key, sig, uid, msg)? {
+            Ok(())
+        } else {
+            Err(ErrorKind::NotFound.into())
+        }
     }
 
+    #[cfg(feature = "openssl")]
     pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
         sm::SM2::encrypt(&data, self.key)
     }
@@ -2,22 +2,62 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "openssl")]
 use crate::util::*;
+#[cfg(feature = "openssl")]
 use openssl::{bn, ecdsa};
-use serde::{Deserialize, Serialize};
-use serde_big_array::BigArray;
 use std::io::{Error, Result};
 
-/// The Raw format of ecdsa signature.
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
-pub struct Signature {
-    #[serde(with = "BigArray")]
-    pub r: [u8; 72],
-    #[serde(with = "BigArray")]
-    pub s: [u8; 72],
+pub use crate::layout::key::Signature;
+
+/// The size of a buffer large enough for any DER encoded [`Signature`].
+pub const MAX_DER_LEN: usize = 3 + 2 * (2 + 1 + 72);
+
+/// Appends a little-endian unsigned integer as a DER INTEGER.
+fn der_integer(le: &[u8], out: &mut [u8], mut off: usize) -> usize {
+    let len = le.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    let pad = len == 0 || le[len - 1] & 0x80 != 0;
+    let content = len + pad as usize;
+
+    out[off] = 0x02;
+    out[off + 1] = content as u8;
+    off += 2;
+    if pad {
+        out[off] = 0;
+        off += 1;
+    }
+    for &b in le[..len].iter().rev() {
+        out[off] = b;
+        off += 1;
+    }
+
+    off
+}
+
+impl Signature {
+    /// Encodes the signature in DER into `buf`, without allocating.
+    pub fn write_der<'a>(&self, buf: &'a mut [u8; MAX_DER_LEN]) -> &'a [u8] {
+        // Leave room for the longest SEQUENCE header, then move it in place.
+        let end = der_integer(&self.r, buf, 3);
+        let end = der_integer(&self.s, buf, end);
+        let content = end - 3;
+
+        let start = if content < 0x80 {
+            buf[1] = 0x30;
+            buf[2] = content as u8;
+            1
+        } else {
+            buf[0] = 0x30;
+            buf[1] = 0x81;
+            buf[2] = content as u8;
+            0
+        };
+
+        &buf[start..end]
+    }
 }
 
+#[cfg(feature = "openssl")]
 impl From<ecdsa::EcdsaSig> for Signature {
     #[inline]
     fn from(value: ecdsa::EcdsaSig) -> Self {
@@ -28,6 +68,7 @@ impl From<ecdsa::EcdsaSig> for Signature {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<&[u8]> for Signature {
     type Error = Error;
 
@@ -37,6 +78,7 @@ impl TryFrom<&[u8]> for Signature {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl TryFrom<&Signature> for ecdsa::EcdsaSig {
     type Error = Error;
 
@@ -53,7 +95,8 @@ impl TryFrom<&Signature> for Vec<u8> {
 
     #[inline]
     fn try_from(value: &Signature) -> Result<Self> {
-        Ok(ecdsa::EcdsaSig::try_from(value)?.to_der()?)
+        let mut buf = [0u8; MAX_DER_LEN];
+        Ok(value.write_der(&mut buf).to_vec())
     }
 }
 
@@ -65,3 +108,27 @@ impl Default for Signature {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn write_der() {
+        let mut sig = Signature::default();
+        for (i, b) in sig.r[..32].iter_mut().enumerate() {
+            *b = i as u8 + 0x70;
+        }
+        sig.s[0] = 0x01;
+
+        let mut buf = [0u8; MAX_DER_LEN];
+        let expected = ecdsa::EcdsaSig::try_from(&sig).unwrap().to_der().unwrap();
+        assert_eq!(sig.write_der(&mut buf), &expected[..]);
+
+        sig.r = [0xff; 72];
+        sig.s = [0xff; 72];
+        let expected = ecdsa::EcdsaSig::try_from(&sig).unwrap().to_der().unwrap();
+        assert_eq!(sig.write_der(&mut buf), &expected[..]);
+    }
+}
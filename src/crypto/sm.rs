@@ -4,20 +4,29 @@
 
 //! Interfaces for GuoMi that is not supported on rust-openssl.
 
-use crate::crypto::key::{ecc, group};
+use crate::crypto::key::ecc;
+#[cfg(feature = "openssl")]
+use crate::crypto::key::group;
+#[cfg(feature = "openssl")]
 use libc::*;
+#[cfg(feature = "openssl")]
 use openssl::nid;
+#[cfg(feature = "openssl")]
 use openssl_sys::*;
+use std::io::Result;
+#[cfg(feature = "openssl")]
 use std::{
-    io::{Error, ErrorKind, Result},
+    io::{Error, ErrorKind},
     ptr,
 };
 
-#[cfg(ossl111)]
+#[cfg(all(feature = "openssl", ossl111))]
 pub const EVP_PKEY_CTRL_SET1_ID: c_int = EVP_PKEY_ALG_CTRL + 11;
 
+#[cfg(feature = "openssl")]
 const ECDH_KDF_MAX: size_t = 1 << 30;
 
+#[cfg(feature = "openssl")]
 extern "C" {
     #[cfg(ossl111)]
     pub fn EVP_PKEY_set_alias_type(pkey: *mut EVP_PKEY, ttype: c_int) -> c_int;
@@ -35,7 +44,7 @@ extern "C" {
     pub fn BN_bn2binpad(a: *const BIGNUM, to: *mut c_uchar, tolen: c_int) -> c_int;
 }
 
-#[cfg(ossl111)]
+#[cfg(all(feature = "openssl", ossl111))]
 #[allow(non_snake_case)]
 pub unsafe fn EVP_PKEY_CTX_set1_id(
     ctx: *mut EVP_PKEY_CTX,
@@ -57,35 +66,48 @@ pub struct SM2 {}
 
 impl SM2 {
     /// use SM2 algorithm to verify a msg's signature
-    pub fn verify(
-        ecc_pubkey: ecc::PubKey,
+    pub fn verify(ecc_pubkey: ecc::PubKey, sig: &[u8], id: &[u8], msg: &[u8]) -> Result<bool> {
+        let pubkey_size = ecc_pubkey.g.size()?;
+
+        // The coordinates are stored little-endian, OpenSSL wants big-endian.
+        let (mut pub_x, mut pub_y) = ([0u8; 72], [0u8; 72]);
+        for (dst, src) in pub_x
+            .iter_mut()
+            .zip(ecc_pubkey.x[..pubkey_size].iter().rev())
+        {
+            *dst = *src;
+        }
+        for (dst, src) in pub_y
+            .iter_mut()
+            .zip(ecc_pubkey.y[..pubkey_size].iter().rev())
+        {
+            *dst = *src;
+        }
+
+        crate::crypto::gm::sm2_verify(&pub_x[..pubkey_size], &pub_y[..pubkey_size], id, msg, sig)
+    }
+
+    /// Verifies with OpenSSL, given the big-endian coordinates of the key.
+    #[cfg(all(feature = "openssl", not(feature = "rust_crypto")))]
+    pub(crate) fn verify_be(
+        pub_x: &[u8],
+        pub_y: &[u8],
+        id: &[u8],
+        msg: &[u8],
         sig: &[u8],
-        id: &Vec<u8>,
-        msg: &Vec<u8>,
     ) -> Result<bool> {
         let mut verify_result = false;
-        let pubkey_size = ecc_pubkey.g.size()?;
 
         unsafe {
             let eckey = EC_KEY_new_by_curve_name(NID_sm2);
-            let pub_x = &ecc_pubkey.x[..pubkey_size]
-                .iter()
-                .rev()
-                .cloned()
-                .collect::<Vec<_>>();
-            let pub_y = &ecc_pubkey.y[..pubkey_size]
-                .iter()
-                .rev()
-                .cloned()
-                .collect::<Vec<_>>();
             let bn_x = BN_bin2bn(
                 pub_x.as_ptr() as *const c_uchar,
-                pubkey_size as c_int,
+                pub_x.len() as c_int,
                 ptr::null_mut(),
             );
             let bn_y = BN_bin2bn(
                 pub_y.as_ptr() as *const c_uchar,
-                pubkey_size as c_int,
+                pub_y.len() as c_int,
                 ptr::null_mut(),
             );
             EC_KEY_set_public_key_affine_coordinates(eckey, bn_x, bn_y);
@@ -117,6 +139,7 @@ impl SM2 {
         Ok(verify_result)
     }
 
+    #[cfg(feature = "openssl")]
     pub fn generate(group: group::Group) -> Result<(ecc::PubKey, *mut EC_KEY)> {
         let value: nid::Nid = group.try_into()?;
         let mut qx: Vec<u8> = vec![0; 32];
@@ -163,13 +186,22 @@ impl SM2 {
         Ok((pubkey, eckey))
     }
 
+    #[cfg(feature = "openssl")]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn sign(pri_key: *mut EC_KEY, id: &Vec<u8>, data: &Vec<u8>) -> Result<Vec<u8>> {
         let r = unsafe {
             let pkey = EVP_PKEY_new();
             if pkey.is_null() {
                 return Err(ErrorKind::InvalidData.into());
             }
+            // The EVP_PKEY takes over a reference, keep the caller's one
+            // valid so that the key can sign again.
+            if EC_KEY_up_ref(pri_key) != 1 {
+                EVP_PKEY_free(pkey);
+                return Err(Error::new(ErrorKind::InvalidData, "EC_KEY_up_ref failed"));
+            }
             if EVP_PKEY_assign(pkey, EVP_PKEY_SM2, pri_key as *mut c_void) <= 0 {
+                EC_KEY_free(pri_key);
                 EVP_PKEY_free(pkey);
                 return Err(Error::new(ErrorKind::InvalidData, "EVP_PKEY_assign failed"));
             }
@@ -199,6 +231,7 @@ impl SM2 {
     }
 
     /// KDF function of ecdh
+    #[cfg(feature = "openssl")]
     pub fn ecdh_kdf_x9_63(out: &mut [u8], input: &[u8]) -> Result<()> {
         let mut outlen = out.len();
         let mut buf = &mut out[..];
@@ -260,6 +293,7 @@ impl SM2 {
     }
 
     /// use SM2 algorithm to encrypt data with pubKey
+    #[cfg(feature = "openssl")]
     pub fn encrypt(data: &[u8], pub_key: ecc::PubKey) -> Result<Vec<u8>> {
         let pubkey_size = pub_key.g.size()?;
         let mut ciphertext_buf: Vec<u8> = Vec::new();
@@ -277,12 +311,12 @@ impl SM2 {
                 .collect::<Vec<_>>();
             let bn_x = BN_bin2bn(
                 pub_x.as_ptr() as *const c_uchar,
-                pubkey_size as c_int,
+                pub_x.len() as c_int,
                 ptr::null_mut(),
             );
             let bn_y = BN_bin2bn(
                 pub_y.as_ptr() as *const c_uchar,
-                pubkey_size as c_int,
+                pub_y.len() as c_int,
                 ptr::null_mut(),
             );
             EC_KEY_set_public_key_affine_coordinates(eckey, bn_x, bn_y);
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+#[cfg(feature = "openssl")]
 use openssl::error::ErrorStack;
 use std::{
     convert::From,
@@ -159,6 +160,159 @@ pub enum Indeterminate<T: Debug> {
     Unknown,
 }
 
+impl Error {
+    /// Returns the firmware error wrapped in `error`, if any, e.g. to tell
+    /// an [`Error::InvalidPlatformState`] from an
+    /// [`Error::InvalidCertificate`] once converted to an [`io::Error`].
+    pub fn find(error: &io::Error) -> Option<&Error> {
+        error.get_ref()?.downcast_ref()
+    }
+
+    /// Returns the status code the firmware reports this error with, or
+    /// `None` for errors raised above the firmware.
+    ///
+    /// ```
+    /// # use csv_rs::error::Error;
+    /// assert_eq!(Error::PolicyFailure.code(), Some(0x07));
+    /// assert_eq!(Error::Unknown.code(), None);
+    /// ```
+    pub fn code(&self) -> Option<u32> {
+        Some(match self {
+            Error::InvalidPlatformState => 0x01,
+            Error::InvalidGuestState => 0x02,
+            Error::InvalidConfig => 0x03,
+            Error::InvalidLen => 0x04,
+            Error::AlreadyOwned => 0x05,
+            Error::InvalidCertificate => 0x06,
+            Error::PolicyFailure => 0x07,
+            Error::Inactive => 0x08,
+            Error::InvalidAddress => 0x09,
+            Error::BadSignature => 0x0A,
+            Error::BadMeasurement => 0x0B,
+            Error::AsidOwned => 0x0C,
+            Error::InvalidAsid => 0x0D,
+            Error::WbinvdRequired => 0x0E,
+            Error::DfFlushRequired => 0x0F,
+            Error::InvalidGuest => 0x10,
+            Error::InvalidCommand => 0x11,
+            Error::Active => 0x12,
+            Error::HardwarePlatform => 0x13,
+            Error::HardwareUnsafe => 0x14,
+            Error::Unsupported => 0x15,
+            Error::InvalidParam => 0x16,
+            Error::ResourceLimit => 0x17,
+            Error::SecureDataInvalid => 0x18,
+            Error::InvalidPageSize => 0x19,
+            Error::InvalidPageState => 0x1A,
+            Error::InvalidMdataEntry => 0x1B,
+            Error::InvalidPageOwner => 0x1C,
+            Error::AEADOFlow => 0x1D,
+            Error::RbModeExited => 0x1F,
+            Error::RMPInitRequired => 0x20,
+            Error::BadSvn => 0x21,
+            Error::BadVersion => 0x22,
+            Error::ShutdownRequired => 0x23,
+            Error::UpdateFailed => 0x24,
+            Error::RestoreRequired => 0x25,
+            Error::RMPInitFailed => 0x26,
+            Error::InvalidKey => 0x27,
+            Error::IoError(_) | Error::Unknown => return None,
+        })
+    }
+
+    /// Returns the likely cause of a firmware error and what an operator
+    /// can do about it, if known.
+    ///
+    /// ```
+    /// # use csv_rs::error::Error;
+    /// assert!(Error::InvalidPlatformState.hint().is_some());
+    /// ```
+    pub fn hint(&self) -> Option<&'static str> {
+        Some(match self {
+            Error::InvalidPlatformState => {
+                "the command is not allowed in the current platform state, see \
+                 Firmware::platform_status; PEK_GEN and PEK_CERT_IMPORT are \
+                 refused while guests are running (Working), and a \
+                 platform_reset is refused in that state too"
+            }
+            Error::InvalidGuestState => {
+                "the guest is not in the state the command expects; commands \
+                 must follow the LAUNCH_START, LAUNCH_UPDATE_DATA, \
+                 LAUNCH_MEASURE, LAUNCH_FINISH order"
+            }
+            Error::InvalidLen => {
+                "a buffer is too small; retry with the length reported by the \
+                 firmware"
+            }
+            Error::AlreadyOwned => {
+                "the platform already has an owner; reset the platform with \
+                 Firmware::platform_reset before taking ownership again"
+            }
+            Error::InvalidCertificate => {
+                "a certificate was rejected; check that it is signed by the \
+                 expected key and matches the platform"
+            }
+            Error::PolicyFailure => {
+                "the guest policy forbids the command, e.g. debugging or \
+                 migration"
+            }
+            Error::Inactive => {
+                "the guest is not bound to an ASID; KVM activates it at \
+                 LAUNCH_START and deactivates it when the VM is destroyed, so \
+                 issue LAUNCH_START first and keep the VM file descriptor open"
+            }
+            Error::BadSignature => {
+                "a signature or MAC did not verify; check that the session and \
+                 certificates belong to this platform"
+            }
+            Error::BadMeasurement => {
+                "the launch measurement does not match; check the guest image \
+                 and the launch secret"
+            }
+            Error::AsidOwned => {
+                "the ASID is in use; KVM releases it when the VM holding it is \
+                 destroyed"
+            }
+            Error::InvalidAsid => "the ASID is out of range for the platform, see Asids::query",
+            Error::WbinvdRequired => "run WBINVD on all cores, then retry",
+            Error::DfFlushRequired => "issue DF_FLUSH, then retry",
+            Error::InvalidGuest => "the guest handle is unknown; launch the guest first",
+            Error::InvalidCommand => {
+                "the firmware does not implement the command; check the \
+                 firmware version"
+            }
+            Error::Active => {
+                "the guest is still bound to its ASID; KVM only deactivates it \
+                 when the VM is destroyed"
+            }
+            Error::HardwarePlatform => "a transient hardware condition occurred; retry",
+            Error::HardwareUnsafe => {
+                "a hardware condition occurred; do not reuse the parameter \
+                 buffers and reset the platform"
+            }
+            Error::Unsupported => {
+                "the feature is not supported by this firmware; update it or \
+                 check the platform capabilities"
+            }
+            Error::ResourceLimit => {
+                "the firmware ran out of resources; decommission unused guests, \
+                 then retry"
+            }
+            Error::SecureDataInvalid => {
+                "an integrity check failed; the data may have been tampered \
+                 with or the platform keys changed"
+            }
+            Error::RbModeExited => "retry the command",
+            Error::BadSvn | Error::BadVersion => {
+                "the firmware image is older than the committed one and cannot \
+                 be installed"
+            }
+            Error::RestoreRequired => "reinstall the committed firmware image",
+            _ => return None,
+        })
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let err_description = match self {
@@ -231,6 +385,7 @@ impl From<io::Error> for Error {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl From<ErrorStack> for Error {
     #[inline]
     fn from(error: ErrorStack) -> Error {
@@ -251,6 +406,16 @@ impl Display for Indeterminate<Error> {
     }
 }
 
+impl Indeterminate<Error> {
+    /// Returns the remediation hint of a known error, see [`Error::hint`].
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Indeterminate::Known(error) => error.hint(),
+            Indeterminate::Unknown => None,
+        }
+    }
+}
+
 impl From<io::Error> for Indeterminate<Error> {
     #[inline]
     fn from(error: io::Error) -> Indeterminate<Error> {
@@ -315,3 +480,139 @@ impl From<Indeterminate<Error>> for io::Error {
         }
     }
 }
+
+/// A failure to decode a structure, locating the offending field.
+///
+/// Decoders of nested structures report the path of the field from the
+/// outermost structure, e.g. `cek.sigs[0]`, and its offset from the start
+/// of the input. It is returned wrapped in an [`io::Error`] of the same kind
+/// as the underlying failure, see [`DecodeError::find`].
+#[derive(Debug)]
+pub struct DecodeError {
+    /// The path of the field.
+    pub field: String,
+
+    /// The offset of the field from the start of the input, in bytes.
+    pub offset: u64,
+
+    /// The failure to decode the field.
+    pub source: io::Error,
+}
+
+impl DecodeError {
+    /// Returns the decoding failure wrapped in `error`, if any.
+    pub fn find(error: &io::Error) -> Option<&DecodeError> {
+        error.get_ref()?.downcast_ref()
+    }
+
+    /// Locates `error`, raised while decoding the field `name` found at
+    /// `offset`.
+    ///
+    /// Errors already located within the field are nested under it.
+    pub(crate) fn locate(error: io::Error, name: &str, offset: u64) -> io::Error {
+        let kind = error.kind();
+
+        let located = if DecodeError::find(&error).is_some() {
+            let inner = error
+                .into_inner()
+                .and_then(|e| e.downcast::<DecodeError>().ok())
+                .unwrap();
+            let separator = if inner.field.starts_with('[') {
+                ""
+            } else {
+                "."
+            };
+            DecodeError {
+                field: format!("{name}{separator}{}", inner.field),
+                offset: offset + inner.offset,
+                source: inner.source,
+            }
+        } else {
+            DecodeError {
+                field: name.to_string(),
+                offset,
+                source: error,
+            }
+        };
+
+        io::Error::new(kind, located)
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}: {}", self.field, self.offset, self.source)
+    }
+}
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// A failure to verify a link of a certificate chain, naming the link.
+///
+/// It is returned wrapped in an [`io::Error`] of the same kind as the
+/// underlying failure, see [`ChainError::find`].
+#[derive(Debug)]
+pub struct ChainError {
+    /// The link which failed to verify.
+    pub link: crate::certs::Link,
+
+    /// The failure to verify the link.
+    pub source: io::Error,
+}
+
+impl ChainError {
+    /// Returns the chain failure wrapped in `error`, if any.
+    pub fn find(error: &io::Error) -> Option<&ChainError> {
+        error.get_ref()?.downcast_ref()
+    }
+
+    /// Names the `link` which failed with `error`.
+    pub(crate) fn locate(error: io::Error, link: crate::certs::Link) -> io::Error {
+        io::Error::new(
+            error.kind(),
+            ChainError {
+                link,
+                source: error,
+            },
+        )
+    }
+}
+
+impl Display for ChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} link: {}", self.link, self.source)
+    }
+}
+
+impl error::Error for ChainError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn firmware_codes() {
+        for code in (0x01..=0x27).filter(|&code| code != 0x1E) {
+            let Indeterminate::Known(error) = Indeterminate::<Error>::from(code) else {
+                panic!("unmapped status code {code:#x}");
+            };
+            assert_eq!(error.code(), Some(code));
+        }
+        assert!(matches!(
+            Indeterminate::<Error>::from(0x1E),
+            Indeterminate::Unknown
+        ));
+
+        let err = io::Error::from(Indeterminate::Known(Error::AlreadyOwned));
+        assert!(matches!(Error::find(&err), Some(Error::AlreadyOwned)));
+        assert!(Error::find(&io::Error::other("elsewhere")).is_none());
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Evidence as an Entity Attestation Token (RFC 9711), for RATS verifiers
+//! handling several TEE types.
+//!
+//! The token is an Unprotected CBOR Web Token claims set (CBOR tag 601),
+//! not wrapped in a COSE envelope: nothing protects the token itself. Only
+//! the report and chain claims carry signed data, and the claims derived
+//! from them are checked once that evidence verified. The claims are:
+//!
+//! | key    | claim          | value                                          |
+//! |--------|----------------|------------------------------------------------|
+//! | 10     | eat_nonce      | the report data, unmasked                      |
+//! | 6      | iat            | when the report was collected, advisory        |
+//! | 263    | dbgstat        | 3 (disabled permanently) with NODBG, else 0    |
+//! | 265    | eat_profile    | [`PROFILE`]                                    |
+//! | -75001 | chip id        | the serial number of the chip                  |
+//! | -75002 | measure        | the launch digest, unmasked                    |
+//! | -75003 | policy         | the guest policy, unmasked                     |
+//! | -75004 | vm id          | the guest owner assigned VM identifier         |
+//! | -75005 | vm version     | the guest owner assigned VM version            |
+//! | -75006 | user pubkey    | the digest of the guest owner public key       |
+//! | -75007 | report         | the report followed by its restored signer     |
+//! | -75008 | chain          | the HSK and CEK certificates                   |
+//!
+//! The CSV specific claims use private keys. The last two carry the
+//! evidence itself, so [`decode`] gives back evidence to verify: the
+//! other claims are only trustworthy once it verified. `iat` is covered by
+//! no signature, so it is never trustworthy; freshness comes from a nonce
+//! in the report data.
+
+use super::Evidence;
+use crate::{
+    api::guest::{AttestationReport, ReportSigner},
+    certs::HskCek,
+    pod,
+};
+
+use ciborium::value::{Integer, Value};
+use codicon::{Decoder, Encoder};
+use std::{
+    io::{Error, ErrorKind, Result},
+    mem::size_of,
+    time::UNIX_EPOCH,
+};
+
+/// The EAT profile of CSV evidence.
+pub const PROFILE: &str = "tag:openanolis.cn,2024:csv-rs/eat";
+
+/// The CBOR tag of an Unprotected CWT Claims Set.
+pub const UCCS_TAG: u64 = 601;
+
+/// The `iat` claim, in seconds since the epoch. Unsigned, so advisory.
+pub const IAT: i64 = 6;
+
+/// The `eat_nonce` claim.
+pub const EAT_NONCE: i64 = 10;
+
+/// The `dbgstat` claim.
+pub const DBGSTAT: i64 = 263;
+
+/// The `eat_profile` claim.
+pub const EAT_PROFILE: i64 = 265;
+
+/// The serial number of the chip, a text string.
+pub const CHIP_ID: i64 = -75001;
+
+/// The launch digest, 32 bytes.
+pub const MEASURE: i64 = -75002;
+
+/// The guest policy, an unsigned integer.
+pub const POLICY: i64 = -75003;
+
+/// The VM identifier, 16 bytes.
+pub const VM_ID: i64 = -75004;
+
+/// The VM version, 16 bytes.
+pub const VM_VERSION: i64 = -75005;
+
+/// The digest of the guest owner public key, 32 bytes.
+pub const USER_PUBKEY_DIGEST: i64 = -75006;
+
+/// The report followed by its restored signer.
+pub const REPORT: i64 = -75007;
+
+/// The HSK and CEK certificates, as served by the KDS.
+pub const CHAIN: i64 = -75008;
+
+/// The `dbgstat` of a guest which may be debugged.
+const DBGSTAT_ENABLED: u8 = 0;
+
+/// The `dbgstat` of a guest launched with NODBG.
+const DBGSTAT_DISABLED_PERMANENTLY: u8 = 3;
+
+fn invalid(e: impl ToString) -> Error {
+    Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+fn claim(key: i64, value: impl Into<Value>) -> (Value, Value) {
+    (Value::Integer(key.into()), value.into())
+}
+
+/// Maps evidence to EAT claims, without the UCCS tag, e.g. to embed them
+/// in a larger token.
+pub fn claims(evidence: &Evidence) -> Result<Value> {
+    let report = &evidence.report;
+    let policy = report.body.policy.xor(&report.anonce);
+
+    let mut chain = Vec::new();
+    evidence.chain.encode(&mut chain, ())?;
+
+    let iat = evidence
+        .collected_at
+        .duration_since(UNIX_EPOCH)
+        .map_err(invalid)?
+        .as_secs();
+    let dbgstat = match policy.nodbg() {
+        0 => DBGSTAT_ENABLED,
+        _ => DBGSTAT_DISABLED_PERMANENTLY,
+    };
+
+    Ok(Value::Map(vec![
+        claim(EAT_PROFILE, PROFILE),
+        claim(IAT, iat),
+        claim(EAT_NONCE, &report.unmask(report.body.report_data)[..]),
+        claim(DBGSTAT, dbgstat),
+        claim(CHIP_ID, evidence.chip_id.as_str()),
+        claim(MEASURE, &report.unmask(report.body.measure)[..]),
+        claim(POLICY, u32::from(policy)),
+        claim(VM_ID, &report.unmask(report.body.vm_id)[..]),
+        claim(VM_VERSION, &report.unmask(report.body.vm_version)[..]),
+        claim(
+            USER_PUBKEY_DIGEST,
+            &report.unmask(report.body.user_pubkey_digest)[..],
+        ),
+        claim(
+            REPORT,
+            [report.as_bytes(), pod::as_bytes(&evidence.signer)].concat(),
+        ),
+        claim(CHAIN, chain),
+    ]))
+}
+
+/// Encodes evidence as a tagged UCCS.
+pub fn encode(evidence: &Evidence) -> Result<Vec<u8>> {
+    let token = Value::Tag(UCCS_TAG, Box::new(claims(evidence)?));
+
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(&token, &mut bytes).map_err(invalid)?;
+    Ok(bytes)
+}
+
+/// Finds a claim in a claims set.
+fn find(claims: &[(Value, Value)], key: i64) -> Result<&Value> {
+    claims
+        .iter()
+        .find(|(k, _)| *k == Value::Integer(Integer::from(key)))
+        .map(|(_, v)| v)
+        .ok_or_else(|| invalid(format!("missing claim {key}")))
+}
+
+fn bytes(claims: &[(Value, Value)], key: i64) -> Result<&[u8]> {
+    find(claims, key)?
+        .as_bytes()
+        .map(Vec::as_slice)
+        .ok_or_else(|| invalid(format!("claim {key} is not a byte string")))
+}
+
+/// Decodes evidence from a UCCS, tagged or not, as produced by [`encode`].
+///
+/// Only the report and chain claims are read: the evidence is not
+/// verified, and the other claims are not checked against it. Its
+/// `collected_at` is the time of decoding, as the unsigned `iat` claim
+/// cannot be trusted.
+pub fn decode(token: &[u8]) -> Result<Evidence> {
+    let value: Value = ciborium::de::from_reader(token).map_err(invalid)?;
+    let value = match value {
+        Value::Tag(UCCS_TAG, value) => *value,
+        Value::Tag(tag, _) => return Err(invalid(format!("unexpected CBOR tag {tag}"))),
+        value => value,
+    };
+    let claims = value
+        .as_map()
+        .ok_or_else(|| invalid("the claims set is not a map"))?;
+
+    let report = bytes(claims, REPORT)?;
+    if report.len() != AttestationReport::SIZE + size_of::<ReportSigner>() {
+        return Err(invalid(format!(
+            "claim {REPORT} holds {} bytes",
+            report.len()
+        )));
+    }
+    let (report, signer) = report.split_at(AttestationReport::SIZE);
+    let report = AttestationReport::from_bytes(report)?;
+    let signer: ReportSigner = pod::from_bytes(signer)?;
+    let chain = HskCek::decode(bytes(claims, CHAIN)?, ())?;
+
+    Evidence::new(report, signer, chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    const REPORT_CERT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    fn evidence() -> Evidence {
+        super::super::hag::read(REPORT_CERT, HSK_CEK).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut evidence = evidence();
+        evidence.collected_at = UNIX_EPOCH + Duration::from_secs(1);
+        let token = encode(&evidence).unwrap();
+
+        let claims = claims(&evidence).unwrap();
+        let iat = find(claims.as_map().unwrap(), IAT).unwrap().as_integer();
+        assert_eq!(iat, Some(1.into()));
+
+        let before = SystemTime::now();
+        let decoded = decode(&token).unwrap();
+        decoded.verify().unwrap();
+        assert_eq!(decoded.report, evidence.report);
+        assert_eq!(decoded.chip_id, evidence.chip_id);
+        // The unsigned `iat` is not taken over.
+        assert!(decoded.collected_at >= before);
+    }
+
+    #[test]
+    fn standard_claims() {
+        let evidence = evidence();
+        let claims = claims(&evidence).unwrap();
+        let claims = claims.as_map().unwrap();
+
+        assert_eq!(find(claims, EAT_PROFILE).unwrap().as_text(), Some(PROFILE));
+        assert_eq!(
+            bytes(claims, EAT_NONCE).unwrap(),
+            evidence.report.unmask(evidence.report.body.report_data)
+        );
+        assert_eq!(
+            find(claims, CHIP_ID).unwrap().as_text(),
+            Some(evidence.chip_id.as_str())
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        decode(&[0xa0]).unwrap_err();
+        decode(b"not cbor").unwrap_err();
+
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&Value::Tag(18, Box::new(Value::Null)), &mut bytes).unwrap();
+        decode(&bytes).unwrap_err();
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The evidence files of HYGON's `hag` utility.
+//!
+//! `hag` stores a report as `report.cert`, the attestation report followed
+//! by the signer evidence exactly as the firmware returned them, masked
+//! with the report's anonce. The chip certificates are stored as
+//! `hsk_cek.cert`, or as separate `hsk.cert` and `cek.cert` files.
+//!
+//! The MAC of the signer evidence is keyed with the mnonce of the request,
+//! which the report echoes. Writing evidence back to `report.cert`
+//! therefore recomputes the MAC over the masked certificate and serial
+//! number, and a zero reserved field.
+
+use super::Evidence;
+use crate::{
+    api::guest::{AttestationReport, ReportSigner},
+    certs::HskCek,
+    crypto::gm,
+    error::Error,
+    pod,
+};
+
+use codicon::{Decoder, Encoder};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// The report file name.
+pub const REPORT_FILE: &str = "report.cert";
+
+/// The chip certificates file name.
+pub const HSK_CEK_FILE: &str = "hsk_cek.cert";
+
+/// The HSK certificate file name, when stored apart from the CEK.
+pub const HSK_FILE: &str = "hsk.cert";
+
+/// The CEK certificate file name, when stored apart from the HSK.
+pub const CEK_FILE: &str = "cek.cert";
+
+/// Reads evidence from a `report.cert` and an `hsk_cek.cert` file.
+///
+/// The signer MAC is verified against the mnonce echoed by the report and
+/// the signer is restored, as by [`ReportSigner::verify`]. The report
+/// signature and the certificates are not verified.
+pub fn read(mut report: impl Read, hsk_cek: impl Read) -> Result<Evidence, Error> {
+    let attestation: AttestationReport = pod::load(&mut report)?;
+    let mut signer: ReportSigner = pod::load(&mut report)?;
+
+    let mnonce = attestation.unmask(attestation.body.mnonce);
+    signer.verify(&mnonce, &attestation.body.mnonce, &attestation.anonce)?;
+
+    Ok(Evidence::new(
+        attestation,
+        signer,
+        HskCek::decode(hsk_cek, ())?,
+    )?)
+}
+
+/// Writes evidence as a `report.cert` and an `hsk_cek.cert` file.
+pub fn write(evidence: &Evidence, mut report: impl Write, hsk_cek: impl Write) -> io::Result<()> {
+    let anonce = evidence.report.anonce;
+    let mut signer = evidence.signer.clone();
+    AttestationReport::mask(anonce, &mut signer.pek_cert);
+    AttestationReport::mask(anonce, &mut signer.sn);
+    signer.reserved = [0u8; 32];
+
+    signer.mac = gm::hmac_sm3(
+        &evidence.report.unmask(evidence.report.body.mnonce),
+        &[&signer.pek_cert, &signer.sn, &signer.reserved],
+    )?;
+
+    pod::save(&mut report, &evidence.report)?;
+    pod::save(&mut report, &signer)?;
+    evidence.chain.encode(hsk_cek, ())
+}
+
+/// Reads evidence from a directory written by `hag`, falling back to
+/// separate HSK and CEK files without an `hsk_cek.cert`.
+pub fn load(dir: impl AsRef<Path>) -> Result<Evidence, Error> {
+    let dir = dir.as_ref();
+    let report = File::open(dir.join(REPORT_FILE))?;
+
+    match File::open(dir.join(HSK_CEK_FILE)) {
+        Ok(hsk_cek) => read(report, hsk_cek),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let hsk = File::open(dir.join(HSK_FILE))?;
+            let cek = File::open(dir.join(CEK_FILE))?;
+            read(report, hsk.chain(cek))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes evidence to `dir` as `hag` lays it out.
+pub fn save(evidence: &Evidence, dir: impl AsRef<Path>) -> io::Result<()> {
+    let dir = dir.as_ref();
+    write(
+        evidence,
+        File::create(dir.join(REPORT_FILE))?,
+        File::create(dir.join(HSK_CEK_FILE))?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    #[test]
+    fn roundtrip() {
+        let evidence = read(REPORT, HSK_CEK).unwrap();
+        assert_eq!(evidence.chip_id.as_str(), "NZA9T14052605");
+        evidence.verify().unwrap();
+
+        let (mut report, mut hsk_cek) = (Vec::new(), Vec::new());
+        write(&evidence, &mut report, &mut hsk_cek).unwrap();
+        assert_eq!(hsk_cek, HSK_CEK);
+        assert_eq!(report.len(), REPORT.len());
+        assert_eq!(report[..2084 + 336], REPORT[..2084 + 336]);
+
+        let again = read(&report[..], &hsk_cek[..]).unwrap();
+        assert_eq!(again.signer.pek_cert, evidence.signer.pek_cert);
+        again.verify().unwrap();
+
+        let mut tampered = REPORT.to_vec();
+        tampered[400] ^= 1;
+        assert!(matches!(
+            read(&tampered[..], HSK_CEK),
+            Err(Error::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        std::fs::write(dir.join(REPORT_FILE), REPORT).unwrap();
+        std::fs::write(dir.join(HSK_FILE), &HSK_CEK[..832]).unwrap();
+        std::fs::write(dir.join(CEK_FILE), &HSK_CEK[832..]).unwrap();
+        let evidence = load(dir).unwrap();
+
+        save(&evidence, dir).unwrap();
+        assert_eq!(std::fs::read(dir.join(HSK_CEK_FILE)).unwrap(), HSK_CEK);
+        load(dir).unwrap().verify().unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The JSON evidence payload used by the HYGON attestation service and
+//! the CSV attesters built on csv-rs.
+//!
+//! The payload carries the report along with the HSK, CEK and PEK
+//! certificates and the chip serial number:
+//!
+//! ```json
+//! {
+//!   "attestation_report": { "body": { ... }, "sig_usage": ..., ... },
+//!   "cert_chain": { "hsk": { ... }, "cek": { ... }, "pek": { ... } },
+//!   "serial_number": [78, 90, 65, ...]
+//! }
+//! ```
+//!
+//! The signer MAC is not part of the payload, so evidence decoded from it
+//! carries a zero MAC and the decoding time as its collection time.
+
+use super::Evidence;
+use crate::{
+    api::guest::{AttestationReport, ReportSigner},
+    certs::{ca, csv, HskCek},
+    pod,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    time::SystemTime,
+};
+
+/// The certificates of the payload.
+#[derive(Serialize, Deserialize)]
+pub struct CertificateChain {
+    /// The HYGON Signing Key certificate.
+    pub hsk: ca::Certificate,
+
+    /// The Chip Endorsement Key certificate.
+    pub cek: csv::Certificate,
+
+    /// The Platform Endorsement Key certificate which signed the report.
+    pub pek: csv::Certificate,
+}
+
+/// The evidence payload of the HYGON attestation service.
+#[derive(Serialize, Deserialize)]
+pub struct Payload {
+    /// The attestation report.
+    pub attestation_report: AttestationReport,
+
+    /// The certificates up to the PEK.
+    pub cert_chain: CertificateChain,
+
+    /// The NUL-padded serial number of the chip.
+    pub serial_number: Vec<u8>,
+}
+
+impl Payload {
+    /// Parses a payload from JSON.
+    pub fn from_json(reader: impl Read) -> Result<Self> {
+        serde_json::from_reader(reader).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the payload as JSON.
+    pub fn to_json(&self, writer: impl Write) -> Result<()> {
+        serde_json::to_writer(writer, self).map_err(Error::other)
+    }
+}
+
+impl TryFrom<Evidence> for Payload {
+    type Error = Error;
+
+    fn try_from(evidence: Evidence) -> Result<Self> {
+        Ok(Self {
+            cert_chain: CertificateChain {
+                hsk: evidence.chain.hsk,
+                cek: evidence.chain.cek,
+                pek: evidence.signer.pek()?,
+            },
+            serial_number: evidence.signer.sn.to_vec(),
+            attestation_report: evidence.report,
+        })
+    }
+}
+
+impl TryFrom<Payload> for Evidence {
+    type Error = Error;
+
+    fn try_from(payload: Payload) -> Result<Self> {
+        let mut signer = ReportSigner::default();
+
+        let pek = pod::as_bytes(&payload.cert_chain.pek);
+        signer.pek_cert[..pek.len()].copy_from_slice(pek);
+
+        if payload.serial_number.len() > signer.sn.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "serial number is too long",
+            ));
+        }
+        signer.sn[..payload.serial_number.len()].copy_from_slice(&payload.serial_number);
+
+        Ok(Self {
+            chip_id: signer.chip_id()?,
+            report: payload.attestation_report,
+            signer,
+            chain: HskCek {
+                hsk: payload.cert_chain.hsk,
+                cek: payload.cert_chain.cek,
+            },
+            collected_at: SystemTime::now(),
+        })
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A canonical bundle of attestation evidence, collected on guests and
+//! validated by verifiers.
+
+#[cfg(feature = "eat")]
+pub mod eat;
+pub mod hag;
+pub mod hygon;
+mod scheduler;
+pub use scheduler::{AttestationScheduler, Change};
+
+use crate::{
+    api::guest::{AttestationReport, CsvGuest, ReportSigner},
+    certs::HskCek,
+    error::Error,
+    verifier::Verifier,
+    ChipId,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{io, time::SystemTime};
+
+/// An attestation report along with everything needed to verify it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Evidence {
+    /// The attestation report.
+    pub report: AttestationReport,
+
+    /// The signer evidence, with the PEK certificate and serial number
+    /// already restored by [`ReportSigner::verify`].
+    pub signer: ReportSigner,
+
+    /// The HSK and CEK certificates of the chip which signed the report.
+    pub chain: HskCek,
+
+    /// When the report was requested.
+    pub collected_at: SystemTime,
+
+    /// The chip which signed the report.
+    pub chip_id: ChipId,
+}
+
+impl Evidence {
+    /// Bundles a report, its restored signer evidence and the chip's
+    /// certificates.
+    pub fn new(report: AttestationReport, signer: ReportSigner, chain: HskCek) -> io::Result<Self> {
+        Ok(Self {
+            chip_id: signer.chip_id()?,
+            report,
+            signer,
+            chain,
+            collected_at: SystemTime::now(),
+        })
+    }
+
+    /// Requests a fresh report from the guest device and bundles it.
+    pub fn collect(
+        guest: &CsvGuest,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+        chain: HskCek,
+    ) -> Result<Self, Error> {
+        let (report, signer) = guest.get_report(data, mnonce)?;
+        Ok(Self::new(report, signer, chain)?)
+    }
+
+    /// Verifies the evidence against the builtin HYGON root key.
+    pub fn verify(&self) -> io::Result<()> {
+        Verifier::new()?.verify(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pod,
+        verifier::{ChainCache, Tcb},
+    };
+    use codicon::Decoder;
+    use std::sync::Arc;
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    fn evidence() -> Evidence {
+        let (report, signer) = REPORT.split_at(AttestationReport::SIZE);
+        let report = AttestationReport::from_bytes(report).unwrap();
+        let mut signer: ReportSigner = pod::from_bytes(signer).unwrap();
+
+        let mnonce = report.unmask(report.body.mnonce);
+        signer
+            .verify(&mnonce, &report.body.mnonce, &report.anonce)
+            .unwrap();
+
+        let chain = HskCek::decode(HSK_CEK, ()).unwrap();
+        Evidence::new(report, signer, chain).unwrap()
+    }
+
+    #[test]
+    fn verify() {
+        let evidence = evidence();
+        assert_eq!(evidence.chip_id.as_str(), "NZA9T14052605");
+        evidence.verify().unwrap();
+    }
+
+    #[test]
+    fn accept() {
+        let verified = Verifier::new().unwrap().accept(evidence()).unwrap();
+        assert_eq!(verified.chip_id().as_str(), "NZA9T14052605");
+        assert_eq!(
+            verified.measure(),
+            verified.report().unmask(evidence().report.body.measure)
+        );
+
+        let mut evidence = evidence();
+        evidence.report.body.measure[0] ^= 1;
+        Verifier::new().unwrap().accept(evidence).unwrap_err();
+
+        // Nothing ties the report to a HYGON chip.
+        let err = Verifier::empty()
+            .step(crate::verifier::SignerChipId)
+            .accept(self::evidence())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn verify_chip_id_mismatch() {
+        let mut evidence = evidence();
+        evidence.chip_id = "NZA0000000000".parse().unwrap();
+        evidence.verify().unwrap_err();
+    }
+
+    #[test]
+    fn verify_pinned_chip() {
+        let evidence = evidence();
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = || crate::kds::Cache::new(tmp.path());
+        let chip_id: ChipId = "NZA9T14052605".parse().unwrap();
+        let verifier = || Verifier::new().unwrap();
+
+        // Nothing is known of the chip yet.
+        let err = verifier()
+            .chip_id(chip_id.clone(), cache())
+            .verify(&evidence)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        cache().put(&chip_id, &evidence.chain).unwrap();
+        verifier()
+            .chip_id(chip_id.clone(), cache())
+            .cek(evidence.chain.cek)
+            .verify(&evidence)
+            .unwrap();
+
+        // Relabelled as coming from another chip, as a relay forging the
+        // signer serial number would.
+        let other: ChipId = "NZA0000000000".parse().unwrap();
+        let mut chain = evidence.chain;
+        chain.cek.body.data.user_id[0] ^= 1;
+        cache().put(&other, &chain).unwrap();
+        let mut relabelled = evidence.clone();
+        relabelled.chip_id = other.clone();
+        let err = Verifier::empty()
+            .chip_id(other, cache())
+            .verify(&relabelled)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn verify_chip_chain() {
+        let evidence = evidence();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache = crate::kds::Cache::new(dir);
+        let verifier = || {
+            Verifier::new()
+                .unwrap()
+                .chip_chain(crate::kds::Cache::new(dir))
+        };
+
+        let err = verifier().verify(&evidence).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        cache.put(&evidence.chip_id, &evidence.chain).unwrap();
+        verifier().verify(&evidence).unwrap();
+
+        // The valid chain of another chip.
+        let mut other = evidence.chain;
+        other.cek.body.data.user_id[0] ^= 1;
+        cache.put(&evidence.chip_id, &other).unwrap();
+        let err = verifier().verify(&evidence).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        // Relabelled as coming from the chip that chain belongs to, while
+        // the signer still names the chip it was produced on.
+        cache.put(&evidence.chip_id, &evidence.chain).unwrap();
+        let mut relabelled = evidence.clone();
+        relabelled.chip_id = "NZA0000000000".parse().unwrap();
+        cache.put(&relabelled.chip_id, &other).unwrap();
+        let err = Verifier::empty()
+            .chip_chain(crate::kds::Cache::new(dir))
+            .verify(&relabelled)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn verify_min_tcb() {
+        let evidence = evidence();
+        let tcb = Tcb::of(&evidence).unwrap();
+        let verifier = || Verifier::new().unwrap();
+
+        verifier().min_tcb(tcb).verify(&evidence).unwrap();
+
+        let minimum = Tcb {
+            hsk: tcb.hsk + 1,
+            ..tcb
+        };
+        let err = verifier().min_tcb(minimum).verify(&evidence).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn verify_cached() {
+        let mut evidence = evidence();
+        let cache = Arc::new(ChainCache::new(16));
+        let verifier = Verifier::with_cache(cache.clone()).unwrap();
+
+        verifier.verify(&evidence).unwrap();
+        verifier.verify(&evidence).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // The report signature is still checked for cached chains.
+        evidence.report.sig.r[0] ^= 1;
+        verifier.verify(&evidence).unwrap_err();
+    }
+
+    #[test]
+    fn hygon_payload() {
+        let payload = hygon::Payload::try_from(evidence()).unwrap();
+        assert_eq!(&payload.serial_number[..13], b"NZA9T14052605");
+
+        let mut json = Vec::new();
+        payload.to_json(&mut json).unwrap();
+
+        let payload = hygon::Payload::from_json(&json[..]).unwrap();
+        let evidence = Evidence::try_from(payload).unwrap();
+        assert_eq!(evidence.chip_id.as_str(), "NZA9T14052605");
+        evidence.verify().unwrap();
+    }
+
+    #[test]
+    fn verify_custom_step() {
+        let evidence = evidence();
+        let verifier = Verifier::new().unwrap().insert(
+            0,
+            ("fresh", |evidence: &Evidence| match evidence.collected_at {
+                SystemTime::UNIX_EPOCH => Err(io::ErrorKind::TimedOut.into()),
+                _ => Ok(()),
+            }),
+        );
+        assert_eq!(
+            verifier.steps().collect::<Vec<_>>(),
+            ["fresh", "signature chain", "signer chip id"]
+        );
+        verifier.verify(&evidence).unwrap();
+
+        let evidence = Evidence {
+            collected_at: SystemTime::UNIX_EPOCH,
+            ..evidence
+        };
+        let err = verifier.verify(&evidence).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.to_string().starts_with("fresh: "));
+    }
+}
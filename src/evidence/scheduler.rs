@@ -0,0 +1,267 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Periodic collection and verification of evidence for long-running
+//! attested services.
+//!
+//! ```no_run
+//! # async fn run() -> std::io::Result<()> {
+//! use codicon::Decoder;
+//! use csv_rs::{
+//!     api::guest::CsvGuest,
+//!     certs::HskCek,
+//!     evidence::{AttestationScheduler, Evidence},
+//!     verifier::Verifier,
+//! };
+//! use std::{fs::File, io, time::Duration};
+//!
+//! // The certificates of the chip, as served by the KDS.
+//! let chain = HskCek::decode(File::open("hsk_cek.cert")?, ())?;
+//! let guest = CsvGuest::open()?;
+//! let collect = move || Evidence::collect(&guest, None, None, chain).map_err(io::Error::other);
+//! let interval = Duration::from_secs(600);
+//! let scheduler = AttestationScheduler::spawn(collect, Verifier::new()?, interval);
+//!
+//! let mut changes = scheduler.subscribe();
+//! while let Ok(change) = changes.recv().await {
+//!     println!("{change:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use super::Evidence;
+use crate::{
+    api::guest::GuestPolicy,
+    verifier::{VerifiedReport, Verifier},
+};
+
+use std::{io, sync::Arc, time::Duration};
+use tokio::{
+    sync::{broadcast, watch, Notify},
+    task::JoinHandle,
+    time::MissedTickBehavior,
+};
+
+/// The number of changes a slow subscriber may lag behind.
+const BACKLOG: usize = 16;
+
+/// A change observed by an [`AttestationScheduler`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The first evidence was verified.
+    Attested,
+
+    /// The launch measurement differs from the previous evidence.
+    Measurement {
+        previous: [u8; 32],
+        current: [u8; 32],
+    },
+
+    /// The guest policy differs from the previous evidence.
+    Policy {
+        previous: GuestPolicy,
+        current: GuestPolicy,
+    },
+
+    /// A refresh failed. The latest verified evidence is kept.
+    Failed(String),
+}
+
+/// Refreshes verified evidence in the background, on an interval or on
+/// demand.
+///
+/// Evidence is collected and verified on the blocking thread pool. The
+/// background task stops when the scheduler is dropped.
+pub struct AttestationScheduler {
+    refresh: Arc<Notify>,
+    latest: watch::Receiver<Option<Arc<VerifiedReport>>>,
+    changes: broadcast::Sender<Change>,
+    task: JoinHandle<()>,
+}
+
+impl AttestationScheduler {
+    /// Starts refreshing evidence every `interval`, beginning immediately.
+    ///
+    /// `collect` gathers fresh evidence, e.g. with [`Evidence::collect`],
+    /// which `verifier` then verifies.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime, or if `interval` is
+    /// zero.
+    pub fn spawn<F>(collect: F, verifier: Verifier, interval: Duration) -> Self
+    where
+        F: Fn() -> io::Result<Evidence> + Send + Sync + 'static,
+    {
+        let refresh = Arc::new(Notify::new());
+        let (latest_tx, latest) = watch::channel(None);
+        let (changes, _) = broadcast::channel(BACKLOG);
+
+        let task = tokio::spawn(run(
+            Arc::new(collect),
+            Arc::new(verifier),
+            interval,
+            refresh.clone(),
+            latest_tx,
+            changes.clone(),
+        ));
+
+        Self {
+            refresh,
+            latest,
+            changes,
+            task,
+        }
+    }
+
+    /// Refreshes the evidence now rather than at the next tick, which is
+    /// then postponed by a whole interval.
+    pub fn refresh(&self) {
+        self.refresh.notify_one();
+    }
+
+    /// The latest verified evidence, if any refresh succeeded yet.
+    pub fn latest(&self) -> Option<Arc<VerifiedReport>> {
+        self.latest.borrow().clone()
+    }
+
+    /// A receiver notified of each newly verified evidence.
+    pub fn watch(&self) -> watch::Receiver<Option<Arc<VerifiedReport>>> {
+        self.latest.clone()
+    }
+
+    /// Subscribes to the changes observed from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.changes.subscribe()
+    }
+}
+
+impl Drop for AttestationScheduler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run<F>(
+    collect: Arc<F>,
+    verifier: Arc<Verifier>,
+    interval: Duration,
+    refresh: Arc<Notify>,
+    latest: watch::Sender<Option<Arc<VerifiedReport>>>,
+    changes: broadcast::Sender<Change>,
+) where
+    F: Fn() -> io::Result<Evidence> + Send + Sync + 'static,
+{
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => (),
+            _ = refresh.notified() => ticker.reset(),
+        }
+
+        let (collect, verifier) = (collect.clone(), verifier.clone());
+        let verified = tokio::task::spawn_blocking(move || verifier.accept(collect()?))
+            .await
+            .map_err(io::Error::other)
+            .and_then(|result| result);
+
+        // Sending only fails without subscribers, which is fine.
+        match verified {
+            Ok(current) => {
+                for change in compare(latest.borrow().as_deref(), &current) {
+                    let _ = changes.send(change);
+                }
+                latest.send_replace(Some(Arc::new(current)));
+            }
+            Err(e) => {
+                let _ = changes.send(Change::Failed(e.to_string()));
+            }
+        }
+    }
+}
+
+/// The changes between two successive verified evidence.
+fn compare(previous: Option<&VerifiedReport>, current: &VerifiedReport) -> Vec<Change> {
+    let Some(previous) = previous else {
+        return vec![Change::Attested];
+    };
+
+    let mut changes = Vec::new();
+    if previous.measure() != current.measure() {
+        changes.push(Change::Measurement {
+            previous: previous.measure(),
+            current: current.measure(),
+        });
+    }
+    if previous.policy() != current.policy() {
+        changes.push(Change::Policy {
+            previous: previous.policy(),
+            current: current.policy(),
+        });
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::hag;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    #[tokio::test]
+    async fn refresh() {
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let counter = calls.clone();
+        let collect = move || {
+            // The second evidence is tampered with, the third collection
+            // fails and the fourth is the first evidence again.
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            if call == 2 {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            let mut evidence = hag::read(REPORT, HSK_CEK).map_err(io::Error::other)?;
+            if call == 1 {
+                evidence.report.body.measure[0] ^= 1;
+            }
+            Ok(evidence)
+        };
+
+        let verifier = Verifier::new().unwrap();
+        let scheduler = AttestationScheduler::spawn(collect, verifier, Duration::from_secs(3600));
+        let mut changes = scheduler.subscribe();
+        let mut latest = scheduler.watch();
+
+        assert_eq!(changes.recv().await.unwrap(), Change::Attested);
+        latest.wait_for(Option::is_some).await.unwrap();
+        let first = scheduler.latest().unwrap();
+
+        scheduler.refresh();
+        let failed = changes.recv().await.unwrap();
+        assert!(
+            matches!(&failed, Change::Failed(e) if e.starts_with("signature chain")),
+            "{failed:?}"
+        );
+
+        scheduler.refresh();
+        assert!(matches!(changes.recv().await.unwrap(), Change::Failed(_)));
+
+        // The same evidence again changes nothing, but is still published.
+        latest.borrow_and_update();
+        scheduler.refresh();
+        latest.changed().await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert!(changes.try_recv().is_err());
+        assert_eq!(scheduler.latest().unwrap().measure(), first.measure());
+        assert!(!Arc::ptr_eq(&scheduler.latest().unwrap(), &first));
+    }
+}
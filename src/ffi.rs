@@ -0,0 +1,400 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A C interface to fetching and verifying attestation reports.
+//!
+//! The functions below are exported with a stable C ABI when building with
+//! the `ffi` feature, e.g. as a shared library:
+//!
+//! ```sh
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! Their declarations are in `include/csv_rs.h`. Every function returns
+//! [`CSV_OK`] or a negative error code, and leaves a message describing
+//! the failure for [`csv_last_error`] on the calling thread.
+//!
+//! Evidence is exchanged as [`CSV_EVIDENCE_SIZE`] bytes: the attestation
+//! report followed by its signer, with the PEK certificate and serial number
+//! already restored, as in [`Evidence`].
+
+use crate::{
+    api::guest::{AttestationReport, CsvGuest, ReportSigner},
+    certs::HskCek,
+    error::Error,
+    evidence::Evidence,
+    pod,
+};
+
+use codicon::Decoder;
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    fmt::Display,
+    fs::File,
+    io::BufReader,
+    mem::size_of,
+    panic::{self, AssertUnwindSafe},
+    slice,
+};
+
+/// The call succeeded.
+pub const CSV_OK: c_int = 0;
+
+/// An argument is NULL, too short or malformed.
+pub const CSV_ERROR_INVALID_ARGUMENT: c_int = -1;
+
+/// The device or a file could not be accessed.
+pub const CSV_ERROR_IO: c_int = -2;
+
+/// The firmware rejected the request.
+pub const CSV_ERROR_FIRMWARE: c_int = -3;
+
+/// The evidence does not verify.
+pub const CSV_ERROR_VERIFICATION: c_int = -4;
+
+/// csv-rs panicked, which is a bug.
+pub const CSV_ERROR_PANIC: c_int = -5;
+
+/// The size of the evidence written by [`csv_get_attestation_report`].
+pub const CSV_EVIDENCE_SIZE: usize = AttestationReport::SIZE + size_of::<ReportSigner>();
+
+/// The HSK and CEK certificates of a chip, opaque to C.
+pub struct CsvHskCek(HskCek);
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Records `message` for [`csv_last_error`] and returns `code`.
+fn fail(code: c_int, message: impl Display) -> c_int {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    code
+}
+
+/// Runs `f`, turning panics into [`CSV_ERROR_PANIC`] as they must not
+/// unwind into C.
+fn guard(f: impl FnOnce() -> c_int) -> c_int {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| fail(CSV_ERROR_PANIC, "csv-rs panicked"))
+}
+
+/// The error code of a failed guest request.
+fn code(err: &Error) -> c_int {
+    match err {
+        Error::IoError(e) if Error::find(e).is_none() => CSV_ERROR_IO,
+        Error::BadSignature => CSV_ERROR_VERIFICATION,
+        _ => CSV_ERROR_FIRMWARE,
+    }
+}
+
+/// Copies `N` bytes from `ptr`, or returns `None` for NULL.
+unsafe fn array<const N: usize>(ptr: *const u8) -> Option<[u8; N]> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    let mut array = [0u8; N];
+    array.copy_from_slice(slice::from_raw_parts(ptr, N));
+    Some(array)
+}
+
+/// Returns the message of the last failure on the calling thread.
+///
+/// The string stays valid until the next call into csv-rs on the same
+/// thread, and is empty if nothing failed yet.
+#[no_mangle]
+pub extern "C" fn csv_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Requests an attestation report from the csv-guest device and writes the
+/// evidence to `out`.
+///
+/// `data` is the 64 bytes of user data bound into the report and `mnonce`
+/// the 16 bytes nonce of the request. Either may be NULL, for zeroes and
+/// a random nonce respectively.
+///
+/// # Safety
+///
+/// `data` and `mnonce` must be NULL or point to 64 and 16 readable bytes,
+/// and `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn csv_get_attestation_report(
+    data: *const u8,
+    mnonce: *const u8,
+    out: *mut u8,
+    out_len: usize,
+) -> c_int {
+    guard(|| {
+        if out.is_null() || out_len < CSV_EVIDENCE_SIZE {
+            return fail(
+                CSV_ERROR_INVALID_ARGUMENT,
+                format!("the output buffer must hold {CSV_EVIDENCE_SIZE} bytes"),
+            );
+        }
+
+        let result = CsvGuest::open()
+            .map_err(Error::from)
+            .and_then(|guest| guest.get_report(array(data), array(mnonce)));
+        let (report, signer) = match result {
+            Ok(evidence) => evidence,
+            Err(e) => return fail(code(&e), e),
+        };
+
+        let out = slice::from_raw_parts_mut(out, CSV_EVIDENCE_SIZE);
+        let (head, tail) = out.split_at_mut(AttestationReport::SIZE);
+        head.copy_from_slice(report.as_bytes());
+        tail.copy_from_slice(pod::as_bytes(&signer));
+        CSV_OK
+    })
+}
+
+/// Parses the HSK and CEK certificates of a chip, as served by the KDS,
+/// from `len` bytes at `bytes`.
+///
+/// On success, `*out` holds a chain to release with [`csv_hsk_cek_free`].
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn csv_hsk_cek_load(
+    bytes: *const u8,
+    len: usize,
+    out: *mut *mut CsvHskCek,
+) -> c_int {
+    guard(|| {
+        if bytes.is_null() || out.is_null() {
+            return fail(CSV_ERROR_INVALID_ARGUMENT, "NULL argument");
+        }
+
+        match HskCek::decode(slice::from_raw_parts(bytes, len), ()) {
+            Ok(chain) => {
+                *out = Box::into_raw(Box::new(CsvHskCek(chain)));
+                CSV_OK
+            }
+            Err(e) => fail(CSV_ERROR_INVALID_ARGUMENT, e),
+        }
+    })
+}
+
+/// Like [`csv_hsk_cek_load`], reading the certificates from the file at
+/// `path`.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn csv_hsk_cek_load_file(
+    path: *const c_char,
+    out: *mut *mut CsvHskCek,
+) -> c_int {
+    guard(|| {
+        if path.is_null() || out.is_null() {
+            return fail(CSV_ERROR_INVALID_ARGUMENT, "NULL argument");
+        }
+
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(e) => return fail(CSV_ERROR_INVALID_ARGUMENT, e),
+        };
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => return fail(CSV_ERROR_IO, format!("{path}: {e}")),
+        };
+
+        match HskCek::decode(BufReader::new(file), ()) {
+            Ok(chain) => {
+                *out = Box::into_raw(Box::new(CsvHskCek(chain)));
+                CSV_OK
+            }
+            Err(e) => fail(CSV_ERROR_INVALID_ARGUMENT, format!("{path}: {e}")),
+        }
+    })
+}
+
+/// Releases a chain returned by [`csv_hsk_cek_load`]. NULL is ignored.
+///
+/// # Safety
+///
+/// `chain` must be NULL or a chain not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn csv_hsk_cek_free(chain: *mut CsvHskCek) {
+    if !chain.is_null() {
+        drop(Box::from_raw(chain));
+    }
+}
+
+/// Verifies `len` bytes of evidence against `chain` and the builtin HYGON
+/// root key.
+///
+/// Returns [`CSV_OK`] if the evidence verifies, [`CSV_ERROR_VERIFICATION`]
+/// if it does not.
+///
+/// # Safety
+///
+/// `evidence` must point to `len` readable bytes and `chain` must be a
+/// chain returned by [`csv_hsk_cek_load`].
+#[no_mangle]
+pub unsafe extern "C" fn csv_verify_attestation_report(
+    evidence: *const u8,
+    len: usize,
+    chain: *const CsvHskCek,
+) -> c_int {
+    guard(|| {
+        if evidence.is_null() || chain.is_null() {
+            return fail(CSV_ERROR_INVALID_ARGUMENT, "NULL argument");
+        }
+        if len != CSV_EVIDENCE_SIZE {
+            return fail(
+                CSV_ERROR_INVALID_ARGUMENT,
+                format!("expected {CSV_EVIDENCE_SIZE} bytes of evidence, got {len}"),
+            );
+        }
+
+        let (report, signer) =
+            slice::from_raw_parts(evidence, len).split_at(AttestationReport::SIZE);
+        let parsed = AttestationReport::from_bytes(report).and_then(|report| {
+            let signer: ReportSigner = pod::from_bytes(signer)?;
+            Evidence::new(report, signer, (*chain).0)
+        });
+        let evidence = match parsed {
+            Ok(evidence) => evidence,
+            Err(e) => return fail(CSV_ERROR_INVALID_ARGUMENT, e),
+        };
+
+        match evidence.verify() {
+            Ok(()) => CSV_OK,
+            Err(e) => fail(CSV_ERROR_VERIFICATION, e),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::hag;
+    use std::ptr;
+
+    const HEADER: &str = include_str!("../include/csv_rs.h");
+
+    const REPORT: &[u8] = include_bytes!("../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../tests/test_data/hsk_cek.cert");
+
+    fn evidence() -> Vec<u8> {
+        let evidence = hag::read(REPORT, HSK_CEK).unwrap();
+        [evidence.report.as_bytes(), pod::as_bytes(&evidence.signer)].concat()
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(csv_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn verify() {
+        let mut chain = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                csv_hsk_cek_load(HSK_CEK.as_ptr(), HSK_CEK.len(), &mut chain),
+                CSV_OK
+            );
+
+            let mut evidence = evidence();
+            assert_eq!(
+                csv_verify_attestation_report(evidence.as_ptr(), evidence.len(), chain),
+                CSV_OK
+            );
+
+            evidence[0] ^= 1;
+            assert_eq!(
+                csv_verify_attestation_report(evidence.as_ptr(), evidence.len(), chain),
+                CSV_ERROR_VERIFICATION
+            );
+            assert!(!last_error().is_empty());
+
+            assert_eq!(
+                csv_verify_attestation_report(evidence.as_ptr(), 10, chain),
+                CSV_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                csv_verify_attestation_report(evidence.as_ptr(), evidence.len(), ptr::null()),
+                CSV_ERROR_INVALID_ARGUMENT
+            );
+
+            csv_hsk_cek_free(chain);
+        }
+    }
+
+    #[test]
+    fn load() {
+        let mut chain = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                csv_hsk_cek_load(HSK_CEK.as_ptr(), 100, &mut chain),
+                CSV_ERROR_INVALID_ARGUMENT
+            );
+            assert!(chain.is_null());
+
+            let path = CString::new("/nonexistent/hsk_cek.cert").unwrap();
+            assert_eq!(
+                csv_hsk_cek_load_file(path.as_ptr(), &mut chain),
+                CSV_ERROR_IO
+            );
+            assert!(last_error().contains("/nonexistent/hsk_cek.cert"));
+
+            let mut out = [0u8; 16];
+            assert_eq!(
+                csv_get_attestation_report(ptr::null(), ptr::null(), out.as_mut_ptr(), out.len()),
+                CSV_ERROR_INVALID_ARGUMENT
+            );
+        }
+    }
+
+    /// Evaluates the `#define` of `name` in the C header, a sum of
+    /// integers.
+    fn define(name: &str) -> i64 {
+        let line = HEADER
+            .lines()
+            .find_map(|line| {
+                line.strip_prefix("#define ")?
+                    .strip_prefix(name)?
+                    .strip_prefix(' ')
+            })
+            .unwrap_or_else(|| panic!("{name} is not defined"));
+        line.trim_matches(|c| c == '(' || c == ')')
+            .split('+')
+            .map(|term| term.trim().parse::<i64>().unwrap())
+            .sum()
+    }
+
+    #[test]
+    fn header() {
+        for (name, code) in [
+            ("CSV_OK", CSV_OK),
+            ("CSV_ERROR_INVALID_ARGUMENT", CSV_ERROR_INVALID_ARGUMENT),
+            ("CSV_ERROR_IO", CSV_ERROR_IO),
+            ("CSV_ERROR_FIRMWARE", CSV_ERROR_FIRMWARE),
+            ("CSV_ERROR_VERIFICATION", CSV_ERROR_VERIFICATION),
+            ("CSV_ERROR_PANIC", CSV_ERROR_PANIC),
+        ] {
+            assert_eq!(define(name), code as i64, "{name}");
+        }
+        assert_eq!(define("CSV_EVIDENCE_SIZE"), CSV_EVIDENCE_SIZE as i64);
+
+        for function in [
+            "csv_last_error(",
+            "csv_get_attestation_report(",
+            "csv_hsk_cek_load(",
+            "csv_hsk_cek_load_file(",
+            "csv_hsk_cek_free(",
+            "csv_verify_attestation_report(",
+        ] {
+            assert!(HEADER.contains(function), "{function} is not declared");
+        }
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Retrieval of chip certificates from the HYGON key distribution server
+//! (KDS), with an on-disk cache for verifier hosts.
+//!
+//! The HTTPS [`Client`] requires the `network` feature, enabled by default.
+//! Without it, [`Urls`] still builds the requests for another HTTP client.
+
+#[cfg(feature = "network")]
+use crate::{
+    api::platform::Identifier,
+    certs::{builtin, ca, Verifiable},
+    util::hex,
+    verifier::{cert_fingerprint, Fingerprint},
+};
+use crate::{certs::HskCek, ChipId};
+
+use codicon::{Decoder, Encoder};
+#[cfg(feature = "network")]
+use hyper::{body::HttpBody as _, Client as HttpClient, StatusCode};
+#[cfg(feature = "network")]
+use hyper_tls::HttpsConnector;
+#[cfg(feature = "network")]
+use std::io::Error;
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
+};
+
+/// The public HYGON key distribution server.
+pub const DEFAULT_URL: &str = "https://cert.hygon.cn";
+
+/// The request of the HSK and CEK certificates, relative to the server.
+pub const HSK_CEK_TEMPLATE: &str = "/hsk_cek?snumber={chip_id}";
+
+/// The request of the HRK certificate, relative to the server.
+pub const HRK_PATH: &str = "/hrk";
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Builds the request URLs of a key distribution server, for use with any
+/// HTTP client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Urls {
+    base: String,
+    hsk_cek: String,
+    hrk: String,
+}
+
+impl Default for Urls {
+    fn default() -> Self {
+        Self::new(DEFAULT_URL)
+    }
+}
+
+impl Urls {
+    /// Uses the server at `base`, e.g. a mirror of [`DEFAULT_URL`].
+    pub fn new(base: impl Into<String>) -> Self {
+        Self {
+            base: base.into(),
+            hsk_cek: HSK_CEK_TEMPLATE.into(),
+            hrk: HRK_PATH.into(),
+        }
+    }
+
+    /// Replaces [`HSK_CEK_TEMPLATE`], for mirrors laid out differently.
+    ///
+    /// `{chip_id}` in the template is replaced by the chip identifier.
+    pub fn hsk_cek_template(mut self, template: impl Into<String>) -> Self {
+        self.hsk_cek = template.into();
+        self
+    }
+
+    /// Replaces [`HRK_PATH`], for mirrors laid out differently.
+    pub fn hrk_path(mut self, path: impl Into<String>) -> Self {
+        self.hrk = path.into();
+        self
+    }
+
+    fn join(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.base.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    /// The URL of the HSK and CEK certificates of a chip.
+    pub fn hsk_cek(&self, chip_id: &ChipId) -> String {
+        self.join(
+            &self
+                .hsk_cek
+                .replace("{chip_id}", &percent_encode(chip_id.as_str())),
+        )
+    }
+
+    /// The URL of the HRK certificate.
+    pub fn hrk(&self) -> String {
+        self.join(&self.hrk)
+    }
+}
+
+/// Checks that `body` holds a self-signed HRK with the given fingerprint.
+#[cfg(feature = "network")]
+fn pinned_hrk(body: &[u8], pin: &Fingerprint) -> Result<ca::Certificate> {
+    let hrk = ca::Certificate::decode(body, ())?;
+    (&hrk, &hrk).verify()?;
+
+    let fingerprint = cert_fingerprint(&hrk)?;
+    if &fingerprint != pin {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "the published HRK {} is not the pinned one {}",
+                hex::encode(&fingerprint),
+                hex::encode(pin)
+            ),
+        ));
+    }
+
+    Ok(hrk)
+}
+
+/// A blocking client of the key distribution server.
+#[cfg(feature = "network")]
+pub struct Client {
+    urls: Urls,
+}
+
+#[cfg(feature = "network")]
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(DEFAULT_URL)
+    }
+}
+
+#[cfg(feature = "network")]
+impl Client {
+    /// Uses the server at `base`, e.g. a mirror of [`DEFAULT_URL`].
+    pub fn new(base: impl Into<String>) -> Self {
+        Self::with_urls(Urls::new(base))
+    }
+
+    /// Uses the given request URLs.
+    pub fn with_urls(urls: Urls) -> Self {
+        Self { urls }
+    }
+
+    /// Downloads the HSK and CEK certificates of a chip.
+    pub fn hsk_cek(&self, chip_id: &ChipId) -> Result<HskCek> {
+        let body = self.get(&self.urls.hsk_cek(chip_id))?;
+        HskCek::decode(&body[..], ())
+    }
+
+    /// Downloads the HSK and CEK certificates of the chip identified by
+    /// `GET_ID`, see [`crate::api::platform::Firmware::get_identifier`].
+    pub fn hsk_cek_of(&self, identifier: &Identifier) -> Result<HskCek> {
+        self.hsk_cek(&ChipId::try_from(identifier)?)
+    }
+
+    /// Downloads the HRK certificate, requiring it to be the builtin one.
+    ///
+    /// This detects a rotation of the HYGON root: a different certificate
+    /// fails with [`ErrorKind::PermissionDenied`], naming both
+    /// fingerprints. A new root is only trusted once pinned explicitly,
+    /// see [`Client::hrk_pinned`].
+    pub fn hrk(&self) -> Result<ca::Certificate> {
+        let builtin = ca::Certificate::decode(builtin::HRK, ())?;
+        self.hrk_pinned(&cert_fingerprint(&builtin)?)
+    }
+
+    /// Downloads the HRK certificate, requiring its SM3 fingerprint to be
+    /// `pin`, see [`cert_fingerprint`].
+    pub fn hrk_pinned(&self, pin: &Fingerprint) -> Result<ca::Certificate> {
+        pinned_hrk(&self.get(&self.urls.hrk())?, pin)
+    }
+
+    fn get(&self, url: &str) -> Result<Vec<u8>> {
+        let client = HttpClient::builder().build::<_, hyper::Body>(HttpsConnector::new());
+        let request = hyper::Request::get(url)
+            .header("User-Agent", "csv-rs")
+            .body(hyper::Body::empty())
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        runtime.block_on(async {
+            let response = client.request(request).await.map_err(Error::other)?;
+            if response.status() != StatusCode::OK {
+                return Err(Error::other(format!(
+                    "{url}: HTTP status {}",
+                    response.status()
+                )));
+            }
+
+            let mut body = Vec::new();
+            let mut response = response.into_body();
+            while let Some(chunk) = response.data().await {
+                body.extend_from_slice(&chunk.map_err(Error::other)?);
+            }
+            Ok(body)
+        })
+    }
+}
+
+/// A directory of `<chip id>.cert` files in the `hsk_cek` layout.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Uses the given directory, created on the first insertion.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The user-level cache (`$HOME/.cache/hygon-csv/kds`), if the cache
+    /// directory of the user is known.
+    pub fn user() -> Option<Self> {
+        dirs::cache_dir().map(|dir| Self::new(dir.join("hygon-csv").join("kds")))
+    }
+
+    /// The directory holding the certificates.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, chip_id: &ChipId) -> PathBuf {
+        self.dir.join(format!("{chip_id}.cert"))
+    }
+
+    /// Returns the cached certificates of a chip, if any.
+    pub fn get(&self, chip_id: &ChipId) -> Result<Option<HskCek>> {
+        match File::open(self.path(chip_id)) {
+            Ok(file) => Ok(Some(HskCek::decode(BufReader::new(file), ())?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores the certificates of a chip.
+    pub fn put(&self, chip_id: &ChipId, hsk_cek: &HskCek) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.path(chip_id);
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        hsk_cek.encode(&mut writer, ())?;
+        writer.flush()?;
+        fs::rename(tmp, path)
+    }
+
+    /// Returns the cached certificates of a chip, downloading and caching
+    /// them first if needed.
+    #[cfg(feature = "network")]
+    pub fn get_or_fetch(&self, chip_id: &ChipId, client: &Client) -> Result<HskCek> {
+        if let Some(hsk_cek) = self.get(chip_id)? {
+            return Ok(hsk_cek);
+        }
+
+        let hsk_cek = client.hsk_cek(chip_id)?;
+        self.put(chip_id, &hsk_cek)?;
+        Ok(hsk_cek)
+    }
+
+    /// Forgets the certificates of a chip, returning true if they were
+    /// cached.
+    pub fn remove(&self, chip_id: &ChipId) -> Result<bool> {
+        match fs::remove_file(self.path(chip_id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lists the chips with cached certificates.
+    pub fn chips(&self) -> Result<Vec<ChipId>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut chips = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension() != Some("cert".as_ref()) {
+                continue;
+            }
+
+            if let Some(chip_id) = path
+                .file_stem()
+                .and_then(|s| s.to_str()?.parse::<ChipId>().ok())
+            {
+                chips.push(chip_id);
+            }
+        }
+
+        chips.sort();
+        Ok(chips)
+    }
+
+    /// Forgets every cached certificate, returning how many chips were
+    /// cached.
+    pub fn clear(&self) -> Result<usize> {
+        let chips = self.chips()?;
+        for chip_id in &chips {
+            self.remove(chip_id)?;
+        }
+        Ok(chips.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    #[test]
+    fn url() {
+        let chip_id = "NZA9T14052605".parse().unwrap();
+        assert_eq!(
+            Urls::new("https://kds.example/").hsk_cek(&chip_id),
+            "https://kds.example/hsk_cek?snumber=NZA9T14052605"
+        );
+        assert_eq!(
+            Urls::new("https://mirror.example/kds")
+                .hsk_cek_template("certs/{chip_id}.cert")
+                .hsk_cek(&chip_id),
+            "https://mirror.example/kds/certs/NZA9T14052605.cert"
+        );
+        assert_eq!(percent_encode("A b/&~"), "A%20b%2F%26~");
+        assert_eq!(Urls::default().hrk(), "https://cert.hygon.cn/hrk");
+    }
+
+    #[test]
+    #[cfg(feature = "network")]
+    fn hrk() {
+        let builtin = ca::Certificate::decode(builtin::HRK, ()).unwrap();
+        let pin = cert_fingerprint(&builtin).unwrap();
+        assert_eq!(pinned_hrk(builtin::HRK, &pin).unwrap(), builtin);
+
+        // A rotated root is reported, not trusted.
+        let err = pinned_hrk(builtin::HRK, &[0u8; 32]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        let mut tampered = builtin::HRK.to_vec();
+        tampered[100] ^= 1;
+        assert!(pinned_hrk(&tampered, &pin).is_err());
+    }
+
+    #[test]
+    fn cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = Cache::new(tmp.path());
+        let chip_id: ChipId = "NZA9T14052605".parse().unwrap();
+        let hsk_cek = HskCek::decode(HSK_CEK, ()).unwrap();
+
+        assert!(cache.get(&chip_id).unwrap().is_none());
+        assert!(cache.chips().unwrap().is_empty());
+
+        cache.put(&chip_id, &hsk_cek).unwrap();
+        let cached = cache.get(&chip_id).unwrap().unwrap();
+        assert_eq!(cached.cek, hsk_cek.cek);
+        assert_eq!(cache.chips().unwrap(), std::slice::from_ref(&chip_id));
+        // Cached certificates are served without contacting the server.
+        #[cfg(feature = "network")]
+        cache
+            .get_or_fetch(&chip_id, &Client::new("http://0.0.0.0:1"))
+            .unwrap();
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(!cache.remove(&chip_id).unwrap());
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The certificates of HYGON's certificate authority (HRK and HSK).
+
+use super::{key, Usage};
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub struct Data {
+    pub kid: [u8; 16],
+    pub sid: [u8; 16],
+    pub usage: Usage,
+    pub reserved: [u8; 24],
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub struct Preamble {
+    #[serde(with = "crate::util::le")]
+    pub ver: u32,
+    pub data: Data,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub struct Body {
+    pub preamble: Preamble,
+    pub pubkey: key::PubKey,
+    #[serde(with = "crate::util::le")]
+    pub uid_size: u16,
+    #[serde(with = "BigArray")]
+    pub user_id: [u8; 254],
+    #[serde(with = "BigArray")]
+    pub reserved: [u8; 108],
+}
+
+/// A Certificate Authority chain.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub struct Certificate {
+    pub body: Body,
+    pub(crate) signature: key::Signature,
+    #[serde(with = "BigArray")]
+    pub(crate) _reserved: [u8; 112],
+}
@@ -0,0 +1,59 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The certificates of CSV platforms (CEK, OCA, PEK and PDH).
+
+use super::{key, Algorithm, Usage, Version};
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct PubKey {
+    pub usage: Usage,
+    pub algo: Algorithm,
+    pub key: key::PubKey,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct Data {
+    pub firmware: Version,
+    #[serde(with = "crate::util::le")]
+    pub reserved1: u16,
+    pub pubkey: PubKey,
+    #[serde(with = "crate::util::le")]
+    pub uid_size: u16,
+    #[serde(with = "BigArray")]
+    pub user_id: [u8; 254],
+    pub sid: [u8; 16],
+    #[serde(with = "BigArray")]
+    pub reserved2: [u8; 608],
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct Body {
+    #[serde(with = "crate::util::le")]
+    pub ver: u32,
+    pub data: Data,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct Signatures {
+    pub(crate) usage: Usage,
+    pub(crate) algo: Algorithm,
+    pub(crate) signature: key::Signature,
+    #[serde(with = "BigArray")]
+    pub(crate) _reserved: [u8; 368],
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct Certificate {
+    pub body: Body,
+    pub sigs: [Signatures; 2],
+}
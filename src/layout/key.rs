@@ -0,0 +1,38 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Public keys and signatures, as embedded in certificates and reports.
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+/// The elliptic curve of a key.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Group(#[serde(with = "crate::util::le")] pub(crate) u32);
+
+impl Group {
+    pub const SM2_256: Group = Group(3u32.to_le());
+}
+
+/// The Raw format of ecc pubkey.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct PubKey {
+    pub g: Group,
+    #[serde(with = "BigArray")]
+    pub x: [u8; 72],
+    #[serde(with = "BigArray")]
+    pub y: [u8; 72],
+}
+
+/// The Raw format of ecdsa signature.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+pub struct Signature {
+    #[serde(with = "BigArray")]
+    pub r: [u8; 72],
+    #[serde(with = "BigArray")]
+    pub s: [u8; 72],
+}
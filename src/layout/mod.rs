@@ -0,0 +1,158 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The firmware structures of reports and certificates, without `std`.
+//!
+//! This module builds with `--no-default-features`, for verifiers running
+//! in enclaves or on embedded targets: it holds the `#[repr(C)]` layouts
+//! exchanged with the firmware and reads them from raw bytes. With the
+//! `alloc` feature, [`verify`] checks the SM2 signatures of reports and
+//! certificate chains as well. Everything else, from the ioctls to the
+//! KDS, needs the `std` feature and operates on the same types,
+//! re-exported from their usual paths, e.g.
+//! [`crate::api::guest::AttestationReport`].
+
+pub mod ca;
+pub mod csv;
+pub mod key;
+pub mod report;
+#[cfg(feature = "alloc")]
+pub mod verify;
+
+use core::{fmt, mem::size_of, ptr::read_unaligned, slice::from_raw_parts};
+
+use serde::{Deserialize, Serialize};
+
+/// Types which can be read from and written to raw bytes.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]` (or transparent), contain no padding,
+/// no pointers or references and no field for which any bit pattern is
+/// invalid (`bool`, enums, ...). All implementations in this crate are
+/// checked against the size the firmware specifies, which would differ
+/// if padding slipped in.
+pub unsafe trait Pod: Sized {}
+
+macro_rules! pod {
+    ($($ty:ty = $size:expr),* $(,)?) => {
+        $(
+            static_assertions::const_assert_eq!(core::mem::size_of::<$ty>(), $size);
+            // SAFETY: firmware layout made of integers and byte arrays only.
+            unsafe impl $crate::layout::Pod for $ty {}
+        )*
+    };
+}
+
+#[cfg(feature = "std")]
+pub(crate) use pod;
+
+pod! {
+    key::Signature = 144,
+    ca::Body = 576,
+    ca::Certificate = 832,
+    csv::Body = 1044,
+    csv::Certificate = 2084,
+    report::Body = 180,
+    report::AttestationReport = 336,
+    report::ReportSigner = 2212,
+}
+
+/// Views a value as its raw bytes.
+pub fn as_bytes<T: Pod>(value: &T) -> &[u8] {
+    // SAFETY: `Pod` types have no padding, so every byte is initialized.
+    unsafe { from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Reads a value from exactly `size_of::<T>()` bytes of `bytes`.
+///
+/// `bytes` needs no particular alignment. Returns `None` if the length
+/// differs, see [`crate::pod::from_bytes`] for a version reporting errors.
+pub fn read<T: Pod>(bytes: &[u8]) -> Option<T> {
+    if bytes.len() != size_of::<T>() {
+        return None;
+    }
+
+    // SAFETY: the length is checked and any bit pattern is a valid `T`.
+    Some(unsafe { read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+/// Information about the CSV platform version.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Version {
+    /// The major version number.
+    pub major: u8,
+
+    /// The minor version number.
+    pub minor: u8,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl From<u16> for Version {
+    fn from(v: u16) -> Self {
+        Self {
+            major: ((v & 0xF0) >> 4) as u8,
+            minor: (v & 0x0F) as u8,
+        }
+    }
+}
+
+/// Denotes a certificate's usage.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage(#[serde(with = "crate::util::le")] pub(crate) u32);
+
+impl Usage {
+    /// HYGON Root Key.
+    pub const HRK: Usage = Usage(0x0000u32.to_le());
+
+    /// HYGON Signing key.
+    pub const HSK: Usage = Usage(0x0013u32.to_le());
+
+    /// Owner Certificate Authority.
+    pub const OCA: Usage = Usage(0x1001u32.to_le());
+
+    /// Platform Endorsement Key.
+    pub const PEK: Usage = Usage(0x1002u32.to_le());
+
+    /// Platform Diffie-Hellman.
+    pub const PDH: Usage = Usage(0x1003u32.to_le());
+
+    /// Chip Endorsement Key.
+    pub const CEK: Usage = Usage(0x1004u32.to_le());
+}
+
+/// Denotes the algorithm of a certificate's key or signature.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Algorithm(#[serde(with = "crate::util::le")] pub(crate) u32);
+
+impl Algorithm {
+    pub const SM2_SA: Algorithm = Algorithm(0x0004u32.to_le());
+    pub const SM2_DH: Algorithm = Algorithm(0x0005u32.to_le());
+    pub const NONE: Algorithm = Algorithm(0x0000u32.to_le());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+
+    #[test]
+    fn read_exact() {
+        let len = size_of::<report::AttestationReport>();
+        let report: report::AttestationReport = read(&REPORT[..len]).unwrap();
+        assert_eq!(as_bytes(&report), &REPORT[..len]);
+
+        assert!(read::<report::ReportSigner>(&REPORT[len..]).is_some());
+        assert!(read::<report::ReportSigner>(REPORT).is_none());
+    }
+}
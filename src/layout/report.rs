@@ -0,0 +1,138 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Attestation reports and the signers returned along with them.
+
+use super::key;
+use crate::util::redact;
+
+use bitfield::bitfield;
+use core::fmt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_big_array::BigArray;
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Body {
+    pub user_pubkey_digest: [u8; 32],
+    pub vm_id: [u8; 16],
+    pub vm_version: [u8; 16],
+    #[serde(with = "BigArray")]
+    pub report_data: [u8; 64],
+    pub mnonce: [u8; 16],
+    pub measure: [u8; 32],
+    #[serde(with = "crate::util::le")]
+    pub policy: GuestPolicy,
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Body")
+            .field("user_pubkey_digest", &self.user_pubkey_digest)
+            .field("vm_id", &self.vm_id)
+            .field("vm_version", &self.vm_version)
+            .field("report_data", &self.report_data)
+            .field("mnonce", &redact::Redacted(&self.mnonce))
+            .field("measure", &self.measure)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+/// Data provieded by the guest owner for requesting an attestation report
+/// from the HYGON Secure Processor.
+#[repr(C)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationReport {
+    pub body: Body,
+    #[serde(with = "crate::util::le")]
+    pub sig_usage: u32,
+    #[serde(with = "crate::util::le")]
+    pub sig_algo: u32,
+    #[serde(with = "crate::util::le")]
+    pub anonce: u32,
+    pub sig: key::Signature,
+}
+
+#[repr(C)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportSigner {
+    #[serde(with = "BigArray")]
+    pub pek_cert: [u8; 2084],
+    #[serde(with = "BigArray")]
+    pub sn: [u8; 64],
+    pub reserved: [u8; 32],
+    pub mac: [u8; 32],
+}
+
+bitfield! {
+    /// The firmware associates each guest with a guest policy that the guest owner provides. The
+    /// firmware restricts what actions the hypervisor can take on the guest according to the guest policy.
+    /// The policy also indicates the minimum firmware version to for the guest.
+    ///
+    /// The guest owner provides the guest policy to the firmware during launch. The firmware then binds
+    /// the policy to the guest. The policy cannot be changed throughout the lifetime of the guest. The
+    /// policy is also migrated with the guest and enforced by the destination platform firmware.
+    ///
+    /// | Bit(s) | Name           | Description                                                                                 >
+    /// |--------|----------------|--------------------------------------------------------------------------------------------->
+    /// | 0      | NODBG          | Debugging of the guest is disallowed when set                                               >
+    /// | 1      | NOKS           | Sharing keys with other guests is disallowed when set                                       >
+    /// | 2      | ES             | CSV2 is required when set                                                                   >
+    /// | 3      | NOSEND         | Sending the guest to another platform is disallowed when set                                >
+    /// | 4      | DOMAIN         | The guest must not be transmitted to another platform that is not in the domain when set.   >
+    /// | 5      | CSV            | The guest must not be transmitted to another platform that is not CSV capable when set.     >
+    /// | 6      | CSV3           | The guest must not be transmitted to another platform that is not CSV3 capable when set.    >
+    /// | 7      | ASID_REUSE     | Sharing asids with other guests owned by same user is allowed when set                      >
+    /// | 11:8   | HSK_VERSION    | The guest must not be transmitted to another platform with a lower HSK version.             >
+    /// | 15:12  | CEK_VERSION    | The guest must not be transmitted to another platform with a lower CEK version.             >
+    /// | 23:16  | API_MAJOR      | The guest must not be transmitted to another platform with a lower platform version.        >
+    /// | 31:24  | API_MINOR      | The guest must not be transmitted to another platform with a lower platform version.        >
+    #[repr(C)]
+    #[derive(Copy, Clone, PartialEq, Eq, Default)]
+    pub struct GuestPolicy(u32);
+    impl Debug;
+    pub nodbg, set_nodbg: 0, 0;
+    pub noks, set_noks: 1, 1;
+    pub es, set_es: 2, 2;
+    pub nosend, set_nosend: 3, 3;
+    pub domain, set_domain: 4, 4;
+    pub csv, set_csv: 5, 5;
+    pub csv3, set_csv3: 6, 6;
+    pub asid_reuse, set_asid_reuse: 7, 7;
+    pub hsk_version, set_hsk_version: 11, 8;
+    pub cek_version, set_cek_version: 15, 12;
+    pub api_major, set_api_major: 23, 16;
+    pub api_minor, set_api_minor: 31, 24;
+}
+
+/// A policy holds its value in the byte order of the host. Only the
+/// report stores it little-endian, like its other integers, see
+/// [`crate::util::le`].
+impl crate::util::le::Integer for GuestPolicy {
+    fn from_le(stored: Self) -> Self {
+        Self(u32::from_le(stored.0))
+    }
+
+    fn to_le(self) -> Self {
+        Self(self.0.to_le())
+    }
+}
+
+impl Serialize for GuestPolicy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct("GuestPolicy", &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for GuestPolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "GuestPolicy")]
+        struct Value(u32);
+
+        let Value(value) = Value::deserialize(deserializer)?;
+        Ok(Self(value))
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! SM2 verification of reports and certificate chains, without `std`.
+//!
+//! The signatures are checked with the pure-Rust `sm2` crate, which needs
+//! an allocator. These are the checks [`crate::certs::Chain`] and
+//! [`crate::verifier::SignatureChain`] make with the `std` feature, minus
+//! the error locations.
+
+use super::{ca, csv, key, report::AttestationReport, Algorithm, Usage};
+
+use core::{fmt, str::from_utf8};
+use sm2::dsa::{signature::Verifier, Signature, VerifyingKey};
+
+/// The size of the SM2 coordinates and signature components.
+const SIZE: usize = 32;
+
+/// Why a verification failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Invalid {
+    /// The signer has another usage or algorithm than expected.
+    Signer,
+
+    /// The public key is not on the SM2 curve, or the user id of its
+    /// owner is not UTF-8.
+    Key,
+
+    /// The signature does not verify.
+    Signature,
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Invalid::Signer => "unexpected signer",
+            Invalid::Key => "invalid SM2 public key",
+            Invalid::Signature => "bad signature",
+        })
+    }
+}
+
+/// Converts the little-endian integers of the firmware to big-endian.
+fn be(le: &[u8]) -> [u8; SIZE] {
+    let mut be = [0u8; SIZE];
+    for (dst, src) in be.iter_mut().zip(le[..SIZE].iter().rev()) {
+        *dst = *src;
+    }
+    be
+}
+
+fn user_id(size: u16, id: &[u8]) -> &[u8] {
+    &id[..(u16::from_le(size) as usize).min(id.len())]
+}
+
+/// Builds the verifying key of the signer `id`, given the big-endian
+/// affine coordinates of its public key.
+pub(crate) fn verifying_key(x: &[u8], y: &[u8], id: &[u8]) -> Result<VerifyingKey, Invalid> {
+    if x.len() != SIZE || y.len() != SIZE {
+        return Err(Invalid::Key);
+    }
+
+    let mut point = [0x04u8; 1 + 2 * SIZE];
+    point[1..1 + SIZE].copy_from_slice(x);
+    point[1 + SIZE..].copy_from_slice(y);
+
+    let id = from_utf8(id).map_err(|_| Invalid::Key)?;
+    VerifyingKey::from_sec1_bytes(id, &point).map_err(|_| Invalid::Key)
+}
+
+/// Verifies the signature `sig` of `msg` by the signer `id`.
+pub fn sm2(key: &key::PubKey, id: &[u8], msg: &[u8], sig: &key::Signature) -> Result<(), Invalid> {
+    if key.g != key::Group::SM2_256 {
+        return Err(Invalid::Key);
+    }
+    let key = verifying_key(&be(&key.x), &be(&key.y), id)?;
+
+    let mut rs = [0u8; 2 * SIZE];
+    rs[..SIZE].copy_from_slice(&be(&sig.r));
+    rs[SIZE..].copy_from_slice(&be(&sig.s));
+    let sig = Signature::from_slice(&rs).map_err(|_| Invalid::Signature)?;
+
+    key.verify(msg, &sig).map_err(|_| Invalid::Signature)
+}
+
+/// Verifies a certificate of HYGON's certificate authority, signed by the
+/// HRK `signer`, e.g. the HRK itself or the HSK.
+pub fn ca(signer: &ca::Certificate, cert: &ca::Certificate) -> Result<(), Invalid> {
+    let data = &signer.body.preamble.data;
+    if data.usage != Usage::HRK || cert.body.preamble.data.sid != data.kid {
+        return Err(Invalid::Signer);
+    }
+
+    sm2(
+        &signer.body.pubkey,
+        user_id(signer.body.uid_size, &signer.body.user_id),
+        super::as_bytes(&cert.body),
+        &cert.signature,
+    )
+}
+
+/// Verifies either signature of a platform certificate by `usage`.
+fn signed(
+    cert: &csv::Certificate,
+    usage: Usage,
+    key: &key::PubKey,
+    id: &[u8],
+) -> Result<(), Invalid> {
+    let mut result = Err(Invalid::Signer);
+    for sig in &cert.sigs {
+        if sig.usage != usage || sig.algo != Algorithm::SM2_SA {
+            continue;
+        }
+
+        result = sm2(key, id, super::as_bytes(&cert.body), &sig.signature);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+/// Verifies the CEK certificate, signed by the HSK.
+pub fn cek(hsk: &ca::Certificate, cek: &csv::Certificate) -> Result<(), Invalid> {
+    if hsk.body.preamble.data.usage != Usage::HSK || cek.body.data.pubkey.usage != Usage::CEK {
+        return Err(Invalid::Signer);
+    }
+
+    signed(
+        cek,
+        Usage::HSK,
+        &hsk.body.pubkey,
+        user_id(hsk.body.uid_size, &hsk.body.user_id),
+    )
+}
+
+/// Verifies a platform certificate signed by another, e.g. the PEK by the
+/// CEK.
+pub fn csv(signer: &csv::Certificate, cert: &csv::Certificate) -> Result<(), Invalid> {
+    let data = &signer.body.data;
+    if data.pubkey.algo != Algorithm::SM2_SA {
+        return Err(Invalid::Signer);
+    }
+
+    signed(
+        cert,
+        data.pubkey.usage,
+        &data.pubkey.key,
+        user_id(data.uid_size, &data.user_id),
+    )
+}
+
+/// Verifies the signature of the report by the PEK.
+pub fn report(pek: &csv::Certificate, report: &AttestationReport) -> Result<(), Invalid> {
+    // The claimed signer is masked like the rest of the report.
+    let usage = Usage(report.sig_usage ^ report.anonce);
+    let algo = Algorithm(report.sig_algo ^ report.anonce);
+    let data = &pek.body.data;
+    if data.pubkey.usage != Usage::PEK || usage != Usage::PEK || algo != Algorithm::SM2_SA {
+        return Err(Invalid::Signer);
+    }
+
+    sm2(
+        &data.pubkey.key,
+        user_id(data.uid_size, &data.user_id),
+        super::as_bytes(&report.body),
+        &report.sig,
+    )
+}
+
+/// Verifies a report along its chain: the HRK signed itself and the HSK,
+/// which signed the CEK, which signed the PEK, which signed the report.
+///
+/// `hrk` must be the trusted root, e.g. the builtin HRK. `pek` is the PEK
+/// certificate of the report signer, unmasked.
+pub fn chain(
+    hrk: &ca::Certificate,
+    hsk: &ca::Certificate,
+    cek: &csv::Certificate,
+    pek: &csv::Certificate,
+    report: &AttestationReport,
+) -> Result<(), Invalid> {
+    ca(hrk, hrk)?;
+    ca(hrk, hsk)?;
+    self::cek(hsk, cek)?;
+    csv(cek, pek)?;
+    self::report(pek, report)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{certs::builtin::HRK, evidence::hag, layout::read};
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    type Parts = (
+        ca::Certificate,
+        ca::Certificate,
+        csv::Certificate,
+        csv::Certificate,
+        AttestationReport,
+    );
+
+    fn parts() -> Parts {
+        let evidence = hag::read(REPORT, HSK_CEK).unwrap();
+        (
+            read(HRK).unwrap(),
+            evidence.chain.hsk,
+            evidence.chain.cek,
+            evidence.signer.pek().unwrap(),
+            evidence.report,
+        )
+    }
+
+    #[test]
+    fn verify_chain() {
+        let (hrk, hsk, cek, pek, report) = parts();
+        chain(&hrk, &hsk, &cek, &pek, &report).unwrap();
+    }
+
+    #[test]
+    fn tampered() {
+        let (hrk, hsk, cek, pek, mut report) = parts();
+        report.body.measure[0] ^= 1;
+        assert_eq!(
+            chain(&hrk, &hsk, &cek, &pek, &report),
+            Err(Invalid::Signature)
+        );
+
+        let (hrk, hsk, cek, pek, report) = parts();
+        assert_eq!(chain(&hrk, &hsk, &pek, &cek, &report), Err(Invalid::Signer));
+        assert_eq!(chain(&hsk, &hsk, &cek, &pek, &report), Err(Invalid::Signer));
+    }
+}
@@ -3,63 +3,186 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Appraisal of attestation reports against reference values.
+#[cfg(feature = "std")]
+pub mod appraisal;
+
+/// Recording and replay of firmware interactions, for debugging.
+#[cfg(feature = "capture")]
+pub mod capture;
+
+/// Detection of the CSV features offered by the machine.
+#[cfg(feature = "std")]
+pub mod capabilities;
+
 /// CSV certificates interface.
+#[cfg(feature = "std")]
 pub mod certs;
 
 /// CSV API interface.
+#[cfg(feature = "std")]
 pub mod api;
 
 /// Crypto module for key and signature.
+#[cfg(feature = "std")]
 pub mod crypto;
 
 /// Error module.
+#[cfg(feature = "std")]
 pub mod error;
 
+/// Attestation evidence bundles.
+#[cfg(feature = "std")]
+pub mod evidence;
+
+/// A C interface to report fetching and verification.
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+/// Access to the HYGON key distribution server.
+#[cfg(feature = "std")]
+pub mod kds;
+
+/// Pre-computation of guest launch digests.
+#[cfg(feature = "openssl")]
+pub mod measurement;
+
+/// The firmware structures of reports and certificates.
+pub mod layout;
+
+/// Raw byte access to the firmware structures.
+#[cfg(feature = "std")]
+pub mod pod;
+
+/// Python bindings.
+#[cfg(feature = "python")]
+pub mod python;
+
+/// Launch measurement and secret injection through QEMU's QMP.
+#[cfg(feature = "qmp")]
+pub mod qmp;
+
+#[cfg(feature = "openssl")]
 pub mod session;
 
+/// A software platform and guest, for running without HYGON hardware.
+#[cfg(all(feature = "openssl", any(test, feature = "sim")))]
+pub mod sim;
+
+/// Generation of test reports for verifier development.
+#[cfg(all(feature = "openssl", any(test, feature = "test-utils")))]
+pub mod test_utils;
+
+/// Verification of attestation evidence.
+#[cfg(feature = "std")]
+pub mod verifier;
+
 mod util;
 
+#[cfg(feature = "std")]
+pub use capabilities::capabilities;
+pub use layout::Version;
+#[cfg(feature = "std")]
 pub use util::cached_chain;
 
-use std::io::Write;
+#[cfg(feature = "std")]
+use std::{
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
 
 use serde::{Deserialize, Serialize};
 
-/// Information about the CSV platform version.
+/// A description of the CSV platform's build information.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub struct Version {
-    /// The major version number.
-    pub major: u8,
+pub struct Build {
+    /// The version information.
+    pub version: Version,
 
-    /// The minor version number.
-    pub minor: u8,
+    /// The build number.
+    pub build: u8,
 }
 
-impl std::fmt::Display for Version {
+pub struct Body;
+
+/// The serial number of a HYGON chip.
+///
+/// This is the identifier returned by GET_ID on the host and embedded into
+/// report signers, and the key under which HYGON publishes the chip's HSK
+/// and CEK certificates.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ChipId(String);
+
+#[cfg(feature = "std")]
+impl ChipId {
+    /// Returns the serial number as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for ChipId {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}.{}", self.major, self.minor)
+        write!(f, "{}", self.0)
     }
 }
 
-impl From<u16> for Version {
-    fn from(v: u16) -> Self {
-        Self {
-            major: ((v & 0xF0) >> 4) as u8,
-            minor: (v & 0x0F) as u8,
+/// Parses a NUL-padded serial number, as found in firmware structures.
+#[cfg(feature = "std")]
+impl TryFrom<&[u8]> for ChipId {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Error> {
+        let len = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+        let (id, padding) = value.split_at(len);
+
+        if padding.iter().any(|&b| b != 0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "chip id is not NUL-padded",
+            ));
         }
+
+        std::str::from_utf8(id)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "chip id is not ASCII"))?
+            .parse()
     }
 }
 
-/// A description of the CSV platform's build information.
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub struct Build {
-    /// The version information.
-    pub version: Version,
+#[cfg(feature = "std")]
+impl FromStr for ChipId {
+    type Err = Error;
 
-    /// The build number.
-    pub build: u8,
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid chip id: {s:?}"),
+            ));
+        }
+
+        Ok(Self(s.to_string()))
+    }
 }
 
-pub struct Body;
+#[cfg(feature = "std")]
+impl TryFrom<String> for ChipId {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Error> {
+        value.parse()
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ChipId> for String {
+    fn from(id: ChipId) -> String {
+        id.0
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pre-computation of the launch digest of a guest from its artifacts.
+//!
+//! The firmware hashes every page encrypted with LAUNCH_UPDATE_DATA, in
+//! order, with SM3. QEMU encrypts the firmware image, then, for direct
+//! kernel boot, the table of kernel hashes the firmware checks the kernel,
+//! initrd and command line against before booting them. The resulting
+//! digest is the `measure` field of the attestation report.
+//!
+//! CSV2 guests additionally measure the initial register state of every
+//! vCPU, see [`LaunchDigest::csv2`].
+//!
+//! ```no_run
+//! # fn run(report: &csv_rs::api::guest::AttestationReport) -> std::io::Result<()> {
+//! use csv_rs::measurement::LaunchDigest;
+//!
+//! let digest = LaunchDigest::new(std::fs::read("OVMF.fd")?)
+//!     .kernel(std::fs::read("vmlinuz")?)
+//!     .initrd(std::fs::read("initrd.img")?)
+//!     .cmdline("console=ttyS0")
+//!     .below_4g(2 << 30)
+//!     .digest()?;
+//! assert_eq!(report.unmask(report.body.measure), digest);
+//! # Ok(())
+//! # }
+//! ```
+
+mod setup;
+mod vmsa;
+pub use vmsa::{ap_reset_eip, Segment, Vmsa, PAGE_SIZE};
+
+use openssl::hash::{hash, Hasher, MessageDigest};
+use std::io::Result;
+use uuid::{uuid, Uuid};
+
+/// The GUID of the table of kernel hashes.
+const TABLE: Uuid = uuid!("9438d606-4f22-4cc9-b479-a793d411fd21");
+
+/// The GUID of the kernel command line entry.
+const CMDLINE: Uuid = uuid!("97d02dd8-bd20-4c94-aa78-e7714d36ab2a");
+
+/// The GUID of the initrd entry.
+const INITRD: Uuid = uuid!("44baf731-3a2f-4bd7-9af1-41e29169781d");
+
+/// The GUID of the kernel entry.
+const KERNEL: Uuid = uuid!("4de79437-abd2-427f-b835-d5b172d2045b");
+
+/// Appends a GUID-tagged entry, whose length includes the tag.
+fn entry(table: &mut Vec<u8>, guid: Uuid, data: &[u8]) {
+    table.extend_from_slice(&guid.to_bytes_le());
+    table.extend_from_slice(&((16 + 2 + data.len()) as u16).to_le_bytes());
+    table.extend_from_slice(data);
+}
+
+/// The SHA-256 digests of the artifacts of a direct kernel boot, as QEMU
+/// places them in guest memory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KernelHashes {
+    /// The digest of the kernel image, with its setup header filled in
+    /// by QEMU.
+    pub kernel: [u8; 32],
+
+    /// The digest of the initrd, or of nothing.
+    pub initrd: [u8; 32],
+
+    /// The digest of the NUL-terminated command line.
+    pub cmdline: [u8; 32],
+}
+
+impl KernelHashes {
+    /// Hashes the kernel, initrd and command line of a direct boot.
+    ///
+    /// The kernel must be a bzImage: QEMU fills in its setup header before
+    /// hashing it, placing the initrd at the top of the `below_4g` bytes of
+    /// guest RAM it maps below 4 GiB, see [`LaunchDigest::below_4g`].
+    pub fn new(kernel: &[u8], initrd: Option<&[u8]>, cmdline: &str, below_4g: u64) -> Result<Self> {
+        let sha256 = |data: &[u8]| -> Result<[u8; 32]> {
+            let mut digest = [0u8; 32];
+            digest.copy_from_slice(&hash(MessageDigest::sha256(), data)?);
+            Ok(digest)
+        };
+
+        let mut line = cmdline.as_bytes().to_vec();
+        line.push(0);
+
+        let kernel = setup::patch(kernel, initrd.map(<[u8]>::len), cmdline, below_4g)?;
+
+        Ok(Self {
+            kernel: sha256(&kernel)?,
+            initrd: sha256(initrd.unwrap_or_default())?,
+            cmdline: sha256(&line)?,
+        })
+    }
+
+    /// The table as encrypted by QEMU, padded to 16 bytes.
+    pub fn table(&self) -> Vec<u8> {
+        let mut entries = Vec::new();
+        entry(&mut entries, CMDLINE, &self.cmdline);
+        entry(&mut entries, INITRD, &self.initrd);
+        entry(&mut entries, KERNEL, &self.kernel);
+
+        let mut table = Vec::new();
+        entry(&mut table, TABLE, &entries);
+        table.resize(table.len().next_multiple_of(16), 0);
+        table
+    }
+}
+
+/// The inputs of a guest launch, from which the launch digest follows.
+#[derive(Clone, Debug, Default)]
+pub struct LaunchDigest {
+    firmware: Vec<u8>,
+    kernel: Option<Vec<u8>>,
+    initrd: Option<Vec<u8>>,
+    cmdline: String,
+    below_4g: u64,
+    vmsas: Vec<Vmsa>,
+}
+
+impl LaunchDigest {
+    /// Starts from the firmware image, e.g. `OVMF.fd`.
+    pub fn new(firmware: impl Into<Vec<u8>>) -> Self {
+        Self {
+            firmware: firmware.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Boots the given kernel directly, which measures the kernel hashes.
+    pub fn kernel(mut self, kernel: impl Into<Vec<u8>>) -> Self {
+        self.kernel = Some(kernel.into());
+        self
+    }
+
+    /// The initrd of a direct kernel boot.
+    pub fn initrd(mut self, initrd: impl Into<Vec<u8>>) -> Self {
+        self.initrd = Some(initrd.into());
+        self
+    }
+
+    /// The command line of a direct kernel boot, empty by default.
+    pub fn cmdline(mut self, cmdline: impl Into<String>) -> Self {
+        self.cmdline = cmdline.into();
+        self
+    }
+
+    /// The guest RAM QEMU maps below 4 GiB, under which it places the
+    /// initrd of a direct kernel boot: the RAM size, but 2 GiB on `q35`
+    /// machines with 2.75 GiB or more and 3 GiB on `pc` machines with
+    /// 3.5 GiB or more.
+    pub fn below_4g(mut self, size: u64) -> Self {
+        self.below_4g = size;
+        self
+    }
+
+    /// Measures the VMSA of a vCPU of a CSV2 guest, after the guest
+    /// memory and the VMSAs of the previous vCPUs.
+    pub fn vmsa(mut self, vmsa: Vmsa) -> Self {
+        self.vmsas.push(vmsa);
+        self
+    }
+
+    /// Measures the reset state of `vcpus` vCPUs of a CSV2 guest: the
+    /// bootstrap processor, then application processors starting at the
+    /// reset vector of the firmware image, see [`ap_reset_eip`].
+    ///
+    /// `cpu_signature` is CPUID function 1 EAX of the host.
+    pub fn csv2(mut self, vcpus: usize, cpu_signature: u32) -> Result<Self> {
+        if vcpus == 0 {
+            return Ok(self);
+        }
+
+        self.vmsas.push(Vmsa::bsp(cpu_signature));
+        if vcpus > 1 {
+            let ap = Vmsa::reset(ap_reset_eip(&self.firmware)?, cpu_signature);
+            self.vmsas.extend((1..vcpus).map(|_| ap));
+        }
+        Ok(self)
+    }
+
+    /// The kernel hashes measured after the firmware, if the kernel is
+    /// booted directly.
+    pub fn kernel_hashes(&self) -> Result<Option<KernelHashes>> {
+        self.kernel
+            .as_deref()
+            .map(|kernel| {
+                KernelHashes::new(kernel, self.initrd.as_deref(), &self.cmdline, self.below_4g)
+            })
+            .transpose()
+    }
+
+    /// Computes the launch digest.
+    pub fn digest(&self) -> Result<[u8; 32]> {
+        let mut hasher = Hasher::new(MessageDigest::sm3())?;
+        hasher.update(&self.firmware)?;
+        if let Some(hashes) = self.kernel_hashes()? {
+            hasher.update(&hashes.table())?;
+        }
+        for vmsa in &self.vmsas {
+            hasher.update(&vmsa.page()[..])?;
+        }
+
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&hasher.finish()?);
+        Ok(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex;
+
+    #[test]
+    fn table() {
+        let hashes = KernelHashes::new(&setup::bzimage(), None, "", 0).unwrap();
+        let table = hashes.table();
+        assert_eq!(table.len(), 176);
+        assert_eq!(&table[..16], &TABLE.to_bytes_le());
+        assert_eq!(u16::from_le_bytes([table[16], table[17]]), 168);
+        assert_eq!(&table[18..34], &CMDLINE.to_bytes_le());
+        assert_eq!(
+            &table[36..68],
+            &hash(MessageDigest::sha256(), b"\0").unwrap()[..]
+        );
+        assert_eq!(
+            &table[86..118],
+            &hash(MessageDigest::sha256(), b"").unwrap()[..]
+        );
+        assert_eq!(&table[136..168], &hashes.kernel);
+    }
+
+    #[test]
+    fn known_answer() {
+        let mut kernel = setup::bzimage();
+        for (i, byte) in kernel.iter_mut().enumerate().skip(5 * 512) {
+            *byte = i as u8;
+        }
+        let initrd: Vec<u8> = (0..4096).map(|i| i as u8).collect();
+
+        // The patched header moves the digest away from that of the file.
+        assert_eq!(
+            hash(MessageDigest::sha256(), &kernel).unwrap()[..],
+            hex::decode::<32>("95350982516de7d17899d163deb8af328d355609230d48fe702999ef59396d49")
+                .unwrap()
+        );
+
+        let hashes = KernelHashes::new(&kernel, Some(&initrd), "console=ttyS0", 1 << 30).unwrap();
+        assert_eq!(
+            hashes,
+            KernelHashes {
+                kernel: hex::decode(
+                    "29e952b5f6b7e6a05da7953347ee1e8b0b61dae991b0e1b35a4a31a48a91d3d1"
+                )
+                .unwrap(),
+                initrd: hex::decode(
+                    "c8f5d0341d54d951a71b136e6e2afcb14d11ed8489a7ae126a8fee0df6ecf193"
+                )
+                .unwrap(),
+                cmdline: hex::decode(
+                    "f18aae9b3c09e55bc3047ad361e2442d7c53372470b2958fb83293209a784f71"
+                )
+                .unwrap(),
+            }
+        );
+
+        // QEMU needs to know where the RAM ends to place the initrd.
+        KernelHashes::new(&kernel, Some(&initrd), "", 0).unwrap_err();
+    }
+
+    #[test]
+    fn digest() {
+        let firmware = vec![0x5au8; 4096];
+        let plain = LaunchDigest::new(firmware.clone()).digest().unwrap();
+        assert_eq!(
+            plain[..],
+            hash(MessageDigest::sm3(), &firmware).unwrap()[..]
+        );
+
+        // The command line only counts for a direct kernel boot.
+        let direct = LaunchDigest::new(firmware.clone()).kernel(setup::bzimage());
+        assert_ne!(direct.digest().unwrap(), plain);
+        assert_eq!(
+            plain,
+            LaunchDigest::new(firmware)
+                .cmdline("quiet")
+                .digest()
+                .unwrap()
+        );
+        assert_ne!(
+            direct.digest().unwrap(),
+            direct.clone().cmdline("quiet").digest().unwrap()
+        );
+    }
+
+    #[test]
+    fn csv2() {
+        let firmware = vec![0u8; 4096];
+        let bsp = Vmsa::bsp(0x0090_0f12);
+
+        let mut hasher = Hasher::new(MessageDigest::sm3()).unwrap();
+        hasher.update(&firmware).unwrap();
+        hasher.update(&bsp.page()[..]).unwrap();
+
+        let digest = LaunchDigest::new(firmware.clone())
+            .csv2(1, 0x0090_0f12)
+            .unwrap()
+            .digest()
+            .unwrap();
+        assert_eq!(digest[..], hasher.finish().unwrap()[..]);
+
+        // Application processors need the reset vector of the firmware.
+        assert!(LaunchDigest::new(firmware).csv2(2, 0x0090_0f12).is_err());
+    }
+}
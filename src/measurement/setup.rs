@@ -0,0 +1,224 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The setup header of a bzImage, as QEMU patches it for a direct boot.
+//!
+//! QEMU fills the boot protocol fields of the kernel image before hashing
+//! it (`x86_load_linux` in `hw/i386/x86.c`), so the kernel hash covers the
+//! loader type, heap, command line and initrd placement, not the file
+//! as it is on disk. See the Linux boot protocol,
+//! `Documentation/arch/x86/boot.rst`.
+
+use std::io::{Error, ErrorKind, Result};
+
+/// `HdrS`, the magic of a setup header.
+const MAGIC: u32 = 0x5372_6448;
+
+/// The loader type of QEMU.
+const LOADER_QEMU: u8 = 0xb0;
+
+/// `loadflags`: `heap_end_ptr` is valid.
+const CAN_USE_HEAP: u8 = 0x80;
+
+/// `xloadflags`: the initrd may be placed above 4 GiB.
+const XLF_CAN_BE_LOADED_ABOVE_4G: u16 = 0x10;
+
+/// The memory QEMU reserves at the top of the low RAM for the ACPI tables.
+const ACPI_DATA_SIZE: u64 = 0x20000 + 0x8000;
+
+/// Where the firmware loads the real-mode code and command line of a
+/// kernel with boot protocol 2.02 or later.
+const REAL_ADDR: u32 = 0x10000;
+const CMDLINE_ADDR: u32 = 0x20000;
+
+/// Where older kernels expect them.
+const LEGACY_REAL_ADDR: u32 = 0x90000;
+const LEGACY_CMDLINE_END: u32 = 0x9a000;
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("bzImage: {msg}"))
+}
+
+fn u16_at(image: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([image[offset], image[offset + 1]])
+}
+
+fn u32_at(image: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(image[offset..offset + 4].try_into().unwrap())
+}
+
+fn put_u16(image: &mut [u8], offset: usize, value: u16) {
+    image[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(image: &mut [u8], offset: usize, value: u32) {
+    image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Parses an integer like `strtol(s, NULL, 0)`: decimal, `0x` hexadecimal
+/// or `0` octal, up to the first invalid digit.
+fn strtol(s: &str) -> i64 {
+    let (radix, digits) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => (16, hex),
+        None if s.starts_with('0') => (8, s),
+        None => (10, s),
+    };
+    digits
+        .chars()
+        .map_while(|c| c.to_digit(radix))
+        .fold(0i64, |n, d| {
+            n.wrapping_mul(radix as i64).wrapping_add(d as i64)
+        })
+}
+
+/// The video mode QEMU sets for a `vga=` parameter of the command line.
+fn video_mode(cmdline: &str) -> Option<u16> {
+    let mode = &cmdline[cmdline.find("vga=")? + 4..];
+    Some(if mode.starts_with("normal") {
+        0xffff
+    } else if mode.starts_with("ext") {
+        0xfffe
+    } else if mode.starts_with("ask") {
+        0xfffd
+    } else {
+        strtol(mode) as u16
+    })
+}
+
+/// Returns the kernel image with its setup header filled in as QEMU does
+/// for a direct boot with the given command line and initrd size.
+///
+/// `below_4g` is the guest RAM QEMU maps below 4 GiB, which decides where
+/// the initrd goes; it is only read if there is an initrd.
+pub fn patch(
+    kernel: &[u8],
+    initrd: Option<usize>,
+    cmdline: &str,
+    below_4g: u64,
+) -> Result<Vec<u8>> {
+    if kernel.len() < 0x250 || u32_at(kernel, 0x202) != MAGIC {
+        return Err(invalid("no setup header"));
+    }
+    let protocol = u16_at(kernel, 0x206);
+    if protocol < 0x200 {
+        return Err(invalid("boot protocol older than 2.00"));
+    }
+
+    let sectors = match kernel[0x1f1] {
+        0 => 4,
+        n => n as usize,
+    };
+    if (sectors + 1) * 512 > kernel.len() {
+        return Err(invalid("truncated setup"));
+    }
+
+    let mut image = kernel.to_vec();
+
+    let cmdline_size = (cmdline.len() as u32 + 16) & !15;
+    let (real_addr, cmdline_addr) = match protocol {
+        0x200..=0x201 => (LEGACY_REAL_ADDR, LEGACY_CMDLINE_END - cmdline_size),
+        _ => (REAL_ADDR, CMDLINE_ADDR),
+    };
+
+    if protocol >= 0x202 {
+        put_u32(&mut image, 0x228, cmdline_addr);
+    } else {
+        put_u16(&mut image, 0x20, 0xa33f);
+        put_u16(&mut image, 0x22, (cmdline_addr - real_addr) as u16);
+    }
+
+    if let Some(mode) = video_mode(cmdline) {
+        put_u16(&mut image, 0x1fa, mode);
+    }
+
+    image[0x210] = LOADER_QEMU;
+    if protocol >= 0x201 {
+        image[0x211] |= CAN_USE_HEAP;
+        put_u16(&mut image, 0x224, (cmdline_addr - real_addr - 0x200) as u16);
+    }
+
+    if let Some(size) = initrd {
+        let mut max =
+            if protocol >= 0x20c && u16_at(&image, 0x236) & XLF_CAN_BE_LOADED_ABOVE_4G != 0 {
+                u32::MAX as u64
+            } else if protocol >= 0x203 {
+                u32_at(&image, 0x22c) as u64
+            } else {
+                0x37ff_ffff
+            };
+        let top = below_4g
+            .checked_sub(ACPI_DATA_SIZE)
+            .ok_or_else(|| invalid("not enough guest RAM below 4 GiB"))?;
+        if max >= top {
+            max = top - 1;
+        }
+
+        let addr = max
+            .checked_sub(size as u64)
+            .ok_or_else(|| invalid("initrd too large"))?
+            & !4095;
+        put_u32(&mut image, 0x218, addr as u32);
+        put_u32(&mut image, 0x21c, size as u32);
+    }
+
+    Ok(image)
+}
+
+/// A loaded-high bzImage with boot protocol 2.15 and a 4-sector setup.
+#[cfg(test)]
+pub(super) fn bzimage() -> Vec<u8> {
+    let mut image = vec![0u8; 5 * 512 + 1024];
+    image[0x1f1] = 4;
+    put_u16(&mut image, 0x1fa, 0xffff);
+    put_u32(&mut image, 0x202, MAGIC);
+    put_u16(&mut image, 0x206, 0x20f);
+    image[0x211] = 0x01;
+    put_u32(&mut image, 0x22c, 0x7fff_ffff);
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_header() {
+        let image = bzimage();
+        let patched = patch(&image, Some(4096), "console=ttyS0 vga=0x317", 128 << 20).unwrap();
+
+        assert_eq!(patched[0x210], LOADER_QEMU);
+        assert_eq!(patched[0x211], 0x01 | CAN_USE_HEAP);
+        assert_eq!(u16_at(&patched, 0x224), 0xfe00);
+        assert_eq!(u32_at(&patched, 0x228), 0x20000);
+        assert_eq!(u16_at(&patched, 0x1fa), 0x317);
+        // Below the ACPI tables, page aligned.
+        assert_eq!(u32_at(&patched, 0x218), 0x07fd_6000);
+        assert_eq!(u32_at(&patched, 0x21c), 4096);
+        // The rest is left as is.
+        assert_eq!(patched[0x250..], image[0x250..]);
+
+        let patched = patch(&image, None, "", 0).unwrap();
+        assert_eq!(u32_at(&patched, 0x218), 0);
+        assert_eq!(u16_at(&patched, 0x1fa), 0xffff);
+    }
+
+    #[test]
+    fn not_bzimage() {
+        patch(b"kernel", None, "", 0).unwrap_err();
+
+        let mut image = bzimage();
+        image[0x1f1] = 10;
+        patch(&image, None, "", 0).unwrap_err();
+        patch(&bzimage(), Some(4096), "", 0).unwrap_err();
+    }
+
+    #[test]
+    fn vga() {
+        assert_eq!(video_mode("quiet"), None);
+        assert_eq!(video_mode("vga=ask"), Some(0xfffd));
+        assert_eq!(video_mode("vga=791 quiet"), Some(791));
+        assert_eq!(video_mode("vga=0x31b"), Some(0x31b));
+        assert_eq!(video_mode("vga=017"), Some(15));
+    }
+}
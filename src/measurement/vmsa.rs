@@ -0,0 +1,263 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The VM save areas (VMSA) of CSV2 guests.
+//!
+//! CSV2 encrypts the register state of every vCPU at launch with
+//! LAUNCH_UPDATE_VMSA, which adds the VMSA pages to the launch digest
+//! after the guest memory. The initial state is the x86 reset state, with
+//! the application processors starting at the reset vector the firmware
+//! publishes in its GUIDed footer table.
+
+use std::io::{Error, ErrorKind, Result};
+use uuid::{uuid, Uuid};
+
+/// The size of a VMSA page.
+pub const PAGE_SIZE: usize = 4096;
+
+/// The GUID ending the footer table of OVMF images.
+const FOOTER: Uuid = uuid!("96b582de-1fb2-45f7-baea-a366c55a082d");
+
+/// The GUID of the footer entry holding the AP reset vector.
+const RESET_BLOCK: Uuid = uuid!("00f771de-1a7e-4fcb-890e-68c77e2fb44e");
+
+/// The GUID and the length preceding the data of a footer entry.
+const ENTRY_HEADER: usize = 18;
+
+/// Returns the data of the entry tagged `guid` in the footer table of an
+/// OVMF image.
+///
+/// The table ends 32 bytes before the end of the image. Entries are read
+/// backwards: each is its data, then its length as a little-endian `u16`,
+/// then its GUID, and the length covers all three.
+fn footer_entry(firmware: &[u8], guid: Uuid) -> Result<&[u8]> {
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("OVMF footer: {msg}"));
+
+    let footer = firmware
+        .len()
+        .checked_sub(32 + ENTRY_HEADER)
+        .ok_or_else(|| invalid("image too small"))?;
+    if firmware[footer + 2..footer + ENTRY_HEADER] != FOOTER.to_bytes_le() {
+        return Err(invalid("no footer table"));
+    }
+
+    let size = u16::from_le_bytes([firmware[footer], firmware[footer + 1]]) as usize;
+    let start = (footer + ENTRY_HEADER)
+        .checked_sub(size)
+        .filter(|_| size >= ENTRY_HEADER)
+        .ok_or_else(|| invalid("invalid table size"))?;
+    let mut table = &firmware[start..footer];
+
+    while table.len() >= ENTRY_HEADER {
+        let (rest, header) = table.split_at(table.len() - ENTRY_HEADER);
+        let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+        if len < ENTRY_HEADER || len > table.len() {
+            return Err(invalid("invalid entry size"));
+        }
+
+        let data = &rest[rest.len() + ENTRY_HEADER - len..];
+        if header[2..] == guid.to_bytes_le() {
+            return Ok(data);
+        }
+        table = &table[..table.len() - len];
+    }
+
+    Err(invalid(&format!("no entry {guid}")))
+}
+
+/// Returns the reset vector of the application processors of CSV2
+/// guests, from the footer table of an OVMF image.
+pub fn ap_reset_eip(firmware: &[u8]) -> Result<u32> {
+    let data = footer_entry(firmware, RESET_BLOCK)?;
+    let eip = data
+        .get(..4)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "OVMF footer: short reset block"))?;
+    Ok(u32::from_le_bytes(eip.try_into().unwrap()))
+}
+
+/// A segment register.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Segment {
+    /// The selector.
+    pub selector: u16,
+
+    /// The attributes, in the VMCB encoding.
+    pub attrib: u16,
+
+    /// The limit.
+    pub limit: u32,
+
+    /// The base address.
+    pub base: u64,
+}
+
+impl Segment {
+    const fn new(selector: u16, attrib: u16, limit: u32, base: u64) -> Self {
+        Self {
+            selector,
+            attrib,
+            limit,
+            base,
+        }
+    }
+
+    fn write(&self, page: &mut [u8]) {
+        page[0..2].copy_from_slice(&self.selector.to_le_bytes());
+        page[2..4].copy_from_slice(&self.attrib.to_le_bytes());
+        page[4..8].copy_from_slice(&self.limit.to_le_bytes());
+        page[8..16].copy_from_slice(&self.base.to_le_bytes());
+    }
+}
+
+/// The initial register state of a vCPU of a CSV2 guest.
+///
+/// Registers not listed are zero in the encrypted VMSA.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Vmsa {
+    pub es: Segment,
+    pub cs: Segment,
+    pub ss: Segment,
+    pub ds: Segment,
+    pub fs: Segment,
+    pub gs: Segment,
+    pub gdtr: Segment,
+    pub ldtr: Segment,
+    pub idtr: Segment,
+    pub tr: Segment,
+    pub efer: u64,
+    pub cr4: u64,
+    pub cr3: u64,
+    pub cr0: u64,
+    pub dr7: u64,
+    pub dr6: u64,
+    pub rflags: u64,
+    pub rip: u64,
+    pub rsp: u64,
+    pub rax: u64,
+    pub rdx: u64,
+    pub g_pat: u64,
+    pub xcr0: u64,
+}
+
+impl Vmsa {
+    /// The reset state of a vCPU starting at `eip`, as set up by KVM.
+    ///
+    /// `cpu_signature` is the processor signature of the host, CPUID
+    /// function 1 EAX, which the vCPU finds in RDX after reset.
+    pub fn reset(eip: u32, cpu_signature: u32) -> Self {
+        let data = Segment::new(0, 0x93, 0xffff, 0);
+
+        Self {
+            es: data,
+            cs: Segment::new(0xf000, 0x9b, 0xffff, (eip & 0xffff_0000) as u64),
+            ss: data,
+            ds: data,
+            fs: data,
+            gs: data,
+            gdtr: Segment::new(0, 0, 0xffff, 0),
+            ldtr: Segment::new(0, 0x82, 0xffff, 0),
+            idtr: Segment::new(0, 0, 0xffff, 0),
+            tr: Segment::new(0, 0x83, 0xffff, 0),
+            efer: 0x1000,
+            cr4: 0x40,
+            cr3: 0,
+            cr0: 0x10,
+            dr7: 0x400,
+            dr6: 0xffff_0ff0,
+            rflags: 0x2,
+            rip: (eip & 0xffff) as u64,
+            rsp: 0,
+            rax: 0,
+            rdx: cpu_signature as u64,
+            g_pat: 0x0007_0406_0007_0406,
+            xcr0: 0x1,
+        }
+    }
+
+    /// The reset state of the bootstrap processor.
+    pub fn bsp(cpu_signature: u32) -> Self {
+        Self::reset(0xffff_fff0, cpu_signature)
+    }
+
+    /// The VMSA page as measured by the firmware.
+    pub fn page(&self) -> Box<[u8; PAGE_SIZE]> {
+        let mut page = Box::new([0u8; PAGE_SIZE]);
+
+        let segments = [
+            &self.es, &self.cs, &self.ss, &self.ds, &self.fs, &self.gs, &self.gdtr, &self.ldtr,
+            &self.idtr, &self.tr,
+        ];
+        for (index, segment) in segments.into_iter().enumerate() {
+            segment.write(&mut page[index * 16..]);
+        }
+
+        for (offset, value) in [
+            (0x0d0, self.efer),
+            (0x148, self.cr4),
+            (0x150, self.cr3),
+            (0x158, self.cr0),
+            (0x160, self.dr7),
+            (0x168, self.dr6),
+            (0x170, self.rflags),
+            (0x178, self.rip),
+            (0x1d8, self.rsp),
+            (0x1f8, self.rax),
+            (0x268, self.g_pat),
+            (0x310, self.rdx),
+            (0x3e8, self.xcr0),
+        ] {
+            page[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+
+        page
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an image ending with a footer table holding `entries`.
+    fn image(entries: &[(Uuid, &[u8])]) -> Vec<u8> {
+        let mut table = Vec::new();
+        for (guid, data) in entries {
+            table.extend_from_slice(data);
+            table.extend_from_slice(&((data.len() + ENTRY_HEADER) as u16).to_le_bytes());
+            table.extend_from_slice(&guid.to_bytes_le());
+        }
+        table.extend_from_slice(&((table.len() + ENTRY_HEADER) as u16).to_le_bytes());
+        table.extend_from_slice(&FOOTER.to_bytes_le());
+
+        let mut image = vec![0u8; 64];
+        image.extend_from_slice(&table);
+        image.extend_from_slice(&[0u8; 32]);
+        image
+    }
+
+    #[test]
+    fn reset_block() {
+        let other = uuid!("7255371f-3a3b-4b04-927b-1da6efa8d454");
+        let firmware = image(&[
+            (RESET_BLOCK, &0xffff_b000u32.to_le_bytes()),
+            (other, &[1u8; 12]),
+        ]);
+        assert_eq!(ap_reset_eip(&firmware).unwrap(), 0xffff_b000);
+        assert!(ap_reset_eip(&image(&[(other, &[1u8; 12])])).is_err());
+        assert!(ap_reset_eip(&[0u8; 4096]).is_err());
+    }
+
+    #[test]
+    fn page() {
+        let ap = Vmsa::reset(0xffff_b000, 0x0090_0f12);
+        assert_eq!(ap.cs.selector, 0xf000);
+        assert_eq!(ap.cs.base, 0xffff_0000);
+        assert_eq!(ap.rip, 0xb000);
+        assert_eq!(Vmsa::bsp(0).rip, 0xfff0);
+
+        let page = ap.page();
+        assert_eq!(page[16..20], [0x00, 0xf0, 0x9b, 0x00]);
+        assert_eq!(page[0x178..0x180], 0xb000u64.to_le_bytes());
+        assert_eq!(page[0x310..0x318], 0x0090_0f12u64.to_le_bytes());
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reading and writing the crate's firmware structures as raw bytes.
+//!
+//! The firmware exchanges `#[repr(C)]` structures verbatim. The functions
+//! below copy them from and to any reader or writer, e.g. a VMM reading a
+//! report out of guest memory, without going through their encoders.
+
+use crate::{
+    api::{guest, launch, platform},
+    layout::pod,
+};
+
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    mem::{size_of, MaybeUninit},
+};
+
+pub use crate::layout::{as_bytes, Pod};
+
+pod! {
+    guest::ReportReq = 112,
+    guest::ReportRsp = 4096,
+    launch::Measurement = 48,
+    platform::PlatformStatus = 12,
+}
+
+/// Reads a value from exactly `size_of::<T>()` bytes of `bytes`.
+///
+/// `bytes` needs no particular alignment. Extra trailing bytes are an error.
+pub fn from_bytes<T: Pod>(bytes: &[u8]) -> Result<T> {
+    crate::layout::read(bytes).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("expected {} bytes, got {}", size_of::<T>(), bytes.len()),
+        )
+    })
+}
+
+/// Reads a value from `reader`.
+pub fn load<T: Pod>(mut reader: impl Read) -> Result<T> {
+    let mut value = MaybeUninit::<T>::zeroed();
+
+    // SAFETY: zeroed memory is a valid `T`, so it may be viewed as
+    // initialized bytes and overwritten with any content.
+    let bytes =
+        unsafe { std::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    reader.read_exact(bytes)?;
+
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Writes a value to `writer`.
+pub fn save<T: Pod>(mut writer: impl Write, value: &T) -> Result<()> {
+    writer.write_all(as_bytes(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+
+    #[test]
+    fn roundtrip() {
+        let len = size_of::<guest::AttestationReport>();
+        let report: guest::AttestationReport = load(REPORT).unwrap();
+        let signer: guest::ReportSigner = from_bytes(&REPORT[len..]).unwrap();
+
+        let mut out = Vec::new();
+        save(&mut out, &report).unwrap();
+        out.extend_from_slice(as_bytes(&signer));
+        assert_eq!(out, REPORT);
+    }
+
+    #[test]
+    fn length() {
+        assert!(from_bytes::<guest::AttestationReport>(&REPORT[1..337]).is_ok());
+        assert!(from_bytes::<guest::AttestationReport>(REPORT).is_err());
+        assert!(load::<guest::ReportSigner>(&REPORT[..100]).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Python bindings, for attestation pipelines scripted in Python.
+//!
+//! Built with the `python` feature as the `csv_rs` extension module, e.g.
+//! with maturin:
+//!
+//! ```sh
+//! maturin build --release --features python,pyo3/extension-module
+//! ```
+//!
+//! Evidence is exchanged as `bytes`: the attestation report followed by
+//! its signer, with the PEK certificate and serial number already
+//! restored, the same layout as the C interface.
+//!
+//! ```python
+//! import csv_rs
+//!
+//! evidence = csv_rs.get_report(data=bytes(64))
+//! csv_rs.verify(evidence, open("hsk_cek.cert", "rb").read())
+//! print(csv_rs.report_to_dict(evidence)["body"]["measure"])
+//! ```
+
+use crate::{
+    api::guest::{json::Hex, AttestationReport, CsvGuest, ReportSigner},
+    certs::HskCek,
+    error::Error,
+    evidence::Evidence,
+    pod,
+};
+
+use codicon::Decoder;
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyOSError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+use std::mem::size_of;
+
+create_exception!(
+    csv_rs,
+    VerificationError,
+    PyException,
+    "The evidence does not verify."
+);
+
+/// The size of evidence: a report followed by its signer.
+const EVIDENCE_SIZE: usize = AttestationReport::SIZE + size_of::<ReportSigner>();
+
+fn invalid(e: impl ToString) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// Copies `N` bytes, the exact length of a field of the request.
+fn array<const N: usize>(name: &str, bytes: Option<&[u8]>) -> PyResult<Option<[u8; N]>> {
+    bytes
+        .map(|bytes| {
+            bytes
+                .try_into()
+                .map_err(|_| invalid(format!("{name} must be {N} bytes")))
+        })
+        .transpose()
+}
+
+/// Parses evidence along with the HSK and CEK certificates of its chip.
+fn evidence(evidence: &[u8], chain: &[u8]) -> PyResult<Evidence> {
+    if evidence.len() != EVIDENCE_SIZE {
+        return Err(invalid(format!(
+            "expected {EVIDENCE_SIZE} bytes of evidence, got {}",
+            evidence.len()
+        )));
+    }
+
+    let (report, signer) = evidence.split_at(AttestationReport::SIZE);
+    let report = AttestationReport::from_bytes(report).map_err(invalid)?;
+    let signer: ReportSigner = pod::from_bytes(signer).map_err(invalid)?;
+    let chain = HskCek::decode(chain, ()).map_err(invalid)?;
+
+    Evidence::new(report, signer, chain).map_err(invalid)
+}
+
+/// Requests an attestation report from the csv-guest device and returns
+/// the evidence.
+///
+/// `data` (64 bytes) is bound into the report, and `mnonce` (16 bytes) is
+/// random unless given.
+#[pyfunction]
+#[pyo3(signature = (data=None, mnonce=None))]
+fn get_report<'py>(
+    py: Python<'py>,
+    data: Option<&[u8]>,
+    mnonce: Option<&[u8]>,
+) -> PyResult<&'py PyBytes> {
+    let data = array("data", data)?;
+    let mnonce = array("mnonce", mnonce)?;
+
+    let (report, signer) = py
+        .allow_threads(|| {
+            CsvGuest::open()
+                .map_err(Error::from)
+                .and_then(|guest| guest.get_report(data, mnonce))
+        })
+        .map_err(|e| PyOSError::new_err(e.to_string()))?;
+
+    Ok(PyBytes::new(
+        py,
+        &[report.as_bytes(), pod::as_bytes(&signer)].concat(),
+    ))
+}
+
+/// Verifies evidence against the HSK and CEK certificates of its chip, as
+/// served by the KDS, and the builtin HYGON root key.
+///
+/// Raises `VerificationError` if the evidence does not verify.
+#[pyfunction]
+fn verify(py: Python<'_>, evidence: &[u8], chain: &[u8]) -> PyResult<()> {
+    let evidence = self::evidence(evidence, chain)?;
+
+    py.allow_threads(|| evidence.verify())
+        .map_err(|e| VerificationError::new_err(e.to_string()))
+}
+
+/// Returns a report, or the report of evidence, as a `dict` with its body
+/// fields unmasked and byte arrays as hex strings, see
+/// [`crate::api::guest::json`].
+#[pyfunction]
+fn report_to_dict(py: Python<'_>, report: &[u8]) -> PyResult<PyObject> {
+    if report.len() != AttestationReport::SIZE && report.len() != EVIDENCE_SIZE {
+        return Err(invalid(format!(
+            "expected {} bytes of report or {EVIDENCE_SIZE} bytes of evidence, got {}",
+            AttestationReport::SIZE,
+            report.len()
+        )));
+    }
+
+    let report =
+        AttestationReport::from_bytes(&report[..AttestationReport::SIZE]).map_err(invalid)?;
+    let json = serde_json::to_string(&Hex(&report)).map_err(invalid)?;
+
+    Ok(py.import("json")?.call_method1("loads", (json,))?.into())
+}
+
+/// The `csv_rs` Python module.
+#[pymodule]
+fn csv_rs(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("VerificationError", py.get_type::<VerificationError>())?;
+    m.add("EVIDENCE_SIZE", EVIDENCE_SIZE)?;
+    m.add_function(wrap_pyfunction!(get_report, m)?)?;
+    m.add_function(wrap_pyfunction!(verify, m)?)?;
+    m.add_function(wrap_pyfunction!(report_to_dict, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evidence::hag, util::hex};
+
+    const REPORT: &[u8] = include_bytes!("../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../tests/test_data/hsk_cek.cert");
+
+    fn bytes() -> (Evidence, Vec<u8>) {
+        let evidence = hag::read(REPORT, HSK_CEK).unwrap();
+        let bytes = [evidence.report.as_bytes(), pod::as_bytes(&evidence.signer)].concat();
+        (evidence, bytes)
+    }
+
+    /// Runs `f` with the `csv_rs` module, as Python imports it.
+    fn with_module(f: impl FnOnce(Python<'_>, &PyModule)) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let m = PyModule::new(py, "csv_rs").unwrap();
+            csv_rs(py, m).unwrap();
+            f(py, m)
+        })
+    }
+
+    #[test]
+    fn parse() {
+        let (_, bytes) = bytes();
+        evidence(&bytes, HSK_CEK).unwrap().verify().unwrap();
+
+        assert!(evidence(&bytes[1..], HSK_CEK).is_err());
+        assert!(evidence(&bytes, &HSK_CEK[1..]).is_err());
+        assert!(array::<16>("mnonce", Some(&[0u8; 15])).is_err());
+        assert_eq!(array::<16>("mnonce", None).unwrap(), None);
+    }
+
+    #[test]
+    fn verify() {
+        let (_, mut bytes) = bytes();
+        with_module(|py, m| {
+            let verify = m.getattr("verify").unwrap();
+            verify
+                .call1((PyBytes::new(py, &bytes), PyBytes::new(py, HSK_CEK)))
+                .unwrap();
+
+            bytes[0] ^= 1;
+            let err = verify
+                .call1((PyBytes::new(py, &bytes), PyBytes::new(py, HSK_CEK)))
+                .unwrap_err();
+            assert!(err.is_instance_of::<VerificationError>(py));
+
+            let err = verify
+                .call1((PyBytes::new(py, &bytes[1..]), PyBytes::new(py, HSK_CEK)))
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+        });
+    }
+
+    #[test]
+    fn report_to_dict() {
+        let (evidence, bytes) = bytes();
+        let report = &evidence.report;
+        with_module(|py, m| {
+            let report_to_dict = m.getattr("report_to_dict").unwrap();
+            for bytes in [&bytes[..], &bytes[..AttestationReport::SIZE]] {
+                let dict = report_to_dict.call1((PyBytes::new(py, bytes),)).unwrap();
+                let measure: String = dict
+                    .get_item("body")
+                    .and_then(|body| body.get_item("measure"))
+                    .and_then(|measure| measure.extract())
+                    .unwrap();
+                assert_eq!(measure, hex::encode(&report.unmask(report.body.measure)));
+            }
+
+            let err = report_to_dict
+                .call1((PyBytes::new(py, &bytes[1..]),))
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+        });
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A QEMU Machine Protocol client for the launch of CSV guests.
+//!
+//! QEMU exposes the launch measurement and secret injection of its
+//! `sev-guest` object, which also drives CSV guests, over QMP. The guest
+//! owner verifies the measurement with a [`crate::session::Session`] and
+//! answers with a secret packet, which the helpers below carry to and from
+//! a running QEMU:
+//!
+//! ```no_run
+//! # fn run(session: csv_rs::session::Session<csv_rs::session::Measuring>)
+//! #     -> std::io::Result<()> {
+//! use csv_rs::{api::launch::HeaderFlags, qmp::Qmp, Build, Version};
+//!
+//! let mut qmp = Qmp::connect("/run/qemu/guest.qmp")?;
+//! let measurement = qmp.launch_measure()?;
+//!
+//! let build = Build { version: Version { major: 1, minor: 2 }, build: 0 };
+//! let session = session.verify(build, measurement)?;
+//! qmp.inject_launch_secret(&session.secret(HeaderFlags::empty(), b"disk key")?, None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    api::launch::{Header, Measurement, Secret},
+    pod,
+};
+
+use openssl::base64;
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Error, ErrorKind, Result, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+};
+
+/// A QMP connection in command mode.
+pub struct Qmp {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Qmp {
+    /// Connects to the QMP socket of a running QEMU, e.g. one started
+    /// with `-qmp unix:/run/qemu/guest.qmp,server=on,wait=off`.
+    pub fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_stream(UnixStream::connect(path)?)
+    }
+
+    /// Negotiates command mode over an already connected stream.
+    pub fn from_stream(stream: UnixStream) -> Result<Self> {
+        let mut qmp = Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+
+        let greeting = qmp.receive()?;
+        if greeting.get("QMP").is_none() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the peer did not greet with QMP",
+            ));
+        }
+
+        qmp.execute("qmp_capabilities", None)?;
+        Ok(qmp)
+    }
+
+    fn receive(&mut self) -> Result<Value> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        serde_json::from_str(&line).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Executes a command, returning its `return` value.
+    ///
+    /// Asynchronous events received meanwhile are discarded. A QMP error
+    /// response fails with its description.
+    pub fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value> {
+        let mut request = json!({ "execute": command });
+        if let Some(arguments) = arguments {
+            request["arguments"] = arguments;
+        }
+
+        let mut line = request.to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+
+        loop {
+            let mut response = self.receive()?;
+            if let Some(value) = response.get_mut("return") {
+                return Ok(value.take());
+            }
+
+            if let Some(error) = response.get("error") {
+                let desc = error["desc"].as_str().unwrap_or("unknown error");
+                return Err(Error::other(format!("{command}: {desc}")));
+            }
+        }
+    }
+
+    /// Retrieves the launch measurement of the guest, as for
+    /// `query-sev-launch-measure`.
+    pub fn launch_measure(&mut self) -> Result<Measurement> {
+        let value = self.execute("query-sev-launch-measure", None)?;
+        let data = value["data"]
+            .as_str()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "no measurement in the response"))?;
+
+        pod::from_bytes(&decode(data)?)
+    }
+
+    /// Injects a secret packet into the guest, as for
+    /// `sev-inject-launch-secret`.
+    ///
+    /// Without `gpa`, QEMU injects the secret into the area the guest
+    /// firmware reserved for it.
+    pub fn inject_launch_secret(&mut self, secret: &Secret, gpa: Option<u64>) -> Result<()> {
+        let mut arguments = json!({
+            "packet-header": base64::encode_block(&header_bytes(&secret.header)),
+            "secret": base64::encode_block(&secret.ciphertext),
+        });
+        if let Some(gpa) = gpa {
+            arguments["gpa"] = gpa.into();
+        }
+
+        self.execute("sev-inject-launch-secret", Some(arguments))?;
+        Ok(())
+    }
+}
+
+/// The packet header in the layout the firmware expects.
+fn header_bytes(header: &Header) -> Vec<u8> {
+    let mut bytes = header.flags.bits().to_ne_bytes().to_vec();
+    bytes.extend_from_slice(&header.iv);
+    bytes.extend_from_slice(&header.mac);
+    bytes
+}
+
+fn decode(data: &str) -> Result<Vec<u8>> {
+    base64::decode_block(data).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::launch::HeaderFlags;
+
+    use std::thread;
+
+    /// Answers each command with the given responses, after an event.
+    fn qemu(stream: UnixStream, responses: Vec<Value>) -> thread::JoinHandle<Vec<Value>> {
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut requests = Vec::new();
+
+            writeln!(
+                writer,
+                r#"{{"QMP": {{"version": {{}}, "capabilities": []}}}}"#
+            )
+            .unwrap();
+            for response in responses {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                requests.push(serde_json::from_str(&line).unwrap());
+
+                writeln!(writer, r#"{{"event": "RESUME"}}"#).unwrap();
+                writeln!(writer, "{response}").unwrap();
+            }
+
+            requests
+        })
+    }
+
+    #[test]
+    fn launch() {
+        let measurement = [[1u8; 32].as_slice(), &[2u8; 16]].concat();
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = qemu(
+            server,
+            vec![
+                json!({ "return": {} }),
+                json!({ "return": { "data": base64::encode_block(&measurement) } }),
+                json!({ "return": {} }),
+                json!({ "error": { "class": "GenericError", "desc": "no SEV guest" } }),
+            ],
+        );
+
+        let mut qmp = Qmp::from_stream(client).unwrap();
+        let measured = qmp.launch_measure().unwrap();
+        assert_eq!(measured.measure, [1u8; 32]);
+        assert_eq!(measured.mnonce, [2u8; 16]);
+
+        let secret = Secret {
+            header: Header {
+                flags: HeaderFlags::empty(),
+                iv: [3u8; 16],
+                mac: [4u8; 32],
+            },
+            ciphertext: vec![5u8; 8],
+        };
+        qmp.inject_launch_secret(&secret, Some(0x1000)).unwrap();
+
+        let err = qmp.execute("query-sev", None).unwrap_err();
+        assert_eq!(err.to_string(), "query-sev: no SEV guest");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0]["execute"], "qmp_capabilities");
+        assert_eq!(requests[1]["execute"], "query-sev-launch-measure");
+
+        let inject = &requests[2]["arguments"];
+        assert_eq!(inject["gpa"], 0x1000);
+        let header = decode(inject["packet-header"].as_str().unwrap()).unwrap();
+        assert_eq!(header.len(), 52);
+        assert_eq!(header[4..20], [3u8; 16]);
+    }
+}
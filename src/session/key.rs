@@ -6,6 +6,7 @@
 use super::*;
 
 use std::{
+    io::Write,
     ops::{Deref, DerefMut},
     ptr::write_volatile,
 };
@@ -25,6 +26,14 @@ impl Drop for Key {
     }
 }
 
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Key")
+            .field(&crate::util::redact::Redacted(&self.0))
+            .finish()
+    }
+}
+
 impl Deref for Key {
     type Target = [u8];
 
@@ -57,9 +66,9 @@ impl Key {
         Key(vec![0u8; size])
     }
 
-    pub fn random(size: usize) -> Result<Self> {
+    pub fn random(size: usize, rng: &mut dyn ::rand::RngCore) -> Result<Self> {
         let mut key = Key::zeroed(size);
-        rand::rand_bytes(&mut key)?;
+        super::fill(rng, &mut key)?;
         Ok(key)
     }
 
@@ -0,0 +1,294 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage of the transport keys outside of a session.
+
+use super::fill;
+use crate::util::redact::Redacted;
+
+use std::{
+    ffi::CString,
+    fmt,
+    io::{Error, ErrorKind, Result},
+    ptr::{null_mut, write_volatile, NonNull},
+};
+
+use ::rand::RngCore;
+
+/// The length of each transport key.
+const LEN: usize = 16;
+
+/// The keyring key type holding arbitrary data.
+const KEY_TYPE: &[u8] = b"user\0";
+
+// From <linux/keyctl.h>.
+const KEY_SPEC_USER_KEYRING: libc::c_long = -4;
+const KEYCTL_UNLINK: libc::c_long = 9;
+const KEYCTL_SEARCH: libc::c_long = 10;
+const KEYCTL_READ: libc::c_long = 11;
+
+/// Converts the result of a keyring syscall.
+fn check(ret: libc::c_long) -> Result<libc::c_long> {
+    if ret < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(ret)
+}
+
+/// Finds the key named `description` in the user keyring.
+fn search(description: &CString) -> Result<libc::c_long> {
+    check(unsafe {
+        libc::syscall(
+            libc::SYS_keyctl,
+            KEYCTL_SEARCH,
+            KEY_SPEC_USER_KEYRING,
+            KEY_TYPE.as_ptr(),
+            description.as_ptr(),
+            0,
+        )
+    })
+}
+
+fn description(description: &str) -> Result<CString> {
+    CString::new(description).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+}
+
+/// The Transport Encryption Key and Transport Integrity Key of a guest.
+///
+/// The keys are zeroed when dropped, and kept on a memory page of their
+/// own, locked against swapping when the process may lock memory, see
+/// `mlock(2)`. They may be persisted in the Linux user keyring, so that the
+/// measurement of a guest can be verified and secrets injected into it by
+/// another process than the one which launched it.
+pub struct SessionKeys {
+    page: NonNull<[u8; 2 * LEN]>,
+    len: usize,
+}
+
+// SAFETY: the page is owned by the keys and only reached through them.
+unsafe impl Send for SessionKeys {}
+unsafe impl Sync for SessionKeys {}
+
+impl fmt::Debug for SessionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionKeys")
+            .field("tek", &Redacted(self.tek()))
+            .field("tik", &Redacted(self.tik()))
+            .finish()
+    }
+}
+
+impl Drop for SessionKeys {
+    fn drop(&mut self) {
+        for b in self.keys_mut().iter_mut() {
+            unsafe {
+                write_volatile(b as *mut u8, 0u8);
+            }
+        }
+
+        // Unmapping the page also unlocks it.
+        unsafe {
+            libc::munmap(self.page.as_ptr() as *mut _, self.len);
+        }
+    }
+}
+
+impl SessionKeys {
+    /// Allocates zeroed keys on a page of their own, locked if permitted.
+    ///
+    /// Locks apply to whole pages and do not nest, so sharing the page
+    /// with other allocations would let their unlocking unlock the keys.
+    /// Failing to lock the page, e.g. under a zero `RLIMIT_MEMLOCK`, does
+    /// not fail the allocation: the keys are zeroed on drop either way.
+    fn zeroed() -> Result<Self> {
+        let len = check(unsafe { libc::sysconf(libc::_SC_PAGESIZE) })? as usize;
+        let page = unsafe {
+            libc::mmap(
+                null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if page == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+
+        unsafe {
+            libc::mlock(page, len);
+        }
+
+        // Anonymous mappings are zero-filled and never null.
+        Ok(Self {
+            page: NonNull::new(page as *mut _).unwrap(),
+            len,
+        })
+    }
+
+    fn keys(&self) -> &[u8; 2 * LEN] {
+        unsafe { self.page.as_ref() }
+    }
+
+    fn keys_mut(&mut self) -> &mut [u8; 2 * LEN] {
+        unsafe { self.page.as_mut() }
+    }
+
+    /// Copies the given keys, see [`SessionKeys`] for how they are kept.
+    pub fn new(tek: &[u8], tik: &[u8]) -> Result<Self> {
+        if tek.len() != LEN || tik.len() != LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("transport keys must be {LEN} bytes long"),
+            ));
+        }
+
+        let mut keys = Self::zeroed()?;
+        keys.keys_mut()[..LEN].copy_from_slice(tek);
+        keys.keys_mut()[LEN..].copy_from_slice(tik);
+        Ok(keys)
+    }
+
+    /// Draws fresh keys from `rng`.
+    pub fn random(rng: &mut dyn RngCore) -> Result<Self> {
+        let mut keys = Self::zeroed()?;
+        fill(rng, keys.keys_mut())?;
+        Ok(keys)
+    }
+
+    /// The Transport Encryption Key.
+    pub fn tek(&self) -> &[u8] {
+        &self.keys()[..LEN]
+    }
+
+    /// The Transport Integrity Key.
+    pub fn tik(&self) -> &[u8] {
+        &self.keys()[LEN..]
+    }
+
+    /// Stores the keys in the user keyring under `description`, replacing
+    /// any keys stored under the same name.
+    pub fn store(&self, description: &str) -> Result<()> {
+        let description = self::description(description)?;
+
+        check(unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                KEY_TYPE.as_ptr(),
+                description.as_ptr(),
+                self.keys().as_ptr(),
+                self.keys().len(),
+                KEY_SPEC_USER_KEYRING,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Reads the keys stored under `description` in the user keyring.
+    pub fn load(description: &str) -> Result<Self> {
+        let serial = search(&self::description(description)?)?;
+
+        let mut keys = Self::zeroed()?;
+        let len = check(unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_READ,
+                serial,
+                keys.keys_mut().as_mut_ptr(),
+                2 * LEN,
+            )
+        })?;
+
+        if len as usize != 2 * LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("the stored keys are {len} bytes long"),
+            ));
+        }
+
+        Ok(keys)
+    }
+
+    /// Removes the keys stored under `description` from the user keyring.
+    pub fn remove(description: &str) -> Result<()> {
+        let serial = search(&self::description(description)?)?;
+
+        check(unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_UNLINK,
+                serial,
+                KEY_SPEC_USER_KEYRING,
+            )
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Session;
+
+    #[test]
+    fn keys() {
+        let tek = [1u8; LEN];
+        let tik = [2u8; LEN];
+
+        let keys = SessionKeys::new(&tek, &tik).unwrap();
+        assert_eq!(keys.tek(), tek);
+        assert_eq!(keys.tik(), tik);
+        assert!(SessionKeys::new(&tek, &tik[1..]).is_err());
+
+        let session = Session::with_keys(Default::default(), &keys);
+        assert_eq!(session.keys().unwrap().tik(), tik);
+
+        let random = SessionKeys::random(&mut crate::crypto::OpensslRng).unwrap();
+        assert_ne!(random.tek(), random.tik());
+    }
+
+    #[test]
+    fn unlocked() {
+        const CHILD: &str = "CSV_RS_TEST_MEMLOCK_ZERO";
+
+        // The limit applies to the whole process, so lower it in a child
+        // running only this test rather than under the other tests.
+        if std::env::var_os(CHILD).is_none() {
+            let output = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "session::keys::tests::unlocked"])
+                .env(CHILD, "1")
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            return;
+        }
+
+        // A zero limit fails every mlock(2) of a process without
+        // CAP_IPC_LOCK.
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        assert_eq!(
+            unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) },
+            0
+        );
+        limit.rlim_cur = 0;
+        assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &limit) }, 0);
+
+        let keys = SessionKeys::new(&[1u8; LEN], &[2u8; LEN]).unwrap();
+        let measurement = crate::api::launch::Measurement {
+            measure: [3u8; 32],
+            mnonce: [4u8; 16],
+        };
+        unsafe { Session::with_keys(Default::default(), &keys).mock_verify(measurement) }
+            .unwrap()
+            .secret(Default::default(), b"disk key")
+            .unwrap();
+    }
+}
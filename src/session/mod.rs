@@ -6,17 +6,29 @@
 //! Utilities for creating a secure channel and facilitating the
 //! attestation process between the tenant and the HYGON SP.
 
+mod channel;
 mod key;
+mod keys;
+mod wrap;
+pub use channel::SecureChannel;
+pub use keys::SessionKeys;
+pub use wrap::{wrap_for_platform, wrap_for_platform_with_rng, Wrapped};
 
 use crate::certs::{csv, Signer, Usage};
-use crate::crypto::PrivateKey;
+use crate::crypto::{OpensslRng, PrivateKey};
 
 use super::*;
 
 use std::io::{Error, ErrorKind, Result};
 
+use ::rand::RngCore;
 use openssl::*;
 
+/// Fills `buf` from `rng`.
+fn fill(rng: &mut dyn RngCore, buf: &mut [u8]) -> Result<()> {
+    rng.try_fill_bytes(buf).map_err(Error::other)
+}
+
 /// Represents a brand-new secure channel with the HYGON SP.
 pub struct Initialized;
 
@@ -42,6 +54,14 @@ pub struct Session<T> {
     data: T,
 }
 
+impl<T> Session<T> {
+    /// Copies the transport keys into [`SessionKeys`], e.g. to persist
+    /// them with [`SessionKeys::store`].
+    pub fn keys(&self) -> Result<SessionKeys> {
+        SessionKeys::new(&self.tek, &self.tik)
+    }
+}
+
 impl api::launch::Policy {
     fn bytes(self) -> [u8; 4] {
         unsafe { std::mem::transmute(self) }
@@ -52,16 +72,35 @@ impl std::convert::TryFrom<api::launch::Policy> for Session<Initialized> {
     type Error = std::io::Error;
 
     fn try_from(value: api::launch::Policy) -> Result<Self> {
+        Self::with_rng(value, &mut OpensslRng)
+    }
+}
+
+impl Session<Initialized> {
+    /// Creates a session whose transport keys are drawn from `rng`.
+    ///
+    /// Injecting a seeded generator makes the session keys, nonces and IVs
+    /// reproducible. The ephemeral SM2 keys are still generated by OpenSSL.
+    pub fn with_rng(policy: api::launch::Policy, rng: &mut dyn RngCore) -> Result<Self> {
         Ok(Self {
-            tek: key::Key::random(16)?,
-            tik: key::Key::random(16)?,
+            tek: key::Key::random(16, rng)?,
+            tik: key::Key::random(16, rng)?,
             data: Initialized,
-            policy: value,
+            policy,
         })
     }
-}
 
-impl Session<Initialized> {
+    /// Creates a session with previously generated transport keys, e.g.
+    /// to verify the measurement of a guest launched by another process.
+    pub fn with_keys(policy: api::launch::Policy, keys: &SessionKeys) -> Self {
+        Self {
+            tek: key::Key::new(keys.tek().to_vec()),
+            tik: key::Key::new(keys.tik().to_vec()),
+            data: Initialized,
+            policy,
+        }
+    }
+
     fn session(
         &self,
         nonce: [u8; 16],
@@ -70,34 +109,13 @@ impl Session<Initialized> {
         pdh_host: &csv::Certificate,
         prv: PrivateKey<Usage>,
     ) -> Result<api::launch::Session> {
-        let master = z.derive(32, &nonce, "csv-master-secret")?;
-        let kek = master.derive(32, &[], "csv-kek")?;
-        let kik = master.derive(32, &[], "csv-kik")?;
         let uid = String::from("GUEST_USER_ID");
 
-        let mut crypter = symm::Crypter::new(
-            symm::Cipher::sm4_ctr(),
-            symm::Mode::Encrypt,
-            // real key is the first 16 bytes of the derived kek.
-            &kek[..16],
-            Some(&iv),
-        )?;
-
-        let mut wrap = [0u8; 32];
-        let mut off = 0;
-        off += crypter.update(&self.tek, &mut wrap[off..])?;
-        off += crypter.update(&self.tik, &mut wrap[off..])?;
-        off += crypter.finalize(&mut wrap[off..])?;
-        assert_eq!(off, wrap.len());
-
-        let mut concatenated_iv_wrap = Vec::new();
-        concatenated_iv_wrap.extend_from_slice(&iv);
-        concatenated_iv_wrap.extend_from_slice(&wrap);
-
-        let mut ms_enc = pdh_host.encrypt(master.get_ref())?;
-        ms_enc.resize(256, 0);
-
-        let wmac = kik.mac(&concatenated_iv_wrap)?;
+        let mut tk = key::Key::zeroed(32);
+        tk[..16].copy_from_slice(&self.tek);
+        tk[16..].copy_from_slice(&self.tik);
+        let wrapped = wrap::wrap(z, nonce, iv, pdh_host, &tk)?;
+        let ms_enc = wrapped.ms_enc;
 
         let mut session_data_needed_mac = Vec::new();
         session_data_needed_mac.extend_from_slice(&self.policy.bytes());
@@ -108,13 +126,13 @@ impl Session<Initialized> {
 
         let body: api::launch::SessionBody = api::launch::SessionBody {
             session_mac,
-            wrap_mac: wmac,
-            wrap_tk: wrap,
+            wrap_mac: wrapped.mac,
+            wrap_tk: wrapped.ciphertext.try_into().unwrap(),
             wrap_iv: iv,
             nonce,
             key_id: [0u8; 16],
             rnd_pub_key_data: [0u8; 148],
-            ms_enc: ms_enc.try_into().unwrap(),
+            ms_enc,
             vm_digest: [0u8; 32],
             pubkey_digest: [0u8; 32],
             vm_id: [0u8; 16],
@@ -136,6 +154,15 @@ impl Session<Initialized> {
 
     /// Produces data needed to initiate the CSV launch sequence.
     pub fn start(&self, chain: certs::Chain) -> Result<api::launch::Start> {
+        self.start_with_rng(chain, &mut OpensslRng)
+    }
+
+    /// Like [`Session::start`], drawing the session secrets from `rng`.
+    pub fn start_with_rng(
+        &self,
+        chain: certs::Chain,
+        rng: &mut dyn RngCore,
+    ) -> Result<api::launch::Start> {
         use certs::*;
 
         let pdh: &csv::Certificate = chain.verify()?;
@@ -143,13 +170,13 @@ impl Session<Initialized> {
         let (crt, prv) = csv::Certificate::generate(Usage::PDH, Some(uid))?;
         // get share key by random
         let mut share_key = [0u8; 16];
-        rand::rand_bytes(&mut share_key)?;
+        fill(rng, &mut share_key)?;
         let z = key::Key::new(share_key.to_vec());
 
         let mut nonce = [0u8; 16];
         let mut iv = [0u8; 16];
-        rand::rand_bytes(&mut nonce)?;
-        rand::rand_bytes(&mut iv)?;
+        fill(rng, &mut nonce)?;
+        fill(rng, &mut iv)?;
 
         let session = self.session(nonce, iv, z, &pdh, prv)?;
 
@@ -165,6 +192,15 @@ impl Session<Initialized> {
     /// Like the above start function, yet takes PDH as input instead of deriving it from a
     /// certificate chain.
     pub fn start_pdh(&self, pdh: certs::csv::Certificate) -> Result<api::launch::Start> {
+        self.start_pdh_with_rng(pdh, &mut OpensslRng)
+    }
+
+    /// Like [`Session::start_pdh`], drawing the session secrets from `rng`.
+    pub fn start_pdh_with_rng(
+        &self,
+        pdh: certs::csv::Certificate,
+        rng: &mut dyn RngCore,
+    ) -> Result<api::launch::Start> {
         use certs::*;
 
         let uid = String::from("GUEST_USER_ID");
@@ -172,13 +208,13 @@ impl Session<Initialized> {
 
         // get share key by random
         let mut share_key = [0u8; 16];
-        rand::rand_bytes(&mut share_key)?;
+        fill(rng, &mut share_key)?;
         let z = key::Key::new(share_key.to_vec());
 
         let mut nonce = [0u8; 16];
         let mut iv = [0u8; 16];
-        rand::rand_bytes(&mut nonce)?;
-        rand::rand_bytes(&mut iv)?;
+        fill(rng, &mut nonce)?;
+        fill(rng, &mut iv)?;
 
         let session = self.session(nonce, iv, z, &pdh, prv)?;
 
@@ -298,29 +334,20 @@ impl Session<Verified> {
         flags: api::launch::HeaderFlags,
         data: &[u8],
     ) -> Result<api::launch::Secret> {
-        let mut iv = [0u8; 16];
-        rand::rand_bytes(&mut iv)?;
-
-        let ciphertext = symm::encrypt(symm::Cipher::sm4_ctr(), &self.tek, Some(&iv), data)?;
-
-        let key = pkey::PKey::hmac(&self.tik)?;
-        let mut sig = sign::Signer::new(hash::MessageDigest::sm3(), &key)?;
-
-        sig.update(&[0x01u8])?;
-        sig.update(&unsafe { std::mem::transmute::<_, [u8; 4]>(flags) })?;
-        sig.update(&iv)?;
-        sig.update(&(data.len() as u32).to_le_bytes())?;
-        sig.update(&(ciphertext.len() as u32).to_le_bytes())?;
-        sig.update(&ciphertext)?;
-        sig.update(&self.data.0.measure)?;
-
-        let mut mac = [0u8; 32];
-        sig.sign(&mut mac)?;
+        self.secret_with_rng(flags, data, &mut OpensslRng)
+    }
 
-        Ok(api::launch::Secret {
-            header: api::launch::Header { flags, iv, mac },
-            ciphertext,
-        })
+    /// Like [`Session::secret`], drawing the IV from `rng`.
+    pub fn secret_with_rng(
+        &self,
+        flags: api::launch::HeaderFlags,
+        data: &[u8],
+        rng: &mut dyn RngCore,
+    ) -> Result<api::launch::Secret> {
+        api::launch::Secret::builder(self.keys()?, &self.data.0)
+            .flags(flags)
+            .rng(rng)
+            .build(data)
     }
 }
 
@@ -376,6 +403,33 @@ mod initialized {
     }
     */
 
+    #[test]
+    fn qemu_files() {
+        let policy = api::launch::Policy {
+            flags: api::launch::PolicyFlags::NO_DEBUG | api::launch::PolicyFlags::NO_SEND,
+            minfw: Version { major: 1, minor: 2 },
+        };
+        assert_eq!(u32::from(policy), 0x0012_0009);
+        assert_eq!(api::launch::Policy::from(u32::from(policy)), policy);
+
+        let (pdh, _) = csv::Certificate::generate(Usage::PDH, None).unwrap();
+        let start = Session::try_from(policy).unwrap().start_pdh(pdh).unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        start.save_qemu_files(dir).unwrap();
+
+        let read = |name| {
+            let b64 = std::fs::read_to_string(dir.join(name)).unwrap();
+            openssl::base64::decode_block(&b64).unwrap()
+        };
+        assert_eq!(
+            read(api::launch::DH_CERT_FILE),
+            crate::pod::as_bytes(&start.cert)
+        );
+        assert_eq!(read(api::launch::SESSION_FILE).len(), 772);
+    }
+
     #[test]
     fn verify() {
         let digest = [
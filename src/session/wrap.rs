@@ -0,0 +1,144 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Wrapping secrets for the HYGON SP of a platform, given its PDH.
+//!
+//! A master secret is derived from a random share key and a nonce, and is
+//! encrypted to the PDH. The KEK and KIK derived from the master secret
+//! then encrypt the secret with SM4-CTR and authenticate the IV and
+//! ciphertext with HMAC-SM3, as the firmware expects of `wrap_tk`.
+
+use super::{fill, key::Key};
+use crate::{certs::csv, crypto::OpensslRng};
+
+use std::io::{Error, ErrorKind, Result};
+
+use ::rand::RngCore;
+use openssl::symm;
+
+/// The length of the master secret encrypted to the PDH, as carried by
+/// the firmware structures.
+const MS_ENC: usize = 256;
+
+/// A secret wrapped for the HYGON SP holding a PDH.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Wrapped {
+    /// The context of the master secret derivation.
+    pub nonce: [u8; 16],
+
+    /// The master secret, encrypted to the PDH and padded with zeros.
+    pub ms_enc: [u8; MS_ENC],
+
+    /// The initialization vector of the encryption under the KEK.
+    pub iv: [u8; 16],
+
+    /// The secret encrypted under the KEK.
+    pub ciphertext: Vec<u8>,
+
+    /// The MAC of the IV and ciphertext under the KIK.
+    pub mac: [u8; 32],
+}
+
+/// Wraps `secret` for the HYGON SP holding `pdh`.
+///
+/// The caller is responsible for verifying `pdh` first, e.g. with
+/// [`crate::certs::Chain::verify`].
+pub fn wrap_for_platform(pdh: &csv::Certificate, secret: &[u8]) -> Result<Wrapped> {
+    wrap_for_platform_with_rng(pdh, secret, &mut OpensslRng)
+}
+
+/// Like [`wrap_for_platform`], drawing the share key, nonce and IV from
+/// `rng`. The encryption to the PDH still draws from OpenSSL.
+pub fn wrap_for_platform_with_rng(
+    pdh: &csv::Certificate,
+    secret: &[u8],
+    rng: &mut dyn RngCore,
+) -> Result<Wrapped> {
+    let z = Key::random(16, rng)?;
+
+    let mut nonce = [0u8; 16];
+    let mut iv = [0u8; 16];
+    fill(rng, &mut nonce)?;
+    fill(rng, &mut iv)?;
+
+    wrap(z, nonce, iv, pdh, secret)
+}
+
+/// Wraps `secret` under the keys derived from the share key `z`.
+pub(super) fn wrap(
+    z: Key,
+    nonce: [u8; 16],
+    iv: [u8; 16],
+    pdh: &csv::Certificate,
+    secret: &[u8],
+) -> Result<Wrapped> {
+    let master = z.derive(32, &nonce, "csv-master-secret")?;
+    let kek = master.derive(32, &[], "csv-kek")?;
+    let kik = master.derive(32, &[], "csv-kik")?;
+
+    // real key is the first 16 bytes of the derived kek.
+    let ciphertext = symm::encrypt(symm::Cipher::sm4_ctr(), &kek[..16], Some(&iv), secret)?;
+
+    let mut wrapped = iv.to_vec();
+    wrapped.extend_from_slice(&ciphertext);
+    let mac = kik.mac(&wrapped)?;
+
+    let mut ms_enc = pdh.encrypt(master.get_ref())?;
+    if ms_enc.len() > MS_ENC {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "the encrypted master secret does not fit",
+        ));
+    }
+    ms_enc.resize(MS_ENC, 0);
+
+    Ok(Wrapped {
+        nonce,
+        ms_enc: ms_enc.try_into().unwrap(),
+        iv,
+        ciphertext,
+        mac,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certs::Usage;
+
+    #[test]
+    fn unwrap() {
+        let (pdh, _) = csv::Certificate::generate(Usage::PDH, None).unwrap();
+        let secret = b"a secret longer than one SM4 block";
+
+        let wrapped = wrap(Key::zeroed(16), [1u8; 16], [2u8; 16], &pdh, secret).unwrap();
+        assert_eq!(wrapped.nonce, [1u8; 16]);
+        assert_eq!(wrapped.iv, [2u8; 16]);
+        assert_eq!(wrapped.ciphertext.len(), secret.len());
+        assert_ne!(wrapped.ms_enc, [0u8; MS_ENC]);
+
+        let master = Key::zeroed(16)
+            .derive(32, &[1u8; 16], "csv-master-secret")
+            .unwrap();
+        let kek = master.derive(32, &[], "csv-kek").unwrap();
+        let kik = master.derive(32, &[], "csv-kik").unwrap();
+
+        let plain = symm::decrypt(
+            symm::Cipher::sm4_ctr(),
+            &kek[..16],
+            Some(&wrapped.iv),
+            &wrapped.ciphertext,
+        )
+        .unwrap();
+        assert_eq!(plain, secret);
+        assert_eq!(
+            kik.mac(&[&[2u8; 16][..], &wrapped.ciphertext].concat())
+                .unwrap(),
+            wrapped.mac
+        );
+
+        let other = wrap_for_platform(&pdh, secret).unwrap();
+        assert_ne!(other.ciphertext, wrapped.ciphertext);
+    }
+}
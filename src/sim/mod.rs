@@ -0,0 +1,379 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A software stand-in for a HYGON platform and its guests.
+//!
+//! A [`Platform`] generates a complete key hierarchy, from a test HRK down
+//! to the PDH, and signs everything with real SM2 keys. The reports of its
+//! [`SimGuest`]s therefore pass a [`Verifier`] anchored at the test HRK,
+//! which allows end-to-end flows to run without HYGON hardware.
+//!
+//! ```
+//! use csv_rs::{api::guest::CsvGuest, evidence::Evidence, sim::Platform};
+//!
+//! let platform = Platform::new("SIM0000000001".parse().unwrap()).unwrap();
+//! let guest = CsvGuest::builder().transport(platform.guest()).open().unwrap();
+//!
+//! let evidence = Evidence::collect(&guest, Some([1u8; 64]), None, platform.hsk_cek()).unwrap();
+//! platform.verifier().verify(&evidence).unwrap();
+//! ```
+//!
+//! The platform side is simulated by the [`PlatformProvider`] commands of
+//! [`Platform`], returning what the matching `Firmware` commands would.
+
+use crate::{
+    api::{
+        guest::{ReportReq, ReportRsp, Transport},
+        platform::{Identifier, PlatformProvider, PlatformStatusFlags, State, Status},
+    },
+    certs::{self, ca, csv, HskCek, Signer, Usage},
+    crypto::PrivateKey,
+    error::{Error, Indeterminate},
+    pod,
+    test_utils::ReportBuilder,
+    verifier::Verifier,
+    Build, ChipId, Version,
+};
+
+use std::{io, mem::size_of, sync::Arc};
+
+/// A private key which may be shared between threads.
+struct Key(PrivateKey<Usage>);
+
+// The key is only read once generated, which OpenSSL allows concurrently.
+unsafe impl Send for Key {}
+unsafe impl Sync for Key {}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe { openssl_sys::EC_KEY_free(self.0.key) }
+    }
+}
+
+/// Generates a certificate signed by `signer`.
+fn issue(usage: Usage, signer: &PrivateKey<Usage>) -> io::Result<(csv::Certificate, Key)> {
+    let (mut cert, key) = csv::Certificate::generate(usage, None)?;
+    signer.sign(&mut cert, String::try_from(signer.usage)?)?;
+    Ok((cert, Key(key)))
+}
+
+/// A simulated platform with its own key hierarchy.
+pub struct Platform {
+    chip_id: ChipId,
+    build: Build,
+    hrk: ca::Certificate,
+    hsk: ca::Certificate,
+    cek: csv::Certificate,
+    oca: csv::Certificate,
+    pek: csv::Certificate,
+    pdh: csv::Certificate,
+    pek_key: Arc<Key>,
+}
+
+impl Platform {
+    /// Generates the key hierarchy of a platform running firmware 1.0
+    /// build 0.
+    pub fn new(chip_id: ChipId) -> io::Result<Self> {
+        Self::with_build(
+            chip_id,
+            Build {
+                version: Version { major: 1, minor: 0 },
+                build: 0,
+            },
+        )
+    }
+
+    /// Generates the key hierarchy of a platform running the given firmware.
+    pub fn with_build(chip_id: ChipId, build: Build) -> io::Result<Self> {
+        let (mut hrk, hrk_key) = ca::Certificate::generate(Usage::HRK, None)?;
+        let hrk_key = Key(hrk_key);
+        let uid = String::try_from(Usage::HRK)?;
+        hrk_key.0.sign(&mut hrk, uid.clone())?;
+
+        let (mut hsk, hsk_key) = ca::Certificate::generate(Usage::HSK, None)?;
+        let hsk_key = Key(hsk_key);
+        hrk_key.0.sign(&mut hsk, uid)?;
+
+        let (cek, cek_key) = issue(Usage::CEK, &hsk_key.0)?;
+
+        let (mut oca, oca_key) = csv::Certificate::generate(Usage::OCA, None)?;
+        let oca_key = Key(oca_key);
+        oca_key.0.sign(&mut oca, String::try_from(Usage::OCA)?)?;
+
+        let (mut pek, pek_key) = csv::Certificate::generate(Usage::PEK, None)?;
+        let pek_key = Key(pek_key);
+        pek.body.data.firmware = build.version;
+        oca_key.0.sign(&mut pek, String::try_from(Usage::OCA)?)?;
+        cek_key.0.sign(&mut pek, String::try_from(Usage::CEK)?)?;
+
+        let (pdh, _) = issue(Usage::PDH, &pek_key.0)?;
+
+        Ok(Self {
+            chip_id,
+            build,
+            hrk,
+            hsk,
+            cek,
+            oca,
+            pek,
+            pdh,
+            pek_key: Arc::new(pek_key),
+        })
+    }
+
+    /// The test HRK anchoring the hierarchy.
+    pub fn hrk(&self) -> &ca::Certificate {
+        &self.hrk
+    }
+
+    /// The HSK and CEK certificates, as the HYGON KDS would serve them.
+    pub fn hsk_cek(&self) -> HskCek {
+        HskCek {
+            hsk: self.hsk,
+            cek: self.cek,
+        }
+    }
+
+    /// The complete certificate chain.
+    pub fn chain(&self) -> certs::Chain {
+        certs::Chain {
+            ca: ca::Chain {
+                hsk: self.hsk,
+                hrk: self.hrk,
+            },
+            csv: self.csv_chain(),
+        }
+    }
+
+    /// The CSV certificate chain, as exported by the firmware.
+    fn csv_chain(&self) -> csv::Chain {
+        csv::Chain {
+            pdh: self.pdh,
+            pek: self.pek,
+            oca: self.oca,
+            cek: self.cek,
+        }
+    }
+
+    /// A verifier trusting the test HRK.
+    pub fn verifier(&self) -> Verifier {
+        Verifier::with_root(self.hrk)
+    }
+
+    /// A guest of this platform, with zero measurement and policy.
+    pub fn guest(&self) -> SimGuest {
+        SimGuest {
+            template: ReportBuilder::default(),
+            pek: self.pek,
+            pek_key: self.pek_key.clone(),
+            chip_id: self.chip_id.clone(),
+        }
+    }
+}
+
+impl PlatformProvider for Platform {
+    fn platform_status(&self) -> Result<Status, Indeterminate<Error>> {
+        Ok(Status {
+            build: self.build,
+            state: State::Initialized,
+            flags: PlatformStatusFlags::empty(),
+            guests: 0,
+        })
+    }
+
+    fn pdh_cert_export(&self) -> Result<csv::Chain, Indeterminate<Error>> {
+        Ok(self.csv_chain())
+    }
+
+    fn get_identifier(&self) -> Result<Identifier, Indeterminate<Error>> {
+        Ok(Identifier(self.chip_id.as_str().as_bytes().to_vec()))
+    }
+}
+
+/// A simulated csv-guest device, signing reports with the PEK of its
+/// [`Platform`].
+pub struct SimGuest {
+    template: ReportBuilder,
+    pek: csv::Certificate,
+    pek_key: Arc<Key>,
+    chip_id: ChipId,
+}
+
+impl SimGuest {
+    /// Sets the content of the reports, such as the measurement or the
+    /// policy. The report data and mnonce are taken from each request.
+    pub fn template(mut self, template: ReportBuilder) -> Self {
+        self.template = template;
+        self
+    }
+}
+
+impl Transport for SimGuest {
+    fn get_report(&mut self, page: &mut [u8]) -> io::Result<()> {
+        if page.len() < size_of::<ReportRsp>() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        let request: ReportReq = pod::from_bytes(&page[..size_of::<ReportReq>()])?;
+        let builder = self
+            .template
+            .clone()
+            .report_data(request.data)
+            .mnonce(request.mnonce);
+
+        let mut rsp = ReportRsp::boxed();
+        rsp.report = builder.sign(&self.pek_key.0)?;
+        rsp.signer = builder.signer(&self.pek, &self.chip_id)?;
+        page[..size_of::<ReportRsp>()].copy_from_slice(rsp.as_bytes());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::{
+            guest::{AttestationReport, CsvGuest},
+            platform::cek,
+        },
+        certs::{Link, Verifiable},
+        error::ChainError,
+        evidence::Evidence,
+        verifier::{SignatureChain, VerifiedReport},
+    };
+
+    fn generate() -> Platform {
+        Platform::new("SIM0000000001".parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn chain() {
+        let platform = generate();
+        let chain = platform.chain();
+        assert_eq!((&chain).verify().unwrap(), &chain.csv.pdh);
+        (&platform.hsk_cek()).verify().unwrap_err();
+        assert_eq!(
+            ChipId::try_from(&platform.get_identifier().unwrap()).unwrap(),
+            platform.chip_id
+        );
+    }
+
+    #[test]
+    fn install_cek() {
+        let platform = generate();
+        let identity = cek::identity(&platform).unwrap();
+        assert_eq!(
+            ChipId::try_from(&identity.identifier).unwrap(),
+            platform.chip_id
+        );
+
+        let exported = platform.pdh_cert_export().unwrap();
+        let chain = cek::complete(exported.clone(), &platform.hsk_cek(), platform.hrk()).unwrap();
+        assert_eq!(chain, platform.chain());
+        assert_eq!(chain.csv.cek, identity.cek);
+
+        // The certificates of another chip are rejected.
+        let other = generate();
+        assert!(cek::complete(exported, &other.hsk_cek(), other.hrk()).is_err());
+    }
+
+    #[test]
+    fn end_to_end() {
+        let platform = generate();
+        let guest = platform
+            .guest()
+            .template(ReportBuilder::default().measure([3u8; 32]));
+        let guest = CsvGuest::builder().transport(guest).open().unwrap();
+
+        let evidence =
+            Evidence::collect(&guest, Some([1u8; 64]), None, platform.hsk_cek()).unwrap();
+        let verified: VerifiedReport = platform.verifier().accept(evidence).unwrap();
+        assert_eq!(verified.measure(), [3u8; 32]);
+        assert_eq!(verified.report_data(), [1u8; 64]);
+
+        // Another platform's root does not endorse the evidence.
+        let evidence = verified.into_evidence();
+        generate().verifier().verify(&evidence).unwrap_err();
+    }
+
+    #[test]
+    fn verify_report() {
+        let platform = generate();
+        let chain = platform.chain();
+        let guest = CsvGuest::builder()
+            .transport(platform.guest())
+            .open()
+            .unwrap();
+        let (mut report, _) = guest.get_report(None, None).unwrap();
+        chain
+            .verify_report_with_root(&report, platform.hrk())
+            .unwrap();
+
+        let link = |chain: &certs::Chain, report: &AttestationReport| {
+            let err = chain
+                .verify_report_with_root(report, platform.hrk())
+                .unwrap_err();
+            ChainError::find(&err).unwrap().link
+        };
+
+        // The simulated HRK is not HYGON's.
+        let err = chain.verify_report(&report).unwrap_err();
+        assert_eq!(ChainError::find(&err).unwrap().link, Link::Hrk);
+
+        let mut other = chain.clone();
+        other.csv.cek = generate().chain().csv.cek;
+        assert_eq!(link(&other, &report), Link::Cek);
+
+        let mut other = chain.clone();
+        other.csv.oca = generate().chain().csv.oca;
+        assert_eq!(link(&other, &report), Link::Pek);
+
+        report.body.measure[0] ^= 1;
+        assert_eq!(link(&chain, &report), Link::Report);
+    }
+
+    #[test]
+    fn bundle() {
+        let chain = generate().chain();
+        let mut bytes = Vec::new();
+        certs::bundle::write(&chain, &mut bytes).unwrap();
+        assert_eq!(certs::bundle::read(&bytes[..]).unwrap(), chain);
+
+        let err = certs::bundle::read(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.to_string(), "certificate bundle digest mismatch");
+
+        let mut corrupted = bytes.clone();
+        corrupted[100] ^= 1;
+        certs::bundle::read(&corrupted[..]).unwrap_err();
+
+        bytes[0] ^= 1;
+        let err = certs::bundle::read(&bytes[..]).unwrap_err();
+        assert_eq!(err.to_string(), "magic at byte 0: not a certificate bundle");
+    }
+
+    #[test]
+    fn pek_signers() {
+        let platform = generate();
+        let chain = platform.pdh_cert_export().unwrap();
+        let signers = chain.pek.pek_signers(&chain.cek, Some(&chain.oca)).unwrap();
+        assert!(signers.both());
+
+        let other = generate().pdh_cert_export().unwrap();
+        let signers = chain.pek.pek_signers(&other.cek, Some(&other.oca)).unwrap();
+        assert_eq!(signers, csv::PekSigners::default());
+        assert!(chain.pdh.pek_signers(&chain.cek, None).is_err());
+
+        // A verifier may require the platform owner's signature too.
+        let guest = CsvGuest::builder()
+            .transport(platform.guest())
+            .open()
+            .unwrap();
+        let evidence = Evidence::collect(&guest, None, None, platform.hsk_cek()).unwrap();
+        let owned = |oca| Verifier::empty().step(SignatureChain::new(*platform.hrk()).oca(oca));
+        owned(chain.oca).verify(&evidence).unwrap();
+        owned(other.oca).verify(&evidence).unwrap_err();
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Construction of arbitrary signed reports, for testing verifiers.
+//!
+//! Reports are masked and signed as the firmware does, but with test keys:
+//! the PEK is endorsed by a generated CEK, which no HSK endorses. Checks
+//! from the CEK down can therefore be exercised, including negative cases
+//! the firmware would never produce.
+
+use crate::{
+    api::guest::{
+        AttestationReport, Body, GuestPolicy, ReportSigner, SigAlgo, SigUsage, VmVersion,
+    },
+    certs::{csv::Certificate, Signer, Usage},
+    crypto::{gm, PrivateKey},
+    pod, ChipId,
+};
+
+use std::io::Result;
+use uuid::Uuid;
+
+/// A CEK and the PEK it endorses, along with their private keys.
+pub struct TestKeys {
+    /// The CEK certificate.
+    pub cek: Certificate,
+
+    /// The CEK private key.
+    pub cek_key: PrivateKey<Usage>,
+
+    /// The PEK certificate, signed by the CEK.
+    pub pek: Certificate,
+
+    /// The PEK private key, which signs reports.
+    pub pek_key: PrivateKey<Usage>,
+}
+
+impl TestKeys {
+    /// Generates a CEK and a PEK signed by it.
+    pub fn generate() -> Result<Self> {
+        let (cek, cek_key) = Certificate::generate(Usage::CEK, None)?;
+        let (mut pek, pek_key) = Certificate::generate(Usage::PEK, None)?;
+        cek_key.sign(&mut pek, String::try_from(Usage::CEK)?)?;
+
+        Ok(Self {
+            cek,
+            cek_key,
+            pek,
+            pek_key,
+        })
+    }
+}
+
+/// Builds attestation reports with arbitrary content.
+///
+/// Every field is given unmasked; the builder applies the anonce mask.
+#[derive(Clone)]
+pub struct ReportBuilder {
+    body: Body,
+    anonce: u32,
+    sig_usage: SigUsage,
+    sig_algo: SigAlgo,
+}
+
+impl Default for ReportBuilder {
+    fn default() -> Self {
+        Self {
+            body: Body::default(),
+            anonce: 0x5a5a_a5a5,
+            sig_usage: SigUsage::Pek,
+            sig_algo: SigAlgo::Sm2Sa,
+        }
+    }
+}
+
+impl ReportBuilder {
+    /// Starts a report with zero fields, signed by a PEK with SM2.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the guest provided report data.
+    pub fn report_data(mut self, data: [u8; 64]) -> Self {
+        self.body.report_data = data;
+        self
+    }
+
+    /// Sets the mnonce, which also keys the signer MAC.
+    pub fn mnonce(mut self, mnonce: [u8; 16]) -> Self {
+        self.body.mnonce = mnonce;
+        self
+    }
+
+    /// Sets the launch digest.
+    pub fn measure(mut self, measure: [u8; 32]) -> Self {
+        self.body.measure = measure;
+        self
+    }
+
+    /// Sets the digest of the guest owner's public key.
+    pub fn user_pubkey_digest(mut self, digest: [u8; 32]) -> Self {
+        self.body.user_pubkey_digest = digest;
+        self
+    }
+
+    /// Sets the VM identifier.
+    pub fn vm_id(mut self, vm_id: Uuid) -> Self {
+        self.body.vm_id = *vm_id.as_bytes();
+        self
+    }
+
+    /// Sets the VM version.
+    pub fn vm_version(mut self, version: VmVersion) -> Self {
+        self.body.vm_version = version.0;
+        self
+    }
+
+    /// Sets the guest policy.
+    pub fn policy(mut self, policy: GuestPolicy) -> Self {
+        self.body.policy = policy;
+        self
+    }
+
+    /// Sets the mask the firmware applies to the report.
+    pub fn anonce(mut self, anonce: u32) -> Self {
+        self.anonce = anonce;
+        self
+    }
+
+    /// Sets the claimed signing key usage.
+    pub fn sig_usage(mut self, usage: SigUsage) -> Self {
+        self.sig_usage = usage;
+        self
+    }
+
+    /// Sets the claimed signature algorithm.
+    pub fn sig_algo(mut self, algo: SigAlgo) -> Self {
+        self.sig_algo = algo;
+        self
+    }
+
+    /// Builds the masked report, with an empty signature.
+    pub fn build(&self) -> Result<AttestationReport> {
+        let mut body = pod::as_bytes(&self.body).to_vec();
+        AttestationReport::mask(self.anonce, &mut body);
+        let mut body: Body = pod::from_bytes(&body)?;
+        body.policy = self.body.policy.mask(&self.anonce);
+
+        Ok(AttestationReport {
+            body,
+            sig_usage: u32::from(self.sig_usage).to_le() ^ self.anonce,
+            sig_algo: u32::from(self.sig_algo).to_le() ^ self.anonce,
+            anonce: self.anonce,
+            sig: Default::default(),
+        })
+    }
+
+    /// Builds the report and signs it with `key`.
+    pub fn sign(&self, key: &PrivateKey<Usage>) -> Result<AttestationReport> {
+        let mut report = self.build()?;
+        key.sign(&mut report, String::try_from(Usage::PEK)?)?;
+        Ok(report)
+    }
+
+    /// Builds the masked signer evidence carrying `pek`, with its MAC
+    /// keyed by the mnonce, as returned along with the report.
+    pub fn signer(&self, pek: &Certificate, chip_id: &ChipId) -> Result<ReportSigner> {
+        let mut signer = ReportSigner::default();
+
+        let cert = pod::as_bytes(pek);
+        signer.pek_cert[..cert.len()].copy_from_slice(cert);
+        let sn = chip_id.as_str().as_bytes();
+        signer.sn[..sn.len()].copy_from_slice(sn);
+        AttestationReport::mask(self.anonce, &mut signer.pek_cert);
+        AttestationReport::mask(self.anonce, &mut signer.sn);
+
+        signer.mac = gm::hmac_sm3(
+            &self.body.mnonce,
+            &[&signer.pek_cert, &signer.sn, &signer.reserved],
+        )?;
+
+        Ok(signer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certs::Verifiable;
+
+    #[test]
+    fn signed() {
+        let keys = TestKeys::generate().unwrap();
+        (&keys.cek, &keys.pek).verify().unwrap();
+
+        let builder = ReportBuilder::new()
+            .mnonce([3u8; 16])
+            .measure([7u8; 32])
+            .vm_version("1.0".parse().unwrap());
+
+        // Keys may sign any number of reports.
+        for _ in 0..2 {
+            let report = builder.sign(&keys.pek_key).unwrap();
+            assert_eq!(report.unmask(report.body.measure), [7u8; 32]);
+            assert_eq!(report.vm_version().to_string(), "1.0");
+            (&keys.pek, &report).verify().unwrap();
+        }
+
+        let chip_id: ChipId = "TEST0000000000".parse().unwrap();
+        let report = builder.sign(&keys.pek_key).unwrap();
+        let mut signer = builder.signer(&keys.pek, &chip_id).unwrap();
+        signer
+            .verify(&[3u8; 16], &report.body.mnonce, &report.anonce)
+            .unwrap();
+        assert_eq!(signer.chip_id().unwrap(), chip_id);
+        assert!(signer.pek().unwrap() == keys.pek);
+    }
+
+    #[test]
+    fn negative() {
+        let keys = TestKeys::generate().unwrap();
+        let other = TestKeys::generate().unwrap();
+
+        let report = ReportBuilder::new().sign(&other.pek_key).unwrap();
+        assert!((&keys.pek, &report).verify().is_err());
+
+        let report = ReportBuilder::new()
+            .sig_algo(SigAlgo::Unknown(7))
+            .sign(&keys.pek_key)
+            .unwrap();
+        assert!((&keys.pek, &report).verify().is_err());
+    }
+}
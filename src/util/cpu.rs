@@ -0,0 +1,100 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Access to the x86 processor registers describing memory encryption.
+//!
+//! This is the only architecture specific code of the crate. On other
+//! architectures no leaf or register is available, so verification
+//! services built for them report no CSV support instead of failing to
+//! compile.
+
+/// The registers returned by a CPUID leaf.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Leaf {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+/// Executes CPUID for `leaf`, or returns `None` if the processor does not
+/// implement it.
+#[cfg(target_arch = "x86_64")]
+#[allow(unused_unsafe)] // __cpuid is only safe on recent toolchains
+pub fn cpuid(leaf: u32) -> Option<Leaf> {
+    use std::arch::x86_64::__cpuid;
+
+    // SAFETY: CPUID is always available on x86_64.
+    let max = unsafe { __cpuid(leaf & 0x8000_0000) }.eax;
+    if leaf > max {
+        return None;
+    }
+
+    let regs = unsafe { __cpuid(leaf) };
+    Some(Leaf {
+        eax: regs.eax,
+        ebx: regs.ebx,
+        ecx: regs.ecx,
+        edx: regs.edx,
+    })
+}
+
+/// Returns `None`: CPUID only exists on x86 processors.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn cpuid(_leaf: u32) -> Option<Leaf> {
+    None
+}
+
+/// Reads an MSR of the first processor through the msr driver, which
+/// requires root and the msr module.
+#[cfg(target_arch = "x86_64")]
+pub fn rdmsr(msr: u64) -> Option<u64> {
+    use std::{fs::File, os::unix::fs::FileExt};
+
+    let mut value = [0u8; 8];
+    File::open("/dev/cpu/0/msr")
+        .and_then(|file| file.read_exact_at(&mut value, msr))
+        .ok()?;
+    Some(u64::from_le_bytes(value))
+}
+
+/// Returns `None`: MSRs only exist on x86 processors.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn rdmsr(_msr: u64) -> Option<u64> {
+    None
+}
+
+/// Reads the SYSCFG MSR of the first processor.
+pub fn syscfg() -> Option<u64> {
+    rdmsr(0xC001_0010)
+}
+
+/// Reads the SEV_STATUS MSR of the first processor, which lists the
+/// memory encryption features active in a guest.
+pub fn sev_status() -> Option<u64> {
+    rdmsr(0xC001_0131)
+}
+
+/// Returns true if the processor runs under a hypervisor.
+pub fn hypervisor() -> bool {
+    cpuid(1).is_some_and(|leaf| leaf.ecx & (1 << 31) != 0)
+}
+
+/// The EAX value of CPUID 0x8000001F, listing the memory encryption
+/// features, or 0 if the leaf is not implemented.
+pub fn encryption() -> u32 {
+    cpuid(0x8000_001F).map_or(0, |leaf| leaf.eax)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unimplemented() {
+        assert_eq!(cpuid(0x7FFF_FFFF), None);
+        assert_eq!(cpuid(0xFFFF_FFFF), None);
+        assert_eq!(cpuid(0).is_some(), cfg!(target_arch = "x86_64"));
+    }
+}
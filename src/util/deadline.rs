@@ -0,0 +1,118 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounding the time spent in blocking firmware commands.
+
+use super::lock::{into_inner, lock};
+
+use std::{
+    fs::File,
+    io,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Runs `f` on a helper thread and waits at most `timeout` for its result.
+///
+/// A command stuck in the kernel cannot be cancelled from userspace, so on
+/// timeout the helper thread is detached and keeps ownership of everything
+/// captured by `f` until the command eventually completes. Callers must
+/// therefore move every buffer the command may write to into `f`.
+pub fn run<T, F>(timeout: Duration, f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::sync_channel(1);
+
+    thread::Builder::new()
+        .name("csv-command".into())
+        .spawn(move || {
+            // The receiver is gone if the caller timed out already.
+            let _ = tx.send(f());
+        })?;
+
+    rx.recv_timeout(timeout).map_err(|e| match e {
+        RecvTimeoutError::Timeout => {
+            io::Error::new(io::ErrorKind::TimedOut, "CSV firmware command timed out")
+        }
+        RecvTimeoutError::Disconnected => io::Error::other("CSV firmware command aborted"),
+    })
+}
+
+/// Like [`run`], for a command on a value shared by the commands of a
+/// handle, such as its device file.
+///
+/// The helper thread locks `shared` itself and holds the lock until `f`
+/// returns, even once the caller timed out: an abandoned command keeps
+/// excluding later ones, which time out in turn while waiting for it.
+pub fn run_locked<S, T, F>(timeout: Duration, shared: &Arc<Mutex<S>>, f: F) -> io::Result<T>
+where
+    S: Send + 'static,
+    T: Send + 'static,
+    F: FnOnce(&mut S) -> T + Send + 'static,
+{
+    let shared = shared.clone();
+    run(timeout, move || f(&mut lock(&shared)))
+}
+
+/// Takes the file out of a handle, waiting for the commands abandoned by
+/// [`run_locked`] to complete.
+///
+/// # Panics
+///
+/// Panics if the file is still referenced by a helper thread and cannot be
+/// duplicated.
+pub fn into_file(shared: Arc<Mutex<File>>) -> File {
+    match Arc::try_unwrap(shared) {
+        Ok(file) => into_inner(file),
+        // The helper thread released the lock but not the file yet.
+        Err(shared) => lock(&shared)
+            .try_clone()
+            .expect("failed to duplicate the device file"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes() {
+        assert_eq!(run(Duration::from_secs(5), || 42).unwrap(), 42);
+    }
+
+    #[test]
+    fn times_out() {
+        let err = run(Duration::from_millis(10), || {
+            thread::sleep(Duration::from_secs(1));
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn abandoned_holds_lock() {
+        let shared = Arc::new(Mutex::new(0));
+        let err = run_locked(Duration::from_millis(50), &shared, |value| {
+            thread::sleep(Duration::from_millis(500));
+            *value += 1;
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // The abandoned command still runs, so the next one waits for it.
+        let err = run_locked(Duration::from_millis(10), &shared, |value| *value).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        assert_eq!(
+            run_locked(Duration::from_secs(5), &shared, |value| *value).unwrap(),
+            1
+        );
+    }
+}
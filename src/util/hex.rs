@@ -0,0 +1,102 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hexadecimal encoding of fixed-size digests, usable with
+//! `#[serde(with = "crate::util::hex")]`, and of buffers of any length,
+//! usable with `#[serde(with = "crate::util::hex::vec")]`.
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+use std::io::{Error, ErrorKind, Result};
+
+/// Encodes bytes as lowercase hexadecimal.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decodes bytes of any length from hexadecimal, in either case.
+pub fn decode_vec(s: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::new(ErrorKind::InvalidData, format!("invalid hex: {s:?}"));
+
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            u8::from_str_radix(pair, 16).map_err(|_| invalid())
+        })
+        .collect()
+}
+
+/// Decodes exactly `N` bytes from hexadecimal, in either case.
+pub fn decode<const N: usize>(s: &str) -> Result<[u8; N]> {
+    decode_vec(s)?.try_into().map_err(|bytes: Vec<u8>| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("expected {N} hex bytes, got {}", bytes.len()),
+        )
+    })
+}
+
+pub fn serialize<S: Serializer, const N: usize>(
+    bytes: &[u8; N],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode(bytes))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+) -> std::result::Result<[u8; N], D::Error> {
+    let s = String::deserialize(deserializer)?;
+    decode(&s).map_err(de::Error::custom)
+}
+
+/// Hexadecimal encoding of byte buffers of any length.
+#[cfg(feature = "capture")]
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        decode_vec(&s).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let bytes = [0x00, 0xab, 0x7f, 0xff];
+        assert_eq!(encode(&bytes), "00ab7fff");
+        assert_eq!(decode::<4>("00AB7fff").unwrap(), bytes);
+    }
+
+    #[test]
+    fn any_length() {
+        assert_eq!(decode_vec("").unwrap(), Vec::<u8>::new());
+        assert_eq!(decode_vec("00AB7f").unwrap(), [0x00, 0xab, 0x7f]);
+    }
+
+    #[test]
+    fn invalid() {
+        decode::<4>("00ab7f").unwrap_err();
+        decode::<2>("0g00").unwrap_err();
+        decode_vec("abc").unwrap_err();
+        decode_vec("+1").unwrap_err();
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serialization of the integers of firmware structures, usable with
+//! `#[serde(with = "crate::util::le")]`.
+//!
+//! Those integers hold the little-endian bytes of the firmware, so that the
+//! structures can be read and written verbatim, see [`crate::pod`]. They are
+//! serialized by value instead, which keeps serialized evidence the same on
+//! big-endian hosts.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An integer stored in little-endian byte order.
+pub trait Integer: Copy {
+    /// Converts a stored value to the byte order of the host.
+    fn from_le(stored: Self) -> Self;
+
+    /// Converts a value to the byte order in which it is stored.
+    fn to_le(self) -> Self;
+}
+
+macro_rules! integer {
+    ($($ty:ty),*) => {
+        $(
+            impl Integer for $ty {
+                fn from_le(stored: Self) -> Self {
+                    <$ty>::from_le(stored)
+                }
+
+                fn to_le(self) -> Self {
+                    <$ty>::to_le(self)
+                }
+            }
+        )*
+    };
+}
+
+integer!(u16, u32, u64);
+
+pub fn serialize<S: Serializer, T: Integer + Serialize>(
+    stored: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    T::from_le(*stored).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>, T: Integer + Deserialize<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    Ok(T::deserialize(deserializer)?.to_le())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{api::guest::GuestPolicy, certs::Usage};
+
+    #[test]
+    fn by_value() {
+        assert_eq!(serde_json::to_string(&Usage::PEK).unwrap(), "4098");
+        let usage: Usage = serde_json::from_str("4098").unwrap();
+        assert_eq!(usage, Usage::PEK);
+
+        let policy = GuestPolicy::from(0x0102_0001);
+        assert_eq!(serde_json::to_string(&policy).unwrap(), "16908289");
+        let back: GuestPolicy = serde_json::from_str("16908289").unwrap();
+        assert_eq!(back, policy);
+    }
+}
@@ -0,0 +1,43 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Locking of mutexes which stay usable after a holder panicked.
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Locks `mutex`, ignoring poisoning.
+///
+/// Every mutex of the crate guards values which are left consistent
+/// between operations, such as file handles, caches or queues, so the
+/// panic of a holder is no reason to fail the next one.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Takes the value out of `mutex`, ignoring poisoning like [`lock`].
+pub fn into_inner<T>(mutex: Mutex<T>) -> T {
+    mutex.into_inner().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{panic, sync::Arc, thread};
+
+    #[test]
+    fn poisoned() {
+        let mutex = Arc::new(Mutex::new(1));
+        let clone = mutex.clone();
+        thread::spawn(move || {
+            let _guard = clone.lock().unwrap();
+            panic::panic_any("poison");
+        })
+        .join()
+        .unwrap_err();
+
+        assert!(mutex.is_poisoned());
+        *lock(&mutex) += 1;
+        assert_eq!(into_inner(Arc::try_unwrap(mutex).unwrap()), 2);
+    }
+}
@@ -4,23 +4,43 @@
 
 //! Helpful primitives for developing the crate.
 
+#[cfg(feature = "std")]
 pub mod cached_chain;
+#[cfg(feature = "std")]
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod deadline;
+#[cfg(feature = "std")]
+pub mod hex;
+#[cfg(feature = "std")]
 mod impl_const_id;
+pub mod le;
+#[cfg(feature = "std")]
+pub mod lock;
+pub mod redact;
+#[cfg(feature = "std")]
+pub mod tracked;
+#[cfg(feature = "std")]
+pub(crate) mod uapi;
 
+#[cfg(feature = "std")]
 use std::{
     io::{Read, Result, Write},
     mem::{size_of, MaybeUninit},
     slice::{from_raw_parts, from_raw_parts_mut},
 };
 
+#[cfg(feature = "openssl")]
 pub trait FromLe: Sized {
     fn from_le(value: &[u8]) -> Result<Self>;
 }
 
+#[cfg(feature = "openssl")]
 pub trait AsLeBytes<T> {
     fn as_le_bytes(&self) -> T;
 }
 
+#[cfg(feature = "openssl")]
 impl FromLe for openssl::bn::BigNum {
     #[inline]
     fn from_le(value: &[u8]) -> Result<Self> {
@@ -30,6 +50,7 @@ impl FromLe for openssl::bn::BigNum {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl AsLeBytes<[u8; 72]> for openssl::bn::BigNumRef {
     fn as_le_bytes(&self) -> [u8; 72] {
         let mut buf = [0u8; 72];
@@ -42,6 +63,7 @@ impl AsLeBytes<[u8; 72]> for openssl::bn::BigNumRef {
     }
 }
 
+#[cfg(feature = "openssl")]
 impl AsLeBytes<[u8; 512]> for openssl::bn::BigNumRef {
     fn as_le_bytes(&self) -> [u8; 512] {
         let mut buf = [0u8; 512];
@@ -54,6 +76,7 @@ impl AsLeBytes<[u8; 512]> for openssl::bn::BigNumRef {
     }
 }
 
+#[cfg(feature = "std")]
 pub trait TypeLoad: Read {
     fn load<T: Sized + Copy>(&mut self) -> Result<T> {
         #[allow(clippy::uninit_assumed_init)]
@@ -65,6 +88,7 @@ pub trait TypeLoad: Read {
     }
 }
 
+#[cfg(feature = "std")]
 pub trait TypeSave: Write {
     fn save<T: Sized + Copy>(&mut self, value: &T) -> Result<()> {
         let p = value as *const T as *const u8;
@@ -73,5 +97,7 @@ pub trait TypeSave: Write {
     }
 }
 
+#[cfg(feature = "std")]
 impl<T: Read> TypeLoad for T {}
+#[cfg(feature = "std")]
 impl<T: Write> TypeSave for T {}
@@ -0,0 +1,44 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Formatting of secrets in `Debug` output.
+//!
+//! Nonces, MACs and keys are printed as a placeholder so they do not end up
+//! in logs. Building with the `unredacted_debug` feature prints them as is,
+//! which is only meant for debugging the crate itself.
+
+use core::fmt::{Debug, Formatter, Result};
+
+/// The placeholder printed instead of a secret.
+pub const PLACEHOLDER: &str = "<redacted>";
+
+/// Formats the wrapped value as [`PLACEHOLDER`].
+pub struct Redacted<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Debug + ?Sized> Debug for Redacted<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if cfg!(feature = "unredacted_debug") {
+            self.0.fmt(f)
+        } else {
+            f.write_str(PLACEHOLDER)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted() {
+        let secret = [0x5au8; 16];
+        let formatted = format!("{:?}", Redacted(&secret));
+
+        if cfg!(feature = "unredacted_debug") {
+            assert_eq!(formatted, format!("{secret:?}"));
+        } else {
+            assert_eq!(formatted, PLACEHOLDER);
+        }
+    }
+}
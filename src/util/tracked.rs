@@ -0,0 +1,73 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A reader locating decoding failures.
+
+use crate::error::DecodeError;
+
+use std::io::{Read, Result};
+
+/// Counts the bytes read, to locate the fields which fail to decode.
+pub struct Tracked<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R: Read> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Tracked<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, offset: 0 }
+    }
+
+    /// Decodes the field `name` with `decode`, locating its failures as a
+    /// [`DecodeError`].
+    pub fn field<T>(
+        &mut self,
+        name: &str,
+        decode: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let offset = self.offset;
+        decode(self).map_err(|e| DecodeError::locate(e, name, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TypeLoad;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn nested() {
+        let mut reader = Tracked::new(&[0u8; 10][..]);
+        reader.field("a", |r| r.load::<[u8; 4]>()).unwrap();
+
+        let err = reader
+            .field("b", |r| {
+                let mut inner = Tracked::new(r);
+                inner.field("[0]", |r| r.load::<[u8; 4]>())?;
+                inner.field("[1]", |r| r.load::<[u8; 4]>())
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let located = DecodeError::find(&err).unwrap();
+        assert_eq!(located.field, "b[1]");
+        assert_eq!(located.offset, 8);
+
+        let err = Tracked::new(&[][..])
+            .field("c", |_| -> Result<()> {
+                Err(Error::new(ErrorKind::InvalidData, "bad"))
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "c at byte 0: bad");
+    }
+}
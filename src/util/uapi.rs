@@ -0,0 +1,20 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compile-time checks of the structures shared with the kernel.
+
+/// Asserts that a type mirroring a kernel UAPI structure has its size and
+/// field offsets, as transcribed from the header on x86_64.
+macro_rules! uapi_layout {
+    ($($ty:ty = $size:expr, { $($field:ident: $offset:expr),* $(,)? }),* $(,)?) => {
+        $(
+            static_assertions::const_assert_eq!(core::mem::size_of::<$ty>(), $size);
+            $(
+                static_assertions::const_assert_eq!(core::mem::offset_of!($ty, $field), $offset);
+            )*
+        )*
+    };
+}
+
+pub(crate) use uapi_layout;
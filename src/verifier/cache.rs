@@ -0,0 +1,114 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Memoization of certificate chain verification.
+
+use crate::{
+    certs::{ca, csv},
+    crypto::gm::Sm3,
+    pod,
+    util::lock::lock,
+};
+
+use std::{
+    collections::{HashSet, VecDeque},
+    io::Result,
+    sync::Mutex,
+};
+
+/// The SM3 digest identifying a verified chain.
+pub type Fingerprint = [u8; 32];
+
+/// Computes the fingerprint of the chain from `hrk` to the raw PEK
+/// certificate.
+pub fn fingerprint(
+    hrk: &ca::Certificate,
+    hsk: &ca::Certificate,
+    cek: &csv::Certificate,
+    pek: &[u8],
+) -> Result<Fingerprint> {
+    let mut hasher = Sm3::new()?;
+    hasher.update(pod::as_bytes(hrk))?;
+    hasher.update(pod::as_bytes(hsk))?;
+    hasher.update(pod::as_bytes(cek))?;
+    hasher.update(pek)?;
+    hasher.finish()
+}
+
+/// Remembers the chains verified successfully, so that reports from the
+/// same platform only need their own signature checked.
+///
+/// Once `capacity` chains are cached, the oldest one is forgotten. A cache
+/// may be shared between verifiers, e.g. behind an `Arc`.
+pub struct ChainCache {
+    capacity: usize,
+    entries: Mutex<(HashSet<Fingerprint>, VecDeque<Fingerprint>)>,
+}
+
+impl ChainCache {
+    /// Creates a cache holding at most `capacity` chains.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Returns true if the chain was verified already.
+    pub fn contains(&self, fingerprint: &Fingerprint) -> bool {
+        lock(&self.entries).0.contains(fingerprint)
+    }
+
+    /// Records a successfully verified chain.
+    pub fn insert(&self, fingerprint: Fingerprint) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = lock(&self.entries);
+        let (set, order) = &mut *entries;
+        if !set.insert(fingerprint) {
+            return;
+        }
+
+        order.push_back(fingerprint);
+        if order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                set.remove(&oldest);
+            }
+        }
+    }
+
+    /// The number of cached chains.
+    pub fn len(&self) -> usize {
+        lock(&self.entries).0.len()
+    }
+
+    /// Returns true if no chain is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eviction() {
+        let cache = ChainCache::new(2);
+        for i in 0..3 {
+            cache.insert([i; 32]);
+        }
+        cache.insert([2; 32]);
+
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&[0; 32]));
+        assert!(cache.contains(&[1; 32]) && cache.contains(&[2; 32]));
+
+        let disabled = ChainCache::new(0);
+        disabled.insert([0; 32]);
+        assert!(disabled.is_empty());
+    }
+}
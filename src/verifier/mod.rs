@@ -0,0 +1,177 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verification of attestation evidence against a HYGON root of trust.
+//!
+//! A [`Verifier`] runs an ordered pipeline of [`VerificationStep`]s and
+//! stops at the first failure. The default pipeline checks the signature
+//! chain and the evidence metadata; further steps may be inserted anywhere.
+//!
+//! Relying parties which only need a verdict on the bytes a guest sent can
+//! call [`verify`] instead, which records the outcome of every check.
+
+mod step;
+pub use step::*;
+
+mod cache;
+pub use cache::*;
+
+mod nonce;
+pub use nonce::*;
+
+mod offline;
+pub use offline::{verify, Check, Expectations, Outcome, VerificationReport};
+
+mod pins;
+pub use pins::*;
+
+mod tcb;
+pub use tcb::*;
+
+mod verified;
+pub use verified::VerifiedReport;
+
+use crate::{
+    certs::{builtin::HRK, ca, csv},
+    evidence::Evidence,
+    ChipId,
+};
+
+use codicon::Decoder;
+use std::{
+    any::Any,
+    io::{Error, ErrorKind, Result},
+    sync::Arc,
+};
+
+/// Verifies attestation evidence.
+pub struct Verifier {
+    steps: Vec<Box<dyn VerificationStep>>,
+
+    /// Whether a [`SignatureChain`] step is part of the pipeline.
+    anchored: bool,
+}
+
+impl Verifier {
+    /// Creates a verifier trusting the builtin HRK.
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_root(ca::Certificate::decode(HRK, ())?))
+    }
+
+    /// Creates a verifier trusting the given HRK.
+    pub fn with_root(hrk: ca::Certificate) -> Self {
+        Self::empty()
+            .step(SignatureChain::new(hrk))
+            .step(SignerChipId)
+    }
+
+    /// Creates a verifier trusting the builtin HRK, which skips the
+    /// certificate checks of the chains found in `cache`.
+    pub fn with_cache(cache: Arc<ChainCache>) -> Result<Self> {
+        let hrk = ca::Certificate::decode(HRK, ())?;
+        Ok(Self::empty()
+            .step(SignatureChain::new(hrk).cache(cache))
+            .step(SignerChipId))
+    }
+
+    /// Creates a verifier without any step, accepting any evidence until
+    /// steps are added.
+    ///
+    /// [`Verifier::accept`] refuses to vouch for evidence until a
+    /// [`SignatureChain`] step is added.
+    pub fn empty() -> Self {
+        Self {
+            steps: Vec::new(),
+            anchored: false,
+        }
+    }
+
+    /// Requires the report to be signed by the given chip, whose published
+    /// certificates must be in `cache`, see [`PinnedChip`].
+    ///
+    /// The chip id carried by the evidence is not authenticated: comparing
+    /// it alone would not stop a relay from relabelling evidence of another
+    /// chip.
+    pub fn chip_id(self, chip_id: ChipId, cache: crate::kds::Cache) -> Self {
+        self.step(PinnedChip { chip_id, cache })
+    }
+
+    /// Requires the report to be signed under the given CEK.
+    pub fn cek(self, cek: csv::Certificate) -> Self {
+        self.step(PinnedCek(cek))
+    }
+
+    /// Requires the HSK and CEK of the evidence to be pinned for its chip,
+    /// see [`PinnedKeys`].
+    pub fn pins(self, pins: Pins) -> Self {
+        self.step(PinnedKeys(pins))
+    }
+
+    /// Requires the HSK and CEK of the evidence to be the ones cached for
+    /// the chip the evidence names, see [`ChipChain`].
+    pub fn chip_chain(self, cache: crate::kds::Cache) -> Self {
+        self.step(ChipChain(cache))
+    }
+
+    /// Rejects evidence produced by firmware older than `minimum`.
+    pub fn min_tcb(self, minimum: Tcb) -> Self {
+        self.step(MinimumTcb(minimum))
+    }
+
+    /// Appends a step to the pipeline.
+    pub fn step(mut self, step: impl VerificationStep + 'static) -> Self {
+        self.anchored |= is_signature_chain(&step);
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Inserts a step at `index` in the pipeline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of steps.
+    pub fn insert(mut self, index: usize, step: impl VerificationStep + 'static) -> Self {
+        self.anchored |= is_signature_chain(&step);
+        self.steps.insert(index, Box::new(step));
+        self
+    }
+
+    /// The names of the steps, in the order they run.
+    pub fn steps(&self) -> impl Iterator<Item = &str> {
+        self.steps.iter().map(|step| step.name())
+    }
+
+    /// Runs every step of the pipeline in order.
+    ///
+    /// The error of the first failing step is returned with its kind
+    /// preserved and the step name prepended to its message.
+    pub fn verify(&self, evidence: &Evidence) -> Result<()> {
+        for step in &self.steps {
+            step.verify(evidence)
+                .map_err(|e| Error::new(e.kind(), format!("{}: {e}", step.name())))?;
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Verifier::verify`], vouching for the evidence on success.
+    ///
+    /// Fails with `InvalidInput` if the pipeline has no [`SignatureChain`]
+    /// step, since nothing would then tie the report to a HYGON chip.
+    pub fn accept(&self, evidence: Evidence) -> Result<VerifiedReport> {
+        if !self.anchored {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the pipeline does not verify the signature chain",
+            ));
+        }
+
+        self.verify(&evidence)?;
+        Ok(VerifiedReport::new(evidence))
+    }
+}
+
+fn is_signature_chain(step: &(impl VerificationStep + 'static)) -> bool {
+    (step as &dyn Any).is::<SignatureChain>()
+}
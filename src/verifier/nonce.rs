@@ -0,0 +1,257 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Issuance and tracking of the challenges guests put in their reports.
+
+use super::VerificationStep;
+use crate::{
+    crypto::DefaultRng,
+    evidence::Evidence,
+    util::{hex, lock::lock},
+};
+
+use rand::RngCore;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A challenge, passed by the guest as the mnonce of its report request.
+pub type Nonce = [u8; 16];
+
+/// Where outstanding challenges are kept.
+pub trait NonceStore: Send + Sync {
+    /// Records a challenge valid until `expires`.
+    fn insert(&self, nonce: Nonce, expires: SystemTime) -> Result<()>;
+
+    /// Removes a challenge, returning its expiry if it was outstanding.
+    fn take(&self, nonce: &Nonce) -> Result<Option<SystemTime>>;
+
+    /// Forgets every challenge expired at `now`.
+    fn purge(&self, now: SystemTime) -> Result<()>;
+}
+
+/// Keeps challenges in memory, so they are lost when the process exits.
+#[derive(Default)]
+pub struct MemoryStore(Mutex<HashMap<Nonce, SystemTime>>);
+
+impl NonceStore for MemoryStore {
+    fn insert(&self, nonce: Nonce, expires: SystemTime) -> Result<()> {
+        lock(&self.0).insert(nonce, expires);
+        Ok(())
+    }
+
+    fn take(&self, nonce: &Nonce) -> Result<Option<SystemTime>> {
+        Ok(lock(&self.0).remove(nonce))
+    }
+
+    fn purge(&self, now: SystemTime) -> Result<()> {
+        lock(&self.0).retain(|_, expires| *expires > now);
+        Ok(())
+    }
+}
+
+/// Keeps challenges in a JSON file, so they survive verifier restarts.
+///
+/// The file maps hex encoded challenges to their expiry in seconds since
+/// the UNIX epoch. It is rewritten on every change.
+pub struct FileStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    /// Uses the file at `path`, which is created on the first insertion.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> Result<HashMap<String, u64>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, nonces: &HashMap<String, u64>) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(nonces)?)?;
+        fs::rename(tmp, &self.path)
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut HashMap<String, u64>) -> T) -> Result<T> {
+        let _guard = lock(&self.lock);
+        let mut nonces = self.read()?;
+        let result = f(&mut nonces);
+        self.write(&nonces)?;
+        Ok(result)
+    }
+}
+
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl NonceStore for FileStore {
+    fn insert(&self, nonce: Nonce, expires: SystemTime) -> Result<()> {
+        self.update(|nonces| nonces.insert(hex::encode(&nonce), secs(expires)))?;
+        Ok(())
+    }
+
+    fn take(&self, nonce: &Nonce) -> Result<Option<SystemTime>> {
+        let expires = self.update(|nonces| nonces.remove(&hex::encode(nonce)))?;
+        Ok(expires.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+
+    fn purge(&self, now: SystemTime) -> Result<()> {
+        let now = secs(now);
+        self.update(|nonces| nonces.retain(|_, expires| *expires > now))
+    }
+}
+
+/// Issues single-use challenges and checks evidence answers one of them.
+///
+/// A manager is also a [`VerificationStep`], to be added to a verifier's
+/// pipeline.
+pub struct NonceManager<S = MemoryStore> {
+    store: S,
+    ttl: Duration,
+    rng: Mutex<Box<dyn RngCore + Send>>,
+}
+
+impl NonceManager {
+    /// Creates a manager keeping challenges valid for `ttl` in memory.
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_store(MemoryStore::default(), ttl)
+    }
+}
+
+impl<S: NonceStore> NonceManager<S> {
+    /// Creates a manager keeping challenges valid for `ttl` in `store`.
+    pub fn with_store(store: S, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            rng: Mutex::new(Box::<DefaultRng>::default()),
+        }
+    }
+
+    /// Uses `rng` to generate challenges instead of the OpenSSL CSPRNG.
+    pub fn rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = Mutex::new(Box::new(rng));
+        self
+    }
+
+    /// Issues a new challenge, forgetting the expired ones.
+    pub fn issue(&self) -> Result<Nonce> {
+        let now = SystemTime::now();
+        self.store.purge(now)?;
+
+        let mut nonce = Nonce::default();
+        lock(&self.rng)
+            .try_fill_bytes(&mut nonce)
+            .map_err(Error::other)?;
+
+        self.store.insert(nonce, now + self.ttl)?;
+        Ok(nonce)
+    }
+
+    /// Consumes the challenge answered by the evidence, failing if it was
+    /// not issued, already used or expired.
+    pub fn validate(&self, evidence: &Evidence) -> Result<()> {
+        let nonce = evidence.report.unmask(evidence.report.body.mnonce);
+
+        match self.store.take(&nonce)? {
+            None => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "unknown or already used challenge",
+            )),
+            Some(expires) if expires <= SystemTime::now() => {
+                Err(Error::new(ErrorKind::PermissionDenied, "challenge expired"))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+impl<S: NonceStore> VerificationStep for NonceManager<S> {
+    fn name(&self) -> &str {
+        "challenge"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        self.validate(evidence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::guest::{mock::MockGuest, CsvGuest},
+        certs::HskCek,
+    };
+    use codicon::Decoder;
+
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    fn answer(nonce: Nonce) -> Evidence {
+        let guest = CsvGuest::builder()
+            .transport(MockGuest::default())
+            .open()
+            .unwrap();
+        let (report, signer) = guest.get_report(None, Some(nonce)).unwrap();
+        Evidence {
+            report,
+            signer,
+            chain: HskCek::decode(HSK_CEK, ()).unwrap(),
+            collected_at: SystemTime::now(),
+            chip_id: "MOCK0000000000".parse().unwrap(),
+        }
+    }
+
+    fn check(manager: &NonceManager<impl NonceStore>) {
+        let nonce = manager.issue().unwrap();
+        let evidence = answer(nonce);
+
+        manager.validate(&evidence).unwrap();
+        // Challenges are single use.
+        let err = manager.validate(&evidence).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        assert!(manager.validate(&answer([0u8; 16])).is_err());
+    }
+
+    #[test]
+    fn memory() {
+        check(&NonceManager::new(Duration::from_secs(60)));
+
+        let expired = NonceManager::new(Duration::ZERO);
+        let evidence = answer(expired.issue().unwrap());
+        assert!(expired.validate(&evidence).is_err());
+    }
+
+    #[test]
+    fn file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("nonces.json");
+        let manager = NonceManager::with_store(FileStore::new(&path), Duration::from_secs(60));
+        check(&manager);
+
+        // A challenge outlives the manager which issued it.
+        let nonce = manager.issue().unwrap();
+        let manager = NonceManager::with_store(FileStore::new(&path), Duration::from_secs(60));
+        manager.validate(&answer(nonce)).unwrap();
+    }
+}
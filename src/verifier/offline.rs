@@ -0,0 +1,328 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verification of a report as received from a guest, without any network
+//! access, recording the outcome of every check.
+
+use super::{cert_fingerprint, Fingerprint, SignatureChain, VerificationStep};
+use crate::{
+    api::guest::{AttestationReport, GuestPolicy, ReportSigner},
+    certs::{builtin::HRK, ca, HskCek},
+    evidence::Evidence,
+    pod,
+    util::hex,
+};
+
+use codicon::Decoder;
+use serde::Serialize;
+use std::{fmt::Display, io, mem::size_of};
+
+/// The claims a report must carry, compared once its signatures verify.
+///
+/// Claims left `None` are not compared, and reported as skipped.
+#[derive(Clone, Debug, Default)]
+pub struct Expectations {
+    /// The launch digest of the guest.
+    pub measure: Option<[u8; 32]>,
+
+    /// The data the guest bound to the report, e.g. a nonce of the relying
+    /// party.
+    pub report_data: Option<[u8; 64]>,
+
+    /// The chip which signed the report, by the fingerprint of the CEK
+    /// certificate HYGON publishes for it, see [`cert_fingerprint`].
+    ///
+    /// The serial number of the report signer is not authenticated, so the
+    /// chip is identified by the CEK the signature chain verified under.
+    pub cek: Option<Fingerprint>,
+
+    /// The exact policy of the guest.
+    pub policy: Option<GuestPolicy>,
+}
+
+/// The outcome of a check.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The check passed.
+    Passed,
+
+    /// The check failed, and so does the verification.
+    Failed,
+
+    /// The claim is not expected, or a check it depends on failed.
+    Skipped,
+}
+
+/// A check run by [`verify`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Check {
+    /// The name of the check.
+    pub name: &'static str,
+
+    /// The outcome of the check.
+    pub outcome: Outcome,
+
+    /// Why the check failed or was skipped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// The checks run by [`verify`], in order.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VerificationReport {
+    /// The checks, in the order they ran.
+    pub checks: Vec<Check>,
+}
+
+impl VerificationReport {
+    /// Whether no check failed.
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// The checks which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|c| c.outcome == Outcome::Failed)
+    }
+
+    /// Looks a check up by name.
+    pub fn check(&self, name: &str) -> Option<&Check> {
+        self.checks.iter().find(|c| c.name == name)
+    }
+
+    fn push(&mut self, name: &'static str, outcome: Outcome, detail: Option<String>) {
+        self.checks.push(Check {
+            name,
+            outcome,
+            detail,
+        });
+    }
+
+    /// Records the outcome of `result`, returning its value on success.
+    fn record<T, E: Display>(&mut self, name: &'static str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.push(name, Outcome::Passed, None);
+                Some(value)
+            }
+            Err(e) => {
+                self.push(name, Outcome::Failed, Some(e.to_string()));
+                None
+            }
+        }
+    }
+
+    /// Compares a claim of the report to its expected value, if any.
+    fn expect<T: PartialEq>(
+        &mut self,
+        name: &'static str,
+        expected: Option<&T>,
+        actual: &T,
+        show: impl Fn(&T) -> String,
+    ) {
+        match expected {
+            None => self.push(name, Outcome::Skipped, Some("not expected".into())),
+            Some(expected) if expected == actual => self.push(name, Outcome::Passed, None),
+            Some(expected) => self.push(
+                name,
+                Outcome::Failed,
+                Some(format!("got {}, expected {}", show(actual), show(expected))),
+            ),
+        }
+    }
+}
+
+/// The checks after the signer MAC, skipped if the evidence is unusable.
+const LATER: [&str; 5] = [
+    "signature chain",
+    "chip id",
+    "measure",
+    "report data",
+    "policy",
+];
+
+/// Verifies a report as returned by the guest device, i.e. followed by its
+/// signer with the PEK certificate and serial number still masked, against
+/// the HSK and CEK certificates of its chip, as served by the KDS.
+///
+/// The signer MAC, the signature chain up to the builtin HRK and the
+/// [`Expectations`] are checked entirely offline. Every check is recorded,
+/// checks depending on a failed one being skipped, so relying parties can
+/// report exactly what did not match; see [`VerificationReport::passed`].
+pub fn verify(report: &[u8], chain: &[u8], expected: &Expectations) -> VerificationReport {
+    let mut checks = VerificationReport::default();
+
+    match evidence(&mut checks, report, chain) {
+        Some(evidence) => appraise(&mut checks, &evidence, expected),
+        None => {
+            for name in LATER {
+                checks.push(name, Outcome::Skipped, Some("no usable evidence".into()));
+            }
+        }
+    }
+
+    checks
+}
+
+fn parse(bytes: &[u8]) -> io::Result<(AttestationReport, ReportSigner)> {
+    let size = AttestationReport::SIZE + size_of::<ReportSigner>();
+    if bytes.len() != size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected {size} bytes, got {}", bytes.len()),
+        ));
+    }
+
+    let (report, signer) = bytes.split_at(AttestationReport::SIZE);
+    Ok((
+        AttestationReport::from_bytes(report)?,
+        pod::from_bytes(signer)?,
+    ))
+}
+
+/// Parses the report and chain and checks the signer MAC.
+fn evidence(checks: &mut VerificationReport, report: &[u8], chain: &[u8]) -> Option<Evidence> {
+    let report = checks.record("report", parse(report));
+    let chain = checks.record("chain", HskCek::decode(chain, ()));
+    let ((report, mut signer), chain) = match (report, chain) {
+        (Some(report), Some(chain)) => (report, chain),
+        _ => {
+            checks.push(
+                "signer MAC",
+                Outcome::Skipped,
+                Some("no usable evidence".into()),
+            );
+            return None;
+        }
+    };
+
+    let mnonce = report.unmask(report.body.mnonce);
+    let result = signer
+        .verify(&mnonce, &report.body.mnonce, &report.anonce)
+        .map_err(io::Error::other)
+        .and_then(|()| Evidence::new(report, signer, chain));
+
+    checks.record("signer MAC", result)
+}
+
+/// Checks the signature chain and compares the claims of the report.
+fn appraise(checks: &mut VerificationReport, evidence: &Evidence, expected: &Expectations) {
+    let hrk =
+        ca::Certificate::decode(HRK, ()).and_then(|hrk| SignatureChain::new(hrk).verify(evidence));
+    let cek = hrk.and_then(|()| cert_fingerprint(&evidence.chain.cek));
+    let Some(cek) = checks.record("signature chain", cek) else {
+        // The claims of an unauthenticated report mean nothing.
+        for name in LATER.into_iter().skip(1) {
+            checks.push(
+                name,
+                Outcome::Skipped,
+                Some("signature chain failed".into()),
+            );
+        }
+        return;
+    };
+
+    let report = &evidence.report;
+    checks.expect("chip id", expected.cek.as_ref(), &cek, |f| hex::encode(f));
+    checks.expect(
+        "measure",
+        expected.measure.as_ref(),
+        &report.unmask(report.body.measure),
+        |m| hex::encode(m),
+    );
+    checks.expect(
+        "report data",
+        expected.report_data.as_ref(),
+        &report.unmask(report.body.report_data),
+        |d| hex::encode(d),
+    );
+    checks.expect(
+        "policy",
+        expected.policy.as_ref(),
+        &report.body.policy.xor(&report.anonce),
+        |p| format!("[{}]", p.explain().join(", ")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &[u8] = include_bytes!("../../tests/test_data/report.cert");
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    fn outcomes(report: &VerificationReport) -> Vec<(&str, Outcome)> {
+        report
+            .checks
+            .iter()
+            .map(|c| (c.name, c.outcome.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn verify_offline() {
+        let report = verify(REPORT, HSK_CEK, &Expectations::default());
+        assert!(report.passed(), "{report:?}");
+        assert_eq!(report.check("measure").unwrap().outcome, Outcome::Skipped);
+
+        let parsed = AttestationReport::from_bytes(&REPORT[..AttestationReport::SIZE]).unwrap();
+        let chain = HskCek::decode(HSK_CEK, ()).unwrap();
+        let mut expected = Expectations {
+            measure: Some(parsed.unmask(parsed.body.measure)),
+            report_data: Some(parsed.unmask(parsed.body.report_data)),
+            cek: Some(cert_fingerprint(&chain.cek).unwrap()),
+            policy: Some(parsed.body.policy.xor(&parsed.anonce)),
+        };
+        let report = verify(REPORT, HSK_CEK, &expected);
+        assert!(
+            report.checks.iter().all(|c| c.outcome == Outcome::Passed),
+            "{report:?}"
+        );
+
+        expected.report_data.as_mut().unwrap()[0] ^= 1;
+        expected.cek.as_mut().unwrap()[0] ^= 1;
+        let report = verify(REPORT, HSK_CEK, &expected);
+        let failures: Vec<_> = report.failures().map(|c| c.name).collect();
+        assert_eq!(failures, ["chip id", "report data"]);
+    }
+
+    #[test]
+    fn tampered() {
+        let mut bytes = REPORT.to_vec();
+        bytes[0] ^= 1;
+        let report = verify(&bytes, HSK_CEK, &Expectations::default());
+        let failures: Vec<_> = report.failures().map(|c| c.name).collect();
+        assert_eq!(failures, ["signature chain"]);
+        assert!(report.checks[4..]
+            .iter()
+            .all(|c| c.outcome == Outcome::Skipped));
+
+        let mut bytes = REPORT.to_vec();
+        *bytes.last_mut().unwrap() ^= 1;
+        let report = verify(&bytes, HSK_CEK, &Expectations::default());
+        assert_eq!(
+            outcomes(&report)[2..4],
+            [
+                ("signer MAC", Outcome::Failed),
+                ("signature chain", Outcome::Skipped)
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let report = verify(&REPORT[1..], &HSK_CEK[1..], &Expectations::default());
+        assert!(!report.passed());
+        assert_eq!(
+            outcomes(&report)[..3],
+            [
+                ("report", Outcome::Failed),
+                ("chain", Outcome::Failed),
+                ("signer MAC", Outcome::Skipped)
+            ]
+        );
+        assert_eq!(report.checks.len(), 3 + LATER.len());
+    }
+}
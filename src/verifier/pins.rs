@@ -0,0 +1,214 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pinning of the intermediate keys each chip may sign evidence under.
+
+use super::{Fingerprint, VerificationStep};
+use crate::{
+    certs::{ca, csv},
+    crypto::gm,
+    evidence::Evidence,
+    pod,
+    util::hex,
+    ChipId,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Error, ErrorKind, Read, Result},
+    path::Path,
+};
+
+/// Computes the SM3 digest of a certificate, as pinned by
+/// [`Pin::Fingerprint`].
+pub fn cert_fingerprint<T: pod::Pod>(cert: &T) -> Result<Fingerprint> {
+    gm::sm3(pod::as_bytes(cert))
+}
+
+/// An expected certificate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pin {
+    /// The key id of an HSK certificate, hex-encoded. CEK certificates
+    /// carry no key id.
+    Kid(#[serde(with = "crate::util::hex")] [u8; 16]),
+
+    /// The SM3 digest of the whole certificate, hex-encoded, see
+    /// [`cert_fingerprint`].
+    Fingerprint(#[serde(with = "crate::util::hex")] Fingerprint),
+}
+
+/// The intermediate certificates accepted for one chip.
+///
+/// An empty list accepts any certificate.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChipPins {
+    /// The accepted HSK certificates.
+    pub hsk: Vec<Pin>,
+
+    /// The accepted CEK certificates, by fingerprint only.
+    pub cek: Vec<Pin>,
+}
+
+/// The intermediate certificates accepted per chip.
+///
+/// The JSON representation maps chip ids to their pins:
+///
+/// ```json
+/// {
+///   "NZA9T14052605": {
+///     "hsk": [{ "kid": "00112233445566778899aabbccddeeff" }],
+///     "cek": [{ "fingerprint": "9f86d081..." }]
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pins(pub BTreeMap<ChipId, ChipPins>);
+
+impl Pins {
+    /// Parses pins from JSON.
+    pub fn from_json(reader: impl Read) -> Result<Self> {
+        let pins: Self =
+            serde_json::from_reader(reader).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        for (chip_id, pins) in &pins.0 {
+            if pins.cek.iter().any(|pin| matches!(pin, Pin::Kid(_))) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{chip_id}: CEK certificates can only be pinned by fingerprint"),
+                ));
+            }
+        }
+
+        Ok(pins)
+    }
+
+    /// Loads pins from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(BufReader::new(File::open(path)?))
+    }
+}
+
+fn hsk_matches(pin: &Pin, hsk: &ca::Certificate) -> Result<bool> {
+    Ok(match pin {
+        Pin::Kid(kid) => &hsk.body.preamble.data.kid == kid,
+        Pin::Fingerprint(fingerprint) => &cert_fingerprint(hsk)? == fingerprint,
+    })
+}
+
+fn cek_matches(pin: &Pin, cek: &csv::Certificate) -> Result<bool> {
+    Ok(match pin {
+        Pin::Kid(_) => false,
+        Pin::Fingerprint(fingerprint) => &cert_fingerprint(cek)? == fingerprint,
+    })
+}
+
+/// Requires the HSK and CEK of the evidence to be pinned for its chip.
+///
+/// Evidence from chips without pins is rejected. The step does not verify
+/// the certificates, so it belongs after [`super::SignatureChain`].
+pub struct PinnedKeys(pub Pins);
+
+impl VerificationStep for PinnedKeys {
+    fn name(&self) -> &str {
+        "pinned keys"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        let denied = |msg: String| Err(Error::new(ErrorKind::PermissionDenied, msg));
+
+        let Some(pins) = self.0 .0.get(&evidence.chip_id) else {
+            return denied(format!("no keys are pinned for chip {}", evidence.chip_id));
+        };
+
+        let hsk = &evidence.chain.hsk;
+        if !pins.hsk.is_empty() && !any(&pins.hsk, |pin| hsk_matches(pin, hsk))? {
+            return denied(format!(
+                "HSK {} is not pinned for chip {}",
+                hex::encode(&hsk.body.preamble.data.kid),
+                evidence.chip_id
+            ));
+        }
+
+        let cek = &evidence.chain.cek;
+        if !pins.cek.is_empty() && !any(&pins.cek, |pin| cek_matches(pin, cek))? {
+            return denied(format!("CEK is not pinned for chip {}", evidence.chip_id));
+        }
+
+        Ok(())
+    }
+}
+
+fn any(pins: &[Pin], mut matches: impl FnMut(&Pin) -> Result<bool>) -> Result<bool> {
+    for pin in pins {
+        if matches(pin)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::guest::{mock::MockGuest, CsvGuest},
+        certs::HskCek,
+    };
+    use codicon::Decoder;
+    use std::time::SystemTime;
+
+    const HSK_CEK: &[u8] = include_bytes!("../../tests/test_data/hsk_cek.cert");
+
+    fn evidence() -> Evidence {
+        let guest = CsvGuest::builder()
+            .transport(MockGuest::default())
+            .open()
+            .unwrap();
+        let (report, signer) = guest.get_report(None, None).unwrap();
+        Evidence {
+            report,
+            signer,
+            chain: HskCek::decode(HSK_CEK, ()).unwrap(),
+            collected_at: SystemTime::now(),
+            chip_id: "NZA9T14052605".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn pinned() {
+        let evidence = evidence();
+        let kid = hex::encode(&evidence.chain.hsk.body.preamble.data.kid);
+        let cek = hex::encode(&cert_fingerprint(&evidence.chain.cek).unwrap());
+
+        let json = format!(
+            r#"{{ "NZA9T14052605": {{ "hsk": [{{ "kid": "{kid}" }}], "cek": [{{ "fingerprint": "{cek}" }}] }} }}"#
+        );
+        let pins = Pins::from_json(json.as_bytes()).unwrap();
+        PinnedKeys(pins.clone()).verify(&evidence).unwrap();
+
+        // Other keys of the same chip are rejected.
+        let mut other = pins.clone();
+        let chip = other.0.values_mut().next().unwrap();
+        chip.hsk = vec![Pin::Kid([0u8; 16])];
+        let err = PinnedKeys(other).verify(&evidence).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        let mut other = pins.clone();
+        other.0.values_mut().next().unwrap().cek = vec![Pin::Fingerprint([0u8; 32])];
+        assert!(PinnedKeys(other).verify(&evidence).is_err());
+
+        // So are chips without pins.
+        assert!(PinnedKeys(Pins::default()).verify(&evidence).is_err());
+
+        let json =
+            r#"{ "NZA9T14052605": { "cek": [{ "kid": "00000000000000000000000000000000" }] } }"#;
+        assert!(Pins::from_json(json.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The checks a [`super::Verifier`] runs, in order, on evidence.
+
+use super::{fingerprint, ChainCache};
+use crate::{
+    certs::{ca, csv, Verifiable},
+    evidence::Evidence,
+    kds, ChipId,
+};
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    sync::Arc,
+};
+
+/// A single check of a verification pipeline.
+///
+/// Implement it to add organization-specific checks to a verifier, or use
+/// a `(name, closure)` tuple for simple ones.
+pub trait VerificationStep: Send + Sync {
+    /// The name reported along with a failure of the step.
+    fn name(&self) -> &str;
+
+    /// Checks the evidence, failing if it is not acceptable.
+    fn verify(&self, evidence: &Evidence) -> Result<()>;
+}
+
+impl<F> VerificationStep for (&'static str, F)
+where
+    F: Fn(&Evidence) -> Result<()> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        (self.1)(evidence)
+    }
+}
+
+/// Attaches the name of the failed check to an error.
+fn check(name: &str, result: Result<()>) -> Result<()> {
+    result.map_err(|e| Error::new(ErrorKind::InvalidData, format!("{name}: {e}")))
+}
+
+/// Verifies the signature chain from the HRK down to the report.
+pub struct SignatureChain {
+    hrk: ca::Certificate,
+    oca: Option<csv::Certificate>,
+    cache: Option<Arc<ChainCache>>,
+}
+
+impl SignatureChain {
+    /// Anchors the chain at the given HRK.
+    pub fn new(hrk: ca::Certificate) -> Self {
+        Self {
+            hrk,
+            oca: None,
+            cache: None,
+        }
+    }
+
+    /// Also requires the PEK to be signed by the given OCA, i.e. the
+    /// platform to be owned by the expected owner.
+    ///
+    /// By default only the CEK signature of the PEK is checked.
+    pub fn oca(mut self, oca: csv::Certificate) -> Self {
+        self.oca = Some(oca);
+        self
+    }
+
+    /// Skips the certificate checks of chains found in `cache`, and
+    /// records the chains verified successfully there.
+    ///
+    /// The report signature is always verified.
+    pub fn cache(mut self, cache: Arc<ChainCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn verify_certs(
+        &self,
+        hsk: &ca::Certificate,
+        cek: &csv::Certificate,
+        pek: &csv::Certificate,
+    ) -> Result<()> {
+        check("HRK", (&self.hrk, &self.hrk).verify())?;
+        check("HRK -> HSK", (&self.hrk, hsk).verify())?;
+        check("HSK -> CEK", (hsk, cek).verify())?;
+        check("CEK -> PEK", (cek, pek).verify())
+    }
+}
+
+impl VerificationStep for SignatureChain {
+    fn name(&self) -> &str {
+        "signature chain"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        let hsk = &evidence.chain.hsk;
+        let cek = &evidence.chain.cek;
+        let pek = evidence.signer.pek()?;
+
+        match &self.cache {
+            None => self.verify_certs(hsk, cek, &pek)?,
+            Some(cache) => {
+                let fingerprint = fingerprint(&self.hrk, hsk, cek, &evidence.signer.pek_cert)?;
+                if !cache.contains(&fingerprint) {
+                    self.verify_certs(hsk, cek, &pek)?;
+                    cache.insert(fingerprint);
+                }
+            }
+        }
+
+        // Checked even for cached chains, which may have been verified
+        // without an OCA.
+        if let Some(oca) = &self.oca {
+            check("OCA", (oca, oca).verify())?;
+            check("OCA -> PEK", (oca, &pek).verify())?;
+        }
+
+        check("PEK -> report", (&pek, &evidence.report).verify())
+    }
+}
+
+/// Verifies the evidence metadata matches the report signer.
+pub struct SignerChipId;
+
+impl VerificationStep for SignerChipId {
+    fn name(&self) -> &str {
+        "signer chip id"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        if evidence.signer.chip_id()? != evidence.chip_id {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "chip id does not match the report signer",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Requires the report to be signed under the CEK HYGON publishes for a
+/// given chip.
+///
+/// The chip id of the evidence is read from the report signer, which is
+/// only protected by a MAC keyed with the `mnonce` the caller chose, so it
+/// cannot identify the chip on its own. Instead, the certificates of the
+/// pinned chip are looked up in a KDS cache and its CEK must be the one the
+/// evidence chain verified under: with [`SignatureChain`] run first, this
+/// binds the report signature to the pinned chip. Chips missing from the
+/// cache are rejected.
+pub struct PinnedChip {
+    /// The chip the report must be signed by.
+    pub chip_id: ChipId,
+
+    /// Where the published certificates of the chip are found.
+    pub cache: kds::Cache,
+}
+
+impl VerificationStep for PinnedChip {
+    fn name(&self) -> &str {
+        "pinned chip"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        let chip_id = &self.chip_id;
+        let Some(published) = self.cache.get(chip_id)? else {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("no certificates are cached for chip {chip_id}"),
+            ));
+        };
+
+        if published.cek.body != evidence.chain.cek.body {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("report is not signed under the CEK of chip {chip_id}"),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Requires the report to be signed under a given CEK.
+pub struct PinnedCek(pub csv::Certificate);
+
+impl VerificationStep for PinnedCek {
+    fn name(&self) -> &str {
+        "pinned CEK"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        if self.0.body != evidence.chain.cek.body {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "report is not signed under the pinned CEK",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Requires the HSK and CEK of the evidence to be the certificates HYGON
+/// publishes for the chip the evidence names.
+///
+/// The signature chain binds the report to the HSK and CEK, but neither the
+/// certificates nor the signed report body carry the chip id: it is taken
+/// from the report signer, which is only authenticated with the `mnonce`
+/// the guest chose. Requiring the published certificates of the named chip
+/// to be the chain the report verified under authenticates that chip id,
+/// so this step is meant to run after [`SignatureChain`]. The published
+/// certificates are looked up in a KDS cache, and chips missing from it
+/// are rejected.
+pub struct ChipChain(pub kds::Cache);
+
+impl VerificationStep for ChipChain {
+    fn name(&self) -> &str {
+        "chip chain"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        let chip_id = &evidence.chip_id;
+        let Some(published) = self.0.get(chip_id)? else {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("no certificates are cached for chip {chip_id}"),
+            ));
+        };
+
+        if published != evidence.chain {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("the HSK and CEK are not the ones of chip {chip_id}"),
+            ));
+        }
+
+        Ok(())
+    }
+}
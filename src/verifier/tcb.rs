@@ -0,0 +1,111 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rejection of evidence from downlevel firmware.
+
+use super::VerificationStep;
+use crate::{evidence::Evidence, Version};
+
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Result};
+
+/// The firmware versions evidence was produced with.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tcb {
+    /// The platform firmware version, from the PEK certificate.
+    pub firmware: Version,
+
+    /// The firmware API version, from the guest policy.
+    pub api: Version,
+
+    /// The HSK version, from the guest policy.
+    pub hsk: u8,
+
+    /// The CEK version, from the guest policy.
+    pub cek: u8,
+}
+
+impl Tcb {
+    /// Reads the versions from the evidence.
+    pub fn of(evidence: &Evidence) -> Result<Self> {
+        let policy = evidence.report.body.policy.xor(&evidence.report.anonce);
+        let pek = evidence.signer.pek()?;
+
+        Ok(Self {
+            firmware: pek.body.data.firmware,
+            api: Version {
+                major: policy.api_major() as u8,
+                minor: policy.api_minor() as u8,
+            },
+            hsk: policy.hsk_version() as u8,
+            cek: policy.cek_version() as u8,
+        })
+    }
+
+    /// Names the first component of `self` lower than in `minimum`.
+    fn downlevel(&self, minimum: &Tcb) -> Option<String> {
+        if self.firmware < minimum.firmware {
+            Some(format!("firmware {} < {}", self.firmware, minimum.firmware))
+        } else if self.api < minimum.api {
+            Some(format!("API {} < {}", self.api, minimum.api))
+        } else if self.hsk < minimum.hsk {
+            Some(format!("HSK version {} < {}", self.hsk, minimum.hsk))
+        } else if self.cek < minimum.cek {
+            Some(format!("CEK version {} < {}", self.cek, minimum.cek))
+        } else {
+            None
+        }
+    }
+}
+
+/// Requires every component of the evidence TCB to be at least the
+/// configured one.
+pub struct MinimumTcb(pub Tcb);
+
+impl VerificationStep for MinimumTcb {
+    fn name(&self) -> &str {
+        "minimum TCB"
+    }
+
+    fn verify(&self, evidence: &Evidence) -> Result<()> {
+        match Tcb::of(evidence)?.downlevel(&self.0) {
+            None => Ok(()),
+            Some(reason) => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("downlevel TCB: {reason}"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downlevel() {
+        let tcb = Tcb {
+            firmware: Version { major: 1, minor: 2 },
+            api: Version { major: 1, minor: 2 },
+            hsk: 3,
+            cek: 4,
+        };
+
+        assert_eq!(tcb.downlevel(&Tcb::default()), None);
+        assert_eq!(tcb.downlevel(&tcb), None);
+
+        let minimum = Tcb {
+            cek: 5,
+            ..Default::default()
+        };
+        assert_eq!(tcb.downlevel(&minimum).unwrap(), "CEK version 4 < 5");
+
+        let minimum = Tcb {
+            firmware: Version { major: 1, minor: 3 },
+            ..tcb
+        };
+        assert_eq!(tcb.downlevel(&minimum).unwrap(), "firmware 1.2 < 1.3");
+    }
+}
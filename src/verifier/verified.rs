@@ -0,0 +1,80 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Evidence which passed verification.
+
+use crate::{
+    api::guest::{AttestationReport, GuestPolicy, VmVersion},
+    evidence::Evidence,
+    ChipId,
+};
+
+use uuid::Uuid;
+
+/// Evidence accepted by a [`super::Verifier`], see [`super::Verifier::accept`].
+///
+/// It cannot be built otherwise, so APIs acting on the claims of a report,
+/// such as appraisal, take it instead of a bare report to make sure the
+/// evidence was verified first. The accessors return unmasked values.
+#[derive(Debug)]
+pub struct VerifiedReport {
+    evidence: Evidence,
+}
+
+impl VerifiedReport {
+    /// Only the verifier may vouch for evidence.
+    pub(super) fn new(evidence: Evidence) -> Self {
+        Self { evidence }
+    }
+
+    /// The verified evidence.
+    pub fn evidence(&self) -> &Evidence {
+        &self.evidence
+    }
+
+    /// Gives the verified evidence back.
+    pub fn into_evidence(self) -> Evidence {
+        self.evidence
+    }
+
+    /// The verified report.
+    pub fn report(&self) -> &AttestationReport {
+        &self.evidence.report
+    }
+
+    /// The chip which signed the report.
+    pub fn chip_id(&self) -> &ChipId {
+        &self.evidence.chip_id
+    }
+
+    /// The data the guest bound to the report.
+    pub fn report_data(&self) -> [u8; 64] {
+        self.report().unmask(self.report().body.report_data)
+    }
+
+    /// The launch digest of the guest.
+    pub fn measure(&self) -> [u8; 32] {
+        self.report().unmask(self.report().body.measure)
+    }
+
+    /// The digest of the public key the guest owner launched the guest with.
+    pub fn user_pubkey_digest(&self) -> [u8; 32] {
+        self.report().unmask(self.report().body.user_pubkey_digest)
+    }
+
+    /// The guest owner assigned identifier of the VM.
+    pub fn vm_id(&self) -> Uuid {
+        self.report().vm_id()
+    }
+
+    /// The guest owner assigned version of the VM.
+    pub fn vm_version(&self) -> VmVersion {
+        self.report().vm_version()
+    }
+
+    /// The policy of the guest.
+    pub fn policy(&self) -> GuestPolicy {
+        self.report().body.policy.xor(&self.report().anonce)
+    }
+}
@@ -13,3 +13,25 @@ fn verify() {
     let cek = csv::Certificate::decode(&mut &CEK[..], ()).unwrap();
     (&hsk, &cek).verify().unwrap();
 }
+
+#[test]
+fn verify_uid_size() {
+    let mut hsk = ca::Certificate::decode(&mut &HSK[..], ()).unwrap();
+    let cek = csv::Certificate::decode(&mut &CEK[..], ()).unwrap();
+    hsk.body.uid_size = u16::MAX.to_le();
+    let err = (&hsk, &cek).verify().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn verify_chain() {
+    let hrk = ca::Certificate::decode(&mut &csv_rs::certs::builtin::HRK[..], ()).unwrap();
+    let hsk = ca::Certificate::decode(&mut &HSK[..], ()).unwrap();
+    let cek = csv::Certificate::decode(&mut &CEK[..], ()).unwrap();
+
+    let chain = ca::Chain { hsk, hrk };
+    (&chain, &cek).verify().unwrap();
+
+    let chain = ca::Chain { hsk: hrk, hrk };
+    assert!((&chain, &cek).verify().is_err());
+}
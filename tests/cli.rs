@@ -0,0 +1,88 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+#![cfg(feature = "cli")]
+
+use codicon::Decoder;
+use csv_rs::{certs::HskCek, kds::Cache};
+use std::{path::PathBuf, process::Command};
+
+fn verify(policy: &str) -> (bool, serde_json::Value) {
+    let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_data");
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("policy.yaml");
+    std::fs::write(&path, policy).unwrap();
+
+    // The certificates HYGON publishes for the chip of the report.
+    let cache = Cache::new(tmp.path().join("kds"));
+    let chain = std::fs::read(data.join("hsk_cek.cert")).unwrap();
+    cache
+        .put(
+            &"NZA9T14052605".parse().unwrap(),
+            &HskCek::decode(&chain[..], ()).unwrap(),
+        )
+        .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_csv-ctl"))
+        .arg("verify")
+        .arg("--report")
+        .arg(data.join("report.cert"))
+        .arg("--chain")
+        .arg(data.join("hsk_cek.cert"))
+        .arg("--policy")
+        .arg(&path)
+        .arg("--cache-dir")
+        .arg(cache.dir())
+        .output()
+        .unwrap();
+
+    (
+        output.status.success(),
+        serde_json::from_slice(&output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn verify_offline() {
+    let (ok, outcome) = verify("chip_id: NZA9T14052605\n");
+    assert!(ok, "{outcome}");
+    assert_eq!(outcome["verified"], true);
+    assert_eq!(outcome["chip_id"], "NZA9T14052605");
+
+    let (ok, outcome) = verify("chip_id: NZA0000000000\n");
+    assert!(!ok);
+    assert_eq!(outcome["verified"], false);
+    assert!(outcome["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("pinned chip"));
+}
+
+#[test]
+fn platform_without_device() {
+    let output = Command::new(env!("CARGO_BIN_EXE_csv-ctl"))
+        .arg("status")
+        .env("CSV_PLATFORM_DEVICE", "/nonexistent/sev")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("csv-ctl: "));
+}
+
+#[test]
+fn attest_without_device() {
+    let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_data");
+    let output = Command::new(env!("CARGO_BIN_EXE_csv-ctl"))
+        .arg("attest")
+        .arg("--chain")
+        .arg(data.join("hsk_cek.cert"))
+        .env("CSV_GUEST_DEVICE", "/nonexistent/csv-guest")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("csv-ctl: "));
+}
@@ -3,6 +3,8 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+#![cfg(feature = "openssl")]
+
 use csv_rs::{crypto::key::group, crypto::sm};
 
 #[test]
@@ -35,7 +35,7 @@ fn get_report() {
         112, 233, 62, 161, 65, 225, 252, 103, 62, 1, 126, 151, 234, 220, 107, 150,
     ];
 
-    let mut csv_guest = CsvGuest::open().unwrap();
+    let csv_guest = CsvGuest::open().unwrap();
 
     let (report, signer) = csv_guest.get_report(Some(data), Some(mnonce)).unwrap();
 
@@ -52,7 +52,7 @@ fn get_report() {
 fn get_report_without_input() {
     let mut data: [u8; 64] = [0; 64];
 
-    let mut csv_guest = CsvGuest::open().unwrap();
+    let csv_guest = CsvGuest::open().unwrap();
 
     let (report, signer) = csv_guest.get_report(None, None).unwrap();
 
@@ -100,7 +100,7 @@ fn get_report_and_verify() {
         65, 33, 61, 220, 135,
     ];
 
-    let mut csv_guest = CsvGuest::open().unwrap();
+    let csv_guest = CsvGuest::open().unwrap();
 
     let (report, signature_evidence) = csv_guest.get_report(Some(data), None).unwrap();
 
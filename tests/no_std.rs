@@ -0,0 +1,31 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The layouts, and with `alloc` their verification, build without `std`.
+
+use std::process::Command;
+
+fn build(features: &str) {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--no-default-features", "--features"])
+        .arg(features)
+        .env(
+            "CARGO_TARGET_DIR",
+            concat!(env!("CARGO_TARGET_TMPDIR"), "/no_std"),
+        )
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "features: {features:?}");
+}
+
+#[test]
+fn no_default_features() {
+    build("");
+}
+
+#[test]
+fn alloc() {
+    build("alloc");
+}
@@ -20,7 +20,7 @@ mod csv {
     #[test]
     #[serial]
     fn platform_reset() {
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
         fw.platform_reset().unwrap();
         rm_cached_chain();
     }
@@ -28,7 +28,7 @@ mod csv {
     #[cfg_attr(not(has_dev_sev), ignore)]
     #[test]
     fn platform_status() {
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
         let status = fw.platform_status().unwrap();
         println!("{:?}", status.build);
         assert!(
@@ -44,7 +44,7 @@ mod csv {
     #[test]
     #[serial]
     fn pek_generate() {
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
         fw.pek_generate().unwrap();
         rm_cached_chain();
     }
@@ -52,7 +52,7 @@ mod csv {
     #[cfg_attr(not(has_dev_sev), ignore)]
     #[test]
     fn pek_csr() {
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
         let pek = fw.pek_csr().unwrap();
         assert_eq!(Usage::try_from(&pek).unwrap(), Usage::PEK);
     }
@@ -61,17 +61,29 @@ mod csv {
     #[test]
     #[serial]
     fn pdh_generate() {
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
         fw.pdh_generate().unwrap();
         rm_cached_chain();
     }
 
+    #[cfg_attr(not(all(has_dev_sev, feature = "dangerous_hw_tests")), ignore)]
+    #[test]
+    #[serial]
+    fn rotate_keys() {
+        let fw = Firmware::open().unwrap();
+        let rotation = fw.rotate_keys().unwrap();
+        rm_cached_chain();
+
+        assert_eq!(Usage::try_from(&rotation.pek_csr).unwrap(), Usage::PEK);
+        assert_eq!(Usage::try_from(&rotation.chain.pdh).unwrap(), Usage::PDH);
+    }
+
     #[cfg_attr(not(has_dev_sev), ignore)]
     #[test]
     fn pdh_cert_export() {
         use csv_rs::certs::Verifiable;
 
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
         let chain = fw.pdh_cert_export().unwrap();
 
         assert_eq!(Usage::try_from(&chain.pdh).unwrap(), Usage::PDH);
@@ -82,13 +94,14 @@ mod csv {
         chain.verify().unwrap();
     }
 
+    #[cfg(feature = "openssl")]
     #[cfg_attr(not(all(has_sev, feature = "dangerous_hw_tests")), ignore)]
     #[test]
     #[serial]
     fn pek_cert_import() {
         use csv_rs::certs::{csv::Certificate, Signer, Verifiable};
 
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
 
         let (mut oca, key) = Certificate::generate(Usage::OCA, None).unwrap();
         let uid = String::try_from(key.usage).unwrap();
@@ -107,10 +120,43 @@ mod csv {
         fw.platform_reset().unwrap();
     }
 
+    #[cfg_attr(not(has_dev_sev), ignore)]
+    #[test]
+    fn asids() {
+        use csv_rs::api::platform::Asids;
+
+        let fw = Firmware::open().unwrap();
+        let status = fw.platform_status().unwrap();
+        let asids = Asids::query().unwrap();
+        assert!(asids.max >= status.guest_count());
+        assert_eq!(asids.available(&status), asids.max - status.guest_count());
+    }
+
+    #[cfg_attr(not(has_dev_sev), ignore)]
+    #[test]
+    fn provision_detect() {
+        use csv_rs::api::platform::provision::Detected;
+
+        let fw = Firmware::open().unwrap();
+        let status = fw.platform_status().unwrap();
+        let detected = Detected::detect(fw).unwrap();
+        assert_eq!(detected.state(), status.state);
+    }
+
+    #[cfg_attr(not(has_dev_sev), ignore)]
+    #[test]
+    fn enumerate_devices() {
+        let devices = csv_rs::api::platform::device::enumerate().unwrap();
+        assert_eq!(devices[0].socket, None);
+        for device in devices {
+            device.chip_id().unwrap();
+        }
+    }
+
     #[cfg_attr(not(has_dev_sev), ignore)]
     #[test]
     fn get_identifier() {
-        let mut fw = Firmware::open().unwrap();
+        let fw = Firmware::open().unwrap();
         let id = fw.get_identifier().unwrap();
         assert_ne!(Vec::from(id), vec![0u8; 64]);
     }